@@ -65,6 +65,12 @@ fn main() {
         k: 1,
     };
 
+    // Toy sizes keep the example fast; real deployments start from
+    // `TfheParams::default_128()`, which `check_security` accepts
+    if let Err(errors) = tfhe_params.check_security(128) {
+        println!("  Demo parameters, not secure: {}", errors[0]);
+    }
+
     let tfhe_sk = TfheSecretKey::generate(tfhe_params);
 
     // Encode boolean values
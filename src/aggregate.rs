@@ -0,0 +1,199 @@
+//! Summing encrypted contributions from many clients.
+//!
+//! Every client encrypts a `width`-bit value under the same key and the
+//! server adds them up as they arrive. The `Aggregator` keeps partial sums
+//! like the digits of a binary counter: level `k` holds the sum of `2^k`
+//! contributions at `width + k` bits, and ingesting a value merges equal
+//! levels with one addition each. The additions form a balanced tree, so
+//! the result has the depth of `tree_sum` over the same inputs, while only
+//! `log n` partial sums are kept instead of the whole batch. Widths grow
+//! by one bit per merge, so no partial sum can overflow; with a limit on
+//! the number of contributions the output width is fixed in advance.
+
+use std::fmt;
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::IntegerOps;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AggregateError {
+    /// A contribution of the wrong width
+    Width { expected: usize, found: usize },
+    /// More contributions than the limit the output width was sized for
+    Full { limit: u64 },
+}
+
+impl fmt::Display for AggregateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AggregateError::Width { expected, found } => {
+                write!(f, "contribution has {} bits, expected {}", found, expected)
+            }
+            AggregateError::Full { limit } => write!(f, "aggregator already holds its limit of {} values", limit),
+        }
+    }
+}
+
+impl std::error::Error for AggregateError {}
+
+// Bits needed to count up to `n`
+fn growth(n: u64) -> usize {
+    (u64::BITS - n.saturating_sub(1).leading_zeros()) as usize
+}
+
+/// Associative sum of equal-width values by a balanced tree of additions;
+/// the result has `growth(len)` more bits than the inputs
+pub fn tree_sum<E: BooleanEvaluator>(values: &[Vec<E::Bit>], eval: &E) -> Vec<E::Bit> {
+    assert!(!values.is_empty(), "sum of no values");
+    let width = values[0].len() + growth(values.len() as u64);
+
+    let mut layer = values.to_vec();
+    while layer.len() > 1 {
+        layer = layer.chunks(2)
+            .map(|pair| match pair {
+                [a, b] => {
+                    let w = a.len().max(b.len());
+                    IntegerOps::add(&IntegerOps::resize(a, w, eval), &IntegerOps::resize(b, w, eval), eval)
+                }
+                [a] => a.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    IntegerOps::resize(&layer[0], width, eval)
+}
+
+/// Running encrypted sum of `width`-bit contributions
+#[derive(Debug, Clone)]
+pub struct Aggregator<B> {
+    pub width: usize,
+    limit: Option<u64>,
+    count: u64,
+    levels: Vec<Option<Vec<B>>>,
+}
+
+impl<B: Clone> Aggregator<B> {
+    pub fn new(width: usize) -> Self {
+        Aggregator { width, limit: None, count: 0, levels: Vec::new() }
+    }
+
+    /// Accepts at most `limit` contributions, so `output_width` is known
+    /// before any arrives
+    pub fn with_limit(width: usize, limit: u64) -> Self {
+        Aggregator { limit: Some(limit), ..Self::new(width) }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Width of the sum: enough for every contribution at its maximum
+    pub fn output_width(&self) -> usize {
+        self.width + growth(self.limit.unwrap_or(self.count).max(1))
+    }
+
+    pub fn ingest<E: BooleanEvaluator<Bit = B>>(&mut self, value: Vec<B>, eval: &E) -> Result<(), AggregateError> {
+        self.check(1, &value)?;
+        self.push(value, eval);
+        Ok(())
+    }
+
+    /// Ingests every value of a batch, or none of them if any is rejected
+    pub fn ingest_batch<E: BooleanEvaluator<Bit = B>>(
+        &mut self,
+        values: Vec<Vec<B>>,
+        eval: &E,
+    ) -> Result<(), AggregateError> {
+        for value in &values {
+            self.check(values.len() as u64, value)?;
+        }
+        for value in values {
+            self.push(value, eval);
+        }
+        Ok(())
+    }
+
+    /// The sum of every contribution, `output_width` bits wide; zero when
+    /// nothing arrived
+    pub fn finish<E: BooleanEvaluator<Bit = B>>(self, eval: &E) -> Vec<B> {
+        let width = self.output_width();
+        let sum = self.levels.into_iter()
+            .flatten()
+            .reduce(|low, high| IntegerOps::add(&IntegerOps::resize(&low, high.len(), eval), &high, eval));
+        match sum {
+            // Partial sums of fewer values than the total fit the width
+            Some(sum) => IntegerOps::resize(&sum, width, eval),
+            None => IntegerOps::constant(0, width, eval),
+        }
+    }
+
+    fn check(&self, incoming: u64, value: &[B]) -> Result<(), AggregateError> {
+        if value.len() != self.width {
+            return Err(AggregateError::Width { expected: self.width, found: value.len() });
+        }
+        match self.limit {
+            Some(limit) if self.count + incoming > limit => Err(AggregateError::Full { limit }),
+            _ => Ok(()),
+        }
+    }
+
+    // Binary increment: merge with each full level until a free one
+    fn push<E: BooleanEvaluator<Bit = B>>(&mut self, value: Vec<B>, eval: &E) {
+        let mut carry = value;
+        for level in self.levels.iter_mut() {
+            match level.take() {
+                Some(partial) => carry = IntegerOps::add(&partial, &carry, eval),
+                None => {
+                    *level = Some(carry);
+                    self.count += 1;
+                    return;
+                }
+            }
+        }
+        self.levels.push(Some(carry));
+        self.count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    fn bits(value: u64, width: usize) -> Vec<bool> {
+        IntegerOps::constant(value, width, &ClearEvaluator)
+    }
+
+    fn value(bits: &[bool]) -> u64 {
+        bits.iter().enumerate().fold(0, |acc, (i, &b)| acc | ((b as u64) << i))
+    }
+
+    #[test]
+    fn test_aggregator_sums_without_overflow() {
+        let eval = ClearEvaluator;
+        let inputs: Vec<u64> = (0..13).map(|i| if i % 3 == 0 { 15 } else { i }).collect();
+
+        let mut agg = Aggregator::new(4);
+        for &x in &inputs[..5] {
+            agg.ingest(bits(x, 4), &eval).unwrap();
+        }
+        agg.ingest_batch(inputs[5..].iter().map(|&x| bits(x, 4)).collect(), &eval).unwrap();
+        assert_eq!(agg.count(), 13);
+        assert_eq!(agg.output_width(), 8);
+        assert_eq!(agg.ingest(bits(1, 3), &eval), Err(AggregateError::Width { expected: 4, found: 3 }));
+
+        let expected: u64 = inputs.iter().sum();
+        assert_eq!(value(&agg.finish(&eval)), expected);
+        let all: Vec<Vec<bool>> = inputs.iter().map(|&x| bits(x, 4)).collect();
+        assert_eq!(value(&tree_sum(&all, &eval)), expected);
+
+        // Every value at its maximum still fits the fixed width
+        let mut agg = Aggregator::with_limit(4, 4);
+        assert_eq!(agg.output_width(), 6);
+        agg.ingest_batch(vec![bits(15, 4); 4], &eval).unwrap();
+        assert_eq!(agg.ingest(bits(1, 4), &eval), Err(AggregateError::Full { limit: 4 }));
+        let sum = agg.finish(&eval);
+        assert_eq!((sum.len(), value(&sum)), (6, 60));
+
+        assert_eq!(value(&Aggregator::<bool>::new(4).finish(&eval)), 0);
+    }
+}
@@ -0,0 +1,972 @@
+#[cfg(feature = "client")]
+use rand::Rng;
+#[cfg(feature = "client")]
+use rand::seq::SliceRandom;
+#[cfg(feature = "client")]
+use crate::entropy::{default_entropy, EntropySource};
+#[cfg(feature = "server")]
+use std::borrow::Cow;
+#[cfg(feature = "server")]
+use std::cell::RefCell;
+#[cfg(feature = "server")]
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+#[cfg(feature = "server")]
+use std::ops::Add;
+#[cfg(all(feature = "bigint", feature = "server"))]
+use std::ops::{Mul, Sub};
+#[cfg(all(feature = "bigint", any(feature = "client", feature = "server")))]
+use num_bigint::BigUint;
+use std::sync::{Arc, OnceLock};
+#[cfg(feature = "server")]
+use crate::operations::HomomorphicOps;
+use crate::tfhe::{TfheCloudKey, TfheParams};
+#[cfg(feature = "client")]
+use crate::tfhe::{TfheEncoder, TfheSecretKey};
+#[cfg(feature = "server")]
+use crate::tfhe::TfheGates;
+use crate::tlwe::{TlweKeySwitchKey, TlweSample};
+#[cfg(feature = "client")]
+use crate::tlwe::{TlweParams, TlweSecretKey};
+use crate::query::Cell;
+use crate::profile::EvalProfile;
+#[cfg(feature = "server")]
+use crate::budget::{EvalContext, EvaluationBudget};
+use crate::transport::TransportTlweSample;
+#[cfg(feature = "client")]
+use crate::radix::recompose_u128;
+#[cfg(all(feature = "bigint", feature = "client"))]
+use crate::radix::recompose_biguint;
+#[cfg(all(feature = "bigint", any(feature = "client", feature = "server")))]
+use crate::radix::decompose_biguint;
+#[cfg(all(feature = "bigint", feature = "server"))]
+use crate::modular::Montgomery;
+#[cfg(feature = "client")]
+use crate::randomness::RandomPool;
+#[cfg(feature = "client")]
+use crate::shuffle::benes_controls;
+#[cfg(feature = "server")]
+use crate::shuffle::shuffle;
+#[cfg(feature = "server")]
+use crate::feistel::FeistelNetwork;
+use crate::float::{FloatBits, FloatFormat};
+#[cfg(feature = "server")]
+use crate::float::FloatOps;
+#[cfg(feature = "server")]
+use crate::sorting::SortingOps;
+#[cfg(feature = "server")]
+use crate::linear::LinearPlan;
+#[cfg(feature = "server")]
+use crate::integer::{CmpOp, IntegerOps};
+#[cfg(feature = "server")]
+use crate::stream::detect_run;
+#[cfg(feature = "server")]
+use crate::evaluator::TfheEvaluator;
+
+/// Builder for the parameter set keys are generated with
+#[derive(Debug, Clone, Default)]
+pub struct ConfigBuilder {
+    params: TfheParams,
+}
+
+impl ConfigBuilder {
+    pub fn params(mut self, params: TfheParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Sets the LWE dimension of gate ciphertexts; the ring of the
+    /// bootstrapping key is left alone
+    pub fn lwe_dimension(mut self, n: usize) -> Self {
+        self.params.n = n;
+        self.params.tlwe_params.n = n;
+        self
+    }
+
+    /// Sets the noise of gate ciphertexts
+    pub fn noise_stddev(mut self, stddev: f64) -> Self {
+        self.params.tlwe_params.stddev = stddev;
+        self
+    }
+
+    /// Sets the noise of the bootstrapping key rows
+    pub fn bootstrap_noise_stddev(mut self, stddev: f64) -> Self {
+        self.params.tgsw_params.tlwe_params.stddev = stddev;
+        self
+    }
+
+    pub fn build(self) -> Config {
+        Config { params: self.params }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub params: TfheParams,
+}
+
+impl Config {
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+}
+
+#[cfg(feature = "client")]
+pub fn generate_keys(config: Config) -> (ClientKey, ServerKey) {
+    let client_key = ClientKey::generate(config);
+    let server_key = client_key.server_key();
+    (client_key, server_key)
+}
+
+/// Secret key: encrypts inputs and decrypts results
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+pub struct ClientKey {
+    pub(crate) key: TfheSecretKey,
+}
+
+#[cfg(feature = "client")]
+impl ClientKey {
+    pub fn generate(config: Config) -> Self {
+        ClientKey { key: TfheSecretKey::generate(config.params) }
+    }
+
+    /// `generate` drawing the key from `rng`
+    pub fn generate_with_rng<R: EntropySource + ?Sized>(config: Config, rng: &mut R) -> Self {
+        ClientKey { key: TfheSecretKey::generate_with_rng(config.params, rng) }
+    }
+
+    pub fn server_key(&self) -> ServerKey {
+        ServerKey::new(self)
+    }
+
+    /// `server_key` drawing the key's masks and noise from `rng`
+    pub fn server_key_with_rng<R: EntropySource + ?Sized>(&self, rng: &mut R) -> ServerKey {
+        ServerKey::from_cloud_key(TfheCloudKey::generate_with_rng(&self.key, rng))
+    }
+
+    pub fn encrypt_bool(&self, value: bool) -> FheBool {
+        FheBool { ct: TfheEncoder::encode_bool(value, &self.key) }
+    }
+
+    pub fn decrypt_bool(&self, value: &FheBool) -> bool {
+        TfheEncoder::decode_bool(&value.ct, &self.key)
+    }
+
+    /// Encrypts the low `width` bits of `value`
+    pub fn encrypt_uint(&self, value: u64, width: usize) -> FheUint {
+        let bits: Vec<bool> = (0..width).map(|i| i < 64 && (value >> i) & 1 == 1).collect();
+        FheUint { bits: TfheEncoder::encode_bits(&bits, &self.key) }
+    }
+
+    pub fn decrypt_uint(&self, value: &FheUint) -> u64 {
+        TfheEncoder::decode_bits(&value.bits, &self.key).iter()
+            .take(64)
+            .enumerate()
+            .fold(0, |acc, (i, &bit)| acc | ((bit as u64) << i))
+    }
+
+    /// Encrypts the low `width` bits of a 128-bit `value`
+    pub fn encrypt_u128(&self, value: u128, width: usize) -> FheUint {
+        let bits: Vec<bool> = (0..width).map(|i| i < 128 && (value >> i) & 1 == 1).collect();
+        FheUint { bits: TfheEncoder::encode_bits(&bits, &self.key) }
+    }
+
+    /// Low 128 bits of `value`
+    pub fn decrypt_u128(&self, value: &FheUint) -> u128 {
+        let bits: Vec<u64> = TfheEncoder::decode_bits(&value.bits, &self.key).iter()
+            .take(128)
+            .map(|&b| b as u64)
+            .collect();
+        recompose_u128(&bits, 2).expect("128 bits fit a u128")
+    }
+
+    /// Encrypts `value` reduced modulo `2^width`; operations on the result wrap at that width
+    #[cfg(feature = "bigint")]
+    pub fn encrypt_biguint(&self, value: &BigUint, width: usize) -> FheBigUint {
+        let bits: Vec<bool> = decompose_biguint(value, 2, width).iter().map(|&b| b == 1).collect();
+        FheBigUint { bits: TfheEncoder::encode_bits(&bits, &self.key) }
+    }
+
+    #[cfg(feature = "bigint")]
+    pub fn decrypt_biguint(&self, value: &FheBigUint) -> BigUint {
+        let bits: Vec<u64> = TfheEncoder::decode_bits_batch(&value.bits, &self.key).iter()
+            .map(|&b| b as u64)
+            .collect();
+        recompose_biguint(&bits, 2)
+    }
+
+    /// Encrypts `value` in `format`, truncating the mantissa; see the `float` module for the caveats
+    pub fn encrypt_float(&self, value: f64, format: FloatFormat) -> FheFloat {
+        let (sign, exponent, mantissa) = format.encode(value);
+        let field = |value: u64, width: usize| {
+            let bits: Vec<bool> = (0..width).map(|i| (value >> i) & 1 == 1).collect();
+            TfheEncoder::encode_bits(&bits, &self.key)
+        };
+        FheFloat {
+            format,
+            bits: FloatBits {
+                sign: TfheEncoder::encode_bool(sign, &self.key),
+                exponent: field(exponent, format.exponent_bits),
+                mantissa: field(mantissa, format.mantissa_bits),
+            },
+        }
+    }
+
+    pub fn decrypt_float(&self, value: &FheFloat) -> f64 {
+        let field = |bits: &[TlweSample]| {
+            TfheEncoder::decode_bits(bits, &self.key).iter()
+                .enumerate()
+                .fold(0, |acc, (i, &bit)| acc | ((bit as u64) << i))
+        };
+        value.format.decode(
+            TfheEncoder::decode_bool(&value.bits.sign, &self.key),
+            field(&value.bits.exponent),
+            field(&value.bits.mantissa),
+        )
+    }
+
+    /// Decrypts a whole result vector through the batch path
+    pub fn decrypt_bits_batch(&self, values: &[FheBool]) -> Vec<bool> {
+        let samples: Vec<TlweSample> = values.iter().map(|v| v.ct.clone()).collect();
+        TfheEncoder::decode_bits_batch(&samples, &self.key)
+    }
+
+    /// Low 32 bits of every value, decrypted in one batch
+    pub fn decrypt_u32_batch(&self, values: &[FheUint]) -> Vec<u32> {
+        let samples: Vec<TlweSample> = values.iter()
+            .flat_map(|v| v.bits.iter().take(32).cloned())
+            .collect();
+        let mut bits = TfheEncoder::decode_bits_batch(&samples, &self.key).into_iter();
+
+        values.iter()
+            .map(|v| {
+                bits.by_ref()
+                    .take(v.width().min(32))
+                    .enumerate()
+                    .fold(0, |acc, (i, bit)| acc | ((bit as u32) << i))
+            })
+            .collect()
+    }
+
+    /// Decrypts bits received through `compress_for_transport`
+    pub fn decrypt_transport_bits(&self, values: &[TransportTlweSample]) -> Vec<bool> {
+        let samples: Vec<TlweSample> = values.iter().map(TransportTlweSample::decompress).collect();
+        TfheEncoder::decode_bits_batch(&samples, &self.key)
+    }
+
+    /// Encrypts `n` fresh uniformly random bits for the server to consume
+    pub fn random_pool(&self, n: usize) -> RandomPool<FheBool> {
+        self.random_pool_with_rng(n, &mut default_entropy())
+    }
+
+    /// `random_pool` with the bits and the pool's seed drawn from `rng`
+    pub fn random_pool_with_rng<R: EntropySource + ?Sized>(&self, n: usize, rng: &mut R) -> RandomPool<FheBool> {
+        let bits = (0..n).map(|_| self.encrypt_bool(rng.random())).collect();
+        RandomPool::new(rng.random(), bits)
+    }
+
+    /// Encrypted switch settings sending item `i` to position `destinations[i]` in `ServerKey::shuffle`
+    pub fn shuffle_controls(&self, destinations: &[usize]) -> Vec<FheBool> {
+        benes_controls(destinations).into_iter().map(|c| self.encrypt_bool(c)).collect()
+    }
+
+    /// Switch settings for a uniformly random permutation of `n` items
+    pub fn random_shuffle_controls(&self, n: usize) -> Vec<FheBool> {
+        self.random_shuffle_controls_with_rng(n, &mut default_entropy())
+    }
+
+    /// `random_shuffle_controls` with the permutation drawn from `rng`
+    pub fn random_shuffle_controls_with_rng<R: EntropySource + ?Sized>(&self, n: usize, rng: &mut R) -> Vec<FheBool> {
+        let mut destinations: Vec<usize> = (0..n).collect();
+        destinations.shuffle(rng);
+        self.shuffle_controls(&destinations)
+    }
+
+    /// Fresh small-dimension key for a constrained client that only decrypts
+    /// results, plus the key switching key the server needs to target it
+    pub fn generate_decryption_key(&self, params: TlweParams) -> (DecryptionKey, ResultKeySwitchKey) {
+        self.generate_decryption_key_with_rng(params, &mut default_entropy())
+    }
+
+    pub fn generate_decryption_key_with_rng<R: EntropySource + ?Sized>(
+        &self,
+        params: TlweParams,
+        rng: &mut R,
+    ) -> (DecryptionKey, ResultKeySwitchKey) {
+        let key = TlweSecretKey::generate_binary_with_rng(params, rng);
+        let ksk = TlweKeySwitchKey::generate_with_rng(
+            &self.key.tlwe_key,
+            &key,
+            RESULT_KEY_SWITCH_LEVELS,
+            RESULT_KEY_SWITCH_BASE_BIT,
+            rng,
+        );
+        (DecryptionKey { key }, ResultKeySwitchKey { ksk: Arc::new(ksk) })
+    }
+
+    /// Low-level secret key, for code mixing the façade with the core modules
+    pub fn inner(&self) -> &TfheSecretKey {
+        &self.key
+    }
+}
+
+/// Decomposition of the result key switch: 8 digits of 2 bits each
+#[cfg(feature = "client")]
+const RESULT_KEY_SWITCH_LEVELS: usize = 8;
+#[cfg(feature = "client")]
+const RESULT_KEY_SWITCH_BASE_BIT: u32 = 2;
+
+/// Small secret key that can only decrypt results switched down to it with a
+/// `ResultKeySwitchKey`; cheap enough for mobile and IoT clients
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+pub struct DecryptionKey {
+    pub(crate) key: TlweSecretKey,
+}
+
+#[cfg(feature = "client")]
+impl DecryptionKey {
+    pub fn params(&self) -> &TlweParams {
+        &self.key.params
+    }
+
+    pub fn decrypt_bool(&self, value: &FheBool) -> bool {
+        value.ct.decrypt_binary(&self.key)
+    }
+
+    pub fn decrypt_uint(&self, value: &FheUint) -> u64 {
+        self.key.batch_decryptor().decrypt_binary_batch(&value.bits).iter()
+            .take(64)
+            .enumerate()
+            .fold(0, |acc, (i, &bit)| acc | ((bit as u64) << i))
+    }
+}
+
+/// Switches results from the full computation dimension down to a
+/// `DecryptionKey`; safe to hand to the server along with the `ServerKey`
+#[derive(Debug, Clone)]
+pub struct ResultKeySwitchKey {
+    pub(crate) ksk: Arc<TlweKeySwitchKey>,
+}
+
+impl ResultKeySwitchKey {
+    pub fn switch_bool(&self, value: &FheBool) -> FheBool {
+        FheBool { ct: self.ksk.key_switch(&value.ct) }
+    }
+
+    pub fn switch_uint(&self, value: &FheUint) -> FheUint {
+        FheUint { bits: value.bits.iter().map(|b| self.ksk.key_switch(b)).collect() }
+    }
+
+    pub fn inner(&self) -> &TlweKeySwitchKey {
+        &self.ksk
+    }
+}
+
+/// Gates timed when a server key calibrates on first use
+#[cfg(feature = "server")]
+const CALIBRATION_ROUNDS: usize = 4;
+
+/// Evaluation key, safe to hand to an untrusted server
+#[derive(Debug, Clone)]
+pub struct ServerKey {
+    pub(crate) key: Arc<TfheCloudKey>,
+    /// Set once, by calibration on first use or from a persisted profile
+    profile: Arc<OnceLock<EvalProfile>>,
+    calibrate_on_first_use: bool,
+}
+
+impl ServerKey {
+    #[cfg(feature = "client")]
+    pub fn new(client_key: &ClientKey) -> Self {
+        Self::from_cloud_key(TfheCloudKey::generate(&client_key.key))
+    }
+
+    pub fn from_cloud_key(key: TfheCloudKey) -> Self {
+        ServerKey {
+            key: Arc::new(key),
+            profile: Arc::new(OnceLock::new()),
+            calibrate_on_first_use: false,
+        }
+    }
+
+    pub fn inner(&self) -> &TfheCloudKey {
+        &self.key
+    }
+
+    /// Calibrates an `EvalProfile` on the operands of the first integer
+    /// operation instead of using the sequential defaults. Clones made from
+    /// here on share the calibration; earlier ones keep their own profile.
+    pub fn calibrate_on_first_use(mut self) -> Self {
+        self.calibrate_on_first_use = true;
+        self.profile = Arc::new(OnceLock::new());
+        self
+    }
+
+    /// Uses a profile persisted from an earlier calibration
+    pub fn with_eval_profile(mut self, profile: EvalProfile) -> Self {
+        self.profile = Arc::new(OnceLock::from(profile));
+        self
+    }
+
+    /// The profile in use, once the first integer operation has settled it
+    pub fn eval_profile(&self) -> Option<&EvalProfile> {
+        self.profile.get()
+    }
+}
+
+#[cfg(feature = "server")]
+impl ServerKey {
+    /// Gate backend for the generic algorithms (`IntegerOps`, circuits, queries)
+    pub fn evaluator(&self) -> TfheEvaluator<'_> {
+        TfheEvaluator::new(&self.key)
+    }
+
+    /// Evaluator for one request, limited by `budget`; see `EvalContext::run`
+    pub fn eval_context(&self, budget: EvaluationBudget) -> EvalContext<TfheEvaluator<'_>> {
+        EvalContext::new(self.evaluator(), budget)
+    }
+
+    // Profile for an operation on `width`-bit operands, calibrated on their
+    // low bits when this is the first use and calibration was requested.
+    // Zero-width operands leave nothing to calibrate on and get the default
+    // until a later operation settles the profile.
+    fn profile_for(&self, a: &[TlweSample], b: &[TlweSample], width: usize) -> Cow<'_, EvalProfile> {
+        let (Some(a), Some(b)) = (a.first(), b.first()) else {
+            return self.profile.get().map_or_else(|| Cow::Owned(EvalProfile::default()), Cow::Borrowed);
+        };
+        Cow::Borrowed(self.profile.get_or_init(|| {
+            if self.calibrate_on_first_use {
+                EvalProfile::calibrate(&self.evaluator(), a, b, width, CALIBRATION_ROUNDS)
+            } else {
+                EvalProfile::default()
+            }
+        }))
+    }
+
+    /// Product modulo `2^width` of `a`, with the multiplier of the evaluation profile
+    pub fn mul(&self, a: &FheUint, b: &FheUint) -> FheUint {
+        let width = a.bits.len();
+        let multiplier = self.profile_for(&a.bits, &b.bits, width).multiplier;
+        FheUint { bits: multiplier.mul(&a.bits, &b.bits, width, &self.evaluator()) }
+    }
+
+    pub fn and(&self, a: &FheBool, b: &FheBool) -> FheBool {
+        FheBool { ct: TfheGates::and(&a.ct, &b.ct, &self.key) }
+    }
+
+    pub fn or(&self, a: &FheBool, b: &FheBool) -> FheBool {
+        FheBool { ct: TfheGates::or(&a.ct, &b.ct, &self.key) }
+    }
+
+    pub fn xor(&self, a: &FheBool, b: &FheBool) -> FheBool {
+        FheBool { ct: TfheGates::xor(&a.ct, &b.ct, &self.key) }
+    }
+
+    pub fn nand(&self, a: &FheBool, b: &FheBool) -> FheBool {
+        FheBool { ct: TfheGates::nand(&a.ct, &b.ct, &self.key) }
+    }
+
+    pub fn not(&self, a: &FheBool) -> FheBool {
+        FheBool { ct: TfheGates::not(&a.ct) }
+    }
+
+    /// Returns `a` when `s` is true, `b` otherwise
+    pub fn mux(&self, s: &FheBool, a: &FheBool, b: &FheBool) -> FheBool {
+        FheBool { ct: TfheGates::mux(&s.ct, &a.ct, &b.ct, &self.key) }
+    }
+
+    /// The `k` largest values in descending order, with the one-hot position of each
+    pub fn top_k(&self, values: &[FheUint], k: usize) -> (Vec<FheUint>, Vec<Vec<FheBool>>) {
+        let bits: Vec<Vec<TlweSample>> = values.iter().map(|v| v.bits.clone()).collect();
+        let top = SortingOps::top_k(&bits, k, true, &self.evaluator());
+
+        let values = top.values.into_iter().map(FheUint::from_bits).collect();
+        let indices = top.indices.into_iter()
+            .map(|onehot| onehot.into_iter().map(FheBool::from_ciphertext).collect())
+            .collect();
+        (values, indices)
+    }
+
+    /// `a + c` for a public `c`, cheaper than adding an encrypted constant
+    pub fn add_clear(&self, a: &FheUint, c: u64) -> FheUint {
+        FheUint { bits: IntegerOps::add_clear(&a.bits, c, &self.evaluator()) }
+    }
+
+    /// Compares against a public value without encrypting it first
+    pub fn cmp_clear(&self, a: &FheUint, c: u64, op: CmpOp) -> FheBool {
+        FheBool { ct: IntegerOps::cmp_clear(&a.bits, c, op, &self.evaluator()) }
+    }
+
+    /// `op` on the `top_bits` most significant bits only, for rankings and
+    /// thresholds where the low bits do not matter; see
+    /// `IntegerOps::compare_approx`
+    pub fn cmp_approx(&self, a: &FheUint, b: &FheUint, op: CmpOp, top_bits: usize) -> FheBool {
+        FheBool { ct: IntegerOps::compare_approx(&a.bits, &b.bits, op, top_bits, &self.evaluator()) }
+    }
+
+    /// Greatest common divisor, through a fixed `2n`-round binary GCD circuit
+    pub fn gcd(&self, a: &FheUint, b: &FheUint) -> FheUint {
+        FheUint { bits: IntegerOps::gcd(&a.bits, &b.bits, &self.evaluator()) }
+    }
+
+    /// `a^exponent`, wrapping within the width of `a`
+    pub fn pow_const(&self, a: &FheUint, exponent: u64) -> FheUint {
+        FheUint { bits: IntegerOps::pow_const(&a.bits, exponent, a.width(), &self.evaluator()) }
+    }
+
+    /// Polynomial with public coefficients, lowest degree first, wrapping within the width of `x`
+    pub fn poly_eval(&self, x: &FheUint, coeffs: &[i64]) -> FheUint {
+        FheUint { bits: IntegerOps::poly_eval(&x.bits, coeffs, x.width(), &self.evaluator()) }
+    }
+
+    /// `matrix * vector`, wrapping within the width of the first entry of `vector`
+    pub fn matrix_vector(&self, matrix: &[Vec<i64>], vector: &[FheUint]) -> Vec<FheUint> {
+        let width = vector.first().map_or(0, FheUint::width);
+        let bits: Vec<Vec<TlweSample>> = vector.iter().map(|v| v.bits.clone()).collect();
+        LinearPlan::new(matrix)
+            .evaluate(&bits, width, &self.evaluator())
+            .into_iter()
+            .map(FheUint::from_bits)
+            .collect()
+    }
+
+    /// Float addition through a full normalization circuit; expensive, see the `float` module
+    pub fn float_add(&self, a: &FheFloat, b: &FheFloat) -> FheFloat {
+        assert_eq!(a.format, b.format, "float formats differ");
+        FheFloat { format: a.format, bits: FloatOps::add(&a.bits, &b.bits, a.format, &self.evaluator()) }
+    }
+
+    pub fn float_mul(&self, a: &FheFloat, b: &FheFloat) -> FheFloat {
+        assert_eq!(a.format, b.format, "float formats differ");
+        FheFloat { format: a.format, bits: FloatOps::mul(&a.bits, &b.bits, a.format, &self.evaluator()) }
+    }
+
+    /// Permutes `items` (a power-of-two count) with client-supplied encrypted switch settings
+    pub fn shuffle(&self, items: &[FheUint], controls: &[FheBool]) -> Vec<FheUint> {
+        let bits: Vec<Vec<TlweSample>> = items.iter().map(|v| v.bits.clone()).collect();
+        let controls: Vec<TlweSample> = controls.iter().map(|c| c.ct.clone()).collect();
+        shuffle(&bits, &controls, &self.evaluator())
+            .into_iter()
+            .map(FheUint::from_bits)
+            .collect()
+    }
+
+    /// Pseudo-random token of `value`, whose width must be the network's block size
+    pub fn feistel_permute(&self, value: &FheUint, network: &FeistelNetwork) -> FheUint {
+        FheUint { bits: network.evaluate(&value.bits, &self.evaluator()) }
+    }
+
+    /// Recovers the value behind a token from `feistel_permute`
+    pub fn feistel_invert(&self, token: &FheUint, network: &FeistelNetwork) -> FheUint {
+        FheUint { bits: network.evaluate_inverse(&token.bits, &self.evaluator()) }
+    }
+
+    /// Encrypted flag set when `k` consecutive events in `events` are true
+    pub fn detect_run(&self, events: &[FheBool], k: usize) -> FheBool {
+        let bits: Vec<TlweSample> = events.iter().map(|e| e.ct.clone()).collect();
+        FheBool { ct: detect_run(&bits, k, &self.evaluator()).detected }
+    }
+}
+
+#[cfg(all(feature = "bigint", feature = "server"))]
+impl ServerKey {
+    pub fn biguint_add(&self, a: &FheBigUint, b: &FheBigUint) -> FheBigUint {
+        a.check_width(b);
+        let adder = self.profile_for(&a.bits, &b.bits, a.width()).adder;
+        FheBigUint { bits: adder.add_mod(&a.bits, &b.bits, &self.evaluator()) }
+    }
+
+    pub fn biguint_sub(&self, a: &FheBigUint, b: &FheBigUint) -> FheBigUint {
+        a.check_width(b);
+        FheBigUint { bits: IntegerOps::sub_mod(&a.bits, &b.bits, &self.evaluator()) }
+    }
+
+    /// Product modulo `2^width`; quadratic in the width
+    pub fn biguint_mul(&self, a: &FheBigUint, b: &FheBigUint) -> FheBigUint {
+        a.check_width(b);
+        let multiplier = self.profile_for(&a.bits, &b.bits, a.width()).multiplier;
+        FheBigUint { bits: multiplier.mul(&a.bits, &b.bits, a.width(), &self.evaluator()) }
+    }
+
+    /// `a b mod modulus` for an odd public modulus, through Montgomery
+    /// reduction; both operands must already be below the modulus. The
+    /// result has the bit width of the modulus.
+    pub fn biguint_mul_mod(&self, a: &FheBigUint, b: &FheBigUint, modulus: &BigUint) -> FheBigUint {
+        let bits: Vec<bool> = decompose_biguint(modulus, 2, modulus.bits() as usize).iter()
+            .map(|&b| b == 1)
+            .collect();
+        let montgomery = Montgomery::new(&bits);
+
+        let eval = self.evaluator();
+        let a = IntegerOps::resize(&a.bits, montgomery.width(), &eval);
+        let b = IntegerOps::resize(&b.bits, montgomery.width(), &eval);
+        FheBigUint { bits: montgomery.mul_mod(&a, &b, &eval) }
+    }
+
+    pub fn biguint_compare(&self, a: &FheBigUint, b: &FheBigUint, op: CmpOp) -> FheBool {
+        a.check_width(b);
+        FheBool { ct: IntegerOps::compare(&a.bits, &b.bits, op, &self.evaluator()) }
+    }
+}
+
+#[cfg(feature = "server")]
+impl ServerKey {
+    /// Wrapping addition; the result has the width of `a`
+    pub fn add(&self, a: &FheUint, b: &FheUint) -> FheUint {
+        let mut bits = HomomorphicOps::add_n_bit(&a.bits, &b.bits, &self.key);
+        bits.truncate(a.bits.len());
+        FheUint { bits }
+    }
+
+    pub fn eq(&self, a: &FheUint, b: &FheUint) -> FheBool {
+        FheBool { ct: HomomorphicOps::equal_n_bit(&a.bits, &b.bits, &self.key) }
+    }
+}
+
+#[cfg(feature = "server")]
+thread_local! {
+    static SERVER_KEY: RefCell<Option<ServerKey>> = const { RefCell::new(None) };
+}
+
+/// Installs the key used by operators on `FheBool`/`FheUint` in this thread
+#[cfg(feature = "server")]
+pub fn set_server_key(key: ServerKey) {
+    SERVER_KEY.with(|k| *k.borrow_mut() = Some(key));
+}
+
+#[cfg(feature = "server")]
+pub fn unset_server_key() {
+    SERVER_KEY.with(|k| *k.borrow_mut() = None);
+}
+
+#[cfg(feature = "server")]
+fn with_server_key<R>(f: impl FnOnce(&ServerKey) -> R) -> R {
+    SERVER_KEY.with(|k| {
+        let key = k.borrow();
+        f(key.as_ref().expect("no server key set, call set_server_key first"))
+    })
+}
+
+/// Encrypted boolean
+#[derive(Debug, Clone)]
+pub struct FheBool {
+    pub(crate) ct: TlweSample,
+}
+
+impl FheBool {
+    pub fn from_ciphertext(ct: TlweSample) -> Self {
+        FheBool { ct }
+    }
+
+    pub fn ciphertext(&self) -> &TlweSample {
+        &self.ct
+    }
+
+    /// Modulus-switched copy for sending back to the client; see `transport`
+    pub fn compress_for_transport(&self, log_modulus: u32) -> TransportTlweSample {
+        self.ct.compress_for_transport(log_modulus)
+    }
+}
+
+#[cfg(feature = "server")]
+impl BitAnd for &FheBool {
+    type Output = FheBool;
+
+    fn bitand(self, rhs: &FheBool) -> FheBool {
+        with_server_key(|key| key.and(self, rhs))
+    }
+}
+
+#[cfg(feature = "server")]
+impl BitOr for &FheBool {
+    type Output = FheBool;
+
+    fn bitor(self, rhs: &FheBool) -> FheBool {
+        with_server_key(|key| key.or(self, rhs))
+    }
+}
+
+#[cfg(feature = "server")]
+impl BitXor for &FheBool {
+    type Output = FheBool;
+
+    fn bitxor(self, rhs: &FheBool) -> FheBool {
+        with_server_key(|key| key.xor(self, rhs))
+    }
+}
+
+#[cfg(feature = "server")]
+impl Not for &FheBool {
+    type Output = FheBool;
+
+    fn not(self) -> FheBool {
+        with_server_key(|key| key.not(self))
+    }
+}
+
+/// Encrypted unsigned integer stored as encrypted bits, least significant first
+#[derive(Debug, Clone)]
+pub struct FheUint {
+    pub(crate) bits: Vec<TlweSample>,
+}
+
+impl FheUint {
+    pub fn from_bits(bits: Vec<TlweSample>) -> Self {
+        FheUint { bits }
+    }
+
+    pub fn bits(&self) -> &[TlweSample] {
+        &self.bits
+    }
+
+    pub fn width(&self) -> usize {
+        self.bits.len()
+    }
+
+    pub fn compress_for_transport(&self, log_modulus: u32) -> Vec<TransportTlweSample> {
+        self.bits.iter().map(|b| b.compress_for_transport(log_modulus)).collect()
+    }
+}
+
+/// Encrypted unsigned integer of a width fixed at encryption time, for
+/// operands well beyond 64 bits; arithmetic wraps modulo `2^width`
+#[cfg(feature = "bigint")]
+#[derive(Debug, Clone)]
+pub struct FheBigUint {
+    pub(crate) bits: Vec<TlweSample>,
+}
+
+#[cfg(feature = "bigint")]
+impl FheBigUint {
+    pub fn from_bits(bits: Vec<TlweSample>) -> Self {
+        FheBigUint { bits }
+    }
+
+    pub fn bits(&self) -> &[TlweSample] {
+        &self.bits
+    }
+
+    pub fn width(&self) -> usize {
+        self.bits.len()
+    }
+
+    #[cfg(feature = "server")]
+    fn check_width(&self, other: &FheBigUint) {
+        assert_eq!(self.width(), other.width(), "FheBigUint operands have different widths");
+    }
+}
+
+#[cfg(all(feature = "bigint", feature = "server"))]
+impl Add for &FheBigUint {
+    type Output = FheBigUint;
+
+    fn add(self, rhs: &FheBigUint) -> FheBigUint {
+        with_server_key(|key| key.biguint_add(self, rhs))
+    }
+}
+
+#[cfg(all(feature = "bigint", feature = "server"))]
+impl Sub for &FheBigUint {
+    type Output = FheBigUint;
+
+    fn sub(self, rhs: &FheBigUint) -> FheBigUint {
+        with_server_key(|key| key.biguint_sub(self, rhs))
+    }
+}
+
+#[cfg(all(feature = "bigint", feature = "server"))]
+impl Mul for &FheBigUint {
+    type Output = FheBigUint;
+
+    fn mul(self, rhs: &FheBigUint) -> FheBigUint {
+        with_server_key(|key| key.biguint_mul(self, rhs))
+    }
+}
+
+/// Experimental encrypted float with encrypted sign, exponent and mantissa fields
+#[derive(Debug, Clone)]
+pub struct FheFloat {
+    pub(crate) format: FloatFormat,
+    pub(crate) bits: FloatBits<TlweSample>,
+}
+
+impl FheFloat {
+    pub fn format(&self) -> FloatFormat {
+        self.format
+    }
+
+    pub fn bits(&self) -> &FloatBits<TlweSample> {
+        &self.bits
+    }
+}
+
+impl From<FheBool> for Cell<TlweSample> {
+    fn from(value: FheBool) -> Self {
+        Cell::Bool(value.ct)
+    }
+}
+
+impl From<FheUint> for Cell<TlweSample> {
+    fn from(value: FheUint) -> Self {
+        Cell::Uint(value.bits)
+    }
+}
+
+#[cfg(feature = "server")]
+impl Add for &FheUint {
+    type Output = FheUint;
+
+    fn add(self, rhs: &FheUint) -> FheUint {
+        with_server_key(|key| key.add(self, rhs))
+    }
+}
+
+#[cfg(all(test, feature = "client", feature = "server"))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn small_config() -> Config {
+        Config::builder()
+            .lwe_dimension(10)
+            .noise_stddev(1e-9)
+            .build()
+    }
+
+    #[test]
+    fn test_client_key_roundtrip() {
+        let client_key = ClientKey::generate(small_config());
+
+        let t = client_key.encrypt_bool(true);
+        assert!(client_key.decrypt_bool(&t));
+
+        let x = client_key.encrypt_uint(0b1011_0110, 8);
+        assert_eq!(x.width(), 8);
+        assert_eq!(client_key.decrypt_uint(&x), 0b1011_0110);
+
+        let values = [client_key.encrypt_uint(7, 3), x.clone(), client_key.encrypt_uint(1 << 40, 48)];
+        assert_eq!(client_key.decrypt_u32_batch(&values), vec![7, 0b1011_0110, 0]);
+        assert_eq!(client_key.decrypt_bits_batch(&[t.clone(), client_key.encrypt_bool(false)]), vec![true, false]);
+
+        let compressed = x.compress_for_transport(12);
+        let expected: Vec<bool> = (0..8).map(|i| (0b1011_0110 >> i) & 1 == 1).collect();
+        assert_eq!(client_key.decrypt_transport_bits(&compressed), expected);
+
+        let mut pool = client_key.random_pool(4);
+        assert_eq!(pool.take(3).unwrap().len(), 3);
+        assert!(pool.take(2).is_err());
+
+        let wide = client_key.encrypt_u128(1 << 100 | 5, 101);
+        assert_eq!(client_key.decrypt_u128(&wide), 1 << 100 | 5);
+        assert_eq!(client_key.decrypt_uint(&wide), 5);
+
+        let f = client_key.encrypt_float(-3.25, FloatFormat::HALF);
+        assert_eq!(client_key.decrypt_float(&f), -3.25);
+    }
+
+    #[test]
+    fn test_results_switch_to_decryption_key() {
+        let client_key = ClientKey::generate(small_config());
+        let (decryption_key, ksk) = client_key.generate_decryption_key(TlweParams { n: 4, stddev: 1e-7 });
+
+        let x = ksk.switch_uint(&client_key.encrypt_uint(0b1101, 4));
+        assert_eq!(x.bits()[0].params.n, 4);
+        assert_eq!(decryption_key.decrypt_uint(&x), 0b1101);
+        assert!(!decryption_key.decrypt_bool(&ksk.switch_bool(&client_key.encrypt_bool(false))));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_biguint_roundtrip() {
+        let client_key = ClientKey::generate(small_config());
+        let value = BigUint::from(3u8).pow(150);
+
+        let x = client_key.encrypt_biguint(&value, 256);
+        assert_eq!(x.width(), 256);
+        assert_eq!(client_key.decrypt_biguint(&x), value);
+
+        let truncated = client_key.encrypt_biguint(&value, 200);
+        assert_eq!(client_key.decrypt_biguint(&truncated), value % (BigUint::from(1u8) << 200));
+
+        let server_key = ServerKey::new(&client_key);
+        let empty = FheBigUint::from_bits(vec![]);
+        assert_eq!(server_key.biguint_add(&empty, &empty).width(), 0);
+        assert_eq!(server_key.biguint_mul(&empty, &empty).width(), 0);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_biguint_arithmetic_matches_num_bigint() {
+        use crate::tfhe::fixtures::small_params;
+
+        let client_key = ClientKey::generate(Config::builder().params(small_params(32)).build());
+        let server_key = ServerKey::new(&client_key);
+
+        // Operands wider than a u64, results reduced modulo `2^width`
+        let width = 72;
+        let modulus = BigUint::from(1u8) << width;
+        let x = (BigUint::from(1u8) << 70) + BigUint::from(0xdead_beef_u64);
+        let y = (BigUint::from(3u8) << 64) + BigUint::from(u64::MAX);
+        let (a, b) = (client_key.encrypt_biguint(&x, width), client_key.encrypt_biguint(&y, width));
+
+        let decrypt = |v: &FheBigUint| client_key.decrypt_biguint(v);
+        assert_eq!(decrypt(&server_key.biguint_add(&a, &b)), (&x + &y) % &modulus);
+        assert_eq!(decrypt(&server_key.biguint_sub(&a, &b)), (&x + &modulus - &y) % &modulus);
+        assert_eq!(decrypt(&server_key.biguint_sub(&b, &a)), (&y + &modulus - &x) % &modulus);
+
+        // A full 72 by 72 bit product costs thousands of bootstraps; an
+        // encrypted 3-bit factor padded with public zeros still wraps
+        let z = BigUint::from(5u8);
+        let c = FheBigUint::from_bits(IntegerOps::resize(&client_key.encrypt_biguint(&z, 3).bits, width, &server_key.evaluator()));
+        assert_eq!(decrypt(&server_key.biguint_mul(&a, &c)), (&x * &z) % &modulus);
+
+        for (op, expected) in [(CmpOp::Lt, x < y), (CmpOp::Ge, x >= y)] {
+            assert_eq!(client_key.decrypt_bool(&server_key.biguint_compare(&a, &b, op)), expected, "{:?}", op);
+        }
+        assert!(client_key.decrypt_bool(&server_key.biguint_compare(&a, &a, CmpOp::Eq)));
+    }
+
+    #[test]
+    fn test_eval_profile_on_first_use() {
+        let (client_key, server_key) = generate_keys(small_config());
+        let a = client_key.encrypt_uint(3, 2);
+        let b = client_key.encrypt_uint(2, 2);
+
+        let uncalibrated = server_key.clone();
+        uncalibrated.mul(&a, &b);
+        assert_eq!(uncalibrated.eval_profile(), Some(&EvalProfile::default()));
+
+        let calibrated = server_key.clone().calibrate_on_first_use();
+        assert_eq!(calibrated.eval_profile(), None);
+        // Zero-width operands have nothing to calibrate on
+        let empty = FheUint::from_bits(vec![]);
+        assert_eq!(calibrated.mul(&empty, &empty).width(), 0);
+        assert_eq!(client_key.decrypt_uint(&calibrated.mul(&a, &empty)), 0);
+        assert_eq!(calibrated.eval_profile(), None);
+        calibrated.mul(&a, &b);
+        let profile = calibrated.eval_profile().unwrap().clone();
+        assert!(profile.bootstrap_latency > Duration::ZERO);
+
+        // A persisted profile skips calibration
+        let restored = EvalProfile::from_json(&profile.to_json()).unwrap();
+        let reloaded = server_key.calibrate_on_first_use().with_eval_profile(restored);
+        reloaded.mul(&a, &b);
+        assert_eq!(reloaded.eval_profile(), Some(&profile));
+    }
+
+    #[test]
+    fn test_operators_use_installed_server_key() {
+        let (client_key, server_key) = generate_keys(small_config());
+        set_server_key(server_key);
+
+        for (x, y) in [(false, false), (false, true), (true, false), (true, true)] {
+            let a = client_key.encrypt_bool(x);
+            let b = client_key.encrypt_bool(y);
+            let c = &(&a & &b) ^ &!&a;
+            assert_eq!(client_key.decrypt_bool(&c), (x & y) ^ !x, "{} {}", x, y);
+        }
+
+        unset_server_key();
+    }
+}
@@ -0,0 +1,286 @@
+//! Bootstrapping many independent samples in one pass.
+//!
+//! Refreshing a wide word bootstraps every bit with the same table, so the
+//! per-call setup of `TfheGates::programmable_bootstrap` is repeated for
+//! each of them. `programmable_bootstrap_batch` builds the test polynomial
+//! once, keeps one TRLWE accumulator per input and walks the bootstrapping
+//! key entry by entry, running the CMUX of each entry's FFT-domain rows
+//! against every accumulator before moving on. This is not a packed
+//! bootstrap: every input still takes a blind rotation of its own, since
+//! each rotates by its own phase, and only the setup and the pass over the
+//! key are shared. `refresh_packed` hands the refreshed bits to a
+//! `PackingKey`, returning the whole word as one TRLWE sample.
+//!
+//! `and_slice` and the other elementwise gates batch the bootstraps of a
+//! gate across whole bit vectors the same way; pairs with a public operand
+//! fold without one. With the `parallel` feature the accumulators of each
+//! row, and the key switches after them, are spread over rayon's pool.
+
+#[cfg(feature = "server")]
+use std::time::Instant;
+#[cfg(feature = "server")]
+use crate::hooks::BootstrapInfo;
+#[cfg(feature = "server")]
+use crate::lutdebug::TestVector;
+#[cfg(feature = "server")]
+use crate::noise::NoiseLevel;
+#[cfg(feature = "server")]
+use crate::packed::PackingKey;
+#[cfg(feature = "server")]
+use crate::tfhe::{bool_phase, gate_lut, GateSpec, TfheCloudKey, TfheGates};
+#[cfg(feature = "server")]
+use crate::tgsw::BootstrappingKey;
+#[cfg(feature = "server")]
+use crate::lwe::LweCiphertext;
+#[cfg(feature = "server")]
+use crate::tlwe::TlweSample;
+#[cfg(feature = "server")]
+use crate::torus::Torus;
+#[cfg(feature = "server")]
+use crate::trlwe::TrlweSample;
+
+#[cfg(feature = "server")]
+impl TfheGates {
+    /// `programmable_bootstrap` of every input with the same `lut`, outputs
+    /// in input order under `TfheSecretKey::extracted_key`
+    pub fn programmable_bootstrap_batch(
+        inputs: &[TlweSample],
+        lut: &[Torus],
+        bk: &BootstrappingKey,
+    ) -> Vec<TlweSample> {
+        let ring = &bk.ring_params;
+        let two_n = 2 * ring.N;
+        let test = TestVector::from_lut(lut, ring.N);
+        let start = TrlweSample::trivial(&test.poly, ring.clone());
+
+        let switched: Vec<LweCiphertext> = inputs
+            .iter()
+            .map(|input| {
+                assert_eq!(input.a.len(), bk.n, "input dimension does not match the bootstrapping key");
+                input.modulus_switch(two_n as u64)
+            })
+            .collect();
+        let mut accs: Vec<TrlweSample> =
+            switched.iter().map(|s| start.mul_by_xai(two_n - s.b as usize)).collect();
+
+        for i in 0..bk.n {
+            let step = |(acc, s): (&mut TrlweSample, &LweCiphertext)| {
+                let power = s.a[i] as usize;
+                if power != 0 {
+                    *acc = bk.cmux(i, acc, &acc.mul_by_xai(power));
+                }
+            };
+            // The accumulators of a row are independent
+            #[cfg(feature = "parallel")]
+            {
+                use rayon::prelude::*;
+                accs.par_iter_mut().zip(switched.par_iter()).for_each(step);
+            }
+            #[cfg(not(feature = "parallel"))]
+            accs.iter_mut().zip(&switched).for_each(step);
+        }
+
+        accs.iter()
+            .map(|acc| {
+                let mut output = TlweSample::extract_from_trlwe(acc, 0);
+                output.noise = NoiseLevel::Fresh;
+                output.b = output.b.add(&test.offset);
+                output
+            })
+            .collect()
+    }
+
+    /// Bootstraps every input with `lut` and key switches the results back
+    /// to the LWE key. Hooks see each input and output; the elapsed time
+    /// they get is the batch's share per sample.
+    pub fn bootstrap_batch(
+        gate: &'static str,
+        inputs: &[TlweSample],
+        lut: &[Torus],
+        ck: &TfheCloudKey,
+    ) -> Vec<TlweSample> {
+        if inputs.is_empty() {
+            return Vec::new();
+        }
+        let info = BootstrapInfo {
+            gate,
+            input_dimension: inputs[0].params.n,
+            lut_size: lut.len(),
+        };
+
+        for input in inputs {
+            assert!(
+                !input.noise.needs_bootstrap(),
+                "{} gate input is past its noise budget; an operand needed a bootstrap first",
+                gate,
+            );
+            ck.expect_namespace(gate, input);
+            ck.hooks.before(&info, input);
+        }
+        let start = Instant::now();
+        let extracted = Self::programmable_bootstrap_batch(inputs, lut, &ck.bootstrapping_key);
+        #[cfg(feature = "parallel")]
+        let mut outputs: Vec<TlweSample> = {
+            use rayon::prelude::*;
+            extracted.par_iter().map(|s| Self::key_switch(s, ck)).collect()
+        };
+        #[cfg(not(feature = "parallel"))]
+        let mut outputs: Vec<TlweSample> = extracted.iter().map(|s| Self::key_switch(s, ck)).collect();
+        let share = start.elapsed() / inputs.len() as u32;
+        for output in &mut outputs {
+            output.noise = NoiseLevel::Fresh;
+            ck.hooks.after(&info, output, share);
+        }
+
+        outputs
+    }
+
+    /// Fresh gate-encoded copies of `bits`
+    pub fn refresh_batch(bits: &[TlweSample], ck: &TfheCloudKey) -> Vec<TlweSample> {
+        let mut lut = vec![bool_phase(true); 1024];
+        lut[..256].fill(bool_phase(false));
+        lut[768..].fill(bool_phase(false));
+        Self::bootstrap_batch("refresh", bits, &lut, ck)
+    }
+
+    // `spec` on each pair of `a` and `b`, the unfolded ones bootstrapped as one batch
+    fn gate_slice(spec: &GateSpec, a: &[TlweSample], b: &[TlweSample], ck: &TfheCloudKey) -> Vec<TlweSample> {
+        assert_eq!(a.len(), b.len(), "{} gate over slices of different lengths", spec.name);
+        let folded: Vec<Option<TlweSample>> = a.iter().zip(b).map(|(x, y)| Self::fold(spec.f, x, y)).collect();
+        let inputs: Vec<TlweSample> = a.iter()
+            .zip(b)
+            .zip(&folded)
+            .filter(|(_, folded)| folded.is_none())
+            .map(|((x, y), _)| Self::gate_input(spec, x, y, ck))
+            .collect();
+
+        let mut outputs = Self::bootstrap_batch(spec.name, &inputs, &gate_lut(), ck).into_iter();
+        folded.into_iter()
+            .map(|bit| bit.unwrap_or_else(|| outputs.next().expect("one output per unfolded pair")))
+            .collect()
+    }
+
+    /// `and` of `a[i]` and `b[i]` for every `i`
+    pub fn and_slice(a: &[TlweSample], b: &[TlweSample], ck: &TfheCloudKey) -> Vec<TlweSample> {
+        Self::gate_slice(&GateSpec::AND, a, b, ck)
+    }
+
+    pub fn or_slice(a: &[TlweSample], b: &[TlweSample], ck: &TfheCloudKey) -> Vec<TlweSample> {
+        Self::gate_slice(&GateSpec::OR, a, b, ck)
+    }
+
+    pub fn xor_slice(a: &[TlweSample], b: &[TlweSample], ck: &TfheCloudKey) -> Vec<TlweSample> {
+        Self::gate_slice(&GateSpec::XOR, a, b, ck)
+    }
+
+    pub fn nand_slice(a: &[TlweSample], b: &[TlweSample], ck: &TfheCloudKey) -> Vec<TlweSample> {
+        Self::gate_slice(&GateSpec::NAND, a, b, ck)
+    }
+
+    pub fn nor_slice(a: &[TlweSample], b: &[TlweSample], ck: &TfheCloudKey) -> Vec<TlweSample> {
+        Self::gate_slice(&GateSpec::NOR, a, b, ck)
+    }
+
+    pub fn xnor_slice(a: &[TlweSample], b: &[TlweSample], ck: &TfheCloudKey) -> Vec<TlweSample> {
+        Self::gate_slice(&GateSpec::XNOR, a, b, ck)
+    }
+
+    /// `!a[i] & b[i]`
+    pub fn andny_slice(a: &[TlweSample], b: &[TlweSample], ck: &TfheCloudKey) -> Vec<TlweSample> {
+        Self::gate_slice(&GateSpec::ANDNY, a, b, ck)
+    }
+
+    /// `a[i] & !b[i]`
+    pub fn andyn_slice(a: &[TlweSample], b: &[TlweSample], ck: &TfheCloudKey) -> Vec<TlweSample> {
+        Self::gate_slice(&GateSpec::ANDYN, a, b, ck)
+    }
+
+    /// `!a[i] | b[i]`
+    pub fn orny_slice(a: &[TlweSample], b: &[TlweSample], ck: &TfheCloudKey) -> Vec<TlweSample> {
+        Self::gate_slice(&GateSpec::ORNY, a, b, ck)
+    }
+
+    /// `a[i] | !b[i]`
+    pub fn oryn_slice(a: &[TlweSample], b: &[TlweSample], ck: &TfheCloudKey) -> Vec<TlweSample> {
+        Self::gate_slice(&GateSpec::ORYN, a, b, ck)
+    }
+
+    /// `not` of every bit; linear, so no bootstrap
+    pub fn not_slice(a: &[TlweSample]) -> Vec<TlweSample> {
+        a.iter().map(Self::not).collect()
+    }
+
+    /// `refresh_batch` with the refreshed bits packed into the slots of one
+    /// TRLWE sample, bit `i` in slot `i`
+    pub fn refresh_packed(bits: &[TlweSample], ck: &TfheCloudKey, pk: &PackingKey) -> TrlweSample {
+        pk.pack(&Self::refresh_batch(bits, ck))
+    }
+}
+
+#[cfg(all(test, feature = "client", feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::tfhe::TfheEncoder;
+    use crate::tfhe::fixtures::small_keys;
+
+    #[test]
+    fn test_batch_matches_single_bootstraps() {
+        let (sk, ck) = small_keys(16);
+        let pk = PackingKey::generate(&sk, sk.params.key_switch_params());
+
+        let bits = [true, false, false, true, true, false];
+        let inputs: Vec<TlweSample> = bits.iter().map(|&b| TfheEncoder::encode_bool(b, &sk)).collect();
+        let mut lut = vec![Torus::new(0.125); 1024];
+        lut[256..768].fill(Torus::new(0.625));
+
+        let batch = TfheGates::programmable_bootstrap_batch(&inputs, &lut, &ck.bootstrapping_key);
+        for (input, out) in inputs.iter().zip(&batch) {
+            let single = TfheGates::programmable_bootstrap(input, &lut, &ck.bootstrapping_key);
+            assert_eq!(out.a, single.a);
+            assert_eq!(out.b, single.b);
+        }
+
+        let refreshed = TfheGates::refresh_batch(&inputs, &ck);
+        let decoded: Vec<bool> = refreshed.iter().map(|s| TfheEncoder::decode_bool(s, &sk)).collect();
+        assert_eq!(decoded, bits);
+
+        let packed = TfheGates::refresh_packed(&inputs, &ck, &pk);
+        assert_eq!(TfheEncoder::decode_packed(&packed, bits.len(), &sk), bits);
+        assert!(TfheGates::refresh_batch(&[], &ck).is_empty());
+    }
+
+    #[test]
+    fn test_gate_slices() {
+        let (sk, ck) = small_keys(32);
+
+        // Every pair of bits, then public operands that fold
+        let x = [false, false, true, true, true, false];
+        let y = [false, true, false, true, true, true];
+        let mut a = TfheEncoder::encode_bits(&x, &sk);
+        let mut b = TfheEncoder::encode_bits(&y, &sk);
+        a[4] = TfheGates::constant(x[4], &ck);
+        b[5] = TfheGates::constant(y[5], &ck);
+
+        type Slice = fn(&[TlweSample], &[TlweSample], &TfheCloudKey) -> Vec<TlweSample>;
+        type Clear = fn(bool, bool) -> bool;
+        let gates: [(Slice, Clear); 10] = [
+            (TfheGates::and_slice, |x, y| x && y),
+            (TfheGates::or_slice, |x, y| x || y),
+            (TfheGates::xor_slice, |x, y| x ^ y),
+            (TfheGates::nand_slice, |x, y| !(x && y)),
+            (TfheGates::nor_slice, |x, y| !(x || y)),
+            (TfheGates::xnor_slice, |x, y| x == y),
+            (TfheGates::andny_slice, |x, y| !x && y),
+            (TfheGates::andyn_slice, |x, y| x && !y),
+            (TfheGates::orny_slice, |x, y| !x || y),
+            (TfheGates::oryn_slice, |x, y| x || !y),
+        ];
+        for (i, (gate, f)) in gates.iter().enumerate() {
+            let expected: Vec<bool> = x.iter().zip(&y).map(|(&x, &y)| f(x, y)).collect();
+            assert_eq!(TfheEncoder::decode_bits(&gate(&a, &b, &ck), &sk), expected, "gate {}", i);
+        }
+        let inverted: Vec<bool> = x.iter().map(|x| !x).collect();
+        assert_eq!(TfheEncoder::decode_bits(&TfheGates::not_slice(&a), &sk), inverted);
+        assert!(TfheGates::and_slice(&[], &[], &ck).is_empty());
+    }
+}
@@ -0,0 +1,225 @@
+//! Per-request limits on homomorphic evaluation.
+//!
+//! A server evaluating requests from many clients wraps each request's
+//! evaluator in an `EvalContext` with an `EvaluationBudget`. Every gate is
+//! charged its `Node::bootstraps` cost and the wall clock is checked before
+//! it runs. Once a limit is hit the context records a `BudgetExceeded` and
+//! answers all further gates with public constants, so the rest of the
+//! computation unwinds without bootstrapping; `EvalContext::run` then
+//! returns the error instead of the meaningless result. The counters are
+//! atomic, so one context can be shared by the threads of a request.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use crate::circuit::{Node, Wire};
+use crate::evaluator::BooleanEvaluator;
+
+/// Limits for one request; `None` leaves a dimension unbounded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvaluationBudget {
+    pub max_bootstraps: Option<u64>,
+    pub max_wall_clock: Option<Duration>,
+}
+
+impl EvaluationBudget {
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    pub fn bootstraps(mut self, max: u64) -> Self {
+        self.max_bootstraps = Some(max);
+        self
+    }
+
+    pub fn wall_clock(mut self, max: Duration) -> Self {
+        self.max_wall_clock = Some(max);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetExceeded {
+    /// The next gate would have brought the count to `needed`
+    Bootstraps { limit: u64, needed: u64 },
+    WallClock { limit: Duration, elapsed: Duration },
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetExceeded::Bootstraps { limit, needed } => {
+                write!(f, "evaluation needs more than {} bootstraps (at least {})", limit, needed)
+            }
+            BudgetExceeded::WallClock { limit, elapsed } => {
+                write!(f, "evaluation exceeded its {:?} time budget after {:?}", limit, elapsed)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/// Evaluator charging every gate against an `EvaluationBudget`
+pub struct EvalContext<E: BooleanEvaluator> {
+    eval: E,
+    budget: EvaluationBudget,
+    start: Instant,
+    bootstraps: AtomicU64,
+    exceeded: OnceLock<BudgetExceeded>,
+}
+
+impl<E: BooleanEvaluator> EvalContext<E> {
+    /// Starts the wall clock
+    pub fn new(eval: E, budget: EvaluationBudget) -> Self {
+        EvalContext {
+            eval,
+            budget,
+            start: Instant::now(),
+            bootstraps: AtomicU64::new(0),
+            exceeded: OnceLock::new(),
+        }
+    }
+
+    /// Evaluates `f` within `budget`, or reports the first limit it hit
+    pub fn run<T>(eval: E, budget: EvaluationBudget, f: impl FnOnce(&Self) -> T) -> Result<T, BudgetExceeded> {
+        let context = Self::new(eval, budget);
+        let result = f(&context);
+        context.check().map(|_| result)
+    }
+
+    pub fn bootstraps_used(&self) -> u64 {
+        self.bootstraps.load(Ordering::Relaxed)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// The first limit hit so far, if any
+    pub fn check(&self) -> Result<(), BudgetExceeded> {
+        match self.exceeded.get() {
+            Some(&err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    // Charges a gate; `false` once any limit is exhausted
+    fn charge(&self, node: Node) -> bool {
+        if self.exceeded.get().is_some() {
+            return false;
+        }
+        if let Some(limit) = self.budget.max_wall_clock {
+            let elapsed = self.elapsed();
+            if elapsed > limit {
+                self.exceeded.get_or_init(|| BudgetExceeded::WallClock { limit, elapsed });
+                return false;
+            }
+        }
+
+        let cost = node.bootstraps() as u64;
+        let before = self.bootstraps.fetch_add(cost, Ordering::Relaxed);
+        match self.budget.max_bootstraps {
+            Some(limit) if before + cost > limit => {
+                self.exceeded.get_or_init(|| BudgetExceeded::Bootstraps { limit, needed: before + cost });
+                false
+            }
+            _ => true,
+        }
+    }
+}
+
+impl<E: BooleanEvaluator> BooleanEvaluator for EvalContext<E> {
+    type Bit = E::Bit;
+
+    fn constant(&self, value: bool) -> E::Bit {
+        self.eval.constant(value)
+    }
+
+    fn not(&self, a: &E::Bit) -> E::Bit {
+        if !self.charge(Node::Not(Wire(0))) {
+            return self.eval.constant(false);
+        }
+        self.eval.not(a)
+    }
+
+    fn and(&self, a: &E::Bit, b: &E::Bit) -> E::Bit {
+        if !self.charge(Node::And(Wire(0), Wire(0))) {
+            return self.eval.constant(false);
+        }
+        self.eval.and(a, b)
+    }
+
+    fn or(&self, a: &E::Bit, b: &E::Bit) -> E::Bit {
+        if !self.charge(Node::Or(Wire(0), Wire(0))) {
+            return self.eval.constant(false);
+        }
+        self.eval.or(a, b)
+    }
+
+    fn xor(&self, a: &E::Bit, b: &E::Bit) -> E::Bit {
+        if !self.charge(Node::Xor(Wire(0), Wire(0))) {
+            return self.eval.constant(false);
+        }
+        self.eval.xor(a, b)
+    }
+
+    fn nand(&self, a: &E::Bit, b: &E::Bit) -> E::Bit {
+        if !self.charge(Node::Nand(Wire(0), Wire(0))) {
+            return self.eval.constant(false);
+        }
+        self.eval.nand(a, b)
+    }
+
+    fn mux(&self, s: &E::Bit, a: &E::Bit, b: &E::Bit) -> E::Bit {
+        if !self.charge(Node::Mux(Wire(0), Wire(0), Wire(0))) {
+            return self.eval.constant(false);
+        }
+        self.eval.mux(s, a, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+    use crate::integer::IntegerOps;
+
+    #[test]
+    fn test_within_budget() {
+        let budget = EvaluationBudget::unlimited().bootstraps(1000).wall_clock(Duration::from_secs(60));
+        let sum = EvalContext::run(ClearEvaluator, budget, |eval| {
+            let a = IntegerOps::constant(5, 4, eval);
+            let b = IntegerOps::constant(6, 4, eval);
+            IntegerOps::add_mod(&a, &b, eval)
+        });
+        assert_eq!(sum.unwrap(), IntegerOps::constant(11, 4, &ClearEvaluator));
+    }
+
+    #[test]
+    fn test_bootstrap_limit_aborts() {
+        let context = EvalContext::new(ClearEvaluator, EvaluationBudget::unlimited().bootstraps(3));
+        context.and(&true, &true);
+        context.mux(&true, &true, &false);
+        assert!(context.not(&false));
+        assert_eq!(context.bootstraps_used(), 3);
+        assert!(context.check().is_ok());
+
+        // Past the limit gates stop evaluating and the error sticks
+        assert!(!context.xor(&true, &false));
+        assert_eq!(context.check(), Err(BudgetExceeded::Bootstraps { limit: 3, needed: 4 }));
+        context.nand(&false, &false);
+        assert_eq!(context.bootstraps_used(), 4);
+        assert!(context.check().unwrap_err().to_string().contains("more than 3 bootstraps"));
+    }
+
+    #[test]
+    fn test_wall_clock_limit_aborts() {
+        let result = EvalContext::run(ClearEvaluator, EvaluationBudget::unlimited().wall_clock(Duration::ZERO), |eval| {
+            std::thread::sleep(Duration::from_millis(1));
+            eval.xor(&true, &false)
+        });
+        assert!(matches!(result, Err(BudgetExceeded::WallClock { .. })));
+    }
+}
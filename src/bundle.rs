@@ -0,0 +1,210 @@
+//! Output ciphertexts packaged with the setup that produced them.
+//!
+//! A server returns a `ResultBundle` instead of bare samples: the outputs
+//! together with the digest of the cloud key they were computed under, the
+//! digest of the parameter set, the crate version and how many operations
+//! the evaluation ran. The bundle has one canonical encoding, so it travels
+//! and is stored as a single unit. Before decrypting, a client calls
+//! `verify` with the key digest and parameters it expects; outputs from
+//! another key, another parameter set or another build are rejected
+//! instead of decrypting to plausible-looking garbage.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::canonical::{
+    write_u64, write_vec, Canonical, CanonicalError, CanonicalReader, ParamsDigest, ParamsMismatch, Parameterized,
+};
+use crate::circuit::Node;
+use crate::oplog::OpLog;
+use crate::tfhe::{TfheCloudKey, TfheParams};
+use crate::tlwe::TlweSample;
+
+/// Version of the crate that builds bundles
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Gates run by an evaluation, by name, and the bootstraps they cost
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpCounts {
+    pub gates: BTreeMap<String, u64>,
+    pub bootstraps: u64,
+}
+
+impl OpCounts {
+    pub fn record(&mut self, node: &Node) {
+        if matches!(node, Node::Input(_) | Node::Constant(_)) {
+            return;
+        }
+        *self.gates.entry(node.name().to_string()).or_default() += 1;
+        self.bootstraps += node.bootstraps() as u64;
+    }
+
+    /// Counts of the steps of a recorded evaluation
+    pub fn from_log(log: &OpLog) -> Self {
+        let mut counts = OpCounts::default();
+        for step in &log.steps {
+            counts.record(&step.node);
+        }
+        counts
+    }
+
+    pub fn total_gates(&self) -> u64 {
+        self.gates.values().sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BundleError {
+    /// The outputs were computed under another cloud key
+    CloudKey,
+    Params(ParamsMismatch),
+    /// Built by another version of the crate
+    Version { expected: String, found: String },
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BundleError::CloudKey => write!(f, "result was computed under a different cloud key"),
+            BundleError::Params(e) => write!(f, "{}", e),
+            BundleError::Version { expected, found } => {
+                write!(f, "result was built by version {}, expected {}", found, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+impl From<ParamsMismatch> for BundleError {
+    fn from(e: ParamsMismatch) -> Self {
+        BundleError::Params(e)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResultBundle {
+    pub outputs: Vec<TlweSample>,
+    /// `TfheCloudKey::digest` of the evaluating key
+    pub cloud_key: [u8; 32],
+    pub params: ParamsDigest,
+    pub crate_version: String,
+    pub ops: OpCounts,
+}
+
+impl ResultBundle {
+    pub fn new(outputs: Vec<TlweSample>, cloud_key: &TfheCloudKey, params: &TfheParams, ops: OpCounts) -> Self {
+        ResultBundle {
+            outputs,
+            cloud_key: cloud_key.digest(),
+            params: ParamsDigest::of(params),
+            crate_version: CRATE_VERSION.to_string(),
+            ops,
+        }
+    }
+
+    /// The outputs, if the bundle was produced under `cloud_key` and
+    /// `params` by this version of the crate
+    pub fn verify(&self, cloud_key: &[u8; 32], params: &TfheParams) -> Result<&[TlweSample], BundleError> {
+        if self.crate_version != CRATE_VERSION {
+            return Err(BundleError::Version { expected: CRATE_VERSION.to_string(), found: self.crate_version.clone() });
+        }
+        let expected = ParamsDigest::of(params);
+        if self.params != expected {
+            return Err(ParamsMismatch { expected, found: self.params }.into());
+        }
+        if &self.cloud_key != cloud_key {
+            return Err(BundleError::CloudKey);
+        }
+        for output in &self.outputs {
+            output.check_params(&params.tlwe_params)?;
+        }
+        Ok(&self.outputs)
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_u64(out, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn read_str(input: &mut CanonicalReader<'_>) -> Result<String, CanonicalError> {
+    let len = input.usize()?;
+    let bytes = input.bytes(len)?;
+    String::from_utf8(bytes.to_vec()).map_err(|_| CanonicalError::Invalid("string"))
+}
+
+impl Canonical for ResultBundle {
+    const TAG: u8 = 10;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_vec(out, &self.outputs);
+        out.extend_from_slice(&self.cloud_key);
+        out.extend_from_slice(&self.params.0);
+        write_str(out, &self.crate_version);
+        write_u64(out, self.ops.gates.len() as u64);
+        for (gate, count) in &self.ops.gates {
+            write_str(out, gate);
+            write_u64(out, *count);
+        }
+        write_u64(out, self.ops.bootstraps);
+    }
+
+    fn read(input: &mut CanonicalReader<'_>) -> Result<Self, CanonicalError> {
+        let outputs = input.vec()?;
+        let cloud_key = input.bytes(32)?.try_into().expect("32 bytes");
+        let params = ParamsDigest(input.bytes(8)?.try_into().expect("eight bytes"));
+        let crate_version = read_str(input)?;
+
+        let mut ops = OpCounts::default();
+        let kinds = input.usize()?;
+        for _ in 0..kinds {
+            let gate = read_str(input)?;
+            if ops.gates.insert(gate, input.u64()?).is_some() {
+                return Err(CanonicalError::Invalid("duplicate gate count"));
+            }
+        }
+        ops.bootstraps = input.u64()?;
+        Ok(ResultBundle { outputs, cloud_key, params, crate_version, ops })
+    }
+}
+
+#[cfg(all(test, feature = "client", feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::evaluator::{BooleanEvaluator, TfheEvaluator};
+    use crate::oplog::Recording;
+    use crate::tfhe::TfheEncoder;
+    use crate::tfhe::fixtures::small_keys;
+
+    #[test]
+    fn test_bundle_round_trip_and_verify() {
+        let (sk, ck) = small_keys(64);
+        let params = sk.params.clone();
+
+        let eval = Recording::new(TfheEvaluator::new(&ck));
+        let a = eval.input(TfheEncoder::encode_bool(true, &sk));
+        let b = eval.input(TfheEncoder::encode_bool(false, &sk));
+        let out = eval.not(&a);
+        let log = eval.finish(&[out.clone(), b]);
+        let ops = OpCounts::from_log(&log);
+        assert_eq!(ops.total_gates(), 1);
+        assert_eq!(ops.gates["NOT"], 1);
+
+        let bundle = ResultBundle::new(vec![out.bit], &ck, &params, ops);
+        let decoded = ResultBundle::from_canonical_bytes(&bundle.to_canonical_bytes()).unwrap();
+        assert_eq!(decoded.cloud_key, bundle.cloud_key);
+        assert_eq!(decoded.ops, bundle.ops);
+
+        let outputs = decoded.verify(&ck.digest(), &params).unwrap();
+        assert!(!TfheEncoder::decode_bool(&outputs[0], &sk));
+
+        let other = TfheCloudKey::generate(&sk);
+        assert!(matches!(decoded.verify(&other.digest(), &params), Err(BundleError::CloudKey)));
+        let mut wrong = params.clone();
+        wrong.N = 128;
+        assert!(matches!(decoded.verify(&ck.digest(), &wrong), Err(BundleError::Params(_))));
+        let mut old = decoded.clone();
+        old.crate_version = "0.0.0".to_string();
+        assert!(matches!(old.verify(&ck.digest(), &params), Err(BundleError::Version { .. })));
+    }
+}
@@ -0,0 +1,616 @@
+//! Canonical byte encoding of parameters and ciphertexts.
+//!
+//! The encoding is fully determined by the value: fields in declaration
+//! order, integers as little-endian `u64`, sequences prefixed by their
+//! length, and torus elements as the 64-bit fixed-point integer
+//! `round(x 2^64)`. No float arithmetic is involved for the integer torus
+//! backends, and the `f64` backend only scales by a power of two, so the same
+//! value hashes to the same digest on every platform, which caching, deduplication and signatures over
+//! ciphertexts rely on. Each top-level encoding starts with a type tag and a
+//! format version.
+//!
+//! Samples and keys embed a `ParamsDigest` of their parameter set right after
+//! the parameters. Reading checks it, and `Parameterized::from_canonical_bytes_for`
+//! additionally checks it against the parameters the caller works with, so
+//! an artifact from the wrong parameter set fails with `ParamsMismatch`
+//! instead of decrypting to garbage.
+//!
+//! Samples may further carry the `Namespace` of the tenant whose cloud key
+//! produced them, and operations refuse to combine two namespaces with a
+//! `NamespaceMismatch` even when the parameters agree.
+
+use std::fmt;
+use sha2::{Digest, Sha256};
+use crate::tfhe::{TfheCloudKey, TfheParams};
+#[cfg(feature = "client")]
+use crate::tfhe::TfheSecretKey;
+use crate::tgsw::TgswParams;
+use crate::tlwe::{TlweParams, TlweSample};
+#[cfg(feature = "client")]
+use crate::tlwe::TlweSecretKey;
+#[cfg(feature = "client")]
+use crate::trlwe::TrlweSecretKey;
+use crate::noise::{NoiseEstimate, NoiseLevel};
+use crate::torus::Torus;
+
+pub const CANONICAL_VERSION: u8 = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CanonicalError {
+    UnexpectedEnd,
+    WrongTag { expected: u8, found: u8 },
+    UnsupportedVersion(u8),
+    TrailingBytes(usize),
+    /// A field holds a value its type cannot take
+    Invalid(&'static str),
+    ParamsMismatch(ParamsMismatch),
+}
+
+impl fmt::Display for CanonicalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CanonicalError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            CanonicalError::WrongTag { expected, found } => {
+                write!(f, "expected type tag {}, found {}", expected, found)
+            }
+            CanonicalError::UnsupportedVersion(v) => write!(f, "unsupported canonical format version {}", v),
+            CanonicalError::TrailingBytes(n) => write!(f, "{} trailing bytes after the value", n),
+            CanonicalError::Invalid(what) => write!(f, "invalid {}", what),
+            CanonicalError::ParamsMismatch(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CanonicalError {}
+
+impl From<ParamsMismatch> for CanonicalError {
+    fn from(e: ParamsMismatch) -> Self {
+        CanonicalError::ParamsMismatch(e)
+    }
+}
+
+/// First eight bytes of the canonical digest of a parameter set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParamsDigest(pub [u8; 8]);
+
+impl ParamsDigest {
+    pub fn of<P: Canonical>(params: &P) -> Self {
+        let digest = params.canonical_digest();
+        ParamsDigest(digest[..8].try_into().expect("eight bytes"))
+    }
+}
+
+impl fmt::Display for ParamsDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+/// An artifact generated under one parameter set met another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParamsMismatch {
+    pub expected: ParamsDigest,
+    pub found: ParamsDigest,
+}
+
+impl fmt::Display for ParamsMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "parameter digest mismatch: expected {}, found {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for ParamsMismatch {}
+
+/// Application-defined tenant of a cloud key and the ciphertexts it
+/// produces: the first eight bytes of the SHA-256 of its name. Samples
+/// without one, such as fresh encryptions and constants, combine with any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Namespace(pub [u8; 8]);
+
+impl Namespace {
+    pub fn new(name: &str) -> Self {
+        let digest = Sha256::digest(name.as_bytes());
+        Namespace(digest[..8].try_into().expect("eight bytes"))
+    }
+
+    /// Namespace of a combination of `expected` and `found`
+    pub fn join(expected: Option<Namespace>, found: Option<Namespace>) -> Result<Option<Namespace>, NamespaceMismatch> {
+        match (expected, found) {
+            (Some(expected), Some(found)) if expected != found => Err(NamespaceMismatch { expected, found }),
+            _ => Ok(expected.or(found)),
+        }
+    }
+}
+
+impl fmt::Display for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+/// Ciphertexts of two tenants met in one operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NamespaceMismatch {
+    pub expected: Namespace,
+    pub found: Namespace,
+}
+
+impl fmt::Display for NamespaceMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "namespace mismatch: expected {}, found {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for NamespaceMismatch {}
+
+/// Cursor over canonical bytes
+pub struct CanonicalReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> CanonicalReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        CanonicalReader { bytes, pos: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], CanonicalError> {
+        if self.remaining() < n {
+            return Err(CanonicalError::UnexpectedEnd);
+        }
+        let slice = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(slice)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, CanonicalError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u64(&mut self) -> Result<u64, CanonicalError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().expect("eight bytes")))
+    }
+
+    pub fn usize(&mut self) -> Result<usize, CanonicalError> {
+        usize::try_from(self.u64()?).map_err(|_| CanonicalError::Invalid("length"))
+    }
+
+    pub fn f64(&mut self) -> Result<f64, CanonicalError> {
+        Ok(f64::from_bits(self.u64()?))
+    }
+
+    /// Exactly `n` raw bytes
+    pub fn bytes(&mut self, n: usize) -> Result<&'a [u8], CanonicalError> {
+        self.take(n)
+    }
+
+    /// Length-prefixed sequence
+    pub fn vec<T: Canonical>(&mut self) -> Result<Vec<T>, CanonicalError> {
+        let len = self.usize()?;
+        // Every element takes at least one byte, so a huge length is malformed
+        if len > self.remaining() {
+            return Err(CanonicalError::UnexpectedEnd);
+        }
+        (0..len).map(|_| T::read(self)).collect()
+    }
+}
+
+pub fn write_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+pub fn write_f64(out: &mut Vec<u8>, value: f64) {
+    let value = if value == 0.0 { 0.0 } else { value };
+    write_u64(out, value.to_bits());
+}
+
+pub fn write_vec<T: Canonical>(out: &mut Vec<u8>, items: &[T]) {
+    write_u64(out, items.len() as u64);
+    for item in items {
+        item.write(out);
+    }
+}
+
+/// Parameters followed by their digest
+pub fn write_params<P: Canonical>(out: &mut Vec<u8>, params: &P) {
+    params.write(out);
+    out.extend_from_slice(&ParamsDigest::of(params).0);
+}
+
+/// Reads what `write_params` wrote, rejecting a digest that does not match
+pub fn read_params<P: Canonical>(input: &mut CanonicalReader<'_>) -> Result<P, CanonicalError> {
+    let params = P::read(input)?;
+    let embedded = ParamsDigest(input.bytes(8)?.try_into().expect("eight bytes"));
+    let computed = ParamsDigest::of(&params);
+    if embedded != computed {
+        return Err(ParamsMismatch { expected: embedded, found: computed }.into());
+    }
+    Ok(params)
+}
+
+/// A zero byte, or a one followed by the namespace
+pub fn write_namespace(out: &mut Vec<u8>, namespace: Option<Namespace>) {
+    match namespace {
+        None => out.push(0),
+        Some(ns) => {
+            out.push(1);
+            out.extend_from_slice(&ns.0);
+        }
+    }
+}
+
+pub fn read_namespace(input: &mut CanonicalReader<'_>) -> Result<Option<Namespace>, CanonicalError> {
+    match input.u8()? {
+        0 => Ok(None),
+        1 => Ok(Some(Namespace(input.bytes(8)?.try_into().expect("eight bytes")))),
+        _ => Err(CanonicalError::Invalid("namespace flag")),
+    }
+}
+
+pub trait Canonical: Sized {
+    /// Distinguishes the encodings of different types
+    const TAG: u8;
+
+    /// Appends the fields, without tag or version
+    fn write(&self, out: &mut Vec<u8>);
+    fn read(input: &mut CanonicalReader<'_>) -> Result<Self, CanonicalError>;
+
+    fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut out = vec![Self::TAG, CANONICAL_VERSION];
+        self.write(&mut out);
+        out
+    }
+
+    fn from_canonical_bytes(bytes: &[u8]) -> Result<Self, CanonicalError> {
+        let mut input = CanonicalReader::new(bytes);
+        let tag = input.u8()?;
+        if tag != Self::TAG {
+            return Err(CanonicalError::WrongTag { expected: Self::TAG, found: tag });
+        }
+        let version = input.u8()?;
+        if version != CANONICAL_VERSION {
+            return Err(CanonicalError::UnsupportedVersion(version));
+        }
+
+        let value = Self::read(&mut input)?;
+        match input.remaining() {
+            0 => Ok(value),
+            n => Err(CanonicalError::TrailingBytes(n)),
+        }
+    }
+
+    /// SHA-256 of the canonical bytes
+    fn canonical_digest(&self) -> [u8; 32] {
+        Sha256::digest(self.to_canonical_bytes()).into()
+    }
+}
+
+/// Artifacts bound to the parameter set that generated them
+pub trait Parameterized: Canonical {
+    type Params: Canonical + PartialEq;
+
+    fn params(&self) -> &Self::Params;
+
+    fn check_params(&self, expected: &Self::Params) -> Result<(), ParamsMismatch> {
+        if self.params() == expected {
+            Ok(())
+        } else {
+            Err(ParamsMismatch { expected: ParamsDigest::of(expected), found: ParamsDigest::of(self.params()) })
+        }
+    }
+
+    /// Decodes `bytes`, failing unless they were generated under `params`
+    fn from_canonical_bytes_for(bytes: &[u8], params: &Self::Params) -> Result<Self, CanonicalError> {
+        let value = Self::from_canonical_bytes(bytes)?;
+        value.check_params(params)?;
+        Ok(value)
+    }
+}
+
+impl Canonical for Torus {
+    const TAG: u8 = 1;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_u64(out, self.fixed64());
+    }
+
+    fn read(input: &mut CanonicalReader<'_>) -> Result<Self, CanonicalError> {
+        Ok(Torus::from_fixed64(input.u64()?))
+    }
+}
+
+/// Plaintext bits, as `ClearEvaluator` produces them
+impl Canonical for bool {
+    const TAG: u8 = 9;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn read(input: &mut CanonicalReader<'_>) -> Result<Self, CanonicalError> {
+        match input.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(CanonicalError::Invalid("bit")),
+        }
+    }
+}
+
+impl Canonical for TlweParams {
+    const TAG: u8 = 2;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_u64(out, self.n as u64);
+        write_f64(out, self.stddev);
+    }
+
+    fn read(input: &mut CanonicalReader<'_>) -> Result<Self, CanonicalError> {
+        Ok(TlweParams { n: input.usize()?, stddev: input.f64()? })
+    }
+}
+
+impl Canonical for TlweSample {
+    const TAG: u8 = 3;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_params(out, &self.params);
+        write_namespace(out, self.namespace);
+        write_vec(out, &self.a);
+        self.b.write(out);
+    }
+
+    fn read(input: &mut CanonicalReader<'_>) -> Result<Self, CanonicalError> {
+        let params: TlweParams = read_params(input)?;
+        let namespace = read_namespace(input)?;
+        let a: Vec<Torus> = input.vec()?;
+        if a.len() != params.n {
+            return Err(CanonicalError::Invalid("mask length"));
+        }
+        let estimate = NoiseEstimate::fresh(params.stddev);
+        Ok(TlweSample { a, b: Torus::read(input)?, params, noise: NoiseLevel::Fresh, estimate, namespace })
+    }
+}
+
+impl Parameterized for TlweSample {
+    type Params = TlweParams;
+
+    fn params(&self) -> &TlweParams {
+        &self.params
+    }
+}
+
+impl Canonical for TgswParams {
+    const TAG: u8 = 4;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_u64(out, self.l as u64);
+        write_u64(out, self.bg_bit as u64);
+        self.tlwe_params.write(out);
+    }
+
+    fn read(input: &mut CanonicalReader<'_>) -> Result<Self, CanonicalError> {
+        let l = input.usize()?;
+        let bg_bit = u32::try_from(input.u64()?).map_err(|_| CanonicalError::Invalid("decomposition base"))?;
+        Ok(TgswParams { l, bg_bit, tlwe_params: TlweParams::read(input)? })
+    }
+}
+
+impl Canonical for TfheParams {
+    const TAG: u8 = 5;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        self.tlwe_params.write(out);
+        self.tgsw_params.write(out);
+        write_u64(out, self.n as u64);
+        write_u64(out, self.N as u64);
+        write_u64(out, self.k as u64);
+    }
+
+    fn read(input: &mut CanonicalReader<'_>) -> Result<Self, CanonicalError> {
+        Ok(TfheParams {
+            tlwe_params: TlweParams::read(input)?,
+            tgsw_params: TgswParams::read(input)?,
+            n: input.usize()?,
+            N: input.usize()?,
+            k: input.usize()?,
+        })
+    }
+}
+
+/// One byte per coefficient, each in {-1, 0, 1}
+#[cfg(feature = "client")]
+impl Canonical for TlweSecretKey {
+    const TAG: u8 = 7;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_params(out, &self.params);
+        out.extend(self.coeffs.iter().map(|&c| c as i8 as u8));
+    }
+
+    fn read(input: &mut CanonicalReader<'_>) -> Result<Self, CanonicalError> {
+        let params: TlweParams = read_params(input)?;
+        let coeffs = input.bytes(params.n)?.iter()
+            .map(|&c| match c as i8 {
+                c @ -1..=1 => Ok(c as i32),
+                _ => Err(CanonicalError::Invalid("secret key coefficient")),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(TlweSecretKey { coeffs, params })
+    }
+}
+
+#[cfg(feature = "client")]
+impl Parameterized for TlweSecretKey {
+    type Params = TlweParams;
+
+    fn params(&self) -> &TlweParams {
+        &self.params
+    }
+}
+
+#[cfg(feature = "client")]
+impl Canonical for TfheSecretKey {
+    const TAG: u8 = 8;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_params(out, &self.params);
+        self.tlwe_key.write(out);
+        // The ring key follows from the TFHE parameters, so only its coefficients
+        for poly in &self.trlwe_key.polys {
+            out.extend(poly.iter().map(|&c| c as i8 as u8));
+        }
+    }
+
+    fn read(input: &mut CanonicalReader<'_>) -> Result<Self, CanonicalError> {
+        let params: TfheParams = read_params(input)?;
+        let tlwe_key = TlweSecretKey::read(input)?;
+        if tlwe_key.params.n != params.tlwe_params.n {
+            return Err(CanonicalError::Invalid("secret key dimension"));
+        }
+        let ring = params.trlwe_params();
+        let polys = (0..ring.k)
+            .map(|_| {
+                input.bytes(ring.N)?.iter()
+                    .map(|&c| match c as i8 {
+                        c @ -1..=1 => Ok(c as i32),
+                        _ => Err(CanonicalError::Invalid("ring key coefficient")),
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, _>>()?;
+        let trlwe_key = TrlweSecretKey { polys, params: ring };
+        Ok(TfheSecretKey { tlwe_key, trlwe_key, params })
+    }
+}
+
+#[cfg(feature = "client")]
+impl Parameterized for TfheSecretKey {
+    type Params = TfheParams;
+
+    fn params(&self) -> &TfheParams {
+        &self.params
+    }
+}
+
+impl TfheCloudKey {
+    /// SHA-256 over the canonical encoding of the bootstrapping and key
+    /// switching keys; hooks are not part of the key
+    pub fn digest(&self) -> [u8; 32] {
+        let bk = &self.bootstrapping_key;
+        let mut out = Vec::new();
+        write_u64(&mut out, bk.n as u64);
+        write_params(&mut out, &bk.params);
+        for row in bk.bk.iter().flat_map(|trgsw| trgsw.rows.iter().flatten()) {
+            for poly in row.a.iter().chain(std::iter::once(&row.b)) {
+                write_vec(&mut out, &poly.coeffs);
+            }
+        }
+
+        let ks = &self.key_switching_key;
+        write_u64(&mut out, ks.n as u64);
+        write_u64(&mut out, ks.t as u64);
+        write_u64(&mut out, ks.base_bit as u64);
+        for sample in ks.samples.iter().flatten() {
+            sample.write(&mut out);
+        }
+        Sha256::digest(out).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> TlweSample {
+        TlweSample {
+            a: vec![Torus::new(0.25), Torus::new(-0.0), Torus::new(0.875)],
+            b: Torus::new(0.5),
+            params: TlweParams { n: 3, stddev: 1e-9 },
+            noise: NoiseLevel::Fresh,
+            estimate: NoiseEstimate::fresh(1e-9),
+            namespace: None,
+        }
+    }
+
+    #[test]
+    fn test_fixed_layout() {
+        let bytes = sample().to_canonical_bytes();
+        assert_eq!(&bytes[..2], &[3, CANONICAL_VERSION]);
+        // params and their digest, no namespace, mask length and three torus
+        // elements, body
+        assert_eq!(bytes.len(), 2 + 16 + 8 + 1 + 8 + 3 * 8 + 8);
+        assert_eq!(&bytes[2..10], &3u64.to_le_bytes());
+        assert_eq!(&bytes[18..26], &ParamsDigest::of(&sample().params).0);
+        assert_eq!(bytes[26], 0);
+        assert_eq!(&bytes[43..51], &0u64.to_le_bytes());
+        // 0.25 as a 64-bit fixed-point integer
+        assert_eq!(&bytes[35..43], &(1u64 << 62).to_le_bytes());
+
+        let digest = sample().canonical_digest();
+        assert_eq!(digest, Sha256::digest(&bytes).as_slice());
+        assert_eq!(digest, sample().canonical_digest());
+    }
+
+    #[test]
+    fn test_roundtrip_and_errors() {
+        let bytes = sample().to_canonical_bytes();
+        let decoded = TlweSample::from_canonical_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_canonical_bytes(), bytes);
+
+        let params = TfheParams::default();
+        let decoded = TfheParams::from_canonical_bytes(&params.to_canonical_bytes()).unwrap();
+        assert_eq!(decoded.canonical_digest(), params.canonical_digest());
+
+        assert_eq!(
+            TlweParams::from_canonical_bytes(&bytes).unwrap_err(),
+            CanonicalError::WrongTag { expected: 2, found: 3 },
+        );
+        assert_eq!(
+            TlweSample::from_canonical_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            CanonicalError::UnexpectedEnd,
+        );
+
+        let mut extended = bytes.clone();
+        extended.push(0);
+        assert_eq!(TlweSample::from_canonical_bytes(&extended).unwrap_err(), CanonicalError::TrailingBytes(1));
+
+        let tenant = Namespace::new("tenant-a");
+        let scoped = TlweSample { namespace: Some(tenant), ..sample() };
+        let bytes = scoped.to_canonical_bytes();
+        assert_eq!(&bytes[26..35], &[&[1], &tenant.0[..]].concat()[..]);
+        assert_eq!(TlweSample::from_canonical_bytes(&bytes).unwrap().namespace, Some(tenant));
+        assert_eq!(
+            Namespace::join(Some(tenant), Some(Namespace::new("tenant-b"))).unwrap_err(),
+            NamespaceMismatch { expected: tenant, found: Namespace::new("tenant-b") },
+        );
+        assert_eq!(Namespace::join(None, Some(tenant)), Ok(Some(tenant)));
+    }
+
+    #[test]
+    fn test_params_digest_mismatch() {
+        let bytes = sample().to_canonical_bytes();
+        let params = sample().params;
+        let other = TlweParams { n: 3, stddev: 1e-8 };
+
+        assert!(TlweSample::from_canonical_bytes_for(&bytes, &params).is_ok());
+        assert_eq!(
+            TlweSample::from_canonical_bytes_for(&bytes, &other).unwrap_err(),
+            CanonicalError::ParamsMismatch(ParamsMismatch {
+                expected: ParamsDigest::of(&other),
+                found: ParamsDigest::of(&params),
+            }),
+        );
+
+        // The embedded digest no longer matches tampered parameters
+        let mut tampered = bytes;
+        tampered[10] ^= 1;
+        assert!(matches!(
+            TlweSample::from_canonical_bytes(&tampered),
+            Err(CanonicalError::ParamsMismatch(_)),
+        ));
+    }
+}
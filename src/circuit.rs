@@ -0,0 +1,590 @@
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::evaluator::BooleanEvaluator;
+use crate::codec::{Codec, CodecError, Encoded, Json};
+
+/// Handle to the value produced by a node of a `Circuit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Wire(pub usize);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Node {
+    Input(usize),
+    Constant(bool),
+    Not(Wire),
+    And(Wire, Wire),
+    Or(Wire, Wire),
+    Xor(Wire, Wire),
+    Nand(Wire, Wire),
+    /// Selects the second wire when the first is true, the third otherwise
+    Mux(Wire, Wire, Wire),
+}
+
+impl Node {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Node::Input(_) => "INPUT",
+            Node::Constant(_) => "CONST",
+            Node::Not(_) => "NOT",
+            Node::And(..) => "AND",
+            Node::Or(..) => "OR",
+            Node::Xor(..) => "XOR",
+            Node::Nand(..) => "NAND",
+            Node::Mux(..) => "MUX",
+        }
+    }
+
+    pub fn operands(&self) -> Vec<Wire> {
+        match *self {
+            Node::Input(_) | Node::Constant(_) => vec![],
+            Node::Not(a) => vec![a],
+            Node::And(a, b) | Node::Or(a, b) | Node::Xor(a, b) | Node::Nand(a, b) => vec![a, b],
+            Node::Mux(s, a, b) => vec![s, a, b],
+        }
+    }
+
+    /// Bootstraps spent by the `TfheGates` implementation of this node
+    pub fn bootstraps(&self) -> usize {
+        match self {
+            Node::Input(_) | Node::Constant(_) | Node::Not(_) => 0,
+            Node::And(..) | Node::Or(..) | Node::Nand(..) | Node::Xor(..) => 1,
+            Node::Mux(..) => 2,
+        }
+    }
+
+    /// Multiplicative depth this node adds when evaluated without bootstrapping:
+    /// linear gates are free, products and selections cost one external product
+    pub fn multiplicative_depth(&self) -> usize {
+        match self {
+            Node::Input(_) | Node::Constant(_) | Node::Not(_) | Node::Xor(..) => 0,
+            Node::And(..) | Node::Or(..) | Node::Nand(..) | Node::Mux(..) => 1,
+        }
+    }
+
+    /// Longest chain of sequential bootstraps inside this node
+    pub fn bootstrap_depth(&self) -> usize {
+        match self {
+            Node::Input(_) | Node::Constant(_) | Node::Not(_) => 0,
+            // The two rotations of a MUX are independent
+            Node::And(..) | Node::Or(..) | Node::Nand(..) | Node::Xor(..) | Node::Mux(..) => 1,
+        }
+    }
+}
+
+/// Result of one iteration of a bounded loop body
+#[derive(Debug, Clone)]
+pub struct LoopStep {
+    pub state: Vec<Wire>,
+    pub exit: Wire,
+}
+
+/// Final state of a bounded loop and whether any iteration requested an exit
+#[derive(Debug, Clone)]
+pub struct LoopOutput {
+    pub state: Vec<Wire>,
+    pub exited: Wire,
+}
+
+/// Measured wall-clock time of each gate kind on one machine and parameter set
+#[derive(Debug, Clone, Default)]
+pub struct GateTimings {
+    pub not: Duration,
+    pub and: Duration,
+    pub or: Duration,
+    pub xor: Duration,
+    pub nand: Duration,
+    pub mux: Duration,
+}
+
+impl GateTimings {
+    /// Times `rounds` evaluations of every gate kind on the sample bits
+    pub fn calibrate<E: BooleanEvaluator>(eval: &E, a: &E::Bit, b: &E::Bit, rounds: usize) -> Self {
+        let rounds = rounds.max(1);
+        let time = |f: &dyn Fn() -> E::Bit| {
+            let start = Instant::now();
+            for _ in 0..rounds {
+                f();
+            }
+            start.elapsed() / rounds as u32
+        };
+
+        GateTimings {
+            not: time(&|| eval.not(a)),
+            and: time(&|| eval.and(a, b)),
+            or: time(&|| eval.or(a, b)),
+            xor: time(&|| eval.xor(a, b)),
+            nand: time(&|| eval.nand(a, b)),
+            mux: time(&|| eval.mux(a, b, a)),
+        }
+    }
+
+    pub fn of(&self, node: &Node) -> Duration {
+        match node {
+            Node::Input(_) | Node::Constant(_) => Duration::ZERO,
+            Node::Not(_) => self.not,
+            Node::And(..) => self.and,
+            Node::Or(..) => self.or,
+            Node::Xor(..) => self.xor,
+            Node::Nand(..) => self.nand,
+            Node::Mux(..) => self.mux,
+        }
+    }
+}
+
+/// Static cost of a circuit, computed without evaluating it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostReport {
+    pub gates: usize,
+    pub bootstraps: usize,
+    /// Bootstraps on the critical path
+    pub depth: usize,
+    /// Sequential evaluation time predicted from the gate timings
+    pub estimated_time: Duration,
+    pub peak_live_ciphertexts: usize,
+    pub peak_memory_bytes: usize,
+}
+
+/// How the gates of a circuit are meant to be evaluated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvaluationMode {
+    /// Every gate bootstraps, so depth is unbounded
+    #[default]
+    Bootstrapped,
+    /// No bootstraps: noise grows with each product, up to `max_depth`
+    /// (see `TfheParams::leveled_depth`)
+    Leveled { max_depth: usize },
+}
+
+/// A leveled circuit needs more multiplicative depth than its parameters support
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepthError {
+    /// First node past the limit
+    pub node: Wire,
+    pub gate: &'static str,
+    pub depth: usize,
+    pub max_depth: usize,
+}
+
+impl std::fmt::Display for DepthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} gate at node {} reaches multiplicative depth {}, but leveled parameters support {}",
+            self.gate, self.node.0, self.depth, self.max_depth,
+        )
+    }
+}
+
+impl std::error::Error for DepthError {}
+
+/// Gate-level circuit built once and evaluated with any `BooleanEvaluator`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Circuit {
+    pub nodes: Vec<Node>,
+    pub num_inputs: usize,
+    pub outputs: Vec<Wire>,
+    #[serde(default)]
+    pub mode: EvaluationMode,
+}
+
+impl Circuit {
+    pub fn new() -> Self {
+        Circuit::default()
+    }
+
+    /// Circuit for evaluation without bootstraps, limited to `max_depth`
+    pub fn leveled(max_depth: usize) -> Self {
+        Circuit { mode: EvaluationMode::Leveled { max_depth }, ..Circuit::default() }
+    }
+
+    fn push(&mut self, node: Node) -> Wire {
+        self.nodes.push(node);
+        Wire(self.nodes.len() - 1)
+    }
+
+    pub fn input(&mut self) -> Wire {
+        let index = self.num_inputs;
+        self.num_inputs += 1;
+        self.push(Node::Input(index))
+    }
+
+    pub fn inputs(&mut self, count: usize) -> Vec<Wire> {
+        (0..count).map(|_| self.input()).collect()
+    }
+
+    pub fn constant(&mut self, value: bool) -> Wire {
+        self.push(Node::Constant(value))
+    }
+
+    pub fn not(&mut self, a: Wire) -> Wire {
+        self.push(Node::Not(a))
+    }
+
+    pub fn and(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Node::And(a, b))
+    }
+
+    pub fn or(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Node::Or(a, b))
+    }
+
+    pub fn xor(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Node::Xor(a, b))
+    }
+
+    pub fn nand(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Node::Nand(a, b))
+    }
+
+    pub fn mux(&mut self, s: Wire, a: Wire, b: Wire) -> Wire {
+        self.push(Node::Mux(s, a, b))
+    }
+
+    pub fn output(&mut self, wire: Wire) {
+        self.outputs.push(wire);
+    }
+
+    /// Unrolls a loop of exactly `max_iterations` iterations over `init`.
+    ///
+    /// The exit condition of every iteration is OR-ed into an encrypted flag and
+    /// each state write is CMUX-guarded by it, so iterations after the exit leave
+    /// the state untouched. The number of gates is independent of when the exit
+    /// happens, so evaluation does not leak the iteration count.
+    pub fn bounded_loop<F>(&mut self, max_iterations: usize, init: &[Wire], mut body: F) -> LoopOutput
+    where
+        F: FnMut(&mut Circuit, usize, &[Wire]) -> LoopStep,
+    {
+        let mut state = init.to_vec();
+        let mut exited = self.constant(false);
+
+        for iteration in 0..max_iterations {
+            let step = body(self, iteration, &state);
+            assert_eq!(step.state.len(), state.len());
+
+            state = state.iter()
+                .zip(step.state.iter())
+                .map(|(&old, &new)| self.mux(exited, old, new))
+                .collect();
+            exited = self.or(exited, step.exit);
+        }
+
+        LoopOutput { state, exited }
+    }
+
+    /// Index of the last node reading each wire; outputs live until the end
+    pub fn last_uses(&self) -> Vec<usize> {
+        let mut last_use: Vec<usize> = (0..self.nodes.len()).collect();
+        for (i, node) in self.nodes.iter().enumerate() {
+            for operand in node.operands() {
+                last_use[operand.0] = i;
+            }
+        }
+        for output in &self.outputs {
+            last_use[output.0] = self.nodes.len();
+        }
+        last_use
+    }
+
+    /// Multiplicative depth of every wire
+    pub fn multiplicative_depths(&self) -> Vec<usize> {
+        let mut depths: Vec<usize> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let inputs = node.operands().iter().map(|w| depths[w.0]).max().unwrap_or(0);
+            depths.push(inputs + node.multiplicative_depth());
+        }
+        depths
+    }
+
+    /// Rejects leveled circuits with a node deeper than the mode allows;
+    /// bootstrapped circuits always pass
+    pub fn check_depth(&self) -> Result<(), DepthError> {
+        let EvaluationMode::Leveled { max_depth } = self.mode else {
+            return Ok(());
+        };
+        let depths = self.multiplicative_depths();
+        match depths.iter().position(|&depth| depth > max_depth) {
+            Some(i) => Err(DepthError {
+                node: Wire(i),
+                gate: self.nodes[i].name(),
+                depth: depths[i],
+                max_depth,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Finishes building, checking the depth of leveled circuits
+    pub fn build(self) -> Result<Circuit, DepthError> {
+        self.check_depth()?;
+        Ok(self)
+    }
+
+    pub fn cost_report(&self, timings: &GateTimings, ciphertext_bytes: usize) -> CostReport {
+        let last_use = self.last_uses();
+
+        let mut bootstraps = 0;
+        let mut estimated_time = Duration::ZERO;
+        let mut depths = vec![0; self.nodes.len()];
+        let mut live = 0;
+        let mut peak_live = 0;
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            let operands = node.operands();
+
+            bootstraps += node.bootstraps();
+            estimated_time += timings.of(node);
+            depths[i] = operands.iter().map(|w| depths[w.0]).max().unwrap_or(0) + node.bootstrap_depth();
+
+            live += 1;
+            peak_live = peak_live.max(live);
+            for (j, operand) in operands.iter().enumerate() {
+                if last_use[operand.0] == i && !operands[..j].contains(operand) {
+                    live -= 1;
+                }
+            }
+            if last_use[i] == i {
+                live -= 1;
+            }
+        }
+
+        CostReport {
+            gates: self.nodes.iter().filter(|n| !n.operands().is_empty()).count(),
+            bootstraps,
+            depth: self.outputs.iter().map(|w| depths[w.0]).max().unwrap_or(0),
+            estimated_time,
+            peak_live_ciphertexts: peak_live,
+            peak_memory_bytes: peak_live * ciphertext_bytes,
+        }
+    }
+
+    /// Graphviz rendering of the gate graph, labelled with bootstrap counts
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph circuit {\n    rankdir=LR;\n");
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            let label = match *node {
+                Node::Input(k) => format!("label=\"in{}\", shape=box", k),
+                Node::Constant(v) => format!("label=\"{}\", shape=plaintext", v as u8),
+                _ => format!("label=\"{} ({})\"", node.name(), node.bootstraps()),
+            };
+            dot.push_str(&format!("    n{} [{}];\n", i, label));
+
+            match *node {
+                Node::Mux(s, a, b) => {
+                    dot.push_str(&format!("    n{} -> n{} [label=\"s\"];\n", s.0, i));
+                    dot.push_str(&format!("    n{} -> n{} [label=\"1\"];\n", a.0, i));
+                    dot.push_str(&format!("    n{} -> n{} [label=\"0\"];\n", b.0, i));
+                }
+                _ => {
+                    for operand in node.operands() {
+                        dot.push_str(&format!("    n{} -> n{};\n", operand.0, i));
+                    }
+                }
+            }
+        }
+
+        for (k, output) in self.outputs.iter().enumerate() {
+            dot.push_str(&format!("    out{} [label=\"out{}\", shape=doublecircle];\n", k, k));
+            dot.push_str(&format!("    n{} -> out{};\n", output.0, k));
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("circuit serializes to JSON")
+    }
+
+    /// Parses a circuit and checks its depth as `build` does
+    pub fn from_json(json: &str) -> Result<Self, CodecError> {
+        let circuit = Self::from_bytes_with::<Json>(json.as_bytes())?;
+        circuit.check_depth().map_err(|err| CodecError::new(Json::NAME, err))?;
+        Ok(circuit)
+    }
+
+    /// `try_evaluate`, panicking on a leveled circuit that is too deep
+    pub fn evaluate<E: BooleanEvaluator>(&self, eval: &E, inputs: &[E::Bit]) -> Vec<E::Bit> {
+        self.try_evaluate(eval, inputs).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Evaluates the circuit on `inputs`, or rejects a leveled circuit with
+    /// a node deeper than its mode allows
+    pub fn try_evaluate<E: BooleanEvaluator>(&self, eval: &E, inputs: &[E::Bit]) -> Result<Vec<E::Bit>, DepthError> {
+        assert_eq!(inputs.len(), self.num_inputs);
+        self.check_depth()?;
+
+        // Values are dropped after their last use so memory matches `cost_report`
+        let last_use = self.last_uses();
+        let mut values: Vec<Option<E::Bit>> = vec![None; self.nodes.len()];
+        let get = |values: &[Option<E::Bit>], w: Wire| -> E::Bit {
+            values[w.0].clone().expect("wire read after its last use")
+        };
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            let value = match *node {
+                Node::Input(k) => inputs[k].clone(),
+                Node::Constant(v) => eval.constant(v),
+                Node::Not(a) => eval.not(&get(&values, a)),
+                Node::And(a, b) => eval.and(&get(&values, a), &get(&values, b)),
+                Node::Or(a, b) => eval.or(&get(&values, a), &get(&values, b)),
+                Node::Xor(a, b) => eval.xor(&get(&values, a), &get(&values, b)),
+                Node::Nand(a, b) => eval.nand(&get(&values, a), &get(&values, b)),
+                Node::Mux(s, a, b) => eval.mux(&get(&values, s), &get(&values, a), &get(&values, b)),
+            };
+            values[i] = Some(value);
+
+            for operand in node.operands() {
+                if last_use[operand.0] == i {
+                    values[operand.0] = None;
+                }
+            }
+            if last_use[i] == i {
+                values[i] = None;
+            }
+        }
+
+        Ok(self.outputs.iter()
+            .map(|&w| get(&values, w))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    // Counts loop iterations in a 2-bit counter until the first true input
+    fn first_true_counter(len: usize) -> Circuit {
+        let mut circuit = Circuit::new();
+        let bits = circuit.inputs(len);
+        let zero = circuit.constant(false);
+        let one = circuit.constant(true);
+
+        let result = circuit.bounded_loop(len, &[zero, zero], |c, i, state| {
+            let low = c.xor(state[0], one);
+            let high = c.xor(state[1], state[0]);
+            LoopStep { state: vec![low, high], exit: bits[i] }
+        });
+
+        circuit.output(result.state[0]);
+        circuit.output(result.state[1]);
+        circuit.output(result.exited);
+        circuit
+    }
+
+    #[test]
+    fn test_circuit_gates() {
+        let mut circuit = Circuit::new();
+        let a = circuit.input();
+        let b = circuit.input();
+        let and = circuit.and(a, b);
+        let x = circuit.xor(a, b);
+        let m = circuit.mux(a, x, and);
+        circuit.output(and);
+        circuit.output(m);
+
+        let out = circuit.evaluate(&ClearEvaluator, &[true, false]);
+        assert_eq!(out, vec![false, true]);
+
+        let out = circuit.evaluate(&ClearEvaluator, &[false, true]);
+        assert_eq!(out, vec![false, false]);
+    }
+
+    #[test]
+    fn test_cost_report() {
+        let mut circuit = Circuit::new();
+        let a = circuit.input();
+        let b = circuit.input();
+        let x = circuit.and(a, b);
+        let y = circuit.or(x, a);
+        circuit.output(y);
+
+        let timings = GateTimings {
+            and: Duration::from_millis(1),
+            or: Duration::from_millis(2),
+            ..Default::default()
+        };
+        let report = circuit.cost_report(&timings, 100);
+
+        assert_eq!(report.gates, 2);
+        assert_eq!(report.bootstraps, 2);
+        assert_eq!(report.depth, 2);
+        assert_eq!(report.estimated_time, Duration::from_millis(3));
+        assert_eq!(report.peak_live_ciphertexts, 3);
+        assert_eq!(report.peak_memory_bytes, 300);
+    }
+
+    #[test]
+    fn test_dot_and_json_export() {
+        let mut circuit = Circuit::new();
+        let s = circuit.input();
+        let a = circuit.input();
+        let b = circuit.constant(true);
+        let m = circuit.mux(s, a, b);
+        circuit.output(m);
+
+        let dot = circuit.to_dot();
+        assert!(dot.starts_with("digraph circuit {"));
+        assert!(dot.contains("n3 [label=\"MUX (2)\"];"));
+        assert!(dot.contains("n0 -> n3 [label=\"s\"];"));
+        assert!(dot.contains("n3 -> out0;"));
+
+        let json = circuit.to_json();
+        assert!(json.contains("\"mux\""));
+        assert_eq!(Circuit::from_json(&json).unwrap(), circuit);
+    }
+
+    #[test]
+    fn test_leveled_depth_check() {
+        let mut circuit = Circuit::leveled(2);
+        let a = circuit.input();
+        let b = circuit.input();
+        let x = circuit.xor(a, b);
+        let y = circuit.and(x, a);
+        let z = circuit.mux(b, y, x);
+        circuit.output(z);
+        assert_eq!(circuit.multiplicative_depths(), vec![0, 0, 0, 1, 2]);
+
+        let circuit = circuit.build().unwrap();
+        assert_eq!(circuit.evaluate(&ClearEvaluator, &[true, true]), vec![false]);
+
+        let mut deeper = circuit.clone();
+        let w = deeper.or(z, a);
+        deeper.output(w);
+        let err = deeper.clone().build().unwrap_err();
+        assert_eq!(err, DepthError { node: Wire(5), gate: "OR", depth: 3, max_depth: 2 });
+        assert!(err.to_string().contains("OR gate at node 5"));
+        assert_eq!(deeper.try_evaluate(&ClearEvaluator, &[true, true]), Err(err));
+        assert!(Circuit::from_json(&deeper.to_json()).unwrap_err().message.contains("OR gate at node 5"));
+
+        // The mode survives serialization, and older JSON defaults to bootstrapped
+        assert_eq!(Circuit::from_json(&circuit.to_json()).unwrap(), circuit);
+        let legacy = Circuit::from_json(r#"{"nodes":[],"num_inputs":0,"outputs":[]}"#).unwrap();
+        assert_eq!(legacy.mode, EvaluationMode::Bootstrapped);
+    }
+
+    #[test]
+    fn test_bounded_loop_early_exit() {
+        let circuit = first_true_counter(3);
+
+        // Exits in the second iteration: counter stops at 2
+        let out = circuit.evaluate(&ClearEvaluator, &[false, true, true]);
+        assert_eq!(out, vec![false, true, true]);
+
+        // Never exits: counter runs all 3 iterations
+        let out = circuit.evaluate(&ClearEvaluator, &[false, false, false]);
+        assert_eq!(out, vec![true, true, false]);
+    }
+
+    #[test]
+    fn test_bounded_loop_unrolls_every_iteration() {
+        // 4 inputs, 2 constants, the exit flag, then per iteration
+        // 2 body gates, 2 guarded writes and the exit OR
+        let circuit = first_true_counter(4);
+        assert_eq!(circuit.nodes.len(), 4 + 2 + 1 + 4 * (2 + 2 + 1));
+    }
+}
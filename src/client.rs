@@ -0,0 +1,8 @@
+//! Client-side surface: key generation, encryption, decryption and encoders.
+//!
+//! Available with the `client` feature.
+
+pub use crate::api::{generate_keys, ClientKey};
+pub use crate::lwe::{LwePublicKey, LweSecretKey, TlwePublicKey};
+pub use crate::tfhe::{TfheEncoder, TfheSecretKey};
+pub use crate::tlwe::TlweSecretKey;
@@ -0,0 +1,160 @@
+//! Interchangeable byte encodings for the serde types of the crate.
+//!
+//! Circuits, compiled circuits, cost tables, evaluation profiles and op logs
+//! derive serde, and a `Codec` turns any of them into bytes and back.
+//! `Json` is always available; `Bincode`, `Postcard` and `Cbor` come with
+//! the features of the same names (`cbor` for the last), so embedded users
+//! can pick compact postcard and web services self-describing CBOR while
+//! every type keeps a single serde definition. `Encoded` gives each of those
+//! types `to_bytes_with` and `from_bytes_with` for a chosen codec. Their
+//! `from_json` helpers decode through `Json` as well; `to_json` stays
+//! pretty-printed for reading and diffing, which the compact `Json` codec
+//! is not, and circuit fingerprints hash the `Json` bytes.
+//!
+//! Ciphertexts and keys are not serde types: their layout is fixed by
+//! `canonical`, which digests and migrations depend on.
+
+use std::fmt;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// A codec failed to encode a value or to decode bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodecError {
+    pub codec: &'static str,
+    pub message: String,
+}
+
+impl CodecError {
+    pub(crate) fn new(codec: &'static str, error: impl fmt::Display) -> Self {
+        CodecError { codec, message: error.to_string() }
+    }
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.codec, self.message)
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+/// A byte encoding of serde types
+pub trait Codec {
+    const NAME: &'static str;
+
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, CodecError>;
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CodecError>;
+}
+
+/// Serde types encoded by any `Codec`
+pub trait Encoded: Serialize + DeserializeOwned {
+    fn to_bytes_with<C: Codec>(&self) -> Result<Vec<u8>, CodecError> {
+        C::encode(self)
+    }
+
+    fn from_bytes_with<C: Codec>(bytes: &[u8]) -> Result<Self, CodecError> {
+        C::decode(bytes)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Encoded for T {}
+
+/// UTF-8 JSON, behind the `from_json` and `to_bytes` helpers
+pub struct Json;
+
+impl Codec for Json {
+    const NAME: &'static str = "json";
+
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, CodecError> {
+        serde_json::to_vec(value).map_err(|e| CodecError::new(Self::NAME, e))
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CodecError> {
+        serde_json::from_slice(bytes).map_err(|e| CodecError::new(Self::NAME, e))
+    }
+}
+
+/// bincode 1 with its default options: fixed-width integers, little endian
+#[cfg(feature = "bincode")]
+pub struct Bincode;
+
+#[cfg(feature = "bincode")]
+impl Codec for Bincode {
+    const NAME: &'static str = "bincode";
+
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, CodecError> {
+        bincode::serialize(value).map_err(|e| CodecError::new(Self::NAME, e))
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CodecError> {
+        bincode::deserialize(bytes).map_err(|e| CodecError::new(Self::NAME, e))
+    }
+}
+
+/// postcard: varint integers and no field names, the smallest encoding
+#[cfg(feature = "postcard")]
+pub struct Postcard;
+
+#[cfg(feature = "postcard")]
+impl Codec for Postcard {
+    const NAME: &'static str = "postcard";
+
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, CodecError> {
+        postcard::to_allocvec(value).map_err(|e| CodecError::new(Self::NAME, e))
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CodecError> {
+        postcard::from_bytes(bytes).map_err(|e| CodecError::new(Self::NAME, e))
+    }
+}
+
+/// CBOR (RFC 8949), self-describing like JSON but binary
+#[cfg(feature = "cbor")]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl Codec for Cbor {
+    const NAME: &'static str = "cbor";
+
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, CodecError> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(value, &mut bytes).map_err(|e| CodecError::new(Self::NAME, e))?;
+        Ok(bytes)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CodecError> {
+        ciborium::from_reader(bytes).map_err(|e| CodecError::new(Self::NAME, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+
+    fn roundtrip<C: Codec>(circuit: &Circuit) {
+        let bytes = circuit.to_bytes_with::<C>().unwrap();
+        assert_eq!(&Circuit::from_bytes_with::<C>(&bytes).unwrap(), circuit, "{}", C::NAME);
+        let error = Circuit::from_bytes_with::<C>(&bytes[..bytes.len() / 2]).unwrap_err();
+        assert_eq!(error.codec, C::NAME);
+    }
+
+    #[test]
+    fn test_codecs_roundtrip() {
+        let mut circuit = Circuit::new();
+        let a = circuit.input();
+        let b = circuit.input();
+        let x = circuit.xor(a, b);
+        let m = circuit.mux(a, x, b);
+        circuit.output(m);
+
+        roundtrip::<Json>(&circuit);
+        #[cfg(feature = "bincode")]
+        roundtrip::<Bincode>(&circuit);
+        #[cfg(feature = "postcard")]
+        roundtrip::<Postcard>(&circuit);
+        #[cfg(feature = "cbor")]
+        roundtrip::<Cbor>(&circuit);
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use crate::circuit::{Circuit, Node, Wire};
+use crate::codec::{CodecError, Encoded, Json};
+use crate::evaluator::BooleanEvaluator;
+
+/// Bumped whenever the compiled layout or the optimization passes change
+pub const COMPILED_FORMAT_VERSION: u32 = 1;
+
+/// What the optimization passes removed from the source circuit
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OptimizationReport {
+    pub source_nodes: usize,
+    pub folded: usize,
+    pub merged: usize,
+    pub dead: usize,
+}
+
+/// Optimized circuit with its evaluation schedule, ready to be cached
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompiledCircuit {
+    pub format_version: u32,
+    /// Fingerprint of the circuit this was compiled from
+    pub source_fingerprint: u64,
+    /// Nodes are stored in schedule order
+    pub circuit: Circuit,
+    /// Node ranges whose members only depend on earlier levels
+    pub levels: Vec<Range<usize>>,
+    pub report: OptimizationReport,
+}
+
+enum Simplified {
+    Wire(Wire),
+    Node(Node),
+}
+
+fn constant_of(circuit: &Circuit, w: Wire) -> Option<bool> {
+    match circuit.nodes[w.0] {
+        Node::Constant(v) => Some(v),
+        _ => None,
+    }
+}
+
+fn simplify(circuit: &Circuit, node: Node) -> Simplified {
+    use Simplified::{Node as N, Wire as W};
+    let c = |w| constant_of(circuit, w);
+
+    match node {
+        Node::Not(a) => match (c(a), &circuit.nodes[a.0]) {
+            (Some(v), _) => N(Node::Constant(!v)),
+            (None, Node::Not(inner)) => W(*inner),
+            _ => N(node),
+        },
+        Node::And(a, b) => match (c(a), c(b)) {
+            (Some(false), _) | (_, Some(false)) => N(Node::Constant(false)),
+            (Some(true), _) => W(b),
+            (_, Some(true)) => W(a),
+            _ if a == b => W(a),
+            _ => N(Node::And(a.min(b), a.max(b))),
+        },
+        Node::Or(a, b) => match (c(a), c(b)) {
+            (Some(true), _) | (_, Some(true)) => N(Node::Constant(true)),
+            (Some(false), _) => W(b),
+            (_, Some(false)) => W(a),
+            _ if a == b => W(a),
+            _ => N(Node::Or(a.min(b), a.max(b))),
+        },
+        Node::Xor(a, b) => match (c(a), c(b)) {
+            (Some(x), Some(y)) => N(Node::Constant(x ^ y)),
+            (Some(false), _) => W(b),
+            (_, Some(false)) => W(a),
+            (Some(true), _) => N(Node::Not(b)),
+            (_, Some(true)) => N(Node::Not(a)),
+            _ if a == b => N(Node::Constant(false)),
+            _ => N(Node::Xor(a.min(b), a.max(b))),
+        },
+        Node::Nand(a, b) => match (c(a), c(b)) {
+            (Some(false), _) | (_, Some(false)) => N(Node::Constant(true)),
+            (Some(true), _) => N(Node::Not(b)),
+            (_, Some(true)) => N(Node::Not(a)),
+            _ if a == b => N(Node::Not(a)),
+            _ => N(Node::Nand(a.min(b), a.max(b))),
+        },
+        Node::Mux(s, a, b) => match (c(s), c(a), c(b)) {
+            (Some(true), _, _) => W(a),
+            (Some(false), _, _) => W(b),
+            _ if a == b => W(a),
+            (_, Some(true), Some(false)) => W(s),
+            (_, Some(false), Some(true)) => N(Node::Not(s)),
+            _ => N(node),
+        },
+        Node::Input(_) | Node::Constant(_) => N(node),
+    }
+}
+
+fn remap(node: &Node, map: &[Wire]) -> Node {
+    let m = |w: Wire| map[w.0];
+    match *node {
+        Node::Input(k) => Node::Input(k),
+        Node::Constant(v) => Node::Constant(v),
+        Node::Not(a) => Node::Not(m(a)),
+        Node::And(a, b) => Node::And(m(a), m(b)),
+        Node::Or(a, b) => Node::Or(m(a), m(b)),
+        Node::Xor(a, b) => Node::Xor(m(a), m(b)),
+        Node::Nand(a, b) => Node::Nand(m(a), m(b)),
+        Node::Mux(s, a, b) => Node::Mux(m(s), m(a), m(b)),
+    }
+}
+
+/// Constant folding, algebraic simplification and common subexpression merging
+fn fold_and_merge(circuit: &Circuit, report: &mut OptimizationReport) -> Circuit {
+    let mut out = Circuit { num_inputs: circuit.num_inputs, mode: circuit.mode, ..Circuit::default() };
+    let mut seen: HashMap<Node, Wire> = HashMap::new();
+    let mut map = Vec::with_capacity(circuit.nodes.len());
+
+    for node in &circuit.nodes {
+        let mut candidate = remap(node, &map);
+        let wire = loop {
+            match simplify(&out, candidate.clone()) {
+                Simplified::Wire(w) => {
+                    report.folded += 1;
+                    break w;
+                }
+                Simplified::Node(n) if n != candidate => {
+                    // Reordering commutative operands is not a fold
+                    if std::mem::discriminant(&n) != std::mem::discriminant(&candidate) {
+                        report.folded += 1;
+                    }
+                    candidate = n;
+                }
+                Simplified::Node(n) => {
+                    if let Some(&w) = seen.get(&n) {
+                        report.merged += 1;
+                        break w;
+                    }
+                    out.nodes.push(n.clone());
+                    let w = Wire(out.nodes.len() - 1);
+                    seen.insert(n, w);
+                    break w;
+                }
+            }
+        };
+        map.push(wire);
+    }
+
+    out.outputs = circuit.outputs.iter().map(|w| map[w.0]).collect();
+    out
+}
+
+/// Drops nodes that no output depends on and orders the rest by level
+fn prune_and_schedule(circuit: &Circuit, report: &mut OptimizationReport) -> (Circuit, Vec<Range<usize>>) {
+    let mut live = vec![false; circuit.nodes.len()];
+    for output in &circuit.outputs {
+        live[output.0] = true;
+    }
+    for i in (0..circuit.nodes.len()).rev() {
+        if live[i] {
+            for operand in circuit.nodes[i].operands() {
+                live[operand.0] = true;
+            }
+        }
+    }
+
+    let mut levels = vec![0usize; circuit.nodes.len()];
+    let mut order = Vec::new();
+    for (i, node) in circuit.nodes.iter().enumerate() {
+        // Inputs are kept so input indices stay valid
+        if !live[i] && !matches!(node, Node::Input(_)) {
+            report.dead += 1;
+            continue;
+        }
+        levels[i] = node.operands().iter().map(|w| levels[w.0] + 1).max().unwrap_or(0);
+        order.push(i);
+    }
+    order.sort_by_key(|&i| levels[i]);
+
+    let mut map = vec![Wire(0); circuit.nodes.len()];
+    for (new, &old) in order.iter().enumerate() {
+        map[old] = Wire(new);
+    }
+
+    let mut out = Circuit { num_inputs: circuit.num_inputs, mode: circuit.mode, ..Circuit::default() };
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (new, &old) in order.iter().enumerate() {
+        out.nodes.push(remap(&circuit.nodes[old], &map));
+        match ranges.last_mut() {
+            Some(range) if levels[order[range.start]] == levels[old] => range.end = new + 1,
+            _ => ranges.push(new..new + 1),
+        }
+    }
+    out.outputs = circuit.outputs.iter().map(|w| map[w.0]).collect();
+
+    (out, ranges)
+}
+
+impl Circuit {
+    /// FNV-1a hash of the `Json` form, stable across runs and platforms
+    pub fn fingerprint(&self) -> u64 {
+        let json = self.to_bytes_with::<Json>().expect("circuit serializes to JSON");
+        json.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+}
+
+impl CompiledCircuit {
+    pub fn compile(circuit: &Circuit) -> Self {
+        let mut report = OptimizationReport {
+            source_nodes: circuit.nodes.len(),
+            ..Default::default()
+        };
+
+        let folded = fold_and_merge(circuit, &mut report);
+        let (scheduled, levels) = prune_and_schedule(&folded, &mut report);
+
+        CompiledCircuit {
+            format_version: COMPILED_FORMAT_VERSION,
+            source_fingerprint: circuit.fingerprint(),
+            circuit: scheduled,
+            levels,
+            report,
+        }
+    }
+
+    pub fn evaluate<E: BooleanEvaluator>(&self, eval: &E, inputs: &[E::Bit]) -> Vec<E::Bit> {
+        self.circuit.evaluate(eval, inputs)
+    }
+
+    /// JSON bytes; `to_bytes_with` picks another `Codec`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_bytes_with::<Json>().expect("compiled circuit serializes to JSON")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CodecError> {
+        Self::from_bytes_with::<Json>(bytes)
+    }
+}
+
+/// Compiled circuits keyed by source fingerprint, optionally persisted to disk
+#[derive(Debug, Default)]
+pub struct CompileCache {
+    pub dir: Option<PathBuf>,
+    entries: HashMap<u64, Arc<CompiledCircuit>>,
+}
+
+impl CompileCache {
+    pub fn new() -> Self {
+        CompileCache::default()
+    }
+
+    pub fn with_dir(dir: impl Into<PathBuf>) -> Self {
+        CompileCache {
+            dir: Some(dir.into()),
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn path_for(&self, fingerprint: u64) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(format!("{:016x}.circuit.json", fingerprint)))
+    }
+
+    /// Returns the cached compilation of `circuit`, compiling and storing it on a miss.
+    ///
+    /// Cache files from another format version or for another circuit are ignored
+    /// and overwritten.
+    pub fn get_or_compile(&mut self, circuit: &Circuit) -> io::Result<Arc<CompiledCircuit>> {
+        let fingerprint = circuit.fingerprint();
+        if let Some(compiled) = self.entries.get(&fingerprint) {
+            return Ok(compiled.clone());
+        }
+
+        let path = self.path_for(fingerprint);
+        let loaded = path.as_ref()
+            .and_then(|p| fs::read(p).ok())
+            .and_then(|bytes| CompiledCircuit::from_bytes(&bytes).ok())
+            .filter(|c| c.format_version == COMPILED_FORMAT_VERSION && c.source_fingerprint == fingerprint);
+
+        let compiled = match loaded {
+            Some(compiled) => compiled,
+            None => {
+                let compiled = CompiledCircuit::compile(circuit);
+                if let Some(path) = &path {
+                    if let Some(dir) = path.parent() {
+                        fs::create_dir_all(dir)?;
+                    }
+                    fs::write(path, compiled.to_bytes())?;
+                }
+                compiled
+            }
+        };
+
+        let compiled = Arc::new(compiled);
+        self.entries.insert(fingerprint, compiled.clone());
+        Ok(compiled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    fn redundant_circuit() -> Circuit {
+        let mut c = Circuit::new();
+        let a = c.input();
+        let b = c.input();
+        let t = c.constant(true);
+        let f = c.constant(false);
+        let x1 = c.and(a, b);
+        let x2 = c.and(b, a);
+        let y = c.xor(x1, x2);
+        let z = c.or(y, f);
+        let w = c.mux(t, z, a);
+        let _unused = c.nand(a, b);
+        let out = c.xor(w, x1);
+        c.output(out);
+        c
+    }
+
+    #[test]
+    fn test_compile_preserves_semantics() {
+        let circuit = redundant_circuit();
+        let compiled = CompiledCircuit::compile(&circuit);
+
+        assert!(compiled.circuit.nodes.len() < circuit.nodes.len());
+        for bits in [[false, false], [false, true], [true, false], [true, true]] {
+            assert_eq!(
+                compiled.evaluate(&ClearEvaluator, &bits),
+                circuit.evaluate(&ClearEvaluator, &bits),
+            );
+        }
+    }
+
+    #[test]
+    fn test_schedule_levels() {
+        let compiled = CompiledCircuit::compile(&redundant_circuit());
+        let nodes = &compiled.circuit.nodes;
+
+        assert_eq!(compiled.levels.first().map(|r| r.start), Some(0));
+        assert_eq!(compiled.levels.last().map(|r| r.end), Some(nodes.len()));
+        for (level, range) in compiled.levels.iter().enumerate() {
+            for i in range.clone() {
+                for operand in nodes[i].operands() {
+                    assert!(compiled.levels[..level].iter().any(|r| r.contains(&operand.0)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_compile_cache_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("ghost-cache-{}", std::process::id()));
+        let circuit = redundant_circuit();
+
+        let mut cache = CompileCache::with_dir(&dir);
+        let compiled = cache.get_or_compile(&circuit).unwrap();
+        assert_eq!(cache.len(), 1);
+
+        let mut reloaded_cache = CompileCache::with_dir(&dir);
+        let reloaded = reloaded_cache.get_or_compile(&circuit).unwrap();
+        assert_eq!(*reloaded, *compiled);
+
+        let bytes = compiled.to_bytes();
+        assert_eq!(CompiledCircuit::from_bytes(&bytes).unwrap(), *compiled);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,177 @@
+//! Machine-readable cost of every operation at a parameter set.
+//!
+//! A `CostTable` lists, per operation, the bootstraps it runs, an estimate
+//! of its floating point work and the working memory it needs beyond its
+//! operands. Gate bootstrap counts come from `Node::bootstraps`, the same
+//! numbers `Circuit::cost_report` and the budget charge, so a scheduler
+//! reading the exported JSON and the circuit analyzer never disagree.
+//!
+//! FLOPs count an FFT of `M = N/2` complex points as `5 M log2 M` and a
+//! complex multiply-accumulate as 8; integer additions count as one each.
+//! They are meant for ranking and capacity planning, not as a substitute
+//! for `GateTimings::calibrate` on the target machine.
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::canonical::ParamsDigest;
+use crate::codec::{CodecError, Encoded, Json};
+use crate::circuit::{Circuit, GateTimings, Node, Wire};
+use crate::tfhe::TfheParams;
+use crate::torus::Torus;
+
+/// Cost of one operation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpCost {
+    pub bootstraps: u64,
+    pub flops: f64,
+    /// Scratch space beyond inputs and outputs, in bytes
+    pub memory_bytes: u64,
+}
+
+impl OpCost {
+    /// `count` runs of this operation one after another; scratch is reused
+    pub fn times(&self, count: u64) -> OpCost {
+        OpCost {
+            bootstraps: self.bootstraps * count,
+            flops: self.flops * count as f64,
+            memory_bytes: self.memory_bytes,
+        }
+    }
+
+    /// This operation followed by `other`
+    pub fn then(&self, other: &OpCost) -> OpCost {
+        OpCost {
+            bootstraps: self.bootstraps + other.bootstraps,
+            flops: self.flops + other.flops,
+            memory_bytes: self.memory_bytes.max(other.memory_bytes),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CostTable {
+    /// `ParamsDigest` of the parameter set, in hex
+    pub params: String,
+    pub ciphertext_bytes: u64,
+    /// Bootstrapping and key switching keys together
+    pub key_bytes: u64,
+    pub ops: BTreeMap<String, OpCost>,
+}
+
+impl CostTable {
+    /// Gates by `Node::name`, plus the primitives `external_product`,
+    /// `blind_rotation`, `key_switch` and `bootstrap` and the composite
+    /// `full_adder`
+    #[allow(non_snake_case)]
+    pub fn new(params: &TfheParams) -> Self {
+        let (n, N, k) = (params.n as f64, params.N as f64, params.k as f64);
+        let l = params.tgsw_params.l as f64;
+        let torus = std::mem::size_of::<Torus>() as f64;
+        let ks = params.key_switch_params();
+
+        let m = N / 2.0;
+        let fft = 5.0 * m * m.log2();
+        let external_product = OpCost {
+            bootstraps: 0,
+            flops: (k + 1.0) * l * fft + (k + 1.0) * (k + 1.0) * l * 8.0 * m + (k + 1.0) * fft,
+            // Digits as complex points, and the accumulated product
+            memory_bytes: ((k + 1.0) * l * m * 16.0 + (k + 1.0) * m * 16.0) as u64,
+        };
+        let accumulator = ((k + 1.0) * N * torus) as u64;
+        let blind_rotation = OpCost {
+            bootstraps: 0,
+            // Each CMUX also rotates, subtracts and adds the accumulator
+            flops: n * (external_product.flops + 3.0 * (k + 1.0) * N),
+            memory_bytes: external_product.memory_bytes + 2 * accumulator,
+        };
+        let key_switch = OpCost {
+            bootstraps: 0,
+            flops: k * N * ks.t as f64 * (n + 1.0),
+            memory_bytes: ((k * N + 1.0) * torus) as u64,
+        };
+        let bootstrap = OpCost { bootstraps: 1, ..blind_rotation.then(&key_switch) };
+
+        let mut ops = BTreeMap::new();
+        let linear = (n + 1.0) * 2.0;
+        for node in [
+            Node::Not(Wire(0)),
+            Node::And(Wire(0), Wire(0)),
+            Node::Or(Wire(0), Wire(0)),
+            Node::Xor(Wire(0), Wire(0)),
+            Node::Nand(Wire(0), Wire(0)),
+            Node::Mux(Wire(0), Wire(0), Wire(0)),
+        ] {
+            let cost = bootstrap.times(node.bootstraps() as u64);
+            ops.insert(node.name().to_string(), OpCost { flops: cost.flops + linear, ..cost });
+        }
+        ops.insert("external_product".to_string(), external_product);
+        ops.insert("blind_rotation".to_string(), blind_rotation);
+        ops.insert("key_switch".to_string(), key_switch);
+        ops.insert("bootstrap".to_string(), bootstrap);
+        // `HomomorphicOps::full_adder`: `xor3` and `majority`, one bootstrap each
+        ops.insert("full_adder".to_string(), bootstrap.times(2));
+
+        let bsk = params.n * params.tgsw_params.sample_bytes(params.N, params.k);
+        let ksk = ks.key_bytes(params.k * params.N, &params.tlwe_params);
+        CostTable {
+            params: ParamsDigest::of(params).to_string(),
+            ciphertext_bytes: params.tlwe_params.ciphertext_bytes() as u64,
+            key_bytes: (bsk + ksk) as u64,
+            ops,
+        }
+    }
+
+    pub fn get(&self, op: &str) -> Option<&OpCost> {
+        self.ops.get(op)
+    }
+
+    /// Cost of a circuit node; inputs and constants are free
+    pub fn node(&self, node: &Node) -> OpCost {
+        self.ops.get(node.name()).copied().unwrap_or_default()
+    }
+
+    /// Sequential cost of a whole circuit, with memory for its peak number
+    /// of live ciphertexts on top of the largest gate scratch
+    pub fn circuit(&self, circuit: &Circuit) -> OpCost {
+        let total = circuit.nodes.iter().fold(OpCost::default(), |acc, node| acc.then(&self.node(node)));
+        let report = circuit.cost_report(&GateTimings::default(), self.ciphertext_bytes as usize);
+        OpCost { memory_bytes: total.memory_bytes + report.peak_memory_bytes as u64, ..total }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("cost table serializes to JSON")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, CodecError> {
+        Self::from_bytes_with::<Json>(json.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cost_table_matches_circuit_analysis() {
+        let table = CostTable::new(&TfheParams::default_128());
+        let bootstrap = table.get("bootstrap").unwrap();
+        assert_eq!(bootstrap.bootstraps, 1);
+        assert!(bootstrap.flops > table.get("key_switch").unwrap().flops);
+        assert_eq!(table.get("OR").unwrap().bootstraps, 1);
+        assert_eq!(table.get("NOT").unwrap().bootstraps, 0);
+        assert!(table.get("full_adder").unwrap().flops < table.get("XOR").unwrap().flops * 5.0);
+
+        let mut circuit = Circuit::new();
+        let a = circuit.input();
+        let b = circuit.input();
+        let x = circuit.and(a, b);
+        let y = circuit.or(x, a);
+        circuit.output(y);
+        let cost = table.circuit(&circuit);
+        let report = circuit.cost_report(&GateTimings::default(), table.ciphertext_bytes as usize);
+        assert_eq!(cost.bootstraps, report.bootstraps as u64);
+        assert!(cost.memory_bytes > report.peak_memory_bytes as u64);
+
+        assert_eq!(CostTable::from_json(&table.to_json()).unwrap(), table);
+    }
+}
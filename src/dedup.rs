@@ -0,0 +1,88 @@
+//! Private deduplication of encrypted records by checksum.
+//!
+//! Clients hash each record to a `width`-bit checksum, e.g. with
+//! `psi::element_hash`, and upload it encrypted next to the record. The
+//! server keeps the encrypted checksums it has ingested and, for a new one,
+//! computes an equality bit against each stored checksum and an OR tree
+//! over those bits. It never learns whether the record was a duplicate or
+//! which one it matched; the client decrypts the verdict and decides.
+//!
+//! Each comparison costs about `width` gates, all independent of the other
+//! comparisons, so checking one record is linear in the stored count.
+//! Distinct records share a checksum with probability `2^-width`.
+
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::IntegerOps;
+
+/// One equality bit per `stored` checksum: whether it equals `record`
+pub fn matches<E: BooleanEvaluator>(record: &[E::Bit], stored: &[Vec<E::Bit>], eval: &E) -> Vec<E::Bit> {
+    stored.iter().map(|checksum| IntegerOps::equal(record, checksum, eval)).collect()
+}
+
+/// Whether `record` equals any of the `stored` checksums
+pub fn is_duplicate<E: BooleanEvaluator>(record: &[E::Bit], stored: &[Vec<E::Bit>], eval: &E) -> E::Bit {
+    IntegerOps::or_all(&matches(record, stored, eval), eval)
+}
+
+/// Encrypted checksums of ingested records
+pub struct DedupIndex<B> {
+    width: usize,
+    stored: Vec<Vec<B>>,
+}
+
+impl<B: Clone> DedupIndex<B> {
+    pub fn new(width: usize) -> Self {
+        DedupIndex { width, stored: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.stored.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stored.is_empty()
+    }
+
+    /// Whether `record` duplicates an ingested checksum; the index is left
+    /// as it was
+    pub fn check<E: BooleanEvaluator<Bit = B>>(&self, record: &[B], eval: &E) -> B {
+        assert_eq!(record.len(), self.width, "checksums are {} bits", self.width);
+        is_duplicate(record, &self.stored, eval)
+    }
+
+    /// `check`, then keeps `record` for later ones. It is stored whatever the
+    /// verdict, since the server cannot read it; the client drops duplicates
+    /// it learns about.
+    pub fn ingest<E: BooleanEvaluator<Bit = B>>(&mut self, record: Vec<B>, eval: &E) -> B {
+        let duplicate = self.check(&record, eval);
+        self.stored.push(record);
+        duplicate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+    use crate::psi::element_hash;
+
+    #[test]
+    fn test_ingest_flags_repeated_records() {
+        let eval = ClearEvaluator;
+        let width = 16;
+        let checksum = |record: &str| IntegerOps::constant(element_hash(record.as_bytes(), width), width, &eval);
+
+        let mut index = DedupIndex::new(width);
+        assert!(!index.check(&checksum("alice"), &eval));
+        let verdicts: Vec<bool> = ["alice", "bob", "alice", "carol", "bob"]
+            .iter()
+            .map(|r| index.ingest(checksum(r), &eval))
+            .collect();
+        assert_eq!(verdicts, [false, false, true, false, true]);
+        assert_eq!(index.len(), 5);
+
+        let stored: Vec<Vec<bool>> = ["bob", "carol", "bob"].iter().map(|r| checksum(r)).collect();
+        assert_eq!(matches(&checksum("bob"), &stored, &eval), [true, false, true]);
+        assert!(!is_duplicate(&checksum("dave"), &stored, &eval));
+    }
+}
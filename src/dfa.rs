@@ -0,0 +1,157 @@
+//! Deterministic finite automata over encrypted bytes.
+//!
+//! The current state is kept one-hot: one encrypted bit per state, all of
+//! them clear once the input is rejected. Bytes are first mapped to a few
+//! symbol classes by one `IntegerOps::lookup`, producing a one-hot class
+//! vector, and the next state is set wherever some transition
+//! `(state, class)` leads to it. A step costs the lookup plus about one AND
+//! and one OR per transition, whatever the input, so the running time
+//! reveals only the input length.
+
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::IntegerOps;
+
+#[derive(Debug, Clone)]
+pub struct Dfa {
+    /// Symbol class of each byte value
+    classes: Vec<usize>,
+    /// `transitions[state][class]`; `None` rejects the input
+    transitions: Vec<Vec<Option<usize>>>,
+    start: usize,
+    accepting: Vec<bool>,
+}
+
+impl Dfa {
+    pub fn new(classes: Vec<usize>, transitions: Vec<Vec<Option<usize>>>, start: usize, accepting: Vec<bool>) -> Self {
+        let states = transitions.len();
+        let class_count = classes.iter().max().map_or(0, |&c| c + 1);
+        assert_eq!(classes.len(), 256, "every byte value needs a class");
+        assert!(class_count <= 64, "at most 64 symbol classes are supported");
+        assert_eq!(accepting.len(), states, "one accepting flag per state");
+        assert!(start < states, "start state {} out of range", start);
+        for row in &transitions {
+            assert_eq!(row.len(), class_count, "one transition per symbol class");
+            assert!(row.iter().flatten().all(|&t| t < states), "transition to an unknown state");
+        }
+        Dfa { classes, transitions, start, accepting }
+    }
+
+    /// Well-formed UTF-8 (RFC 3629): shortest encodings only, no
+    /// surrogates, nothing above U+10FFFF
+    pub fn utf8() -> Self {
+        // ASCII, continuations 80-8F, 90-9F and A0-BF, never valid, C2-DF,
+        // E0, E1-EC and EE-EF, ED, F0, F1-F3, F4
+        let classes = (0..=255u8)
+            .map(|b| match b {
+                0x00..=0x7f => 0,
+                0x80..=0x8f => 1,
+                0x90..=0x9f => 2,
+                0xa0..=0xbf => 3,
+                0xc0 | 0xc1 | 0xf5..=0xff => 4,
+                0xc2..=0xdf => 5,
+                0xe0 => 6,
+                0xe1..=0xec | 0xee | 0xef => 7,
+                0xed => 8,
+                0xf0 => 9,
+                0xf1..=0xf3 => 10,
+                0xf4 => 11,
+            })
+            .collect();
+
+        // States: between characters, 1 to 3 continuations left, and the
+        // restricted second bytes after E0, ED, F0 and F4
+        let n = None;
+        let transitions = vec![
+            vec![Some(0), n, n, n, n, Some(1), Some(3), Some(2), Some(4), Some(6), Some(5), Some(7)],
+            vec![n, Some(0), Some(0), Some(0), n, n, n, n, n, n, n, n],
+            vec![n, Some(1), Some(1), Some(1), n, n, n, n, n, n, n, n],
+            vec![n, n, n, Some(1), n, n, n, n, n, n, n, n],
+            vec![n, Some(1), Some(1), n, n, n, n, n, n, n, n, n],
+            vec![n, Some(2), Some(2), Some(2), n, n, n, n, n, n, n, n],
+            vec![n, n, Some(2), Some(2), n, n, n, n, n, n, n, n],
+            vec![n, Some(2), n, n, n, n, n, n, n, n, n, n],
+        ];
+        let mut accepting = vec![false; transitions.len()];
+        accepting[0] = true;
+        Dfa::new(classes, transitions, 0, accepting)
+    }
+
+    pub fn states(&self) -> usize {
+        self.transitions.len()
+    }
+
+    /// Plaintext run, for reference
+    pub fn accepts_clear(&self, input: &[u8]) -> bool {
+        input
+            .iter()
+            .try_fold(self.start, |state, &b| self.transitions[state][self.classes[b as usize]])
+            .is_some_and(|state| self.accepting[state])
+    }
+
+    /// One-hot state after reading `input`, bytes least significant bit first
+    pub fn run<E: BooleanEvaluator>(&self, input: &[Vec<E::Bit>], eval: &E) -> Vec<E::Bit> {
+        let class_count = self.transitions[0].len();
+        let table: Vec<u64> = self.classes.iter().map(|&c| 1 << c).collect();
+
+        let mut state: Vec<E::Bit> = (0..self.states()).map(|s| eval.constant(s == self.start)).collect();
+        for byte in input {
+            let class = IntegerOps::lookup(byte, &table, class_count, eval);
+            let mut next: Vec<Vec<E::Bit>> = vec![Vec::new(); self.states()];
+            for (from, row) in self.transitions.iter().enumerate() {
+                for (c, to) in row.iter().enumerate() {
+                    if let Some(to) = to {
+                        next[*to].push(eval.and(&state[from], &class[c]));
+                    }
+                }
+            }
+            state = next.iter().map(|terms| IntegerOps::or_all(terms, eval)).collect();
+        }
+        state
+    }
+
+    /// Whether the automaton accepts `input`
+    pub fn accepts<E: BooleanEvaluator>(&self, input: &[Vec<E::Bit>], eval: &E) -> E::Bit {
+        let state = self.run(input, eval);
+        let accepted: Vec<E::Bit> = state
+            .into_iter()
+            .zip(&self.accepting)
+            .filter(|&(_, &accepting)| accepting)
+            .map(|(bit, _)| bit)
+            .collect();
+        IntegerOps::or_all(&accepted, eval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    #[test]
+    fn test_utf8_matches_std() {
+        let dfa = Dfa::utf8();
+        for x in 0..=255u8 {
+            for y in 0..=255u8 {
+                assert_eq!(dfa.accepts_clear(&[x, y]), std::str::from_utf8(&[x, y]).is_ok(), "{:02x} {:02x}", x, y);
+            }
+        }
+
+        let eval = ClearEvaluator;
+        let inputs: [&[u8]; 9] = [
+            b"plain",
+            "h\u{e9}llo \u{20ac} \u{1f600}".as_bytes(),
+            b"",
+            b"\xc3",
+            b"\xe0\x80\x80",
+            b"\xed\xa0\x80",
+            b"\xf4\x90\x80\x80",
+            b"\xf0\x9f\x98",
+            b"\xc0\xaf",
+        ];
+        for input in inputs {
+            let bytes: Vec<Vec<bool>> = input.iter().map(|&b| IntegerOps::constant(b as u64, 8, &eval)).collect();
+            assert_eq!(dfa.accepts(&bytes, &eval), std::str::from_utf8(input).is_ok(), "{:?}", input);
+            assert_eq!(dfa.accepts_clear(input), std::str::from_utf8(input).is_ok());
+        }
+    }
+}
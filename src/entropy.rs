@@ -0,0 +1,82 @@
+//! Randomness behind keys, masks and noise.
+//!
+//! Functions that generate keys or encrypt have a `_with_rng` variant taking
+//! any `EntropySource`, so a hardware generator can be plugged in or a test
+//! can replay the same keys. The plain functions draw from
+//! `default_entropy`, `rand`'s thread-local ChaCha generator seeded and
+//! periodically reseeded from the operating system. `seeded_entropy` is a
+//! ChaCha20 stream fixed by its seed, for reproducible tests only: keys
+//! generated from a known seed are known to everyone.
+
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand::rngs::ThreadRng;
+use rand_chacha::ChaCha20Rng;
+
+/// A cryptographically secure random number generator
+pub trait EntropySource: RngCore + CryptoRng {}
+
+impl<R: RngCore + CryptoRng + ?Sized> EntropySource for R {}
+
+/// The generator behind functions without an explicit source
+pub fn default_entropy() -> ThreadRng {
+    rand::rng()
+}
+
+/// Deterministic ChaCha20 stream from `seed`
+pub fn seeded_entropy(seed: [u8; 32]) -> ChaCha20Rng {
+    ChaCha20Rng::from_seed(seed)
+}
+
+#[cfg(all(test, feature = "client"))]
+mod tests {
+    use super::*;
+    use crate::tfhe::{TfheParams, TfheSecretKey};
+    use crate::tlwe::{TlweParams, TlweSample};
+    use crate::torus::Torus;
+
+    #[test]
+    fn test_seeded_generation_replays() {
+        let params = TfheParams { tlwe_params: TlweParams { n: 16, stddev: 1e-9 }, ..TfheParams::default() };
+        let generate = |seed| {
+            let mut rng = seeded_entropy(seed);
+            let key = TfheSecretKey::generate_with_rng(params.clone(), &mut rng);
+            let sample = TlweSample::encrypt_with_rng(&Torus::new(0.625), &key.tlwe_key, &mut rng);
+            (key, sample)
+        };
+
+        let (key, sample) = generate([1; 32]);
+        let (again, replayed) = generate([1; 32]);
+        assert_eq!(again.tlwe_key.coeffs, key.tlwe_key.coeffs);
+        assert_eq!(again.trlwe_key.polys, key.trlwe_key.polys);
+        assert_eq!((replayed.a, replayed.b), (sample.a.clone(), sample.b));
+        assert!(sample.decrypt_binary(&key.tlwe_key));
+
+        let (other, _) = generate([2; 32]);
+        assert_ne!(other.trlwe_key.polys, key.trlwe_key.polys);
+    }
+
+    #[test]
+    fn test_seeded_encryption_paths_replay() {
+        use crate::lwe::TlwePublicKey;
+        use crate::seeded::CompressedTlweSample;
+        use crate::tfhe::TfheEncoder;
+
+        let key = TfheSecretKey::generate_with_rng(
+            TfheParams { tlwe_params: TlweParams { n: 16, stddev: 1e-9 }, ..TfheParams::default() },
+            &mut seeded_entropy([1; 32]),
+        );
+        let encrypt = |seed| {
+            let mut rng = seeded_entropy(seed);
+            let compressed = CompressedTlweSample::encrypt_with_rng(&Torus::new(0.625), &key.tlwe_key, &mut rng);
+            let pk = TlwePublicKey::generate_with_rng(&key.tlwe_key, TlwePublicKey::min_len(&key.tlwe_key.params), &mut rng);
+            let public = TlweSample::encrypt_with_public_key_with_rng(&Torus::new(0.125), &pk, &mut rng);
+            let stream: Vec<TlweSample> = TfheEncoder::encrypt_iter_with_rng([true, false], &key, &mut rng).collect();
+            (compressed, (public.a, public.b), stream.into_iter().map(|s| s.b).collect::<Vec<_>>())
+        };
+
+        let (compressed, public, stream) = encrypt([3; 32]);
+        assert_eq!(encrypt([3; 32]), (compressed.clone(), public.clone(), stream.clone()));
+        assert_ne!(encrypt([4; 32]).0, compressed);
+        assert!(compressed.decompress().decrypt_binary(&key.tlwe_key));
+    }
+}
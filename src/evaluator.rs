@@ -0,0 +1,118 @@
+#[cfg(feature = "server")]
+use crate::tfhe::{TfheCloudKey, TfheGates};
+#[cfg(feature = "server")]
+use crate::tlwe::TlweSample;
+
+/// Backend able to evaluate boolean gates on its own bit representation
+pub trait BooleanEvaluator {
+    type Bit: Clone;
+
+    fn constant(&self, value: bool) -> Self::Bit;
+    fn not(&self, a: &Self::Bit) -> Self::Bit;
+    fn and(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit;
+    fn or(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit;
+    fn xor(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit;
+    fn nand(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit;
+
+    /// Returns `a` when `s` is true, `b` otherwise
+    fn mux(&self, s: &Self::Bit, a: &Self::Bit, b: &Self::Bit) -> Self::Bit;
+}
+
+/// Evaluates gates homomorphically with a cloud key
+#[cfg(feature = "server")]
+pub struct TfheEvaluator<'a> {
+    pub ck: &'a TfheCloudKey,
+}
+
+#[cfg(feature = "server")]
+impl<'a> TfheEvaluator<'a> {
+    pub fn new(ck: &'a TfheCloudKey) -> Self {
+        TfheEvaluator { ck }
+    }
+}
+
+#[cfg(feature = "server")]
+impl BooleanEvaluator for TfheEvaluator<'_> {
+    type Bit = TlweSample;
+
+    fn constant(&self, value: bool) -> TlweSample {
+        TfheGates::constant(value, self.ck)
+    }
+
+    fn not(&self, a: &TlweSample) -> TlweSample {
+        TfheGates::not(a)
+    }
+
+    fn and(&self, a: &TlweSample, b: &TlweSample) -> TlweSample {
+        TfheGates::and(a, b, self.ck)
+    }
+
+    fn or(&self, a: &TlweSample, b: &TlweSample) -> TlweSample {
+        TfheGates::or(a, b, self.ck)
+    }
+
+    fn xor(&self, a: &TlweSample, b: &TlweSample) -> TlweSample {
+        TfheGates::xor(a, b, self.ck)
+    }
+
+    fn nand(&self, a: &TlweSample, b: &TlweSample) -> TlweSample {
+        TfheGates::nand(a, b, self.ck)
+    }
+
+    fn mux(&self, s: &TlweSample, a: &TlweSample, b: &TlweSample) -> TlweSample {
+        TfheGates::mux(s, a, b, self.ck)
+    }
+}
+
+/// Evaluates gates on plaintext bits, useful for testing circuits
+pub struct ClearEvaluator;
+
+impl BooleanEvaluator for ClearEvaluator {
+    type Bit = bool;
+
+    fn constant(&self, value: bool) -> bool {
+        value
+    }
+
+    fn not(&self, a: &bool) -> bool {
+        !a
+    }
+
+    fn and(&self, a: &bool, b: &bool) -> bool {
+        *a && *b
+    }
+
+    fn or(&self, a: &bool, b: &bool) -> bool {
+        *a || *b
+    }
+
+    fn xor(&self, a: &bool, b: &bool) -> bool {
+        a ^ b
+    }
+
+    fn nand(&self, a: &bool, b: &bool) -> bool {
+        !(*a && *b)
+    }
+
+    fn mux(&self, s: &bool, a: &bool, b: &bool) -> bool {
+        if *s { *a } else { *b }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clear_evaluator_gates() {
+        let eval = ClearEvaluator;
+
+        assert!(eval.and(&true, &true));
+        assert!(!eval.and(&true, &false));
+        assert!(eval.or(&false, &true));
+        assert!(eval.xor(&true, &false));
+        assert!(!eval.nand(&true, &true));
+        assert!(eval.mux(&true, &true, &false));
+        assert!(!eval.mux(&false, &true, &false));
+    }
+}
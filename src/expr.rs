@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::circuit::{Circuit, Wire};
+use crate::evaluator::BooleanEvaluator;
+
+/// Boolean expression over named inputs, e.g. `(a & b) | !c`.
+///
+/// Precedence from loosest to tightest: `|`, `^`, `&`, `!`. The literals
+/// `true`/`false`/`1`/`0` are accepted as constants.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BoolExpr {
+    Var(String),
+    Const(bool),
+    Not(Box<BoolExpr>),
+    And(Box<BoolExpr>, Box<BoolExpr>),
+    Or(Box<BoolExpr>, Box<BoolExpr>),
+    Xor(Box<BoolExpr>, Box<BoolExpr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprError {
+    UnexpectedChar { position: usize, found: char },
+    UnexpectedEnd,
+    UnexpectedToken { position: usize },
+    MissingInput(String),
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExprError::UnexpectedChar { position, found } => {
+                write!(f, "unexpected character '{}' at position {}", found, position)
+            }
+            ExprError::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            ExprError::UnexpectedToken { position } => write!(f, "unexpected token at position {}", position),
+            ExprError::MissingInput(name) => write!(f, "no value supplied for input '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    And,
+    Or,
+    Xor,
+    Not,
+    Open,
+    Close,
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ExprError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(position, c)) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '&' => Token::And,
+            '|' => Token::Or,
+            '^' => Token::Xor,
+            '!' => Token::Not,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    ident.push(c);
+                    chars.next();
+                }
+                tokens.push((position, Token::Ident(ident)));
+                continue;
+            }
+            found => return Err(ExprError::UnexpectedChar { position, found }),
+        };
+        chars.next();
+        tokens.push((position, token));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn binary(
+        &mut self,
+        op: Token,
+        next: fn(&mut Parser) -> Result<BoolExpr, ExprError>,
+        build: fn(Box<BoolExpr>, Box<BoolExpr>) -> BoolExpr,
+    ) -> Result<BoolExpr, ExprError> {
+        let mut lhs = next(self)?;
+        while self.peek() == Some(&op) {
+            self.pos += 1;
+            let rhs = next(self)?;
+            lhs = build(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn or(&mut self) -> Result<BoolExpr, ExprError> {
+        self.binary(Token::Or, Parser::xor, BoolExpr::Or)
+    }
+
+    fn xor(&mut self) -> Result<BoolExpr, ExprError> {
+        self.binary(Token::Xor, Parser::and, BoolExpr::Xor)
+    }
+
+    fn and(&mut self) -> Result<BoolExpr, ExprError> {
+        self.binary(Token::And, Parser::unary, BoolExpr::And)
+    }
+
+    fn unary(&mut self) -> Result<BoolExpr, ExprError> {
+        let (position, token) = self.tokens.get(self.pos).cloned().ok_or(ExprError::UnexpectedEnd)?;
+        self.pos += 1;
+
+        match token {
+            Token::Not => Ok(BoolExpr::Not(Box::new(self.unary()?))),
+            Token::Open => {
+                let inner = self.or()?;
+                match self.peek() {
+                    Some(Token::Close) => {
+                        self.pos += 1;
+                        Ok(inner)
+                    }
+                    Some(_) => Err(ExprError::UnexpectedToken { position: self.tokens[self.pos].0 }),
+                    None => Err(ExprError::UnexpectedEnd),
+                }
+            }
+            Token::Ident(name) => Ok(match name.as_str() {
+                "true" | "1" => BoolExpr::Const(true),
+                "false" | "0" => BoolExpr::Const(false),
+                _ => BoolExpr::Var(name),
+            }),
+            _ => Err(ExprError::UnexpectedToken { position }),
+        }
+    }
+}
+
+impl BoolExpr {
+    pub fn parse(source: &str) -> Result<Self, ExprError> {
+        let mut parser = Parser { tokens: tokenize(source)?, pos: 0 };
+        let expr = parser.or()?;
+
+        match parser.tokens.get(parser.pos) {
+            Some(&(position, _)) => Err(ExprError::UnexpectedToken { position }),
+            None => Ok(expr),
+        }
+    }
+
+    /// Input names in order of first appearance
+    pub fn variables(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        self.collect_variables(&mut names);
+        names
+    }
+
+    fn collect_variables(&self, names: &mut Vec<String>) {
+        match self {
+            BoolExpr::Var(name) => {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+            BoolExpr::Const(_) => {}
+            BoolExpr::Not(a) => a.collect_variables(names),
+            BoolExpr::And(a, b) | BoolExpr::Or(a, b) | BoolExpr::Xor(a, b) => {
+                a.collect_variables(names);
+                b.collect_variables(names);
+            }
+        }
+    }
+
+    fn build(&self, circuit: &mut Circuit, inputs: &HashMap<String, Wire>) -> Wire {
+        match self {
+            BoolExpr::Var(name) => inputs[name],
+            BoolExpr::Const(v) => circuit.constant(*v),
+            BoolExpr::Not(a) => {
+                let a = a.build(circuit, inputs);
+                circuit.not(a)
+            }
+            BoolExpr::And(a, b) => {
+                let (a, b) = (a.build(circuit, inputs), b.build(circuit, inputs));
+                circuit.and(a, b)
+            }
+            BoolExpr::Or(a, b) => {
+                let (a, b) = (a.build(circuit, inputs), b.build(circuit, inputs));
+                circuit.or(a, b)
+            }
+            BoolExpr::Xor(a, b) => {
+                let (a, b) = (a.build(circuit, inputs), b.build(circuit, inputs));
+                circuit.xor(a, b)
+            }
+        }
+    }
+
+    /// Lowers the expression to a single-output circuit whose inputs follow `variables()`
+    pub fn to_circuit(&self) -> Circuit {
+        let mut circuit = Circuit::new();
+        let inputs: HashMap<String, Wire> = self.variables()
+            .into_iter()
+            .map(|name| (name, circuit.input()))
+            .collect();
+
+        let out = self.build(&mut circuit, &inputs);
+        circuit.output(out);
+        circuit
+    }
+
+    pub fn evaluate<E: BooleanEvaluator>(
+        &self,
+        eval: &E,
+        inputs: &HashMap<String, E::Bit>,
+    ) -> Result<E::Bit, ExprError> {
+        let values = self.variables()
+            .into_iter()
+            .map(|name| inputs.get(&name).cloned().ok_or(ExprError::MissingInput(name)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(self.to_circuit().evaluate(eval, &values).remove(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    fn inputs(values: &[(&str, bool)]) -> HashMap<String, bool> {
+        values.iter().map(|&(k, v)| (k.to_string(), v)).collect()
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        let expr = BoolExpr::parse("a | b & !c ^ d").unwrap();
+        let a = Box::new(BoolExpr::Var("a".into()));
+        let b = Box::new(BoolExpr::Var("b".into()));
+        let not_c = Box::new(BoolExpr::Not(Box::new(BoolExpr::Var("c".into()))));
+        let d = Box::new(BoolExpr::Var("d".into()));
+
+        let expected = BoolExpr::Or(a, Box::new(BoolExpr::Xor(Box::new(BoolExpr::And(b, not_c)), d)));
+        assert_eq!(expr, expected);
+        assert_eq!(expr.variables(), vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_evaluate_policy() {
+        let expr = BoolExpr::parse("(is_admin & !locked) | override_flag").unwrap();
+
+        let allowed = expr.evaluate(&ClearEvaluator, &inputs(&[
+            ("is_admin", true), ("locked", false), ("override_flag", false),
+        ]));
+        assert_eq!(allowed, Ok(true));
+
+        let denied = expr.evaluate(&ClearEvaluator, &inputs(&[
+            ("is_admin", true), ("locked", true), ("override_flag", false),
+        ]));
+        assert_eq!(denied, Ok(false));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(BoolExpr::parse("a & "), Err(ExprError::UnexpectedEnd));
+        assert_eq!(BoolExpr::parse("(a | b"), Err(ExprError::UnexpectedEnd));
+        assert_eq!(BoolExpr::parse("a + b"), Err(ExprError::UnexpectedChar { position: 2, found: '+' }));
+        assert_eq!(BoolExpr::parse("a b"), Err(ExprError::UnexpectedToken { position: 2 }));
+
+        let expr = BoolExpr::parse("a & true").unwrap();
+        assert_eq!(
+            expr.evaluate(&ClearEvaluator, &HashMap::new()),
+            Err(ExprError::MissingInput("a".to_string())),
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! Balanced Feistel network over encrypted blocks, a format-preserving
+//! pseudo-random permutation of `2h`-bit values for tokenization.
+//!
+//! Round `i` maps `(L, R)` to `(R, L ^ F_i(R))`, where `F_i` is a public
+//! table of `2^h` entries derived from round key `i` with SHA-256. On
+//! encrypted input a table lookup is a `mux` tree over public entries: the
+//! leaves fold into constants or the index bit itself, so a round costs at
+//! most `h * 2^(h-1)` gates. The keys are public, so the permutation hides
+//! nothing by itself; what stays private is which input maps to which token.
+
+use sha2::{Digest, Sha256};
+use crate::evaluator::BooleanEvaluator;
+
+#[derive(Debug, Clone)]
+pub struct FeistelNetwork {
+    half_bits: usize,
+    /// One round function per round, indexed by the right half
+    tables: Vec<Vec<u64>>,
+}
+
+impl FeistelNetwork {
+    /// Round functions derived from `round_keys`, on blocks of `2 * half_bits` bits
+    pub fn new(half_bits: usize, round_keys: &[u64]) -> Self {
+        assert!((1..=16).contains(&half_bits), "half blocks must have between 1 and 16 bits");
+        let mask = (1u64 << half_bits) - 1;
+
+        let tables = round_keys.iter()
+            .map(|key| {
+                (0..1u64 << half_bits)
+                    .map(|x| {
+                        let digest = Sha256::new()
+                            .chain_update(key.to_le_bytes())
+                            .chain_update(x.to_le_bytes())
+                            .finalize();
+                        u64::from_le_bytes(digest[..8].try_into().expect("eight bytes")) & mask
+                    })
+                    .collect()
+            })
+            .collect();
+
+        FeistelNetwork { half_bits, tables }
+    }
+
+    pub fn block_bits(&self) -> usize {
+        2 * self.half_bits
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// Plaintext permutation; the low half of `x` is the right half of the block
+    pub fn permute(&self, x: u64) -> u64 {
+        let mask = (1u64 << self.half_bits) - 1;
+        let (mut left, mut right) = ((x >> self.half_bits) & mask, x & mask);
+        for table in &self.tables {
+            (left, right) = (right, left ^ table[right as usize]);
+        }
+        (left << self.half_bits) | right
+    }
+
+    pub fn invert(&self, y: u64) -> u64 {
+        let mask = (1u64 << self.half_bits) - 1;
+        let (mut left, mut right) = ((y >> self.half_bits) & mask, y & mask);
+        for table in self.tables.iter().rev() {
+            (left, right) = (right ^ table[left as usize], left);
+        }
+        (left << self.half_bits) | right
+    }
+
+    /// `permute` on an encrypted block, least significant bit first
+    pub fn evaluate<E: BooleanEvaluator>(&self, block: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        assert_eq!(block.len(), self.block_bits(), "block width does not match the network");
+        let (right, left) = block.split_at(self.half_bits);
+        let (mut left, mut right) = (left.to_vec(), right.to_vec());
+
+        for table in &self.tables {
+            let f = self.lookup(table, &right, eval);
+            let mixed = left.iter().zip(f.iter()).map(|(l, f)| eval.xor(l, f)).collect();
+            (left, right) = (right, mixed);
+        }
+        right.extend(left);
+        right
+    }
+
+    /// `invert` on an encrypted block
+    pub fn evaluate_inverse<E: BooleanEvaluator>(&self, block: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        assert_eq!(block.len(), self.block_bits(), "block width does not match the network");
+        let (right, left) = block.split_at(self.half_bits);
+        let (mut left, mut right) = (left.to_vec(), right.to_vec());
+
+        for table in self.tables.iter().rev() {
+            let f = self.lookup(table, &left, eval);
+            let mixed = right.iter().zip(f.iter()).map(|(r, f)| eval.xor(r, f)).collect();
+            (left, right) = (mixed, left);
+        }
+        right.extend(left);
+        right
+    }
+
+    // Selects table[index] bit by bit, halving the candidates with each index bit
+    fn lookup<E: BooleanEvaluator>(&self, table: &[u64], index: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        (0..self.half_bits)
+            .map(|bit| {
+                let mut layer: Vec<E::Bit> = table.iter().map(|&entry| eval.constant((entry >> bit) & 1 == 1)).collect();
+                for s in index {
+                    layer = layer.chunks(2).map(|pair| eval.mux(s, &pair[1], &pair[0])).collect();
+                }
+                layer.remove(0)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+    use crate::integer::IntegerOps;
+
+    fn value(bits: &[bool]) -> u64 {
+        bits.iter().enumerate().fold(0, |acc, (i, &b)| acc | ((b as u64) << i))
+    }
+
+    #[test]
+    fn test_permutation_and_inverse() {
+        let network = FeistelNetwork::new(4, &[11, 22, 33, 44]);
+        assert_eq!((network.block_bits(), network.rounds()), (8, 4));
+
+        let mut seen = [false; 256];
+        for x in 0..256 {
+            let y = network.permute(x);
+            assert!(y < 256 && !seen[y as usize]);
+            seen[y as usize] = true;
+            assert_eq!(network.invert(y), x);
+        }
+        assert_ne!((0..256).filter(|&x| network.permute(x) == x).count(), 256);
+    }
+
+    #[test]
+    fn test_encrypted_evaluation_matches_plaintext() {
+        let eval = ClearEvaluator;
+        let network = FeistelNetwork::new(3, &[7, 1, 2024]);
+
+        for x in 0..64 {
+            let block = IntegerOps::constant(x, 6, &eval);
+            let token = network.evaluate(&block, &eval);
+            assert_eq!(value(&token), network.permute(x));
+            assert_eq!(value(&network.evaluate_inverse(&token, &eval)), x);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Negacyclic FFT for products of polynomials modulo `X^N + 1`.
+//!
+//! Twisting coefficient `j` by `psi^j`, with `psi = e^(i pi / N)` a primitive
+//! `2N`-th root of unity, turns the negacyclic convolution into a cyclic one,
+//! which a length-`N` complex FFT diagonalizes. A product then costs two
+//! forward transforms, `N` pointwise multiplications and one inverse
+//! transform instead of `N^2` coefficient products. Torus coefficients are
+//! centered in `[-1/2, 1/2)` first so the rounding error of `f64` stays far
+//! below the noise of the ciphertexts being multiplied.
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::ops::{Add, Mul, Sub};
+use std::sync::{Arc, Mutex, OnceLock};
+use crate::torus::Torus;
+use crate::trlwe::TorusPolynomial;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Complex { re, im }
+    }
+
+    /// `e^(i theta)`
+    pub fn from_angle(theta: f64) -> Self {
+        Complex { re: theta.cos(), im: theta.sin() }
+    }
+
+    pub fn conj(self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+/// Precomputed twists and roots of unity for one ring degree
+#[derive(Debug, Clone)]
+pub struct NegacyclicFft {
+    n: usize,
+    /// `psi^j` for each coefficient
+    twist: Vec<Complex>,
+    /// `e^(-2 pi i k / N)` for `k < N/2`
+    roots: Vec<Complex>,
+}
+
+impl NegacyclicFft {
+    /// The transform of degree `n` shared by the whole process, built on
+    /// first use
+    pub fn cached(n: usize) -> Arc<NegacyclicFft> {
+        static CACHE: OnceLock<Mutex<HashMap<usize, Arc<NegacyclicFft>>>> = OnceLock::new();
+        let mut cache = CACHE.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+        cache.entry(n).or_insert_with(|| Arc::new(NegacyclicFft::new(n))).clone()
+    }
+
+    pub fn new(n: usize) -> Self {
+        assert!(n.is_power_of_two(), "ring degree must be a power of two");
+        NegacyclicFft {
+            n,
+            twist: (0..n).map(|j| Complex::from_angle(PI * j as f64 / n as f64)).collect(),
+            roots: (0..n / 2).map(|k| Complex::from_angle(-2.0 * PI * k as f64 / n as f64)).collect(),
+        }
+    }
+
+    /// Evaluations of the polynomial at the odd powers of `psi`
+    pub fn forward(&self, coeffs: &[f64]) -> Vec<Complex> {
+        assert_eq!(coeffs.len(), self.n, "polynomial degree does not match the transform");
+        let mut values: Vec<Complex> = coeffs.iter()
+            .zip(self.twist.iter())
+            .map(|(&c, &t)| Complex::new(c * t.re, c * t.im))
+            .collect();
+        self.transform(&mut values, false);
+        values
+    }
+
+    /// Inverse of `forward`, keeping the real parts
+    pub fn inverse(&self, spectrum: &[Complex]) -> Vec<f64> {
+        assert_eq!(spectrum.len(), self.n, "spectrum length does not match the transform");
+        let mut values = spectrum.to_vec();
+        self.transform(&mut values, true);
+        let scale = 1.0 / self.n as f64;
+        values.iter()
+            .zip(self.twist.iter())
+            .map(|(&v, &t)| (v * t.conj()).re * scale)
+            .collect()
+    }
+
+    /// Negacyclic product of two real polynomials
+    pub fn mul_f64(&self, a: &[f64], b: &[f64]) -> Vec<f64> {
+        let fa = self.forward(a);
+        let fb = self.forward(b);
+        let product: Vec<Complex> = fa.iter().zip(fb.iter()).map(|(&x, &y)| x * y).collect();
+        self.inverse(&product)
+    }
+
+    pub fn degree(&self) -> usize {
+        self.n
+    }
+
+    /// `forward` of a torus polynomial, centered in `[-1/2, 1/2)`
+    pub fn forward_torus(&self, a: &TorusPolynomial) -> Vec<Complex> {
+        self.forward(&centered(a))
+    }
+
+    pub fn forward_int(&self, b: &[i32]) -> Vec<Complex> {
+        let b: Vec<f64> = b.iter().map(|&x| x as f64).collect();
+        self.forward(&b)
+    }
+
+    /// `inverse` reduced modulo 1
+    pub fn inverse_torus(&self, spectrum: &[Complex]) -> TorusPolynomial {
+        TorusPolynomial { coeffs: self.inverse(spectrum).into_iter().map(Torus::new).collect() }
+    }
+
+    /// Negacyclic product of a torus polynomial with an integer polynomial
+    pub fn poly_mul(&self, a: &TorusPolynomial, b: &[i32]) -> TorusPolynomial {
+        let b: Vec<f64> = b.iter().map(|&x| x as f64).collect();
+        TorusPolynomial { coeffs: self.mul_f64(&centered(a), &b).into_iter().map(Torus::new).collect() }
+    }
+
+    // In-place iterative radix-2 transform; `invert` uses the conjugate roots
+    fn transform(&self, values: &mut [Complex], invert: bool) {
+        let n = self.n;
+        let bits = n.trailing_zeros();
+        for i in 0..n {
+            let j = i.reverse_bits() >> (usize::BITS - bits).min(usize::BITS - 1);
+            if n > 1 && i < j {
+                values.swap(i, j);
+            }
+        }
+
+        let mut len = 2;
+        while len <= n {
+            let stride = n / len;
+            for start in (0..n).step_by(len) {
+                for k in 0..len / 2 {
+                    let root = self.roots[k * stride];
+                    let root = if invert { root.conj() } else { root };
+                    let u = values[start + k];
+                    let v = values[start + k + len / 2] * root;
+                    values[start + k] = u + v;
+                    values[start + k + len / 2] = u - v;
+                }
+            }
+            len <<= 1;
+        }
+    }
+}
+
+fn centered(a: &TorusPolynomial) -> Vec<f64> {
+    a.coeffs.iter()
+        .map(|c| if c.value() >= 0.5 { c.value() - 1.0 } else { c.value() })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    fn naive(a: &[f64], b: &[f64]) -> Vec<f64> {
+        let n = a.len();
+        let mut out = vec![0.0; n];
+        for i in 0..n {
+            for j in 0..n {
+                let sign = if i + j < n { 1.0 } else { -1.0 };
+                out[(i + j) % n] += sign * a[i] * b[j];
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_roundtrip_and_negacyclic_product() {
+        let mut rng = rand::rng();
+        for n in [1, 2, 16, 256] {
+            let fft = NegacyclicFft::new(n);
+            let a: Vec<f64> = (0..n).map(|_| rng.random_range(-1.0..1.0)).collect();
+            let b: Vec<f64> = (0..n).map(|_| rng.random_range(-512i32..512) as f64).collect();
+
+            for (x, y) in fft.inverse(&fft.forward(&a)).iter().zip(a.iter()) {
+                assert!((x - y).abs() < 1e-12);
+            }
+            for (x, y) in fft.mul_f64(&a, &b).iter().zip(naive(&a, &b).iter()) {
+                assert!((x - y).abs() < 1e-8, "{} != {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_torus_poly_mul_matches_schoolbook() {
+        let n = 1024;
+        let fft = NegacyclicFft::new(n);
+        let a = TorusPolynomial::random(n);
+        let mut rng = rand::rng();
+        let b: Vec<i32> = (0..n).map(|_| rng.random_range(-512..512)).collect();
+
+        let fast = fft.poly_mul(&a, &b);
+        let slow = a.mul_int_schoolbook(&b);
+        for (x, y) in fast.coeffs.iter().zip(slow.coeffs.iter()) {
+            let error = (x.value() - y.value() + 0.5).rem_euclid(1.0) - 0.5;
+            assert!(error.abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::IntegerOps;
+
+/// How the bits dropped by a requantization are rounded away
+#[derive(Debug, Clone, Copy)]
+pub enum Rounding<'a, B> {
+    /// Round toward negative infinity, free
+    Truncate,
+    /// Round half up
+    Nearest,
+    /// Round up with probability equal to the dropped fraction, using one
+    /// fresh encrypted random bit per dropped bit. Unbiased in expectation,
+    /// which keeps long low-precision accumulations from drifting.
+    Stochastic(&'a [B]),
+}
+
+/// Fixed-point helpers on little-endian bit vectors with an implicit binary point
+pub struct FixedOps;
+
+impl FixedOps {
+    /// Drops the `drop` lowest fraction bits of `a`.
+    ///
+    /// The result is one bit wider than `a.len() - drop` so rounding up can
+    /// never wrap; `signed` selects two's complement (sign extension) over
+    /// unsigned values.
+    pub fn requantize<E: BooleanEvaluator>(
+        a: &[E::Bit],
+        drop: usize,
+        signed: bool,
+        rounding: Rounding<'_, E::Bit>,
+        eval: &E,
+    ) -> Vec<E::Bit> {
+        assert!(drop <= a.len(), "cannot drop more bits than the value has");
+        let extended = Self::extend(a, a.len() + 1, signed, eval);
+
+        let offset = match rounding {
+            Rounding::Truncate => return extended[drop..].to_vec(),
+            Rounding::Nearest if drop == 0 => return extended,
+            Rounding::Nearest => IntegerOps::constant(1 << (drop - 1), drop, eval),
+            Rounding::Stochastic(random) => {
+                assert!(random.len() >= drop, "stochastic rounding needs one random bit per dropped bit");
+                random[..drop].to_vec()
+            }
+        };
+
+        // floor((a + offset) / 2^drop), with the offset confined to the dropped bits
+        let offset = IntegerOps::resize(&offset, extended.len(), eval);
+        IntegerOps::add_mod(&extended, &offset, eval)[drop..].to_vec()
+    }
+
+    /// Product of two `n`-bit values with `frac_bits` fraction bits, rounded
+    /// back to `n` bits with the same scale; wraps if the integer part overflows
+    pub fn mul<E: BooleanEvaluator>(
+        a: &[E::Bit],
+        b: &[E::Bit],
+        frac_bits: usize,
+        signed: bool,
+        rounding: Rounding<'_, E::Bit>,
+        eval: &E,
+    ) -> Vec<E::Bit> {
+        assert_eq!(a.len(), b.len());
+        let width = 2 * a.len();
+
+        // Extending first makes the wrapped product correct for two's complement too
+        let product = IntegerOps::mul(
+            &Self::extend(a, width, signed, eval),
+            &Self::extend(b, width, signed, eval),
+            width,
+            eval,
+        );
+        let mut result = Self::requantize(&product, frac_bits, signed, rounding, eval);
+        result.truncate(a.len());
+        result
+    }
+
+    fn extend<E: BooleanEvaluator>(a: &[E::Bit], width: usize, signed: bool, eval: &E) -> Vec<E::Bit> {
+        match a.last() {
+            Some(msb) if signed => {
+                let mut bits = a.to_vec();
+                bits.resize(width.max(a.len()), msb.clone());
+                bits.truncate(width);
+                bits
+            }
+            _ => IntegerOps::resize(a, width, eval),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    fn bits(value: i64, width: usize) -> Vec<bool> {
+        IntegerOps::constant(value as u64, width, &ClearEvaluator)
+    }
+
+    fn signed(bits: &[bool]) -> i64 {
+        let raw = bits.iter().enumerate().fold(0i64, |acc, (i, &b)| acc | ((b as i64) << i));
+        if bits.last() == Some(&true) { raw - (1 << bits.len()) } else { raw }
+    }
+
+    #[test]
+    fn test_requantize_modes() {
+        let eval = ClearEvaluator;
+        // -2.75 and 2.75 with two fraction bits
+        for (value, truncated, nearest) in [(-11, -3, -3), (11, 2, 3), (10, 2, 3), (9, 2, 2)] {
+            let x = bits(value, 6);
+            assert_eq!(signed(&FixedOps::requantize(&x, 2, true, Rounding::Truncate, &eval)), truncated);
+            assert_eq!(signed(&FixedOps::requantize(&x, 2, true, Rounding::Nearest, &eval)), nearest);
+        }
+
+        // Unsigned maximum rounds up into the extra bit instead of wrapping
+        let max = FixedOps::requantize(&bits(15, 4), 1, false, Rounding::Nearest, &eval);
+        assert_eq!(max, vec![false, false, false, true]);
+    }
+
+    #[test]
+    fn test_stochastic_rounding_is_unbiased() {
+        let eval = ClearEvaluator;
+        // 2.25 with two fraction bits rounds up for exactly one in four random offsets
+        let x = bits(9, 6);
+        let results: Vec<i64> = (0..4)
+            .map(|r| {
+                let random = bits(r, 2);
+                signed(&FixedOps::requantize(&x, 2, true, Rounding::Stochastic(&random), &eval))
+            })
+            .collect();
+        assert_eq!(results.iter().filter(|&&v| v == 3).count(), 1);
+        assert_eq!(results.iter().sum::<i64>() as f64 / 4.0, 2.25);
+    }
+
+    #[test]
+    fn test_fixed_mul() {
+        let eval = ClearEvaluator;
+        // 1.5 * -2.25 = -3.375 with two fraction bits
+        let product = FixedOps::mul(&bits(6, 8), &bits(-9, 8), 2, true, Rounding::Truncate, &eval);
+        assert_eq!(signed(&product), -14);
+        let product = FixedOps::mul(&bits(6, 8), &bits(-9, 8), 2, true, Rounding::Nearest, &eval);
+        assert_eq!(signed(&product), -13);
+    }
+}
@@ -0,0 +1,123 @@
+//! Functional bootstrapping over the whole torus, without a padding bit.
+//!
+//! Blind rotation reads a negacyclic test polynomial, so a plain
+//! programmable bootstrap only evaluates tables with `F(x + 1/2) = -F(x)`
+//! up to a constant, which is why `apply_lut` keeps messages in the first
+//! half of the torus. Any table splits into an odd part
+//! `O(x) = (F(x) - F(x + 1/2)) / 2`, negacyclic by construction, and an
+//! even part `E(x) = (F(x) + F(x + 1/2)) / 2` of period `1/2`. Three
+//! padded bootstraps then evaluate `F` on the full torus:
+//!
+//! 1. the sign `s`, `1/4` on the first half and `-1/4` on the second,
+//! 2. `E` on `x - s + 1/4`, which is `x mod 1/2` back in the first half,
+//! 3. `O` on `x` itself,
+//!
+//! and `F(x) = E + O`. Inputs must stay clear of `0` and `1/2` by more than
+//! their noise, where the sign is ambiguous; `encode_int_full` centres
+//! messages so that an even modulus always does.
+
+#[cfg(feature = "client")]
+use crate::tfhe::{TfheEncoder, TfheSecretKey};
+#[cfg(feature = "server")]
+use crate::lutdebug::TestVector;
+#[cfg(feature = "server")]
+use crate::tfhe::{TfheCloudKey, TfheGates};
+#[cfg(any(feature = "client", feature = "server"))]
+use crate::tlwe::TlweSample;
+#[cfg(any(feature = "client", feature = "server"))]
+use crate::torus::Torus;
+
+// Centre of slot `m` when the whole torus is cut into `p` slots
+#[cfg(any(feature = "client", feature = "server"))]
+fn full_phase(m: u64, p: u64) -> Torus {
+    Torus::new((2 * m + 1) as f64 / (2 * p) as f64)
+}
+
+#[cfg(feature = "server")]
+impl TfheGates {
+    /// `lut[phase * lut.len()]` for a phase anywhere on the torus, at the
+    /// cost of three bootstraps; `lut` needs an even length. Entries are
+    /// read as reals in `[0, 1)`.
+    pub fn bootstrap_full_domain(input: &TlweSample, lut: &[Torus], ck: &TfheCloudKey) -> TlweSample {
+        assert!(!lut.is_empty() && lut.len().is_multiple_of(2), "full domain tables need an even, nonzero length");
+        let big_n = ck.bootstrapping_key.ring_params.N;
+        let len = lut.len();
+        let half = len / 2;
+        // Entries at `x` and `x + 1/2`, for `x` in the first half
+        let at = |x: f64| ((x * len as f64) as usize).min(half - 1);
+        let even = |x: f64| Torus::new((lut[at(x)].value() + lut[at(x) + half].value()) / 2.0);
+        let odd = |x: f64| Torus::new((lut[at(x)].value() - lut[at(x) + half].value()) / 2.0);
+
+        let sign = TestVector::negacyclic(|_| Torus::new(0.25), big_n);
+        let even = TestVector::negacyclic(even, big_n);
+        let odd = TestVector::negacyclic(odd, big_n);
+
+        let s = Self::bootstrap_with("full_domain", input, &sign, len, ck);
+        let mut folded = input.sub(&s);
+        folded.b = folded.b.add(&Torus::new(0.25));
+        let e = Self::bootstrap_with("full_domain", &folded, &even, len, ck);
+        let o = Self::bootstrap_with("full_domain", input, &odd, len, ck);
+        e.add(&o)
+    }
+
+    /// `apply_lut` for messages of `[0, message_modulus)` spread over the
+    /// whole torus by `TfheEncoder::encode_int_full`, twice the precision
+    /// for the same parameters. The modulus must be even and at most `N`.
+    pub fn apply_lut_full(
+        input: &TlweSample,
+        f: impl Fn(u64) -> u64,
+        message_modulus: u64,
+        ck: &TfheCloudKey,
+    ) -> TlweSample {
+        let big_n = ck.bootstrapping_key.ring_params.N as u64;
+        assert!(
+            message_modulus >= 2 && message_modulus.is_multiple_of(2) && message_modulus <= big_n,
+            "message modulus {} needs to be even and at most N = {}",
+            message_modulus,
+            big_n,
+        );
+
+        let two_n = 2 * big_n;
+        let lut: Vec<Torus> = (0..two_n)
+            .map(|j| full_phase(f(j * message_modulus / two_n) % message_modulus, message_modulus))
+            .collect();
+        Self::bootstrap_full_domain(input, &lut, ck)
+    }
+}
+
+#[cfg(feature = "client")]
+impl TfheEncoder {
+    /// Message `m` of `[0, message_modulus)` at `(2m + 1) / 2p`, the
+    /// encoding `TfheGates::apply_lut_full` reads and writes
+    pub fn encode_int_full(m: u64, message_modulus: u64, sk: &TfheSecretKey) -> TlweSample {
+        assert!(m < message_modulus, "message {} is not below the modulus {}", m, message_modulus);
+        TlweSample::encrypt(&full_phase(m, message_modulus), &sk.tlwe_key)
+    }
+
+    pub fn decode_int_full(sample: &TlweSample, message_modulus: u64, sk: &TfheSecretKey) -> u64 {
+        let phase = sample.decrypt_phase(&sk.tlwe_key).value();
+        (phase * message_modulus as f64).floor() as u64 % message_modulus
+    }
+}
+
+#[cfg(all(test, feature = "client", feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::tfhe::fixtures::small_keys;
+
+    #[test]
+    fn test_full_domain_lut() {
+        let (sk, ck) = small_keys(64);
+
+        // Neither table is negacyclic over the full domain
+        let square = |x: u64| x * x;
+        let step = |x: u64| if x >= 5 { 15 } else { 3 };
+        for m in 0..16 {
+            let input = TfheEncoder::encode_int_full(m, 16, &sk);
+            let out = TfheGates::apply_lut_full(&input, square, 16, &ck);
+            assert_eq!(TfheEncoder::decode_int_full(&out, 16, &sk), m * m % 16, "{}", m);
+            let out = TfheGates::apply_lut_full(&input, step, 16, &ck);
+            assert_eq!(TfheEncoder::decode_int_full(&out, 16, &sk), step(m), "{}", m);
+        }
+    }
+}
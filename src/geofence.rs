@@ -0,0 +1,94 @@
+//! Encrypted geofencing: is a location inside a rectangle.
+//!
+//! Coordinates are unsigned fixed-width integers, e.g. latitude and
+//! longitude shifted and scaled onto a grid. Bounds are inclusive and each
+//! may be public or encrypted: a public bound costs at most one gate per
+//! bit through `cmp_clear`, an encrypted one a full comparison. The answer
+//! is one encrypted bit, four comparisons and three ANDs.
+
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::{CmpOp, IntegerOps};
+
+/// One bound of a rectangle
+#[derive(Debug, Clone)]
+pub enum Coord<B> {
+    Clear(u64),
+    /// Little-endian bits, as wide as the coordinates
+    Encrypted(Vec<B>),
+}
+
+/// Axis-aligned rectangle, bounds included
+#[derive(Debug, Clone)]
+pub struct Rect<B> {
+    pub x_min: Coord<B>,
+    pub x_max: Coord<B>,
+    pub y_min: Coord<B>,
+    pub y_max: Coord<B>,
+}
+
+impl<B> Rect<B> {
+    /// A rectangle with public bounds
+    pub fn clear(x_min: u64, x_max: u64, y_min: u64, y_max: u64) -> Self {
+        Rect {
+            x_min: Coord::Clear(x_min),
+            x_max: Coord::Clear(x_max),
+            y_min: Coord::Clear(y_min),
+            y_max: Coord::Clear(y_max),
+        }
+    }
+}
+
+// `value op bound`
+fn compare<E: BooleanEvaluator>(value: &[E::Bit], op: CmpOp, bound: &Coord<E::Bit>, eval: &E) -> E::Bit {
+    match bound {
+        Coord::Clear(c) => IntegerOps::cmp_clear(value, *c, op, eval),
+        Coord::Encrypted(b) => IntegerOps::compare(value, b, op, eval),
+    }
+}
+
+/// Whether `(x, y)` lies inside `rect`
+pub fn in_rect<E: BooleanEvaluator>(x: &[E::Bit], y: &[E::Bit], rect: &Rect<E::Bit>, eval: &E) -> E::Bit {
+    let checks = [
+        compare(x, CmpOp::Ge, &rect.x_min, eval),
+        compare(x, CmpOp::Le, &rect.x_max, eval),
+        compare(y, CmpOp::Ge, &rect.y_min, eval),
+        compare(y, CmpOp::Le, &rect.y_max, eval),
+    ];
+    IntegerOps::and_all(&checks, eval)
+}
+
+/// Whether `(x, y)` lies inside any of `rects`, e.g. the zones of a fence
+pub fn in_any_rect<E: BooleanEvaluator>(x: &[E::Bit], y: &[E::Bit], rects: &[Rect<E::Bit>], eval: &E) -> E::Bit {
+    let inside: Vec<E::Bit> = rects.iter().map(|r| in_rect(x, y, r, eval)).collect();
+    IntegerOps::or_all(&inside, eval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    #[test]
+    fn test_point_in_rectangles() {
+        let eval = ClearEvaluator;
+        let bits = |v: u64| IntegerOps::constant(v, 10, &eval);
+        let public = Rect::clear(100, 200, 300, 400);
+        let encrypted = Rect {
+            x_min: Coord::Encrypted(bits(500)),
+            x_max: Coord::Encrypted(bits(600)),
+            y_min: Coord::Clear(0),
+            y_max: Coord::Encrypted(bits(50)),
+        };
+
+        for (x, y) in [(100, 300), (200, 400), (150, 350), (99, 350), (150, 401), (550, 0), (550, 51), (601, 10)] {
+            let expected_public = (100..=200).contains(&x) && (300..=400).contains(&y);
+            let expected_encrypted = (500..=600).contains(&x) && y <= 50;
+            assert_eq!(in_rect(&bits(x), &bits(y), &public, &eval), expected_public, "({}, {})", x, y);
+            assert_eq!(in_rect(&bits(x), &bits(y), &encrypted, &eval), expected_encrypted, "({}, {})", x, y);
+            assert_eq!(
+                in_any_rect(&bits(x), &bits(y), &[public.clone(), encrypted.clone()], &eval),
+                expected_public || expected_encrypted,
+            );
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::tlwe::TlweSample;
+
+/// Metadata about the bootstrap a hook is invoked for
+#[derive(Debug, Clone)]
+pub struct BootstrapInfo {
+    /// Gate performing the bootstrap, e.g. "nand"
+    pub gate: &'static str,
+    pub input_dimension: usize,
+    pub lut_size: usize,
+}
+
+/// Callbacks run around every bootstrap performed with a cloud key.
+///
+/// Hooks can collect metrics, throttle callers by blocking in `before_bootstrap`,
+/// or experiment with noise management by rewriting the bootstrapped output.
+pub trait BootstrapHook: Send + Sync {
+    fn before_bootstrap(&self, _info: &BootstrapInfo, _input: &TlweSample) {}
+
+    fn after_bootstrap(&self, _info: &BootstrapInfo, _output: &mut TlweSample, _elapsed: Duration) {}
+}
+
+/// Ordered list of hooks shared by clones of a cloud key
+#[derive(Clone, Default)]
+pub struct BootstrapHooks {
+    hooks: Vec<Arc<dyn BootstrapHook>>,
+}
+
+impl BootstrapHooks {
+    pub fn register(&mut self, hook: Arc<dyn BootstrapHook>) {
+        self.hooks.push(hook);
+    }
+
+    pub fn clear(&mut self) {
+        self.hooks.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    pub fn before(&self, info: &BootstrapInfo, input: &TlweSample) {
+        for hook in &self.hooks {
+            hook.before_bootstrap(info, input);
+        }
+    }
+
+    pub fn after(&self, info: &BootstrapInfo, output: &mut TlweSample, elapsed: Duration) {
+        for hook in &self.hooks {
+            hook.after_bootstrap(info, output, elapsed);
+        }
+    }
+}
+
+impl fmt::Debug for BootstrapHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BootstrapHooks")
+            .field("len", &self.hooks.len())
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "client", feature = "server"))]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::tfhe::{TfheGates, TfheEncoder};
+    use crate::torus::Torus;
+    use crate::tfhe::fixtures::small_keys;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl BootstrapHook for Recorder {
+        fn before_bootstrap(&self, info: &BootstrapInfo, _input: &TlweSample) {
+            self.events.lock().unwrap().push(format!("before {}", info.gate));
+        }
+
+        fn after_bootstrap(&self, info: &BootstrapInfo, _output: &mut TlweSample, _elapsed: Duration) {
+            self.events.lock().unwrap().push(format!("after {}", info.gate));
+        }
+    }
+
+    #[test]
+    fn test_hooks_wrap_every_bootstrap() {
+        let (sk, mut ck) = small_keys(32);
+        let recorder = Arc::new(Recorder::default());
+        ck.hooks.register(recorder.clone());
+
+        let a = TfheEncoder::encode_bool(true, &sk);
+        let b = TfheEncoder::encode_bool(false, &sk);
+        TfheGates::and(&a, &b, &ck);
+        TfheGates::mux(&a, &b, &a, &ck);
+
+        let events = recorder.events.lock().unwrap();
+        assert_eq!(*events, vec!["before and", "after and", "before mux", "before mux", "after mux"]);
+    }
+
+    #[test]
+    fn test_trivial_operands_skip_bootstrap() {
+        let (sk, mut ck) = small_keys(32);
+        let recorder = Arc::new(Recorder::default());
+        ck.hooks.register(recorder.clone());
+
+        let params = ck.key_switching_key.output_params();
+        let t = TlweSample::trivial(&Torus::new(0.375), params.clone());
+        let f = TlweSample::trivial(&Torus::new(0.125), params.clone());
+        let x = TfheEncoder::encode_bool(true, &sk);
+
+        let and_false = TfheGates::and(&x, &f, &ck);
+        assert!(and_false.is_trivial());
+        assert!(!and_false.decrypt_binary(&sk.tlwe_key));
+
+        assert_eq!(TfheGates::xor(&f, &x, &ck).b.value(), x.b.value());
+        assert_eq!(TfheGates::or(&f, &x, &ck).b.value(), x.b.value());
+        assert_eq!(TfheGates::mux(&t, &x, &f, &ck).b.value(), x.b.value());
+        assert!(TfheGates::nand(&t, &t, &ck).is_trivial());
+        // XOR with true is a NOT, which is linear
+        assert!(!TfheGates::xor(&t, &x, &ck).decrypt_binary(&sk.tlwe_key));
+        assert!(recorder.events.lock().unwrap().is_empty());
+
+        TfheGates::xnor(&t, &x, &ck);
+        TfheGates::orny(&x, &x, &ck);
+        assert_eq!(*recorder.events.lock().unwrap(), vec!["before orny", "after orny"]);
+    }
+}
@@ -0,0 +1,894 @@
+use crate::evaluator::BooleanEvaluator;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+// A bit whose value is public until it first depends on a ciphertext
+enum Tracked<B> {
+    Known(bool),
+    Secret(B),
+}
+
+impl<B: Clone> Tracked<B> {
+    fn into_bit<E: BooleanEvaluator<Bit = B>>(self, eval: &E) -> B {
+        match self {
+            Tracked::Known(v) => eval.constant(v),
+            Tracked::Secret(b) => b,
+        }
+    }
+}
+
+/// Unsigned integer arithmetic on little-endian bit vectors, generic over the
+/// gate backend so the same algorithms run encrypted or in the clear
+pub struct IntegerOps;
+
+impl IntegerOps {
+    /// Encodes the low `width` bits of `value` as backend constants
+    pub fn constant<E: BooleanEvaluator>(value: u64, width: usize, eval: &E) -> Vec<E::Bit> {
+        (0..width)
+            .map(|i| eval.constant(i < 64 && (value >> i) & 1 == 1))
+            .collect()
+    }
+
+    /// Zero-extends or truncates `a` to `width` bits
+    pub fn resize<E: BooleanEvaluator>(a: &[E::Bit], width: usize, eval: &E) -> Vec<E::Bit> {
+        let mut result: Vec<E::Bit> = a.iter().take(width).cloned().collect();
+        while result.len() < width {
+            result.push(eval.constant(false));
+        }
+        result
+    }
+
+    /// Returns `a` when `s` is true, `b` otherwise, bit by bit
+    pub fn mux<E: BooleanEvaluator>(s: &E::Bit, a: &[E::Bit], b: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        assert_eq!(a.len(), b.len());
+        a.iter()
+            .zip(b.iter())
+            .map(|(x, y)| eval.mux(s, x, y))
+            .collect()
+    }
+
+    /// `table[index]` as `width` bits, through a tree of `mux` over the
+    /// public entries; indices past the table read 0. Backends that fold
+    /// constant operands, like `TfheGates::mux`, skip the branches where
+    /// both entries agree.
+    pub fn lookup<E: BooleanEvaluator>(index: &[E::Bit], table: &[u64], width: usize, eval: &E) -> Vec<E::Bit> {
+        assert!(index.len() < usize::BITS as usize, "index of {} bits is too wide", index.len());
+        let mut layer: Vec<Vec<E::Bit>> = (0..1usize << index.len())
+            .map(|i| Self::constant(table.get(i).copied().unwrap_or(0), width, eval))
+            .collect();
+        for bit in index {
+            layer = layer.chunks(2).map(|pair| Self::mux(bit, &pair[1], &pair[0], eval)).collect();
+        }
+        layer.pop().expect("a table of one entry is left")
+    }
+
+    /// Ripple-carry addition; the result has one extra bit for the carry
+    pub fn add<E: BooleanEvaluator>(a: &[E::Bit], b: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        assert_eq!(a.len(), b.len());
+
+        let mut result = Vec::with_capacity(a.len() + 1);
+        let mut carry = eval.constant(false);
+        for (x, y) in a.iter().zip(b.iter()) {
+            let x_xor_y = eval.xor(x, y);
+            result.push(eval.xor(&x_xor_y, &carry));
+            carry = eval.mux(&x_xor_y, &carry, x);
+        }
+        result.push(carry);
+        result
+    }
+
+    /// Product of `a` and `b` modulo `2^width`, by shift-and-add of partial products
+    pub fn mul<E: BooleanEvaluator>(a: &[E::Bit], b: &[E::Bit], width: usize, eval: &E) -> Vec<E::Bit> {
+        let a = Self::resize(a, width, eval);
+        let mut acc = Self::constant(0, width, eval);
+
+        for (shift, y) in b.iter().take(width).enumerate() {
+            // Only the low `width - shift` bits of the shifted partial product survive
+            let partial: Vec<E::Bit> = a[..width - shift].iter().map(|x| eval.and(x, y)).collect();
+            let mut sum = Self::add(&acc[shift..], &partial, eval);
+            sum.truncate(width - shift);
+            acc.splice(shift.., sum);
+        }
+        acc
+    }
+
+    /// Sklansky parallel-prefix addition: same result as `add` in logarithmic
+    /// depth, at the cost of about `n/2 log n` extra carry combinations
+    pub fn add_prefix<E: BooleanEvaluator>(a: &[E::Bit], b: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        assert_eq!(a.len(), b.len());
+        let n = a.len();
+
+        let propagate: Vec<E::Bit> = a.iter().zip(b.iter()).map(|(x, y)| eval.xor(x, y)).collect();
+        // (generate, propagate) of the prefix ending at each position
+        let mut generate: Vec<E::Bit> = a.iter().zip(b.iter()).map(|(x, y)| eval.and(x, y)).collect();
+        let mut group = propagate.clone();
+
+        let mut span = 1;
+        while span < n {
+            for i in (0..n).filter(|i| i & span != 0) {
+                let j = (i & !(span - 1)) - 1;
+                // Generate and propagate never hold together, so a mux merges them
+                generate[i] = eval.mux(&group[i], &generate[j], &generate[i]);
+                group[i] = eval.and(&group[i], &group[j]);
+            }
+            span <<= 1;
+        }
+
+        let mut result = Vec::with_capacity(n + 1);
+        for i in 0..n {
+            result.push(if i == 0 { propagate[0].clone() } else { eval.xor(&propagate[i], &generate[i - 1]) });
+        }
+        result.push(generate.last().cloned().unwrap_or_else(|| eval.constant(false)));
+        result
+    }
+
+    /// Same product as `mul`, with the partial products reduced three rows at
+    /// a time by carry-save full adders and a single carry-propagating addition
+    /// at the end, so the depth grows with `log width` rather than `width`
+    pub fn mul_carry_save<E: BooleanEvaluator>(a: &[E::Bit], b: &[E::Bit], width: usize, eval: &E) -> Vec<E::Bit> {
+        let a = Self::resize(a, width, eval);
+        let mut rows: Vec<Vec<E::Bit>> = b.iter().take(width).enumerate()
+            .map(|(shift, y)| {
+                let mut row = Self::constant(0, shift, eval);
+                row.extend(a[..width - shift].iter().map(|x| eval.and(x, y)));
+                row
+            })
+            .collect();
+
+        while rows.len() > 2 {
+            let mut next = Vec::with_capacity(rows.len() * 2 / 3 + 2);
+            let mut chunks = rows.chunks_exact(3);
+            for triple in &mut chunks {
+                let (x, y, z) = (&triple[0], &triple[1], &triple[2]);
+                let mut sums = Vec::with_capacity(width);
+                let mut carries = Self::constant(0, 1, eval);
+                for i in 0..width {
+                    let x_xor_y = eval.xor(&x[i], &y[i]);
+                    sums.push(eval.xor(&x_xor_y, &z[i]));
+                    if i + 1 < width {
+                        carries.push(eval.mux(&x_xor_y, &z[i], &x[i]));
+                    }
+                }
+                next.push(sums);
+                next.push(carries);
+            }
+            next.extend(chunks.remainder().iter().cloned());
+            rows = next;
+        }
+
+        match rows.len() {
+            0 => Self::constant(0, width, eval),
+            1 => rows.remove(0),
+            _ => Self::add_mod(&rows[0], &rows[1], eval),
+        }
+    }
+
+    /// `a * c` modulo `2^width` for a public `c`, with one shifted addition or
+    /// subtraction per non-zero digit of the CSD recoding of `c`
+    pub fn mul_const<E: BooleanEvaluator>(a: &[E::Bit], c: u64, width: usize, eval: &E) -> Vec<E::Bit> {
+        let a = Self::resize(a, width, eval);
+        let mut acc: Option<Vec<E::Bit>> = None;
+
+        // The leading CSD digit is +1 unless it was shifted out of the width
+        for (shift, digit) in csd_digits(c).into_iter().enumerate().rev() {
+            if digit == 0 || shift >= width {
+                continue;
+            }
+            let mut shifted = Self::constant(0, shift, eval);
+            shifted.extend_from_slice(&a[..width - shift]);
+            acc = Some(match acc {
+                None if digit > 0 => shifted,
+                None => Self::sub_mod(&Self::constant(0, width, eval), &shifted, eval),
+                Some(acc) if digit > 0 => Self::add_mod(&acc, &shifted, eval),
+                Some(acc) => Self::sub_mod(&acc, &shifted, eval),
+            });
+        }
+        acc.unwrap_or_else(|| Self::constant(0, width, eval))
+    }
+
+    /// Evaluates `coeffs[0] + coeffs[1] x + ... + coeffs[d] x^d` modulo `2^width`
+    /// with Horner's rule.
+    ///
+    /// Negative coefficients wrap as two's complement, so the result can be read
+    /// as a signed `width`-bit value. While the accumulator is still the public
+    /// leading coefficient it is folded with `mul_const` instead of a full
+    /// multiplication, and zero coefficients add nothing.
+    pub fn poly_eval<E: BooleanEvaluator>(x: &[E::Bit], coeffs: &[i64], width: usize, eval: &E) -> Vec<E::Bit> {
+        let x = Self::resize(x, width, eval);
+        let mut known = Some(coeffs.last().copied().unwrap_or(0) as u64);
+        let mut acc = Vec::new();
+
+        for &c in coeffs.iter().rev().skip(1) {
+            acc = match known.take() {
+                Some(k) => Self::mul_const(&x, k, width, eval),
+                None => Self::mul(&acc, &x, width, eval),
+            };
+            if c != 0 {
+                acc = Self::add_mod(&acc, &Self::constant(c as u64, width, eval), eval);
+            }
+        }
+
+        match known {
+            Some(k) => Self::constant(k, width, eval),
+            None => acc,
+        }
+    }
+
+    /// `a + b` wrapping within the width of `a`
+    pub fn add_mod<E: BooleanEvaluator>(a: &[E::Bit], b: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        let mut sum = Self::add(a, b, eval);
+        sum.pop();
+        sum
+    }
+
+    /// `a - b` wrapping within the width of `a`, as `a + !b + 1`
+    pub fn sub_mod<E: BooleanEvaluator>(a: &[E::Bit], b: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        assert_eq!(a.len(), b.len());
+
+        let mut result = Vec::with_capacity(a.len());
+        let mut carry = eval.constant(true);
+        for (x, y) in a.iter().zip(b.iter()) {
+            let y = eval.not(y);
+            let x_xor_y = eval.xor(x, &y);
+            result.push(eval.xor(&x_xor_y, &carry));
+            carry = eval.mux(&x_xor_y, &carry, x);
+        }
+        result
+    }
+
+    /// `a^exponent` modulo `2^width` by square-and-multiply over the public exponent
+    pub fn pow_const<E: BooleanEvaluator>(a: &[E::Bit], exponent: u64, width: usize, eval: &E) -> Vec<E::Bit> {
+        if exponent == 0 {
+            return Self::constant(1, width, eval);
+        }
+
+        // Left-to-right: the leading one starts from `a` itself instead of multiplying by 1
+        let mut result = Self::resize(a, width, eval);
+        for i in (0..63 - exponent.leading_zeros()).rev() {
+            result = Self::mul(&result, &result, width, eval);
+            if (exponent >> i) & 1 == 1 {
+                result = Self::mul(&result, a, width, eval);
+            }
+        }
+        result
+    }
+
+    /// Adds one to `a` when `bit` is set, wrapping within the width of `a`
+    pub fn increment_if<E: BooleanEvaluator>(a: &[E::Bit], bit: &E::Bit, eval: &E) -> Vec<E::Bit> {
+        let mut carry = bit.clone();
+        a.iter()
+            .map(|x| {
+                let sum = eval.xor(x, &carry);
+                carry = eval.and(x, &carry);
+                sum
+            })
+            .collect()
+    }
+
+    pub fn and_all<E: BooleanEvaluator>(bits: &[E::Bit], eval: &E) -> E::Bit {
+        Self::reduce(bits, eval, true, |a, b| eval.and(a, b))
+    }
+
+    pub fn or_all<E: BooleanEvaluator>(bits: &[E::Bit], eval: &E) -> E::Bit {
+        Self::reduce(bits, eval, false, |a, b| eval.or(a, b))
+    }
+
+    // Balanced tree so the depth grows logarithmically
+    fn reduce<E: BooleanEvaluator>(
+        bits: &[E::Bit],
+        eval: &E,
+        empty: bool,
+        op: impl Fn(&E::Bit, &E::Bit) -> E::Bit,
+    ) -> E::Bit {
+        if bits.is_empty() {
+            return eval.constant(empty);
+        }
+
+        let mut layer = bits.to_vec();
+        while layer.len() > 1 {
+            layer = layer.chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => op(a, b),
+                    [a] => a.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+        layer.remove(0)
+    }
+
+    pub fn equal<E: BooleanEvaluator>(a: &[E::Bit], b: &[E::Bit], eval: &E) -> E::Bit {
+        let width = a.len().max(b.len());
+        let (a, b) = (Self::resize(a, width, eval), Self::resize(b, width, eval));
+
+        let same: Vec<E::Bit> = a.iter()
+            .zip(b.iter())
+            .map(|(x, y)| eval.not(&eval.xor(x, y)))
+            .collect();
+        Self::and_all(&same, eval)
+    }
+
+    /// Unsigned `a < b`
+    pub fn less_than<E: BooleanEvaluator>(a: &[E::Bit], b: &[E::Bit], eval: &E) -> E::Bit {
+        let width = a.len().max(b.len());
+        let (a, b) = (Self::resize(a, width, eval), Self::resize(b, width, eval));
+
+        // Scanning from the LSB, the highest differing bit decides
+        let mut lt = eval.constant(false);
+        for (x, y) in a.iter().zip(b.iter()) {
+            let differ = eval.xor(x, y);
+            lt = eval.mux(&differ, y, &lt);
+        }
+        lt
+    }
+
+    /// `a << amount` for an encrypted `amount`, as a barrel shifter of `mux` stages
+    pub fn shift_left_by<E: BooleanEvaluator>(a: &[E::Bit], amount: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        Self::barrel_shift(a, amount, true, eval)
+    }
+
+    /// Logical `a >> amount` for an encrypted `amount`
+    pub fn shift_right_by<E: BooleanEvaluator>(a: &[E::Bit], amount: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        Self::barrel_shift(a, amount, false, eval)
+    }
+
+    fn barrel_shift<E: BooleanEvaluator>(a: &[E::Bit], amount: &[E::Bit], left: bool, eval: &E) -> Vec<E::Bit> {
+        let width = a.len();
+        let zero = eval.constant(false);
+        let mut result = a.to_vec();
+
+        for (k, bit) in amount.iter().enumerate() {
+            let step = 1usize.checked_shl(k as u32).unwrap_or(usize::MAX);
+            let shifted: Vec<E::Bit> = (0..width)
+                .map(|i| {
+                    let source = if left { i.checked_sub(step) } else { i.checked_add(step).filter(|&j| j < width) };
+                    source.map_or_else(|| zero.clone(), |j| result[j].clone())
+                })
+                .collect();
+            result = Self::mux(bit, &shifted, &result, eval);
+        }
+        result
+    }
+
+    /// Number of zero bits above the most significant one; `a.len()` when `a` is zero
+    pub fn leading_zeros<E: BooleanEvaluator>(a: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        let width = (usize::BITS - a.len().leading_zeros()).max(1) as usize;
+        let mut count = Self::constant(0, width, eval);
+        let mut seen = eval.constant(false);
+        for bit in a.iter().rev() {
+            seen = eval.or(&seen, bit);
+            count = Self::increment_if(&count, &eval.not(&seen), eval);
+        }
+        count
+    }
+
+    /// Two's complement `a < b`; both operands must have the same width
+    pub fn less_than_signed<E: BooleanEvaluator>(a: &[E::Bit], b: &[E::Bit], eval: &E) -> E::Bit {
+        assert_eq!(a.len(), b.len());
+        if a.is_empty() {
+            return eval.constant(false);
+        }
+
+        // Flipping the sign bits maps two's complement order onto unsigned order
+        let flip = |x: &[E::Bit]| {
+            let mut x = x.to_vec();
+            let last = x.len() - 1;
+            x[last] = eval.not(&x[last]);
+            x
+        };
+        Self::less_than(&flip(a), &flip(b), eval)
+    }
+
+    pub fn max<E: BooleanEvaluator>(a: &[E::Bit], b: &[E::Bit], signed: bool, eval: &E) -> Vec<E::Bit> {
+        let a_lt_b = if signed {
+            Self::less_than_signed(a, b, eval)
+        } else {
+            Self::less_than(a, b, eval)
+        };
+        Self::mux(&a_lt_b, b, a, eval)
+    }
+
+    /// Maximum of non-empty `values` by a balanced tree of `max`
+    pub fn max_all<E: BooleanEvaluator>(values: &[Vec<E::Bit>], signed: bool, eval: &E) -> Vec<E::Bit> {
+        assert!(!values.is_empty(), "maximum of no values");
+
+        let mut layer = values.to_vec();
+        while layer.len() > 1 {
+            layer = layer.chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => Self::max(a, b, signed, eval),
+                    [a] => a.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+        layer.remove(0)
+    }
+
+    /// Unsigned comparison of two encrypted values
+    pub fn compare<E: BooleanEvaluator>(a: &[E::Bit], b: &[E::Bit], op: CmpOp, eval: &E) -> E::Bit {
+        match op {
+            CmpOp::Eq => Self::equal(a, b, eval),
+            CmpOp::Ne => eval.not(&Self::equal(a, b, eval)),
+            CmpOp::Lt => Self::less_than(a, b, eval),
+            CmpOp::Ge => eval.not(&Self::less_than(a, b, eval)),
+            CmpOp::Gt => Self::less_than(b, a, eval),
+            CmpOp::Le => eval.not(&Self::less_than(b, a, eval)),
+        }
+    }
+
+    /// `compare` on the `top_bits` most significant bits only, after
+    /// resizing both operands to the wider width. Values are compared by
+    /// the bucket of `2^(width - top_bits)` they fall in, so `Eq` holds for
+    /// values that agree in their top bits and `Lt` can miss a difference
+    /// confined to the low bits; the gates spent shrink in proportion.
+    /// `top_bits` at or above the width is an exact `compare`.
+    pub fn compare_approx<E: BooleanEvaluator>(
+        a: &[E::Bit],
+        b: &[E::Bit],
+        op: CmpOp,
+        top_bits: usize,
+        eval: &E,
+    ) -> E::Bit {
+        let width = a.len().max(b.len());
+        let (a, b) = (Self::resize(a, width, eval), Self::resize(b, width, eval));
+        let low = width.saturating_sub(top_bits);
+        Self::compare(&a[low..], &b[low..], op, eval)
+    }
+
+    /// `a + c` for a public `c`, wrapping within the width of `a`.
+    ///
+    /// While the carry is still public, bits of `a` pass through or are
+    /// negated without any two-input gate; only once a carry depends on `a`
+    /// does each bit cost an XOR and an AND/OR.
+    pub fn add_clear<E: BooleanEvaluator>(a: &[E::Bit], c: u64, eval: &E) -> Vec<E::Bit> {
+        let mut carry = Tracked::Known(false);
+        a.iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let y = i < 64 && (c >> i) & 1 == 1;
+                let (sum, next) = match (&carry, y) {
+                    (Tracked::Known(false), false) => (x.clone(), Tracked::Known(false)),
+                    (Tracked::Known(false), true) | (Tracked::Known(true), false) => {
+                        (eval.not(x), Tracked::Secret(x.clone()))
+                    }
+                    (Tracked::Known(true), true) => (x.clone(), Tracked::Known(true)),
+                    (Tracked::Secret(k), false) => (eval.xor(x, k), Tracked::Secret(eval.and(x, k))),
+                    (Tracked::Secret(k), true) => (eval.not(&eval.xor(x, k)), Tracked::Secret(eval.or(x, k))),
+                };
+                carry = next;
+                sum
+            })
+            .collect()
+    }
+
+    /// Unsigned comparison against a public value, with one gate per bit at most
+    pub fn cmp_clear<E: BooleanEvaluator>(a: &[E::Bit], c: u64, op: CmpOp, eval: &E) -> E::Bit {
+        match op {
+            CmpOp::Eq => Self::equal_clear(a, c, eval),
+            CmpOp::Ne => eval.not(&Self::equal_clear(a, c, eval)),
+            CmpOp::Lt => Self::less_than_clear(a, c, eval),
+            CmpOp::Ge => eval.not(&Self::less_than_clear(a, c, eval)),
+            CmpOp::Gt => Self::greater_than_clear(a, c, eval),
+            CmpOp::Le => eval.not(&Self::greater_than_clear(a, c, eval)),
+        }
+    }
+
+    fn fits(width: usize, c: u64) -> bool {
+        width >= 64 || c >> width == 0
+    }
+
+    fn equal_clear<E: BooleanEvaluator>(a: &[E::Bit], c: u64, eval: &E) -> E::Bit {
+        if !Self::fits(a.len(), c) {
+            return eval.constant(false);
+        }
+        let matches: Vec<E::Bit> = a.iter()
+            .enumerate()
+            .map(|(i, x)| if i < 64 && (c >> i) & 1 == 1 { x.clone() } else { eval.not(x) })
+            .collect();
+        Self::and_all(&matches, eval)
+    }
+
+    fn less_than_clear<E: BooleanEvaluator>(a: &[E::Bit], c: u64, eval: &E) -> E::Bit {
+        if !Self::fits(a.len(), c) {
+            return eval.constant(true);
+        }
+        // Same LSB-first scan as `less_than`, with the mux resolved by the public bit
+        let mut lt = Tracked::Known(false);
+        for (i, x) in a.iter().enumerate() {
+            let y = i < 64 && (c >> i) & 1 == 1;
+            lt = match (lt, y) {
+                (Tracked::Known(false), false) => Tracked::Known(false),
+                (Tracked::Known(false), true) => Tracked::Secret(eval.not(x)),
+                (Tracked::Secret(lt), false) => Tracked::Secret(eval.and(&eval.not(x), &lt)),
+                (Tracked::Secret(lt), true) => Tracked::Secret(eval.or(&eval.not(x), &lt)),
+                (Tracked::Known(true), _) => unreachable!("the scan starts from false"),
+            };
+        }
+        lt.into_bit(eval)
+    }
+
+    fn greater_than_clear<E: BooleanEvaluator>(a: &[E::Bit], c: u64, eval: &E) -> E::Bit {
+        if !Self::fits(a.len(), c) {
+            return eval.constant(false);
+        }
+        let mut gt = Tracked::Known(false);
+        for (i, x) in a.iter().enumerate() {
+            let y = i < 64 && (c >> i) & 1 == 1;
+            gt = match (gt, y) {
+                (Tracked::Known(false), false) => Tracked::Secret(x.clone()),
+                (Tracked::Known(false), true) => Tracked::Known(false),
+                (Tracked::Secret(gt), false) => Tracked::Secret(eval.or(x, &gt)),
+                (Tracked::Secret(gt), true) => Tracked::Secret(eval.and(x, &gt)),
+                (Tracked::Known(true), _) => unreachable!("the scan starts from false"),
+            };
+        }
+        gt.into_bit(eval)
+    }
+
+    /// Number of set bits, wide enough to never overflow
+    pub fn popcount<E: BooleanEvaluator>(bits: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        let width = (usize::BITS - bits.len().leading_zeros()).max(1) as usize;
+        let mut count = Self::constant(0, width, eval);
+        for bit in bits {
+            count = Self::increment_if(&count, bit, eval);
+        }
+        count
+    }
+
+    /// Whether `sum(weights[i] * bits[i]) >= threshold`, e.g. a linear
+    /// scorecard over encrypted yes/no features. A negative weight counts
+    /// the complement of its bit instead, as `w b = w + |w| !b`, so every
+    /// term is non-negative and the sum only needs enough bits for the total
+    /// magnitude. Thresholds no assignment can miss, or reach, are constants.
+    /// Panics if the weight magnitudes sum past `u64::MAX`.
+    pub fn weighted_threshold<E: BooleanEvaluator>(bits: &[E::Bit], weights: &[i64], threshold: i64, eval: &E) -> E::Bit {
+        assert_eq!(bits.len(), weights.len(), "one weight per bit");
+        let total = weights.iter()
+            .try_fold(0u64, |acc, w| acc.checked_add(w.unsigned_abs()))
+            .expect("weight magnitudes must sum to at most u64::MAX");
+        let negative: u64 = weights.iter().filter(|&&w| w < 0).map(|w| w.unsigned_abs()).sum();
+        let target = threshold as i128 + negative as i128;
+        if target <= 0 {
+            return eval.constant(true);
+        }
+        if target > total as i128 {
+            return eval.constant(false);
+        }
+
+        let width = (u64::BITS - total.leading_zeros()) as usize;
+        let mut sum = Self::constant(0, width, eval);
+        for (bit, &w) in bits.iter().zip(weights) {
+            let bit = if w < 0 { eval.not(bit) } else { bit.clone() };
+            let magnitude = w.unsigned_abs();
+            let term: Vec<E::Bit> = (0..width)
+                .map(|j| if (magnitude >> j) & 1 == 1 { bit.clone() } else { eval.constant(false) })
+                .collect();
+            sum = Self::add_mod(&sum, &term, eval);
+        }
+        Self::cmp_clear(&sum, target as u64, CmpOp::Ge, eval)
+    }
+
+    /// Whether at least `k` of `bits` are set, e.g. a quorum of votes or of
+    /// redundant sensors. Any and all are a chain of gates; other `k` count
+    /// the bits with `popcount` and compare the count against `k`.
+    pub fn at_least_k_of_n<E: BooleanEvaluator>(bits: &[E::Bit], k: usize, eval: &E) -> E::Bit {
+        match k {
+            0 => eval.constant(true),
+            k if k > bits.len() => eval.constant(false),
+            1 => Self::or_all(bits, eval),
+            k if k == bits.len() => Self::and_all(bits, eval),
+            k => Self::cmp_clear(&Self::popcount(bits, eval), k as u64, CmpOp::Ge, eval),
+        }
+    }
+
+    /// Whether more than half of `bits` are set; a tie is not a majority
+    pub fn majority<E: BooleanEvaluator>(bits: &[E::Bit], eval: &E) -> E::Bit {
+        Self::at_least_k_of_n(bits, bits.len() / 2 + 1, eval)
+    }
+
+    /// Binary (Stein) GCD with a data-independent schedule: `2n` rounds
+    /// for `n`-bit operands, each updating both operands through `mux`
+    /// whatever their values. `gcd(0, 0)` is 0.
+    pub fn gcd<E: BooleanEvaluator>(a: &[E::Bit], b: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        let width = a.len().max(b.len());
+        if width == 0 {
+            return Vec::new();
+        }
+        let (mut a, mut b) = (Self::resize(a, width, eval), Self::resize(b, width, eval));
+        let mut shift = Self::constant(0, (usize::BITS - width.leading_zeros()) as usize, eval);
+
+        // Every round with both operands non-zero removes at least one bit
+        for _ in 0..2 * width {
+            let active = eval.and(&Self::or_all(&a, eval), &Self::or_all(&b, eval));
+            let a_ge_b = eval.not(&Self::less_than(&a, &b, eval));
+
+            // Halve an even operand; when both are odd, halve the difference into the larger
+            let halve_a = eval.and(&active, &eval.or(&eval.not(&a[0]), &eval.and(&b[0], &a_ge_b)));
+            let halve_b = eval.and(&active, &eval.or(&eval.not(&b[0]), &eval.and(&a[0], &eval.not(&a_ge_b))));
+            let both_even = eval.and(&active, &eval.not(&eval.or(&a[0], &b[0])));
+
+            let a_source = Self::mux(&a[0], &Self::sub_mod(&a, &b, eval), &a, eval);
+            let b_source = Self::mux(&b[0], &Self::sub_mod(&b, &a, eval), &b, eval);
+            a = Self::mux(&halve_a, &Self::halve(&a_source, eval), &a, eval);
+            b = Self::mux(&halve_b, &Self::halve(&b_source, eval), &b, eval);
+            shift = Self::increment_if(&shift, &both_even, eval);
+        }
+
+        // One operand is zero now, the other holds the odd part of the gcd
+        let odd_part: Vec<E::Bit> = a.iter().zip(b.iter()).map(|(x, y)| eval.or(x, y)).collect();
+        Self::shift_left_by(&odd_part, &shift, eval)
+    }
+
+    fn halve<E: BooleanEvaluator>(a: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        let mut halved = a[1..].to_vec();
+        halved.push(eval.constant(false));
+        halved
+    }
+}
+
+/// Canonical signed digit recoding of `c`, least significant digit first.
+///
+/// Every digit is -1, 0 or 1 and no two adjacent digits are non-zero, which is
+/// the minimal number of non-zero digits of any signed-binary representation.
+pub fn csd_digits(c: u64) -> Vec<i8> {
+    let mut digits = Vec::with_capacity(65);
+    let mut c = c as u128;
+    while c != 0 {
+        if c & 1 == 0 {
+            digits.push(0);
+        } else if c & 3 == 3 {
+            // ...11 becomes ...0(-1) with a carry into the next position
+            digits.push(-1);
+            c += 1;
+        } else {
+            digits.push(1);
+            c -= 1;
+        }
+        c >>= 1;
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::OpCounts;
+    use crate::evaluator::ClearEvaluator;
+    use crate::oplog::Recording;
+
+    fn bits(value: u64, width: usize) -> Vec<bool> {
+        IntegerOps::constant(value, width, &ClearEvaluator)
+    }
+
+    fn value(bits: &[bool]) -> u64 {
+        bits.iter().enumerate().fold(0, |acc, (i, &b)| acc | ((b as u64) << i))
+    }
+
+    #[test]
+    fn test_add_and_increment() {
+        let eval = ClearEvaluator;
+        for (x, y) in [(0, 0), (5, 9), (15, 15), (7, 1)] {
+            assert_eq!(value(&IntegerOps::add(&bits(x, 4), &bits(y, 4), &eval)), x + y);
+        }
+
+        assert_eq!(value(&IntegerOps::increment_if(&bits(6, 4), &true, &eval)), 7);
+        assert_eq!(value(&IntegerOps::increment_if(&bits(6, 4), &false, &eval)), 6);
+        assert_eq!(value(&IntegerOps::increment_if(&bits(15, 4), &true, &eval)), 0);
+    }
+
+    #[test]
+    fn test_mul_and_pow_const() {
+        let eval = ClearEvaluator;
+        for (x, y) in [(0, 7), (3, 5), (15, 15), (9, 1)] {
+            assert_eq!(value(&IntegerOps::mul(&bits(x, 4), &bits(y, 4), 8, &eval)), x * y);
+            assert_eq!(value(&IntegerOps::mul(&bits(x, 4), &bits(y, 4), 4, &eval)), (x * y) % 16);
+        }
+
+        for (x, e) in [(3u64, 0u64), (3, 1), (3, 4), (5, 3), (7, 11), (2, 9)] {
+            let expected = x.pow(e as u32) % 256;
+            assert_eq!(value(&IntegerOps::pow_const(&bits(x, 4), e, 8, &eval)), expected);
+        }
+    }
+
+    #[test]
+    fn test_prefix_adder_and_carry_save_multiplier() {
+        let eval = ClearEvaluator;
+        for &(x, y) in &[(0u64, 0u64), (1, 1), (200, 99), (255, 255), (170, 85), (128, 128)] {
+            let a = IntegerOps::constant(x, 8, &eval);
+            let b = IntegerOps::constant(y, 8, &eval);
+            assert_eq!(IntegerOps::add_prefix(&a, &b, &eval), IntegerOps::add(&a, &b, &eval));
+            for width in [1, 5, 8, 12] {
+                assert_eq!(
+                    IntegerOps::mul_carry_save(&a, &b, width, &eval),
+                    IntegerOps::mul(&a, &b, width, &eval),
+                );
+            }
+        }
+        // Odd widths exercise the incomplete prefix groups
+        let a = IntegerOps::constant(0b10111, 5, &eval);
+        let b = IntegerOps::constant(0b01101, 5, &eval);
+        assert_eq!(IntegerOps::add_prefix(&a, &b, &eval), IntegerOps::add(&a, &b, &eval));
+    }
+
+    #[test]
+    fn test_csd_digits() {
+        assert_eq!(csd_digits(0), Vec::<i8>::new());
+        assert_eq!(csd_digits(7), vec![-1, 0, 0, 1]);
+        assert_eq!(csd_digits(0b1011_0111), vec![-1, 0, 0, -1, 0, 0, -1, 0, 1]);
+
+        for c in (0..2000u64).chain([u64::MAX, u64::MAX / 3]) {
+            let digits = csd_digits(c);
+            let value = digits.iter().rev().fold(0i128, |acc, &d| 2 * acc + d as i128);
+            assert_eq!(value, c as i128);
+            assert!(digits.windows(2).all(|w| w[0] == 0 || w[1] == 0));
+        }
+    }
+
+    #[test]
+    fn test_mul_const_and_poly_eval() {
+        let eval = ClearEvaluator;
+        assert_eq!(value(&IntegerOps::sub_mod(&bits(3, 4), &bits(5, 4), &eval)), 14);
+        for c in [0u64, 1, 6, 13, 255] {
+            assert_eq!(value(&IntegerOps::mul_const(&bits(11, 4), c, 8, &eval)), (11 * c) % 256);
+        }
+
+        // 3x^3 - 2x + 7
+        let coeffs = [7, -2, 0, 3];
+        for x in 0..16i64 {
+            let expected = (3 * x.pow(3) - 2 * x + 7).rem_euclid(1 << 10) as u64;
+            assert_eq!(value(&IntegerOps::poly_eval(&bits(x as u64, 4), &coeffs, 10, &eval)), expected);
+        }
+
+        assert_eq!(value(&IntegerOps::poly_eval(&bits(5, 4), &[-3], 8, &eval)), 253);
+        assert_eq!(value(&IntegerOps::poly_eval(&bits(5, 4), &[], 8, &eval)), 0);
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let eval = ClearEvaluator;
+        for x in 0..8 {
+            for y in 0..8 {
+                let signed = |v: u64| if v >= 4 { v as i64 - 8 } else { v as i64 };
+                assert_eq!(IntegerOps::less_than_signed(&bits(x, 3), &bits(y, 3), &eval), signed(x) < signed(y));
+                assert_eq!(IntegerOps::less_than(&bits(x, 3), &bits(y, 3), &eval), x < y);
+                assert_eq!(IntegerOps::equal(&bits(x, 3), &bits(y, 5), &eval), x == y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_approximate_comparisons() {
+        let eval = ClearEvaluator;
+        for x in 0..64 {
+            for y in 0..64 {
+                let (hx, hy) = (x >> 4, y >> 4);
+                assert_eq!(IntegerOps::compare_approx(&bits(x, 6), &bits(y, 6), CmpOp::Lt, 2, &eval), hx < hy);
+                assert_eq!(IntegerOps::compare_approx(&bits(x, 6), &bits(y, 6), CmpOp::Eq, 2, &eval), hx == hy);
+                assert_eq!(IntegerOps::compare_approx(&bits(x, 6), &bits(y, 6), CmpOp::Ge, 6, &eval), x >= y);
+            }
+        }
+
+        // Gates scale with the bits inspected
+        let count = |top| {
+            let eval = Recording::new(ClearEvaluator);
+            let (a, b) = (eval.inputs(bits(40000, 16)), eval.inputs(bits(1234, 16)));
+            let lt = IntegerOps::compare_approx(&a, &b, CmpOp::Lt, top, &eval);
+            OpCounts::from_log(&eval.finish(&[lt])).bootstraps
+        };
+        assert!(count(4) * 3 < count(16));
+    }
+
+    #[test]
+    fn test_shifts_and_leading_zeros() {
+        let eval = ClearEvaluator;
+        for amount in 0..10 {
+            let shift = bits(amount, 4);
+            assert_eq!(value(&IntegerOps::shift_left_by(&bits(0b1011, 8), &shift, &eval)), (0b1011 << amount) & 0xff);
+            assert_eq!(value(&IntegerOps::shift_right_by(&bits(0b1011_0110, 8), &shift, &eval)), 0b1011_0110 >> amount);
+        }
+
+        for x in [0u64, 1, 5, 64, 255] {
+            assert_eq!(value(&IntegerOps::leading_zeros(&bits(x, 8), &eval)), x.leading_zeros() as u64 - 56);
+        }
+    }
+
+    #[test]
+    fn test_clear_operands() {
+        let eval = ClearEvaluator;
+        let ops = [CmpOp::Eq, CmpOp::Ne, CmpOp::Lt, CmpOp::Le, CmpOp::Gt, CmpOp::Ge];
+        for x in 0..16 {
+            for c in 0..20 {
+                assert_eq!(value(&IntegerOps::add_clear(&bits(x, 4), c, &eval)), (x + c) % 16);
+                for op in ops {
+                    let expected = IntegerOps::compare(&bits(x, 5), &bits(c, 5), op, &eval);
+                    assert_eq!(IntegerOps::cmp_clear(&bits(x, 4), c, op, &eval), expected, "{} {:?} {}", x, op, c);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_max_all() {
+        let eval = ClearEvaluator;
+        let values: Vec<Vec<bool>> = [3, 14, 7, 9, 2].iter().map(|&v| bits(v, 4)).collect();
+        assert_eq!(value(&IntegerOps::max_all(&values, false, &eval)), 14);
+        // 14 and 9 are negative as 4-bit two's complement
+        assert_eq!(value(&IntegerOps::max_all(&values, true, &eval)), 7);
+    }
+
+    #[test]
+    fn test_gcd() {
+        let eval = ClearEvaluator;
+        for &(a, b, expected) in &[(48, 18, 6), (0, 20, 20), (20, 0, 20), (0, 0, 0), (255, 1, 1), (128, 96, 32), (221, 255, 17), (97, 89, 1)] {
+            assert_eq!(value(&IntegerOps::gcd(&bits(a, 8), &bits(b, 8), &eval)), expected, "gcd({}, {})", a, b);
+        }
+        assert_eq!(value(&IntegerOps::gcd(&bits(12, 4), &bits(1 << 9, 10), &eval)), 4);
+
+        let reference = |mut a: u64, mut b: u64| {
+            while b != 0 {
+                (a, b) = (b, a % b);
+            }
+            a
+        };
+        for a in 0..32 {
+            for b in 0..32 {
+                assert_eq!(value(&IntegerOps::gcd(&bits(a, 5), &bits(b, 5), &eval)), reference(a, b));
+            }
+        }
+    }
+
+    #[test]
+    fn test_popcount() {
+        let eval = ClearEvaluator;
+        let input = [true, false, true, true, false, true, true];
+        let count = IntegerOps::popcount(&input, &eval);
+        assert_eq!(count.len(), 3);
+        assert_eq!(value(&count), 5);
+    }
+
+    #[test]
+    fn test_weighted_threshold() {
+        let eval = ClearEvaluator;
+        let weights = [40, -25, 0, 13, 7];
+        for threshold in [-30, -25, 0, 20, 47, 60, 61] {
+            for mask in 0..32u32 {
+                let input: Vec<bool> = (0..5).map(|i| (mask >> i) & 1 == 1).collect();
+                let score: i64 = input.iter().zip(&weights).map(|(&b, &w)| b as i64 * w).sum();
+                let result = IntegerOps::weighted_threshold(&input, &weights, threshold, &eval);
+                assert_eq!(result, score >= threshold, "{:?} against {}", input, threshold);
+            }
+        }
+        let extreme = IntegerOps::weighted_threshold(&[true, false], &[i64::MIN, i64::MAX], 0, &eval);
+        assert!(!extreme);
+    }
+
+    #[test]
+    #[should_panic(expected = "weight magnitudes")]
+    fn test_weighted_threshold_rejects_overflowing_weights() {
+        IntegerOps::weighted_threshold(&[true, true], &[i64::MIN, i64::MIN], 0, &ClearEvaluator);
+    }
+
+    #[test]
+    fn test_at_least_k_of_n_and_majority() {
+        let eval = ClearEvaluator;
+        for n in [0usize, 1, 4, 5] {
+            for mask in 0..1u32 << n {
+                let input: Vec<bool> = (0..n).map(|i| (mask >> i) & 1 == 1).collect();
+                let set = mask.count_ones() as usize;
+                for k in 0..=n + 1 {
+                    assert_eq!(IntegerOps::at_least_k_of_n(&input, k, &eval), set >= k, "{:?} k = {}", input, k);
+                }
+                assert_eq!(IntegerOps::majority(&input, &eval), 2 * set > n, "{:?}", input);
+            }
+        }
+    }
+}
@@ -1,8 +1,106 @@
-pub mod torus;
-pub mod noise;
+// Torus, LWE and TGSW building blocks. Their interfaces change with the
+// scheme, so outside the crate they are only reachable through `internals`,
+// apart from the types re-exported by `types`.
+macro_rules! internal_modules {
+    ($($name:ident),* $(,)?) => {
+        $(
+            #[cfg(feature = "internals")]
+            #[doc(hidden)]
+            pub mod $name;
+            #[cfg(not(feature = "internals"))]
+            mod $name;
+        )*
+    };
+}
+
+internal_modules!(torus, noise, noise_model);
+pub mod entropy;
 pub mod encoding;
-pub mod lwe;
-pub mod tlwe;
-pub mod tgsw;
+internal_modules!(lwe, tlwe, trlwe, fft, tgsw, lutdebug);
 pub mod tfhe;
-pub mod operations;
\ No newline at end of file
+pub mod packed;
+pub mod batch;
+pub mod multivalue;
+pub mod fulldomain;
+pub mod refresh;
+pub mod canonical;
+pub mod transport;
+pub mod seeded;
+pub mod reencrypt;
+pub mod migrate;
+#[cfg(feature = "client")]
+pub mod threshold;
+#[cfg(feature = "server")]
+pub mod operations;
+pub mod hooks;
+pub mod evaluator;
+pub mod circuit;
+pub mod codec;
+pub mod compiler;
+pub mod strategy;
+pub mod profile;
+pub mod budget;
+pub mod cost;
+pub mod scheduler;
+pub mod provenance;
+pub mod oplog;
+pub mod transcript;
+pub mod bundle;
+pub mod expr;
+pub mod integer;
+pub mod radix;
+pub mod modular;
+pub mod fixed;
+pub mod float;
+pub mod linear;
+pub mod nn;
+pub mod image_ops;
+pub mod geofence;
+pub mod query;
+pub mod psi;
+pub mod dedup;
+pub mod randomness;
+pub mod shuffle;
+pub mod feistel;
+pub mod sorting;
+pub mod dfa;
+pub mod strings;
+pub mod textcodec;
+pub mod stream;
+pub mod aggregate;
+pub mod modcounter;
+#[cfg(feature = "parallel")]
+pub mod par;
+pub mod api;
+pub mod prelude;
+
+/// Scheme types that appear in the signatures of the stable modules, e.g.
+/// the samples a `BootstrapHook` receives or the parameters of a
+/// `TfheParams`, nameable whether or not `internals` is enabled
+pub mod types {
+    pub use crate::lutdebug::{SlotRead, TestVector};
+    pub use crate::lwe::{LweCiphertext, LweParams};
+    pub use crate::noise::{NoiseEstimate, NoiseLevel};
+    pub use crate::tgsw::{BootstrappingKey, TgswParams, TgswTradeoff, TrgswSample};
+    pub use crate::tlwe::{KeySwitchParams, TlweKeySwitchKey, TlweParams, TlweSample};
+    pub use crate::torus::{Torus, Torus32, Torus64, TorusF64};
+    pub use crate::trlwe::{TorusPolynomial, TrlweParams, TrlweSample};
+    #[cfg(feature = "client")]
+    pub use crate::{tlwe::TlweBatchDecryptor, trlwe::TrlweSecretKey};
+}
+
+/// Low-level scheme modules, outside the semver guarantees of the rest of
+/// the crate: anything here may change in a minor release. Enabled by the
+/// `internals` feature.
+#[cfg(feature = "internals")]
+pub mod internals {
+    pub use crate::{fft, lutdebug, lwe, noise, noise_model, tgsw, tlwe, torus, trlwe};
+}
+
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "server")]
+pub mod server;
+
+#[cfg(feature = "tfhe-rs")]
+pub mod tfhe_rs;
@@ -0,0 +1,223 @@
+use std::collections::BTreeSet;
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::{csd_digits, IntegerOps};
+
+/// `±(var << shift)`, one term of a row sum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Term {
+    pub var: usize,
+    pub shift: u32,
+    pub negative: bool,
+}
+
+/// Intermediate value `first ± (second << shift)` shared between rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SharedSum {
+    pub first: usize,
+    pub second: usize,
+    pub shift: u32,
+    pub subtract: bool,
+}
+
+// (first, second, shift difference, subtract) with `first` the lower-shift term
+type PairKey = (usize, usize, u32, bool);
+
+/// Addition plan for `y = M x` with a public integer matrix `M`.
+///
+/// Every entry is CSD recoded into shifted terms, then two-term patterns that
+/// recur across rows are extracted greedily (most frequent first) into shared
+/// sums, so a pattern like `x0 + (x1 << 2)` is computed once for all the rows
+/// using it. Variables `0..inputs` are the entries of `x`; shared sum `i` is
+/// variable `inputs + i`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearPlan {
+    pub inputs: usize,
+    pub shared: Vec<SharedSum>,
+    pub rows: Vec<Vec<Term>>,
+    naive_additions: usize,
+}
+
+fn pair_key(a: &Term, b: &Term) -> (PairKey, Term) {
+    let (lo, hi) = if (a.shift, a.var) <= (b.shift, b.var) { (a, b) } else { (b, a) };
+    let key = (lo.var, hi.var, hi.shift - lo.shift, lo.negative != hi.negative);
+    (key, *lo)
+}
+
+// Greedy disjoint occurrences of `key` in `row`, as (lower, upper) term indices
+fn occurrences(row: &[Term], key: PairKey) -> Vec<(usize, usize)> {
+    let mut used = vec![false; row.len()];
+    let mut found = Vec::new();
+    for i in 0..row.len() {
+        for j in i + 1..row.len() {
+            if used[i] || used[j] || pair_key(&row[i], &row[j]).0 != key {
+                continue;
+            }
+            used[i] = true;
+            used[j] = true;
+            found.push((i, j));
+        }
+    }
+    found
+}
+
+fn row_additions(row: &[Term]) -> usize {
+    row.len().saturating_sub(1)
+}
+
+impl LinearPlan {
+    pub fn new(matrix: &[Vec<i64>]) -> Self {
+        let inputs = matrix.iter().map(|row| row.len()).max().unwrap_or(0);
+
+        let mut rows: Vec<Vec<Term>> = matrix.iter()
+            .map(|row| {
+                let mut terms = Vec::new();
+                for (var, &m) in row.iter().enumerate() {
+                    for (shift, digit) in csd_digits(m.unsigned_abs()).into_iter().enumerate() {
+                        if digit != 0 {
+                            terms.push(Term { var, shift: shift as u32, negative: (digit < 0) != (m < 0) });
+                        }
+                    }
+                }
+                terms
+            })
+            .collect();
+        let naive_additions = rows.iter().map(|row| row_additions(row)).sum();
+
+        let mut shared = Vec::new();
+        loop {
+            let candidates: BTreeSet<PairKey> = rows.iter()
+                .flat_map(|row| {
+                    (0..row.len()).flat_map(move |i| (i + 1..row.len()).map(move |j| pair_key(&row[i], &row[j]).0))
+                })
+                .collect();
+
+            let best = candidates.into_iter()
+                .map(|key| (rows.iter().map(|row| occurrences(row, key).len()).sum::<usize>(), key))
+                .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+
+            let key = match best {
+                Some((count, key)) if count >= 2 => key,
+                _ => break,
+            };
+
+            let var = inputs + shared.len();
+            shared.push(SharedSum { first: key.0, second: key.1, shift: key.2, subtract: key.3 });
+
+            for row in rows.iter_mut() {
+                let found = occurrences(row, key);
+                let mut replaced: Vec<Term> = found.iter()
+                    .map(|&(i, j)| {
+                        let lo = pair_key(&row[i], &row[j]).1;
+                        Term { var, shift: lo.shift, negative: lo.negative }
+                    })
+                    .collect();
+                let removed: BTreeSet<usize> = found.iter().flat_map(|&(i, j)| [i, j]).collect();
+
+                let mut kept: Vec<Term> = row.iter()
+                    .enumerate()
+                    .filter(|(i, _)| !removed.contains(i))
+                    .map(|(_, t)| *t)
+                    .collect();
+                kept.append(&mut replaced);
+                kept.sort();
+                *row = kept;
+            }
+        }
+
+        LinearPlan { inputs, shared, rows, naive_additions }
+    }
+
+    /// Additions and subtractions of the shared plan
+    pub fn additions(&self) -> usize {
+        self.shared.len() + self.rows.iter().map(|row| row_additions(row)).sum::<usize>()
+    }
+
+    /// Additions when every dot product is computed on its own
+    pub fn naive_additions(&self) -> usize {
+        self.naive_additions
+    }
+
+    /// Computes every row modulo `2^width`
+    pub fn evaluate<E: BooleanEvaluator>(&self, x: &[Vec<E::Bit>], width: usize, eval: &E) -> Vec<Vec<E::Bit>> {
+        assert_eq!(x.len(), self.inputs, "vector length does not match the matrix");
+
+        let shifted = |value: &[E::Bit], shift: u32| -> Option<Vec<E::Bit>> {
+            let shift = shift as usize;
+            if shift >= width {
+                return None;
+            }
+            let mut bits = IntegerOps::constant(0, shift, eval);
+            bits.extend_from_slice(&value[..width - shift]);
+            Some(bits)
+        };
+
+        let mut vars: Vec<Vec<E::Bit>> = x.iter().map(|v| IntegerOps::resize(v, width, eval)).collect();
+        for sum in &self.shared {
+            let value = match shifted(&vars[sum.second], sum.shift) {
+                Some(rhs) if sum.subtract => IntegerOps::sub_mod(&vars[sum.first], &rhs, eval),
+                Some(rhs) => IntegerOps::add_mod(&vars[sum.first], &rhs, eval),
+                None => vars[sum.first].clone(),
+            };
+            vars.push(value);
+        }
+
+        self.rows.iter()
+            .map(|row| {
+                let mut acc: Option<Vec<E::Bit>> = None;
+                for term in row {
+                    let Some(value) = shifted(&vars[term.var], term.shift) else { continue };
+                    acc = Some(match acc {
+                        None if term.negative => IntegerOps::sub_mod(&IntegerOps::constant(0, width, eval), &value, eval),
+                        None => value,
+                        Some(acc) if term.negative => IntegerOps::sub_mod(&acc, &value, eval),
+                        Some(acc) => IntegerOps::add_mod(&acc, &value, eval),
+                    });
+                }
+                acc.unwrap_or_else(|| IntegerOps::constant(0, width, eval))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    fn value(bits: &[bool]) -> u64 {
+        bits.iter().enumerate().fold(0, |acc, (i, &b)| acc | ((b as u64) << i))
+    }
+
+    #[test]
+    fn test_shared_plan_matches_matrix_product() {
+        let matrix = vec![
+            vec![5, 3, -7, 0],
+            vec![5, 3, 2, 1],
+            vec![-5, -3, 9, 12],
+            vec![0, 0, 0, 0],
+            vec![10, 6, -14, 1],
+        ];
+        let x = [3u64, 11, 6, 9];
+        let width = 12;
+
+        let plan = LinearPlan::new(&matrix);
+        assert!(!plan.shared.is_empty());
+        assert!(plan.additions() < plan.naive_additions());
+
+        let eval = ClearEvaluator;
+        let encoded: Vec<Vec<bool>> = x.iter().map(|&v| IntegerOps::constant(v, 4, &eval)).collect();
+        let y = plan.evaluate(&encoded, width, &eval);
+
+        for (row, out) in matrix.iter().zip(y.iter()) {
+            let expected: i64 = row.iter().zip(x.iter()).map(|(&m, &v)| m * v as i64).sum();
+            assert_eq!(value(out), expected.rem_euclid(1 << width) as u64);
+        }
+    }
+
+    #[test]
+    fn test_no_sharing_without_repeats() {
+        let plan = LinearPlan::new(&[vec![1, 2]]);
+        assert!(plan.shared.is_empty());
+        assert_eq!(plan.additions(), 1);
+    }
+}
@@ -0,0 +1,180 @@
+//! Blind rotation index arithmetic, simulated in the clear.
+//!
+//! Blind rotation multiplies the test polynomial by `X^-p`, `p` the input
+//! phase switched to `[0, 2N)`, and extracts coefficient 0. Since
+//! `X^N = -1`, that reads slot `p` for `p < N` and the negation of slot
+//! `p - N` past it, so a table is only evaluated right if its test
+//! polynomial anticipates the sign flip. `TestVector` builds the polynomial
+//! `TfheGates::programmable_bootstrap` rotates, and `probe` reports, for a
+//! plaintext phase, the rotation, the slot read, whether it wrapped, and
+//! the value the bootstrap would output. Off-by-one and sign errors in a
+//! lookup table show up in the `table` printout without any key material.
+
+use std::fmt;
+use crate::torus::Torus;
+use crate::trlwe::TorusPolynomial;
+
+/// Test polynomial of a lookup table and the constant added after extraction
+#[derive(Debug, Clone)]
+pub struct TestVector {
+    pub poly: TorusPolynomial,
+    pub offset: Torus,
+}
+
+impl TestVector {
+    /// The offset is half of `lut(x) + lut(x + 1/2)` and slot `j` holds
+    /// `lut(x)` less the offset at `x = j / 2N`, the part negacyclic rotation
+    /// reproduces. Sums are taken modulo 1, so tables where the sum is
+    /// constant on the torus come out exact; otherwise the offset follows
+    /// its mean and each slot splits its deviation between both halves.
+    #[allow(non_snake_case)]
+    pub fn from_lut(lut: &[Torus], N: usize) -> Self {
+        let two_n = 2 * N;
+        let entry = |x: f64| lut[((x * lut.len() as f64) as usize).min(lut.len() - 1)].fixed64();
+        let pairs: Vec<(u64, u64)> = (0..N)
+            .map(|j| j as f64 / two_n as f64)
+            .map(|x| (entry(x), entry(x + 0.5)))
+            .collect();
+
+        // Mean of the sums, as signed deviations from the first so wrapping cancels
+        let first = pairs[0].0.wrapping_add(pairs[0].1);
+        let spread: i128 = pairs.iter()
+            .map(|&(low, high)| low.wrapping_add(high).wrapping_sub(first) as i64 as i128)
+            .sum();
+        let sum = first.wrapping_add((spread / N as i128) as i64 as u64);
+        let offset = sum / 2;
+
+        let coeffs = pairs.iter()
+            .map(|&(low, high)| {
+                let deviation = low.wrapping_add(high).wrapping_sub(sum) as i64;
+                Torus::from_fixed64(low.wrapping_sub(offset).wrapping_sub((deviation / 2) as u64))
+            })
+            .collect();
+        TestVector { poly: TorusPolynomial { coeffs }, offset: Torus::from_fixed64(offset) }
+    }
+
+    /// Test vector of a negacyclic table given by its first half: slot `j`
+    /// holds `half(j / 2N)` and the rotation reads its negation past `N`,
+    /// without the averaging `from_lut` relies on. Exact for any values.
+    #[allow(non_snake_case)]
+    pub fn negacyclic(half: impl Fn(f64) -> Torus, N: usize) -> Self {
+        let coeffs = (0..N).map(|j| half(j as f64 / (2 * N) as f64)).collect();
+        TestVector { poly: TorusPolynomial { coeffs }, offset: Torus::new(0.0) }
+    }
+
+    /// What the rotation by an already switched phase in `[0, 2N)` reads
+    pub fn read(&self, rotation: usize) -> SlotRead {
+        let n = self.poly.degree();
+        assert!(rotation < 2 * n, "rotation {} is outside [0, {})", rotation, 2 * n);
+        let wrapped = rotation >= n;
+        let slot = if wrapped { rotation - n } else { rotation };
+        let coefficient = self.poly.coeffs[slot];
+        let read = if wrapped { -coefficient } else { coefficient };
+
+        SlotRead {
+            phase: Torus::new(rotation as f64 / (2 * n) as f64),
+            rotation,
+            slot,
+            wrapped,
+            coefficient,
+            output: read.add(&self.offset),
+        }
+    }
+
+    /// `read` of a noiseless phase, switched as `TlweSample::modulus_switch` does
+    pub fn probe(&self, phase: Torus) -> SlotRead {
+        let two_n = 2 * self.poly.degree();
+        let rotation = (phase.value() * two_n as f64).round() as usize % two_n;
+        SlotRead { phase, ..self.read(rotation) }
+    }
+
+    /// One line per phase, as printed by `SlotRead`'s `Display`
+    pub fn table(&self, phases: &[Torus]) -> String {
+        phases.iter().map(|&phase| self.probe(phase).to_string()).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// One simulated blind rotation
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotRead {
+    pub phase: Torus,
+    /// Exponent `p` of the rotation `X^-p`
+    pub rotation: usize,
+    pub slot: usize,
+    /// Read past `N`, so the slot's coefficient comes out negated
+    pub wrapped: bool,
+    pub coefficient: Torus,
+    /// Extracted coefficient plus offset, the bootstrap's message
+    pub output: Torus,
+}
+
+impl fmt::Display for SlotRead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "phase {:.6} -> X^-{} -> {}slot {}{} = {:.6} -> output {:.6}",
+            self.phase.value(),
+            self.rotation,
+            if self.wrapped { "-" } else { "+" },
+            self.slot,
+            if self.wrapped { " (wrapped)" } else { "" },
+            self.coefficient.value(),
+            self.output.value(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn not_lut() -> Vec<Torus> {
+        let mut lut = vec![Torus::new(0.625); 1024];
+        lut[..512].fill(Torus::new(0.125));
+        lut
+    }
+
+    #[test]
+    fn test_read_matches_negacyclic_rotation() {
+        let lut: Vec<Torus> = (0..64).map(|i| Torus::new((i * 7 % 64) as f64 / 64.0)).collect();
+        let test = TestVector::from_lut(&lut, 16);
+        for rotation in 0..32 {
+            let rotated = test.poly.mul_by_xai((32 - rotation) % 32);
+            let read = test.read(rotation);
+            assert_eq!(read.wrapped, rotation >= 16);
+            let expected = if read.wrapped { -read.coefficient } else { read.coefficient };
+            assert_eq!(rotated.coeffs[0], expected, "rotation {}", rotation);
+        }
+    }
+
+    #[test]
+    fn test_probe_gate_table() {
+        let test = TestVector::from_lut(&not_lut(), 16);
+        let close = |t: Torus, v: f64| {
+            let d = Torus::new(t.value() - v).value();
+            d.min(1.0 - d) < 1e-9
+        };
+
+        // Both encodings of a bit land mid-slot, on either side of the wrap
+        let low = test.probe(Torus::new(0.125));
+        assert_eq!((low.rotation, low.slot, low.wrapped), (4, 4, false));
+        assert!(close(low.output, 0.125));
+        let high = test.probe(Torus::new(0.625));
+        assert_eq!((high.rotation, high.slot, high.wrapped), (20, 4, true));
+        assert!(close(high.output, 0.625));
+
+        let table = test.table(&[Torus::new(0.125), Torus::new(0.625)]);
+        assert_eq!(table.lines().count(), 2);
+        assert!(table.lines().nth(1).unwrap().contains("-slot 4 (wrapped)"));
+    }
+
+    #[test]
+    fn test_from_lut_sums_modulo_one() {
+        // Both halves sum to 0 modulo 1, though not as reals
+        let lut = [0.0, 0.25, 0.0, 0.75].map(Torus::new);
+        let test = TestVector::from_lut(&lut, 2);
+        for (rotation, &expected) in lut.iter().enumerate() {
+            assert_eq!(test.read(rotation).output, expected, "rotation {}", rotation);
+        }
+    }
+}
@@ -1,5 +1,13 @@
+use std::fmt;
+#[cfg(feature = "client")]
 use rand::Rng;
-use crate::noise::gaussian_noise;
+use crate::entropy::{default_entropy, EntropySource};
+#[cfg(feature = "client")]
+use crate::noise::DiscreteGaussian;
+use crate::tlwe::{TlweParams, TlweSample};
+#[cfg(feature = "client")]
+use crate::tlwe::TlweSecretKey;
+use crate::torus::Torus;
 
 #[derive(Debug, Clone)]
 pub struct LweParams {
@@ -8,6 +16,16 @@ pub struct LweParams {
     pub stddev: f64,
 }
 
+impl LweParams {
+    /// Standard deviation, in units of the new modulus, of the phase error
+    /// a `modulus_switch` adds under a binary key: each of the `n + 1`
+    /// coefficients is off by a rounding error uniform in `[-1/2, 1/2]`, the
+    /// body's counting once and about half of the mask's meeting a 1 in the key
+    pub fn modulus_switch_stddev(&self) -> f64 {
+        ((1.0 + self.n as f64 / 2.0) / 12.0).sqrt()
+    }
+}
+
 impl Default for LweParams {
     fn default() -> Self {
         LweParams {
@@ -18,15 +36,20 @@ impl Default for LweParams {
     }
 }
 
+#[cfg(feature = "client")]
 #[derive(Debug, Clone)]
 pub struct LweSecretKey {
     pub coeffs: Vec<i32>,
     pub params: LweParams,
 }
 
+#[cfg(feature = "client")]
 impl LweSecretKey {
     pub fn generate_binary(params: LweParams) -> Self {
-        let mut rng = rand::rng();
+        Self::generate_binary_with_rng(params, &mut default_entropy())
+    }
+
+    pub fn generate_binary_with_rng<R: EntropySource + ?Sized>(params: LweParams, rng: &mut R) -> Self {
         let coeffs: Vec<i32> = (0..params.n)
             .map(|_| if rng.random_bool(0.5) { 1 } else { 0 })
             .collect();
@@ -35,7 +58,10 @@ impl LweSecretKey {
     }
 
     pub fn generate_ternary(params: LweParams) -> Self {
-        let mut rng = rand::rng();
+        Self::generate_ternary_with_rng(params, &mut default_entropy())
+    }
+
+    pub fn generate_ternary_with_rng<R: EntropySource + ?Sized>(params: LweParams, rng: &mut R) -> Self {
         let coeffs: Vec<i32> = (0..params.n)
             .map(|_| {
                 let r: f64 = rng.random();
@@ -57,9 +83,14 @@ pub struct LweCiphertext {
 }
 
 impl LweCiphertext {
+    #[cfg(feature = "client")]
     pub fn encrypt(message: u64, sk: &LweSecretKey) -> Self {
-        let mut rng = rand::rng();
+        Self::encrypt_with_rng(message, sk, &mut default_entropy())
+    }
 
+    /// `encrypt` drawing the mask and noise from `rng`
+    #[cfg(feature = "client")]
+    pub fn encrypt_with_rng<R: EntropySource + ?Sized>(message: u64, sk: &LweSecretKey, rng: &mut R) -> Self {
         let a: Vec<u64> = (0..sk.params.n)
             .map(|_| rng.random::<u64>() % sk.params.q)
             .collect();
@@ -70,7 +101,7 @@ impl LweCiphertext {
             inner_product %= sk.params.q as i64;
         }
 
-        let error = gaussian_noise(sk.params.stddev) as i64;
+        let error = DiscreteGaussian::integer(sk.params.stddev).sample_int(rng);
         let b = ((inner_product + message as i64 + error) % sk.params.q as i64) as u64;
 
         LweCiphertext {
@@ -80,6 +111,7 @@ impl LweCiphertext {
         }
     }
 
+    #[cfg(feature = "client")]
     pub fn decrypt(&self, sk: &LweSecretKey) -> u64 {
         let mut inner_product: i64 = 0;
         for i in 0..sk.params.n {
@@ -113,6 +145,33 @@ impl LweCiphertext {
         }
     }
 
+    /// Rescales to the smaller modulus `new_q`, every coefficient becoming
+    /// `round(x new_q / q)`. The message and the encryption noise scale by
+    /// `new_q / q`; the rounding adds `LweParams::modulus_switch_stddev` on
+    /// top, which is what has to fit the margin before bootstrapping.
+    pub fn modulus_switch(&self, new_q: u64) -> LweCiphertext {
+        assert!(new_q > 0 && new_q <= self.params.q, "modulus switching only goes to a smaller modulus");
+        let q = self.params.q as u128;
+        // `round(x new_q / q)` from the exact product, which fits a u128
+        // even for moduli near `u64::MAX`; halves round up
+        let switch = |x: u64| {
+            let product = x as u128 * new_q as u128;
+            let (quotient, remainder) = (product / q, product % q);
+            let rounded = quotient + (remainder >= q - remainder) as u128;
+            (rounded % new_q as u128) as u64
+        };
+
+        LweCiphertext {
+            a: self.a.iter().map(|&x| switch(x)).collect(),
+            b: switch(self.b),
+            params: LweParams {
+                n: self.params.n,
+                q: new_q,
+                stddev: self.params.stddev * new_q as f64 / self.params.q as f64,
+            },
+        }
+    }
+
     pub fn scalar_mul(&self, scalar: u64) -> LweCiphertext {
         let a: Vec<u64> = self.a.iter()
             .map(|x| (x * scalar) % self.params.q)
@@ -128,9 +187,182 @@ impl LweCiphertext {
     }
 }
 
-#[cfg(test)]
+impl TlweSample {
+    /// Discretizes the torus to `modulus` steps, `round(t modulus)` per
+    /// coefficient, as blind rotation does with `2N`. The rounding noise is
+    /// that of `LweCiphertext::modulus_switch` to the same modulus.
+    pub fn modulus_switch(&self, modulus: u64) -> LweCiphertext {
+        assert!(modulus > 0, "modulus must be positive");
+        // round(x modulus / 2^64) on the 64-bit fixed point, exactly
+        let switch = |t: &Torus| {
+            let product = t.fixed64() as u128 * modulus as u128;
+            (((product >> 64) + ((product >> 63) & 1)) % modulus as u128) as u64
+        };
+
+        LweCiphertext {
+            a: self.a.iter().map(switch).collect(),
+            b: switch(&self.b),
+            params: LweParams {
+                n: self.params.n,
+                q: modulus,
+                stddev: self.params.stddev * modulus as f64,
+            },
+        }
+    }
+}
+
+/// A public key holds fewer encryptions of zero than its subset sum needs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicKeyError {
+    pub len: usize,
+    pub min_len: usize,
+}
+
+impl fmt::Display for PublicKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a public key of {} encryptions of zero, at least {} are required", self.len, self.min_len)
+    }
+}
+
+impl std::error::Error for PublicKeyError {}
+
+// `(n + 1) log2 q + 128`: enough zeros for the subset sum to be close to
+// uniform (leftover hash lemma), and for the empty subset, which would
+// publish the message, to have probability `2^-min_len`
+fn min_public_key_len(n: usize, log_q: u32) -> usize {
+    (n + 1) * log_q as usize + 128
+}
+
+fn check_public_key_len(len: usize, min_len: usize) -> Result<(), PublicKeyError> {
+    if len < min_len {
+        return Err(PublicKeyError { len, min_len });
+    }
+    Ok(())
+}
+
+/// Encryptions of zero under an LWE key, published so that anyone can
+/// encrypt without the key.
+///
+/// A ciphertext is the sum of a uniformly random subset of the zeros plus
+/// the message (Regev): its noise is that of up to `len` fresh samples. For
+/// the subset sum to hide which samples went in, `len` must be at least
+/// `min_len`, `(n + 1) log2 q + 128`.
+#[derive(Debug, Clone)]
+pub struct LwePublicKey {
+    params: LweParams,
+    zeros: Vec<LweCiphertext>,
+}
+
+impl LwePublicKey {
+    pub fn min_len(params: &LweParams) -> usize {
+        min_public_key_len(params.n, u64::BITS - params.q.saturating_sub(1).leading_zeros())
+    }
+
+    /// Panics when `len` is below `min_len`
+    #[cfg(feature = "client")]
+    pub fn generate(sk: &LweSecretKey, len: usize) -> Self {
+        Self::generate_with_rng(sk, len, &mut default_entropy())
+    }
+
+    #[cfg(feature = "client")]
+    pub fn generate_with_rng<R: EntropySource + ?Sized>(sk: &LweSecretKey, len: usize, rng: &mut R) -> Self {
+        check_public_key_len(len, Self::min_len(&sk.params)).unwrap_or_else(|e| panic!("{}", e));
+        let zeros = (0..len).map(|_| LweCiphertext::encrypt_with_rng(0, sk, rng)).collect();
+        LwePublicKey { params: sk.params.clone(), zeros }
+    }
+
+    /// Key from encryptions of zero under `params` computed elsewhere
+    pub fn from_zeros(params: LweParams, zeros: Vec<LweCiphertext>) -> Result<Self, PublicKeyError> {
+        check_public_key_len(zeros.len(), Self::min_len(&params))?;
+        Ok(LwePublicKey { params, zeros })
+    }
+
+    pub fn params(&self) -> &LweParams {
+        &self.params
+    }
+
+    pub fn zeros(&self) -> &[LweCiphertext] {
+        &self.zeros
+    }
+}
+
+/// `LwePublicKey` for TLWE samples
+#[derive(Debug, Clone)]
+pub struct TlwePublicKey {
+    params: TlweParams,
+    zeros: Vec<TlweSample>,
+}
+
+impl TlwePublicKey {
+    /// `(n + 1) 64 + 128`, the torus having 64 bits
+    pub fn min_len(params: &TlweParams) -> usize {
+        min_public_key_len(params.n, 64)
+    }
+
+    /// Panics when `len` is below `min_len`
+    #[cfg(feature = "client")]
+    pub fn generate(sk: &TlweSecretKey, len: usize) -> Self {
+        Self::generate_with_rng(sk, len, &mut default_entropy())
+    }
+
+    #[cfg(feature = "client")]
+    pub fn generate_with_rng<R: EntropySource + ?Sized>(sk: &TlweSecretKey, len: usize, rng: &mut R) -> Self {
+        check_public_key_len(len, Self::min_len(&sk.params)).unwrap_or_else(|e| panic!("{}", e));
+        let zeros = (0..len).map(|_| TlweSample::encrypt_with_rng(&Torus::default(), sk, rng)).collect();
+        TlwePublicKey { params: sk.params.clone(), zeros }
+    }
+
+    /// Key from encryptions of zero under `params` computed elsewhere, e.g.
+    /// assembled from threshold shares
+    pub fn from_zeros(params: TlweParams, zeros: Vec<TlweSample>) -> Result<Self, PublicKeyError> {
+        check_public_key_len(zeros.len(), Self::min_len(&params))?;
+        Ok(TlwePublicKey { params, zeros })
+    }
+
+    pub fn params(&self) -> &TlweParams {
+        &self.params
+    }
+
+    pub fn zeros(&self) -> &[TlweSample] {
+        &self.zeros
+    }
+}
+
+// Random half of `items`, each kept with probability 1/2
+fn random_subset<'a, T, R: EntropySource + ?Sized>(items: &'a [T], rng: &'a mut R) -> impl Iterator<Item = &'a T> {
+    items.iter().filter(move |_| rand::Rng::random_bool(rng, 0.5))
+}
+
+impl LweCiphertext {
+    pub fn encrypt_with_public_key(message: u64, pk: &LwePublicKey) -> Self {
+        Self::encrypt_with_public_key_with_rng(message, pk, &mut default_entropy())
+    }
+
+    /// `encrypt_with_public_key` with the subset drawn from `rng`
+    pub fn encrypt_with_public_key_with_rng<R: EntropySource + ?Sized>(message: u64, pk: &LwePublicKey, rng: &mut R) -> Self {
+        let params = pk.params().clone();
+        let start = LweCiphertext { a: vec![0; params.n], b: message % params.q, params };
+        random_subset(&pk.zeros, rng).fold(start, |acc, zero| acc.add(zero))
+    }
+}
+
+impl TlweSample {
+    /// Noise level and estimate count every zero the subset sum took
+    pub fn encrypt_with_public_key(message: &Torus, pk: &TlwePublicKey) -> Self {
+        Self::encrypt_with_public_key_with_rng(message, pk, &mut default_entropy())
+    }
+
+    /// `encrypt_with_public_key` with the subset drawn from `rng`
+    pub fn encrypt_with_public_key_with_rng<R: EntropySource + ?Sized>(message: &Torus, pk: &TlwePublicKey, rng: &mut R) -> Self {
+        let start = TlweSample::trivial(message, pk.params.clone());
+        random_subset(&pk.zeros, rng).fold(start, |acc, zero| acc.add(zero))
+    }
+}
+
+#[cfg(all(test, feature = "client"))]
 mod tests {
     use super::*;
+    use crate::tlwe::{TlweParams, TlweSecretKey};
 
     #[test]
     fn test_lwe_encrypt_decrypt() {
@@ -168,4 +400,92 @@ mod tests {
 
         assert!((decrypted as i64 - (m1 + m2) as i64).abs() < 10);
     }
-}
\ No newline at end of file
+
+    // Signed distance between two residues modulo q
+    fn distance(x: u64, y: u64, q: u64) -> i64 {
+        let d = (x as i64 - y as i64).rem_euclid(q as i64);
+        if d > q as i64 / 2 { d - q as i64 } else { d }
+    }
+
+    #[test]
+    fn test_modulus_switch_rounding_noise() {
+        let params = LweParams { n: 64, q: 1 << 32, stddev: 3.2 };
+        let sk = LweSecretKey::generate_binary(params.clone());
+        let new_q = 1 << 10;
+        let expected_stddev = params.modulus_switch_stddev();
+        assert!((expected_stddev - (33.0f64 / 12.0).sqrt()).abs() < 1e-12);
+
+        // 5/16 of the modulus before and after
+        let mut sum_squares = 0.0;
+        let trials = 400;
+        for _ in 0..trials {
+            let switched = LweCiphertext::encrypt(5 << 28, &sk).modulus_switch(new_q);
+            assert_eq!(switched.params.q, new_q);
+            let key = LweSecretKey { coeffs: sk.coeffs.clone(), params: switched.params.clone() };
+            let error = distance(switched.decrypt(&key), 5 << 6, new_q);
+            assert!((error.abs() as f64) < 6.0 * expected_stddev);
+            sum_squares += (error * error) as f64;
+        }
+        // The measured spread matches the analysis within sampling error
+        let measured = (sum_squares / trials as f64).sqrt();
+        assert!(measured > 0.5 * expected_stddev && measured < 1.5 * expected_stddev, "{}", measured);
+    }
+
+    #[test]
+    fn test_modulus_switch_near_u64_max() {
+        let q = u64::MAX;
+        let ct = LweCiphertext { a: vec![q - 1, 4, 5], b: 3, params: LweParams { n: 3, q, stddev: 1.0 } };
+        assert_eq!(ct.modulus_switch(q).a, ct.a);
+
+        // `q` is a multiple of 3: every coefficient is divided by 3 and rounded
+        let switched = ct.modulus_switch(q / 3);
+        assert_eq!((switched.a, switched.b), (vec![0, 1, 2], 1));
+    }
+
+    #[test]
+    fn test_torus_modulus_switch() {
+        let tlwe_key = TlweSecretKey::generate_binary(TlweParams { n: 32, stddev: 1e-9 });
+        let switched = TlweSample::encrypt(&Torus::new(0.3), &tlwe_key).modulus_switch(2048);
+        let key = LweSecretKey { coeffs: tlwe_key.coeffs.clone(), params: switched.params.clone() };
+        let error = distance(switched.decrypt(&key), (0.3f64 * 2048.0).round() as u64, 2048);
+        assert!((error.abs() as f64) < 6.0 * switched.params.modulus_switch_stddev());
+
+        // (1/2 + 2^-53)(2^64 - 1) is just below 2^63 + 2^11 - 1/2
+        let t = Torus::from_fixed64((1 << 63) + (1 << 11));
+        let sample = TlweSample::trivial(&t, TlweParams { n: 0, stddev: 0.0 });
+        assert_eq!(sample.modulus_switch(u64::MAX).b, (1 << 63) + (1 << 11) - 1);
+        assert_eq!(sample.modulus_switch(2).b, 1);
+    }
+
+    #[test]
+    fn test_public_key_encryption() {
+        let params = LweParams { n: 16, q: 1 << 32, stddev: 3.2 };
+        let sk = LweSecretKey::generate_binary(params.clone());
+        let len = LwePublicKey::min_len(&params);
+        assert_eq!(len, 17 * 32 + 128);
+        let pk = LwePublicKey::generate(&sk, len);
+        for m in [0u64, 7, 12] {
+            let ct = LweCiphertext::encrypt_with_public_key(m << 28, &pk);
+            // At most 672 samples of noise 3.2 on a step of 2^28
+            assert!(distance(ct.decrypt(&sk), m << 28, 1 << 32).abs() < 1 << 12);
+        }
+
+        let sk = TlweSecretKey::generate_binary(TlweParams { n: 16, stddev: 1e-9 });
+        let len = TlwePublicKey::min_len(&sk.params);
+        let pk = TlwePublicKey::generate(&sk, len);
+        for bit in [false, true] {
+            let message = Torus::new(if bit { 0.625 } else { 0.125 });
+            let ct = TlweSample::encrypt_with_public_key(&message, &pk);
+            assert!(!ct.is_trivial());
+            assert_eq!(ct.decrypt_binary(&sk), bit);
+            assert!(ct.estimate.variance <= len as f64 * 1e-18);
+        }
+
+        // Short keys are refused: with few zeros the subset is often empty
+        let short = pk.zeros()[..64].to_vec();
+        let error = TlwePublicKey::from_zeros(sk.params.clone(), short).unwrap_err();
+        assert_eq!(error, PublicKeyError { len: 64, min_len: len });
+        assert!(TlwePublicKey::from_zeros(sk.params.clone(), Vec::new()).is_err());
+    }
+
+}
@@ -0,0 +1,379 @@
+//! Upgrades of serialized values and keys across releases.
+//!
+//! Canonical encodings carry a format version (see `canonical`). `migrate`
+//! reads bytes written by any earlier release and returns the value in the
+//! current layout, applying one upgrade step per version. Layout history:
+//!
+//! - version 1: torus elements as `f64` bits, first canonical layout
+//! - version 2: samples and keys embed a `ParamsDigest` after their parameters
+//! - version 3: `TfheSecretKey` carries the ring key of the blind rotation
+//! - version 4: torus elements as 64-bit fixed point instead of `f64` bits
+//! - version 5: samples carry an optional `Namespace` after their parameters
+//!
+//! Parameter changes are a different matter: a secret key can only follow
+//! new parameters that leave its coefficients meaningful, and a cloud key
+//! encrypts the secret key under the old parameters, so it rarely can.
+//! `migrate_secret_key` and `check_cloud_key` say exactly which change
+//! forces regeneration.
+
+use std::fmt;
+use crate::canonical::{read_params, write_namespace, write_params, write_u64, Canonical, CanonicalError, CanonicalReader, CANONICAL_VERSION};
+use crate::seeded::SEED_BYTES;
+use crate::tfhe::TfheParams;
+use crate::tlwe::TlweParams;
+use crate::torus::Torus;
+#[cfg(feature = "client")]
+use crate::tfhe::TfheSecretKey;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationError {
+    Canonical(CanonicalError),
+    /// Written by a newer release; only upgrades are supported
+    NewerFormat { found: u8, supported: u8 },
+    /// The value cannot be carried over and must be generated again
+    RegenerationRequired { field: &'static str, reason: &'static str },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Canonical(e) => write!(f, "{}", e),
+            MigrationError::NewerFormat { found, supported } => write!(
+                f,
+                "format version {} comes from a newer release, this one reads up to {}",
+                found, supported,
+            ),
+            MigrationError::RegenerationRequired { field, reason } => {
+                write!(f, "changing `{}` requires regenerating the key: {}", field, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<CanonicalError> for MigrationError {
+    fn from(e: CanonicalError) -> Self {
+        MigrationError::Canonical(e)
+    }
+}
+
+/// Reads `bytes` written by this or any earlier release
+pub fn migrate<T: Canonical>(bytes: &[u8]) -> Result<T, MigrationError> {
+    let mut input = CanonicalReader::new(bytes);
+    let tag = input.u8()?;
+    if tag != T::TAG {
+        return Err(CanonicalError::WrongTag { expected: T::TAG, found: tag }.into());
+    }
+    let version = input.u8()?;
+    if version > CANONICAL_VERSION {
+        return Err(MigrationError::NewerFormat { found: version, supported: CANONICAL_VERSION });
+    }
+
+    let mut body = bytes[2..].to_vec();
+    for from in version..CANONICAL_VERSION {
+        body = upgrade(tag, from, &body)?;
+    }
+
+    let mut current = vec![tag, CANONICAL_VERSION];
+    current.extend_from_slice(&body);
+    Ok(T::from_canonical_bytes(&current)?)
+}
+
+/// Rewrites a body in layout `from` as layout `from + 1`
+fn upgrade(tag: u8, from: u8, body: &[u8]) -> Result<Vec<u8>, MigrationError> {
+    match from {
+        1 => embed_params_digests(tag, body),
+        // Nothing in an older secret key determines the ring key
+        2 if tag == 8 => Err(MigrationError::RegenerationRequired {
+            field: "trlwe_key",
+            reason: "secret keys before version 3 have no ring key for bootstrapping",
+        }),
+        2 => Ok(body.to_vec()),
+        3 => fixed_point_torus(tag, body),
+        4 => untagged_samples(tag, body),
+        // Version 1 is the first canonical layout, nothing was written before it
+        _ => Err(CanonicalError::UnsupportedVersion(from).into()),
+    }
+}
+
+fn embed_params_digests(tag: u8, body: &[u8]) -> Result<Vec<u8>, MigrationError> {
+    let mut input = CanonicalReader::new(body);
+    let mut out = Vec::with_capacity(body.len() + 16);
+
+    match tag {
+        // TlweSample, TlweSecretKey
+        3 | 7 => write_params(&mut out, &TlweParams::read(&mut input)?),
+        // TransportTlweSample: log_modulus first
+        6 => {
+            out.extend_from_slice(input.bytes(8)?);
+            write_params(&mut out, &TlweParams::read(&mut input)?);
+        }
+        // TfheSecretKey, whose TLWE key carries its own parameters
+        8 => {
+            write_params(&mut out, &TfheParams::read(&mut input)?);
+            write_params(&mut out, &TlweParams::read(&mut input)?);
+        }
+        _ => {}
+    }
+
+    out.extend_from_slice(&body[body.len() - input.remaining()..]);
+    Ok(out)
+}
+
+fn fixed_point_torus(tag: u8, body: &[u8]) -> Result<Vec<u8>, MigrationError> {
+    let mut input = CanonicalReader::new(body);
+    let mut out = Vec::with_capacity(body.len());
+
+    match tag {
+        1 => legacy_torus(&mut input, &mut out)?,
+        3 => legacy_sample(&mut input, &mut out)?,
+        // ResultBundle: the outputs come first
+        10 => {
+            let len = input.usize()?;
+            write_u64(&mut out, len as u64);
+            for _ in 0..len {
+                legacy_sample(&mut input, &mut out)?;
+            }
+        }
+        // CompressedTlweSample: parameters, seed, body
+        11 => {
+            write_params(&mut out, &read_params::<TlweParams>(&mut input)?);
+            out.extend_from_slice(input.bytes(SEED_BYTES)?);
+            legacy_torus(&mut input, &mut out)?;
+        }
+        // SeededBootstrappingKey: parameters, seed, then `N` coefficients per body
+        12 => {
+            let params: TfheParams = read_params(&mut input)?;
+            write_params(&mut out, &params);
+            out.extend_from_slice(input.bytes(SEED_BYTES)?);
+            let len = input.usize()?;
+            write_u64(&mut out, len as u64);
+            for _ in 0..len * params.N {
+                legacy_torus(&mut input, &mut out)?;
+            }
+        }
+        _ => {}
+    }
+
+    out.extend_from_slice(&body[body.len() - input.remaining()..]);
+    Ok(out)
+}
+
+fn untagged_samples(tag: u8, body: &[u8]) -> Result<Vec<u8>, MigrationError> {
+    let mut input = CanonicalReader::new(body);
+    let mut out = Vec::with_capacity(body.len() + 1);
+
+    match tag {
+        3 => untagged_sample(&mut input, &mut out)?,
+        // ResultBundle: the outputs come first
+        10 => {
+            let len = input.usize()?;
+            write_u64(&mut out, len as u64);
+            for _ in 0..len {
+                untagged_sample(&mut input, &mut out)?;
+            }
+        }
+        _ => {}
+    }
+
+    out.extend_from_slice(&body[body.len() - input.remaining()..]);
+    Ok(out)
+}
+
+// Samples written before namespaces existed belong to no tenant
+fn untagged_sample(input: &mut CanonicalReader<'_>, out: &mut Vec<u8>) -> Result<(), MigrationError> {
+    write_params(out, &read_params::<TlweParams>(input)?);
+    write_namespace(out, None);
+    let len = input.usize()?;
+    write_u64(out, len as u64);
+    out.extend_from_slice(input.bytes((len + 1) * 8)?);
+    Ok(())
+}
+
+// A TLWE sample whose mask and body are still `f64` bits
+fn legacy_sample(input: &mut CanonicalReader<'_>, out: &mut Vec<u8>) -> Result<(), MigrationError> {
+    write_params(out, &read_params::<TlweParams>(input)?);
+    let len = input.usize()?;
+    write_u64(out, len as u64);
+    for _ in 0..=len {
+        legacy_torus(input, out)?;
+    }
+    Ok(())
+}
+
+fn legacy_torus(input: &mut CanonicalReader<'_>, out: &mut Vec<u8>) -> Result<(), MigrationError> {
+    let value = input.f64()?;
+    if !(0.0..1.0).contains(&value) {
+        return Err(CanonicalError::Invalid("torus element").into());
+    }
+    write_u64(out, Torus::new(value).fixed64());
+    Ok(())
+}
+
+/// Carries a secret key over to `params`. Only the noise parameters may
+/// change: the binary coefficients do not depend on them, while a new
+/// dimension needs coefficients the old key never had. Existing ciphertexts
+/// keep the digest of their old parameters and are not accepted by the
+/// migrated key.
+#[cfg(feature = "client")]
+pub fn migrate_secret_key(key: &TfheSecretKey, params: TfheParams) -> Result<TfheSecretKey, MigrationError> {
+    if params.tlwe_params.n != key.params.tlwe_params.n {
+        return Err(MigrationError::RegenerationRequired {
+            field: "tlwe_params.n",
+            reason: "the secret key has one coefficient per dimension, so a new dimension needs a new key",
+        });
+    }
+
+    if params.N != key.params.N || params.k != key.params.k {
+        return Err(MigrationError::RegenerationRequired {
+            field: "N",
+            reason: "the ring key has one coefficient per ring dimension and mask polynomial",
+        });
+    }
+
+    let mut migrated = key.clone();
+    migrated.tlwe_key.params = params.tlwe_params.clone();
+    migrated.trlwe_key.params = params.trlwe_params();
+    migrated.params = params;
+    Ok(migrated)
+}
+
+/// Whether a cloud key generated under `old` is still valid under `new`
+pub fn check_cloud_key(old: &TfheParams, new: &TfheParams) -> Result<(), MigrationError> {
+    let regenerate = |field, reason| Err(MigrationError::RegenerationRequired { field, reason });
+
+    if new.tlwe_params.n != old.tlwe_params.n || new.n != old.n {
+        return regenerate("n", "the bootstrapping key holds one TGSW sample per secret key coefficient");
+    }
+    if new.tlwe_params.stddev != old.tlwe_params.stddev || new.tgsw_params.tlwe_params.stddev != old.tgsw_params.tlwe_params.stddev {
+        return regenerate(
+            "stddev",
+            "the bootstrapping key samples were encrypted with the old noise and keep it",
+        );
+    }
+    if new.tgsw_params.l != old.tgsw_params.l || new.tgsw_params.bg_bit != old.tgsw_params.bg_bit {
+        return regenerate("tgsw_params", "the bootstrapping key is encrypted under the old gadget decomposition");
+    }
+    if new.N != old.N || new.k != old.k || new.tgsw_params.tlwe_params.n != old.tgsw_params.tlwe_params.n {
+        return regenerate("N", "the bootstrapping key lives in the old ring dimension");
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "client"))]
+mod tests {
+    use super::*;
+    use crate::canonical::Parameterized;
+    use crate::tlwe::TlweSample;
+
+    // `sample` in the version 3 layout, torus elements as `f64` bits
+    fn version_3(sample: &TlweSample) -> Vec<u8> {
+        let mut bytes = vec![TlweSample::TAG, 3];
+        write_params(&mut bytes, &sample.params);
+        write_u64(&mut bytes, sample.a.len() as u64);
+        for t in sample.a.iter().chain([&sample.b]) {
+            bytes.extend_from_slice(&t.value().to_bits().to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_current_layout_and_versions() {
+        let params = TfheParams::default();
+        let bytes = params.to_canonical_bytes();
+        let migrated: TfheParams = migrate(&bytes).unwrap();
+        assert_eq!(migrated.canonical_digest(), params.canonical_digest());
+
+        let mut newer = bytes.clone();
+        newer[1] = CANONICAL_VERSION + 1;
+        assert_eq!(
+            migrate::<TfheParams>(&newer).unwrap_err(),
+            MigrationError::NewerFormat { found: CANONICAL_VERSION + 1, supported: CANONICAL_VERSION },
+        );
+
+        let mut unknown = bytes;
+        unknown[1] = 0;
+        assert_eq!(
+            migrate::<TfheParams>(&unknown).unwrap_err(),
+            MigrationError::Canonical(CanonicalError::UnsupportedVersion(0)),
+        );
+    }
+
+    #[test]
+    fn test_version_1_gains_params_digest() {
+        let key = TfheSecretKey::generate(TfheParams {
+            tlwe_params: TlweParams { n: 8, stddev: 1e-9 },
+            ..TfheParams::default()
+        });
+        let sample = TlweSample::encrypt(&Torus::new(0.125), &key.tlwe_key);
+
+        // Version 1 had no digests: drop them from the version 3 encoding
+        let mut v1 = version_3(&sample);
+        v1[1] = 1;
+        v1.drain(18..26);
+        let migrated: TlweSample = migrate(&v1).unwrap();
+        assert_eq!(migrated.params, sample.params);
+
+        let current = key.to_canonical_bytes();
+        let mut v1 = current.clone();
+        v1[1] = 1;
+        // Tag and version, then the TFHE parameters, their digest, the TLWE parameters and theirs
+        let params_end = key.params.to_canonical_bytes().len();
+        v1.drain(params_end + 24..params_end + 32);
+        v1.drain(params_end..params_end + 8);
+        // The digests are restored, but a version 2 key has no ring key to carry over
+        assert!(matches!(
+            migrate::<TfheSecretKey>(&v1),
+            Err(MigrationError::RegenerationRequired { field: "trlwe_key", .. }),
+        ));
+    }
+
+    #[test]
+    fn test_version_3_torus_becomes_fixed_point() {
+        let key = crate::tlwe::TlweSecretKey::generate_binary(TlweParams { n: 8, stddev: 1e-9 });
+        let sample = TlweSample::encrypt(&Torus::new(0.625), &key);
+        let migrated: TlweSample = migrate(&version_3(&sample)).unwrap();
+        assert!(migrated.decrypt_binary(&key));
+        for (old, new) in sample.a.iter().chain([&sample.b]).zip(migrated.a.iter().chain([&migrated.b])) {
+            assert_eq!(new, &Torus::new(old.value()));
+        }
+
+        let mut out_of_range = version_3(&sample);
+        let end = out_of_range.len();
+        out_of_range[end - 8..].copy_from_slice(&1.5f64.to_bits().to_le_bytes());
+        assert_eq!(
+            migrate::<TlweSample>(&out_of_range).unwrap_err(),
+            MigrationError::Canonical(CanonicalError::Invalid("torus element")),
+        );
+    }
+
+    #[test]
+    fn test_secret_key_follows_noise_but_not_dimension() {
+        let params = TfheParams { tlwe_params: TlweParams { n: 16, stddev: 1e-9 }, ..TfheParams::default() };
+        let key = TfheSecretKey::generate(params.clone());
+
+        let restored: TfheSecretKey = migrate(&key.to_canonical_bytes()).unwrap();
+        assert_eq!(restored.tlwe_key.coeffs, key.tlwe_key.coeffs);
+        assert_eq!(restored.trlwe_key.polys, key.trlwe_key.polys);
+
+        let mut noisier = params.clone();
+        noisier.tlwe_params.stddev = 1e-8;
+        let migrated = migrate_secret_key(&key, noisier.clone()).unwrap();
+        assert_eq!(migrated.tlwe_key.coeffs, key.tlwe_key.coeffs);
+        let sample = TlweSample::encrypt(&Torus::new(0.625), &migrated.tlwe_key);
+        assert!(sample.decrypt_binary(&migrated.tlwe_key));
+        assert!(sample.check_params(&params.tlwe_params).is_err());
+        assert!(matches!(
+            check_cloud_key(&params, &noisier),
+            Err(MigrationError::RegenerationRequired { field: "stddev", .. }),
+        ));
+
+        let mut wider = params;
+        wider.tlwe_params.n = 32;
+        assert!(matches!(
+            migrate_secret_key(&key, wider),
+            Err(MigrationError::RegenerationRequired { field: "tlwe_params.n", .. }),
+        ));
+    }
+}
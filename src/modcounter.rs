@@ -0,0 +1,128 @@
+//! Encrypted counters modulo a public modulus.
+//!
+//! An `FheModCounter` holds a value below `modulus` in just enough bits for
+//! `modulus - 1`. Each update adds in one extra bit, compares the sum
+//! against the public modulus and subtracts it under a `mux` when the sum
+//! reached it, so the value wraps like a clock whatever the modulus. The
+//! wrap bit is returned encrypted and can be fed to `increment_if` of the
+//! next counter: a row of mod 10 counters is a decimal counter, mod 60 and
+//! mod 24 ones a clock, and the server learns neither values nor carries.
+
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::{CmpOp, IntegerOps};
+
+/// Encrypted value in `0..modulus`
+#[derive(Debug, Clone)]
+pub struct FheModCounter<B> {
+    modulus: u64,
+    value: Vec<B>,
+}
+
+impl<B: Clone> FheModCounter<B> {
+    /// Bits of a counter modulo `modulus`
+    pub fn width(modulus: u64) -> usize {
+        assert!(modulus >= 2, "counters need a modulus of at least 2");
+        (u64::BITS - (modulus - 1).leading_zeros()) as usize
+    }
+
+    /// A counter at zero
+    pub fn new<E: BooleanEvaluator<Bit = B>>(modulus: u64, eval: &E) -> Self {
+        FheModCounter { modulus, value: IntegerOps::constant(0, Self::width(modulus), eval) }
+    }
+
+    /// A counter at the encrypted `value`, which the caller keeps below
+    /// `modulus`; nothing here can check it
+    pub fn from_bits(modulus: u64, value: Vec<B>) -> Self {
+        assert_eq!(value.len(), Self::width(modulus), "counters modulo {} are {} bits", modulus, Self::width(modulus));
+        FheModCounter { modulus, value }
+    }
+
+    pub fn modulus(&self) -> u64 {
+        self.modulus
+    }
+
+    /// Little-endian bits of the value
+    pub fn value(&self) -> &[B] {
+        &self.value
+    }
+
+    pub fn into_bits(self) -> Vec<B> {
+        self.value
+    }
+
+    /// Adds one when `bit` is set; returns whether the counter wrapped to zero
+    pub fn increment_if<E: BooleanEvaluator<Bit = B>>(&mut self, bit: &B, eval: &E) -> B {
+        let width = self.value.len();
+        let next = IntegerOps::increment_if(&IntegerOps::resize(&self.value, width + 1, eval), bit, eval);
+        // Only `modulus - 1 + 1` reaches the modulus, and it wraps to zero
+        let wrap = IntegerOps::cmp_clear(&next, self.modulus, CmpOp::Eq, eval);
+        let zero = IntegerOps::constant(0, width, eval);
+        self.value = IntegerOps::mux(&wrap, &zero, &next[..width], eval);
+        wrap
+    }
+
+    /// Adds one; returns whether the counter wrapped to zero
+    pub fn increment<E: BooleanEvaluator<Bit = B>>(&mut self, eval: &E) -> B {
+        self.increment_if(&eval.constant(true), eval)
+    }
+
+    /// Adds an encrypted `amount` below the modulus, of the counter's width;
+    /// returns whether the sum wrapped
+    pub fn add<E: BooleanEvaluator<Bit = B>>(&mut self, amount: &[B], eval: &E) -> B {
+        assert_eq!(amount.len(), self.value.len(), "amounts have the width of the counter");
+        self.add_wrapping(&IntegerOps::add(&self.value, amount, eval), eval)
+    }
+
+    /// Adds a public `amount`, reduced modulo the modulus first; returns
+    /// whether the sum wrapped
+    pub fn add_clear<E: BooleanEvaluator<Bit = B>>(&mut self, amount: u64, eval: &E) -> B {
+        let amount = IntegerOps::constant(amount % self.modulus, self.value.len(), eval);
+        self.add(&amount, eval)
+    }
+
+    /// Both addends are below the modulus, so one conditional subtraction
+    /// brings `sum` back below it
+    fn add_wrapping<E: BooleanEvaluator<Bit = B>>(&mut self, sum: &[B], eval: &E) -> B {
+        let width = self.value.len();
+        let wrap = IntegerOps::cmp_clear(sum, self.modulus, CmpOp::Ge, eval);
+        let reduced = IntegerOps::sub_mod(sum, &IntegerOps::constant(self.modulus, width + 1, eval), eval);
+        self.value = IntegerOps::mux(&wrap, &reduced[..width], &sum[..width], eval);
+        wrap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    fn read(counter: &FheModCounter<bool>) -> u64 {
+        counter.value().iter().rev().fold(0, |acc, &b| (acc << 1) | b as u64)
+    }
+
+    #[test]
+    fn test_counters_wrap_at_modulus() {
+        let eval = ClearEvaluator;
+        assert_eq!(FheModCounter::<bool>::width(10), 4);
+        assert_eq!(FheModCounter::<bool>::width(64), 6);
+
+        // Two decimal digits, the carry of the units ticking the tens
+        let mut units = FheModCounter::new(10, &eval);
+        let mut tens = FheModCounter::new(10, &eval);
+        for n in 1..=123u64 {
+            let carry = units.increment(&eval);
+            tens.increment_if(&carry, &eval);
+            assert_eq!(read(&tens) * 10 + read(&units), n % 100);
+        }
+
+        let mut seconds = FheModCounter::new(60, &eval);
+        for (amount, expected, wrapped) in [(45, 45, false), (14, 59, false), (1, 0, true), (59, 59, false), (130, 9, true)] {
+            assert_eq!(seconds.add_clear(amount, &eval), wrapped);
+            assert_eq!(read(&seconds), expected);
+        }
+
+        let mut seconds = FheModCounter::from_bits(60, IntegerOps::constant(50, 6, &eval));
+        assert!(seconds.add(&IntegerOps::constant(10, 6, &eval), &eval));
+        assert_eq!(read(&seconds), 0);
+    }
+}
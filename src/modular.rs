@@ -0,0 +1,123 @@
+//! Modular multiplication of encrypted integers by a public odd modulus.
+//!
+//! Montgomery reduction avoids encrypted division: each of the `n` rounds
+//! adds one partial product, adds the modulus when the running sum is odd
+//! and halves it, ending below `2m` with a single conditional subtraction.
+//! That computes `a b 2^-n mod m`; a second pass against the public constant
+//! `2^2n mod m` cancels the factor. Both passes cost about `n` additions of
+//! `n + 2` bits, and the second one is cheaper since its multiplier is public.
+
+use crate::evaluator::{BooleanEvaluator, ClearEvaluator};
+use crate::integer::IntegerOps;
+
+/// Public modulus with its precomputed Montgomery constant
+#[derive(Debug, Clone)]
+pub struct Montgomery {
+    /// Little-endian bits, most significant bit set
+    modulus: Vec<bool>,
+    /// `2^2n mod m`
+    r2: Vec<bool>,
+}
+
+impl Montgomery {
+    /// `modulus` as little-endian bits; it must be odd and greater than 1
+    pub fn new(modulus: &[bool]) -> Self {
+        let n = modulus.iter().rposition(|&b| b).map_or(0, |top| top + 1);
+        let modulus = modulus[..n].to_vec();
+        assert!(n >= 2 && modulus[0], "Montgomery reduction needs an odd modulus greater than 1");
+
+        // Plaintext doubling, 2n times, reduced after each step
+        let eval = ClearEvaluator;
+        let m = IntegerOps::resize(&modulus, n + 1, &eval);
+        let mut r2 = IntegerOps::constant(1, n + 1, &eval);
+        for _ in 0..2 * n {
+            let doubled = IntegerOps::add_mod(&r2, &r2, &eval);
+            let reduce = !IntegerOps::less_than(&doubled, &m, &eval);
+            r2 = IntegerOps::mux(&reduce, &IntegerOps::sub_mod(&doubled, &m, &eval), &doubled, &eval);
+        }
+        r2.truncate(n);
+
+        Montgomery { modulus, r2 }
+    }
+
+    /// Bits of the modulus, and of every operand and result
+    pub fn width(&self) -> usize {
+        self.modulus.len()
+    }
+
+    /// `a b 2^-n mod m` for `a`, `b` below the modulus
+    pub fn mont_mul<E: BooleanEvaluator>(&self, a: &[E::Bit], b: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        let n = self.width();
+        assert!(a.len() == n && b.len() == n, "operands must have the width of the modulus");
+
+        let zero = eval.constant(false);
+        let b = IntegerOps::resize(b, n + 2, eval);
+        let mut t: Vec<E::Bit> = vec![zero.clone(); n + 2];
+
+        for a_i in a {
+            let partial: Vec<E::Bit> = b.iter().map(|b_j| eval.and(a_i, b_j)).collect();
+            t = IntegerOps::add_mod(&t, &partial, eval);
+
+            let odd = t[0].clone();
+            let multiple: Vec<E::Bit> = (0..n + 2)
+                .map(|j| if self.modulus.get(j) == Some(&true) { odd.clone() } else { zero.clone() })
+                .collect();
+            t = IntegerOps::add_mod(&t, &multiple, eval);
+
+            t.remove(0);
+            t.push(zero.clone());
+        }
+
+        // t < 2m here
+        let m: Vec<E::Bit> = (0..n + 2)
+            .map(|j| eval.constant(self.modulus.get(j) == Some(&true)))
+            .collect();
+        let below = IntegerOps::less_than(&t, &m, eval);
+        let mut result = IntegerOps::mux(&below, &t, &IntegerOps::sub_mod(&t, &m, eval), eval);
+        result.truncate(n);
+        result
+    }
+
+    /// `a b mod m` for `a`, `b` below the modulus
+    pub fn mul_mod<E: BooleanEvaluator>(&self, a: &[E::Bit], b: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        let reduced = self.mont_mul(a, b, eval);
+        // Public multiplier first: its zero bits make whole rounds trivial
+        let r2: Vec<E::Bit> = self.r2.iter().map(|&bit| eval.constant(bit)).collect();
+        self.mont_mul(&r2, &reduced, eval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(value: u64, width: usize) -> Vec<bool> {
+        IntegerOps::constant(value, width, &ClearEvaluator)
+    }
+
+    fn value(bits: &[bool]) -> u64 {
+        bits.iter().enumerate().fold(0, |acc, (i, &b)| acc | ((b as u64) << i))
+    }
+
+    #[test]
+    fn test_mul_mod() {
+        let eval = ClearEvaluator;
+
+        for &m in &[97u64, 255, (1 << 61) - 1, 0xffff_ffff_0000_0001] {
+            let width = 64 - m.leading_zeros() as usize;
+            let montgomery = Montgomery::new(&bits(m, 64));
+            assert_eq!(montgomery.width(), width);
+
+            for &(a, b) in &[(0, 5), (1, m - 1), (m - 1, m - 1), (12345 % m, 67890 % m), (m / 3, m / 2)] {
+                let product = montgomery.mul_mod(&bits(a, width), &bits(b, width), &eval);
+                assert_eq!(value(&product) as u128, a as u128 * b as u128 % m as u128);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "odd modulus")]
+    fn test_even_modulus_rejected() {
+        Montgomery::new(&bits(96, 8));
+    }
+}
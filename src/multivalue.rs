@@ -0,0 +1,157 @@
+//! Several lookup tables from one blind rotation.
+//!
+//! Blind rotation is linear in its test polynomial, so it can rotate a
+//! polynomial `v0` every table shares and leave the table to an integer
+//! polynomial applied afterwards. With `v0 = tau (1 + X + ... + X^(N-1))`,
+//! `v0 (1 - X) = 2 tau` in the negacyclic ring, so the accumulator
+//! `X^-p v0` times `(1 - X) Q` equals `X^-p 2 tau Q`: the rotation of the
+//! test polynomial `2 tau Q`. Each table costs one multiplication by a
+//! small integer polynomial instead of a blind rotation (Carpov, Izabachène
+//! and Mollimard). Test polynomial coefficients are rounded to multiples of
+//! `2 tau = 2^-MULTI_VALUE_BITS`; gate tables are exact. The noise of the
+//! shared rotation is scaled by the norm of `(1 - X) Q`, small for step
+//! functions whose coefficients change at few slots.
+
+#[cfg(feature = "server")]
+use std::time::Instant;
+#[cfg(feature = "server")]
+use crate::hooks::BootstrapInfo;
+#[cfg(feature = "server")]
+use crate::lutdebug::TestVector;
+#[cfg(feature = "server")]
+use crate::noise::NoiseLevel;
+#[cfg(feature = "server")]
+use crate::tfhe::{TfheCloudKey, TfheGates};
+#[cfg(feature = "server")]
+use crate::tgsw::BootstrappingKey;
+#[cfg(feature = "server")]
+use crate::tlwe::TlweSample;
+#[cfg(feature = "server")]
+use crate::torus::Torus;
+#[cfg(feature = "server")]
+use crate::trlwe::{TorusPolynomial, TrlweSample};
+
+/// Precision of multi-value test polynomial coefficients
+pub const MULTI_VALUE_BITS: u32 = 6;
+
+// Integer polynomial `(1 - X) Q` standing in for `lut` against the
+// rotation of `v0`, and the constant added after extraction
+#[cfg(feature = "server")]
+#[allow(non_snake_case)]
+fn factor(lut: &[Torus], N: usize) -> (Vec<i32>, Torus) {
+    let test = TestVector::from_lut(lut, N);
+    let unit = (MULTI_VALUE_BITS as f64).exp2();
+    let q: Vec<i32> = test
+        .poly
+        .coeffs
+        .iter()
+        .map(|c| {
+            // Centred, so a negative coefficient stays a small integer
+            let v = c.value();
+            let centred = if v >= 0.5 { v - 1.0 } else { v };
+            (centred * unit).round() as i32
+        })
+        .collect();
+    let mut r = vec![0; N];
+    r[0] = q[0] + q[N - 1];
+    for j in 1..N {
+        r[j] = q[j] - q[j - 1];
+    }
+    (r, test.offset)
+}
+
+#[cfg(feature = "server")]
+impl TfheGates {
+    /// `programmable_bootstrap` of `input` with each of `luts`, sharing one
+    /// blind rotation; outputs in table order under the extracted key
+    pub fn programmable_bootstrap_multi(
+        input: &TlweSample,
+        luts: &[Vec<Torus>],
+        bk: &BootstrappingKey,
+    ) -> Vec<TlweSample> {
+        let ring = &bk.ring_params;
+        let tau = Torus::new((-(MULTI_VALUE_BITS as f64 + 1.0)).exp2());
+        let v0 = TorusPolynomial { coeffs: vec![tau; ring.N] };
+        let acc = Self::blind_rotate(input, &v0, bk);
+
+        luts.iter()
+            .map(|lut| {
+                let (r, offset) = factor(lut, ring.N);
+                let table = TrlweSample {
+                    a: acc.a.iter().map(|p| p.mul_int(&r)).collect(),
+                    b: acc.b.mul_int(&r),
+                    params: ring.clone(),
+                    noise: acc.noise,
+                    estimate: acc.estimate.scale_poly(&r),
+                };
+                let mut output = TlweSample::extract_from_trlwe(&table, 0);
+                output.noise = NoiseLevel::Fresh;
+                output.b = output.b.add(&offset);
+                output
+            })
+            .collect()
+    }
+
+    /// `bootstrap` with several tables at the cost of one blind rotation;
+    /// hooks see it as a single bootstrap
+    pub fn bootstrap_multi(
+        gate: &'static str,
+        input: &TlweSample,
+        luts: &[Vec<Torus>],
+        ck: &TfheCloudKey,
+    ) -> Vec<TlweSample> {
+        let info = BootstrapInfo {
+            gate,
+            input_dimension: input.params.n,
+            lut_size: luts.first().map_or(0, Vec::len),
+        };
+
+        assert!(
+            !input.noise.needs_bootstrap(),
+            "{} gate input is past its noise budget; an operand needed a bootstrap first",
+            gate,
+        );
+        ck.expect_namespace(gate, input);
+
+        ck.hooks.before(&info, input);
+        let start = Instant::now();
+        let mut outputs: Vec<TlweSample> = Self::programmable_bootstrap_multi(input, luts, &ck.bootstrapping_key)
+            .iter()
+            .map(|s| Self::key_switch(s, ck))
+            .collect();
+        let elapsed = start.elapsed();
+        for output in &mut outputs {
+            output.noise = NoiseLevel::Fresh;
+        }
+        if let Some(first) = outputs.first_mut() {
+            ck.hooks.after(&info, first, elapsed);
+        }
+
+        outputs
+    }
+}
+
+#[cfg(all(test, feature = "client", feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::tfhe::TfheEncoder;
+    use crate::tfhe::fixtures::small_keys;
+
+    #[test]
+    fn test_multi_value_matches_gate_tables() {
+        let (sk, ck) = small_keys(64);
+
+        // Identity and negation of a gate-encoded bit
+        let mut same = vec![Torus::new(0.125); 1024];
+        same[256..768].fill(Torus::new(0.375));
+        let not: Vec<Torus> = same.iter().map(|t| Torus::new(0.5 - t.value())).collect();
+
+        for bit in [false, true] {
+            let input = TfheEncoder::encode_bool(bit, &sk);
+            let outputs = TfheGates::bootstrap_multi("test", &input, &[same.clone(), not.clone()], &ck);
+            assert_eq!(outputs.len(), 2);
+            assert_eq!(TfheEncoder::decode_bool(&outputs[0], &sk), bit);
+            assert_eq!(TfheEncoder::decode_bool(&outputs[1], &sk), !bit);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::IntegerOps;
+use crate::linear::LinearPlan;
+
+/// Single-channel 2D map of integers, row-major, each a little-endian bit vector
+#[derive(Debug, Clone)]
+pub struct FeatureMap<B> {
+    pub height: usize,
+    pub width: usize,
+    pub pixels: Vec<Vec<B>>,
+}
+
+impl<B> FeatureMap<B> {
+    pub fn new(height: usize, width: usize, pixels: Vec<Vec<B>>) -> Self {
+        assert_eq!(pixels.len(), height * width, "pixel count does not match the shape");
+        FeatureMap { height, width, pixels }
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> &[B] {
+        &self.pixels[row * self.width + col]
+    }
+}
+
+/// Stride and zero padding, applied equally on both axes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conv2dParams {
+    pub stride: usize,
+    pub padding: usize,
+}
+
+impl Default for Conv2dParams {
+    fn default() -> Self {
+        Conv2dParams { stride: 1, padding: 0 }
+    }
+}
+
+impl Conv2dParams {
+    /// Output length along an axis of `input` cells for a window of `window` cells
+    pub fn output_size(&self, input: usize, window: usize) -> usize {
+        assert!(self.stride > 0, "stride must be positive");
+        let padded = input + 2 * self.padding;
+        if padded < window {
+            0
+        } else {
+            (padded - window) / self.stride + 1
+        }
+    }
+}
+
+/// Cross-correlation of `input` with a public kernel, as in CNN layers.
+///
+/// Padding cells are zero and contribute nothing, so every output is a dot
+/// product of the in-bounds window with the matching weights. All outputs go
+/// through one `LinearPlan`, which shares the sums that overlapping windows
+/// have in common. Results are `bits` wide, two's complement.
+pub fn conv2d<E: BooleanEvaluator>(
+    input: &FeatureMap<E::Bit>,
+    kernel: &[Vec<i8>],
+    params: Conv2dParams,
+    bits: usize,
+    eval: &E,
+) -> FeatureMap<E::Bit> {
+    let kh = kernel.len();
+    let kw = kernel.first().map_or(0, Vec::len);
+    assert!(kernel.iter().all(|row| row.len() == kw), "kernel rows must have equal length");
+
+    let out_h = params.output_size(input.height, kh);
+    let out_w = params.output_size(input.width, kw);
+
+    let mut matrix = vec![vec![0i64; input.pixels.len()]; out_h * out_w];
+    for (oy, ox) in (0..out_h).flat_map(|y| (0..out_w).map(move |x| (y, x))) {
+        let row = &mut matrix[oy * out_w + ox];
+        for (ky, weights) in kernel.iter().enumerate() {
+            for (kx, &w) in weights.iter().enumerate() {
+                let y = (oy * params.stride + ky).checked_sub(params.padding);
+                let x = (ox * params.stride + kx).checked_sub(params.padding);
+                if let (Some(y), Some(x)) = (y, x) && y < input.height && x < input.width {
+                    row[y * input.width + x] = w as i64;
+                }
+            }
+        }
+    }
+
+    let pixels = LinearPlan::new(&matrix).evaluate(&input.pixels, bits, eval);
+    FeatureMap::new(out_h, out_w, pixels)
+}
+
+/// Maximum over `window`×`window` tiles taken every `stride` cells, without padding.
+///
+/// `signed` selects two's complement comparison, as needed directly after
+/// `conv2d`; after a ReLU the cheaper unsigned comparison is enough.
+pub fn max_pool2d<E: BooleanEvaluator>(
+    input: &FeatureMap<E::Bit>,
+    window: usize,
+    stride: usize,
+    signed: bool,
+    eval: &E,
+) -> FeatureMap<E::Bit> {
+    assert!(window > 0, "pooling window must be positive");
+    let params = Conv2dParams { stride, padding: 0 };
+    let out_h = params.output_size(input.height, window);
+    let out_w = params.output_size(input.width, window);
+
+    let pixels = (0..out_h)
+        .flat_map(|oy| (0..out_w).map(move |ox| (oy, ox)))
+        .map(|(oy, ox)| {
+            let tile: Vec<Vec<E::Bit>> = (0..window)
+                .flat_map(|dy| (0..window).map(move |dx| (oy * stride + dy, ox * stride + dx)))
+                .map(|(y, x)| input.get(y, x).to_vec())
+                .collect();
+            IntegerOps::max_all(&tile, signed, eval)
+        })
+        .collect();
+
+    FeatureMap::new(out_h, out_w, pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    fn signed(bits: &[bool]) -> i64 {
+        let raw = bits.iter().enumerate().fold(0i64, |acc, (i, &b)| acc | ((b as i64) << i));
+        if bits.last() == Some(&true) { raw - (1 << bits.len()) } else { raw }
+    }
+
+    fn reference(image: &[Vec<i64>], kernel: &[Vec<i8>], params: Conv2dParams) -> Vec<i64> {
+        let (h, w) = (image.len(), image[0].len());
+        let (kh, kw) = (kernel.len(), kernel[0].len());
+        let mut out = Vec::new();
+        for oy in 0..params.output_size(h, kh) {
+            for ox in 0..params.output_size(w, kw) {
+                let mut sum = 0;
+                for (ky, weights) in kernel.iter().enumerate() {
+                    for (kx, &weight) in weights.iter().enumerate() {
+                        let y = (oy * params.stride + ky) as i64 - params.padding as i64;
+                        let x = (ox * params.stride + kx) as i64 - params.padding as i64;
+                        if y >= 0 && x >= 0 && (y as usize) < h && (x as usize) < w {
+                            sum += image[y as usize][x as usize] * weight as i64;
+                        }
+                    }
+                }
+                out.push(sum);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_conv2d_padding_and_stride() {
+        let image = vec![
+            vec![1, 4, 0, 2, 7],
+            vec![3, 3, 1, 0, 5],
+            vec![6, 0, 2, 2, 1],
+            vec![0, 5, 7, 1, 3],
+        ];
+        let kernel = vec![vec![1, -2, 1], vec![0, 3, -1]];
+
+        let eval = ClearEvaluator;
+        let pixels = image.iter().flatten().map(|&v| IntegerOps::constant(v as u64, 3, &eval)).collect();
+        let input = FeatureMap::new(4, 5, pixels);
+
+        for params in [
+            Conv2dParams::default(),
+            Conv2dParams { stride: 2, padding: 0 },
+            Conv2dParams { stride: 1, padding: 1 },
+            Conv2dParams { stride: 2, padding: 2 },
+        ] {
+            let output = conv2d(&input, &kernel, params, 8, &eval);
+            assert_eq!(output.height, params.output_size(4, 2));
+            assert_eq!(output.width, params.output_size(5, 3));
+
+            let values: Vec<i64> = output.pixels.iter().map(|p| signed(p)).collect();
+            assert_eq!(values, reference(&image, &kernel, params));
+        }
+    }
+
+    #[test]
+    fn test_max_pool2d() {
+        let eval = ClearEvaluator;
+        let values: [i64; 16] = [1, -3, 4, 0, 2, 5, -7, -1, -8, -2, 3, 3, -4, -6, 0, 6];
+        let pixels = values.iter().map(|&v| IntegerOps::constant(v as u64, 4, &eval)).collect();
+        let input = FeatureMap::new(4, 4, pixels);
+
+        let pooled = max_pool2d(&input, 2, 2, true, &eval);
+        let result: Vec<i64> = pooled.pixels.iter().map(|p| signed(p)).collect();
+        assert_eq!((pooled.height, pooled.width), (2, 2));
+        assert_eq!(result, vec![5, 4, -2, 6]);
+
+        let overlapping = max_pool2d(&input, 3, 1, true, &eval);
+        let result: Vec<i64> = overlapping.pixels.iter().map(|p| signed(p)).collect();
+        assert_eq!(result, vec![5, 5, 5, 6]);
+    }
+}
@@ -1,32 +1,239 @@
-use rand::rng;
-use rand_distr::{Distribution, Normal};
+#[cfg(feature = "client")]
+use rand::Rng;
+#[cfg(feature = "client")]
+use crate::torus::Torus;
+use crate::noise_model::{self, DECRYPTION_MARGIN, MARGIN_SIGMAS};
 
-pub fn gaussian_noise(stddev: f64) -> f64 {
-    let normal = Normal::new(0.0, stddev).unwrap();
-    let mut rng = rng();
-    normal.sample(&mut rng)
+/// Discrete Gaussian over the multiples of `2^-precision`, the noise of
+/// every encryption.
+///
+/// Draws an integer `k` uniformly within `TAIL_SIGMAS` deviations and keeps
+/// it with probability `exp(-k^2 / 2 sigma^2)`, compared as a 64-bit
+/// fixed-point threshold against a uniform `u64`. The sample lands on the
+/// torus exactly, without rounding an `f64`, and the number of rounds
+/// depends only on the random draws and the public deviation, never on the
+/// message or the key.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiscreteGaussian {
+    /// Deviation in units of `2^-precision`
+    sigma: f64,
+    precision: u32,
+    tail: i64,
 }
 
-pub fn gaussian_noise_vec(len: usize, stddev: f64) -> Vec<f64> {
-    (0..len).map(|_| gaussian_noise(stddev)).collect()
+#[cfg(feature = "client")]
+impl DiscreteGaussian {
+    /// Samples further out are never drawn; the mass there is below `2^-100`
+    pub const TAIL_SIGMAS: f64 = 12.0;
+
+    /// Torus noise of deviation `stddev` at the finest precision, up to 64
+    /// bits, whose tail fits an `i64`
+    pub fn new(stddev: f64) -> Self {
+        let precision = if stddev > 0.0 {
+            (61.0 - (Self::TAIL_SIGMAS * stddev).log2()).floor().clamp(0.0, 64.0) as u32
+        } else {
+            64
+        };
+        Self::with_precision(stddev, precision)
+    }
+
+    /// Torus noise on the multiples of `2^-precision`
+    pub fn with_precision(stddev: f64, precision: u32) -> Self {
+        assert!(precision <= 64, "precision is at most 64 bits");
+        assert!(stddev >= 0.0, "standard deviation must be non-negative");
+        let sigma = stddev * (precision as f64).exp2();
+        let tail = (Self::TAIL_SIGMAS * sigma).ceil();
+        assert!(tail < 62f64.exp2(), "a deviation of {} is too wide for {} bits of precision", stddev, precision);
+        DiscreteGaussian { sigma, precision, tail: tail as i64 }
+    }
+
+    /// Integer noise of deviation `stddev`
+    pub fn integer(stddev: f64) -> Self {
+        Self::with_precision(stddev, 0)
+    }
+
+    /// `k`, the sample being `k / 2^precision`
+    pub fn sample_int<R: Rng + ?Sized>(&self, rng: &mut R) -> i64 {
+        if self.tail == 0 {
+            return 0;
+        }
+        loop {
+            let k = rng.random_range(-self.tail..=self.tail);
+            let density = (-(k as f64).powi(2) / (2.0 * self.sigma * self.sigma)).exp();
+            // Saturates at u64::MAX for k = 0, which is then kept but for a 2^-64 chance
+            if rng.random::<u64>() < (density * 64f64.exp2()) as u64 {
+                return k;
+            }
+        }
+    }
+
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Torus {
+        let k = self.sample_int(rng) as u64;
+        Torus::from_fixed64(k.checked_shl(64 - self.precision).unwrap_or(0))
+    }
+}
+
+/// Noise a ciphertext has picked up since it was encrypted or bootstrapped,
+/// in multiples of the fresh encryption variance.
+///
+/// Linear operations grow it and bootstraps reset it to `Fresh`. Once it
+/// passes the budget of the parameters the level sticks at `NeedsBootstrap`
+/// and gates refuse the ciphertext. This is runtime bookkeeping and is not
+/// serialized: decoded samples start out `Fresh`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NoiseLevel {
+    /// Straight out of encryption or a bootstrap
+    #[default]
+    Fresh,
+    /// After linear operations that multiplied the fresh variance by `k`
+    PostLinear(u64),
+    /// Past the budget: decryption may fail, bootstrap before further use
+    NeedsBootstrap,
+}
+
+impl NoiseLevel {
+    /// Largest variance multiple that still leaves `MARGIN_SIGMAS` standard
+    /// deviations inside the decryption margin, `noise_model::max_variance`
+    /// in fresh variances
+    pub fn budget(stddev: f64) -> u64 {
+        if stddev <= 0.0 {
+            return u64::MAX;
+        }
+        (DECRYPTION_MARGIN / (MARGIN_SIGMAS * stddev)).powi(2).min(u64::MAX as f64) as u64
+    }
+
+    /// Multiple of the fresh variance, or `None` once past the budget
+    pub fn variance_factor(self) -> Option<u64> {
+        match self {
+            NoiseLevel::Fresh => Some(1),
+            NoiseLevel::PostLinear(k) => Some(k),
+            NoiseLevel::NeedsBootstrap => None,
+        }
+    }
+
+    pub fn needs_bootstrap(self) -> bool {
+        self == NoiseLevel::NeedsBootstrap
+    }
+
+    /// Level of a sum or difference of two ciphertexts
+    pub fn add(self, other: NoiseLevel, stddev: f64) -> NoiseLevel {
+        let factor = self.variance_factor()
+            .zip(other.variance_factor())
+            .and_then(|(a, b)| a.checked_add(b));
+        Self::within_budget(factor, stddev)
+    }
+
+    /// Level after multiplying by an integer, which scales the variance by its square
+    pub fn scale(self, scalar: i64, stddev: f64) -> NoiseLevel {
+        let square = scalar.unsigned_abs().checked_mul(scalar.unsigned_abs());
+        let factor = match self.variance_factor() {
+            // A noiseless sample stays noiseless whatever the scalar
+            Some(0) => Some(0),
+            k => k.zip(square).and_then(|(k, s)| k.checked_mul(s)),
+        };
+        Self::within_budget(factor, stddev)
+    }
+
+    fn within_budget(factor: Option<u64>, stddev: f64) -> NoiseLevel {
+        match factor {
+            Some(k) if k <= Self::budget(stddev) => NoiseLevel::PostLinear(k),
+            _ => NoiseLevel::NeedsBootstrap,
+        }
+    }
+}
+
+/// Bound on the variance of a ciphertext's error, propagated through each
+/// operation with the formulas of `noise_model`.
+///
+/// Unlike `NoiseLevel` it is absolute rather than a multiple of the fresh
+/// variance, so it follows samples across key switches and bootstraps
+/// whose output noise differs from that of an encryption. Decoded samples
+/// restart from the fresh variance of their parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NoiseEstimate {
+    pub variance: f64,
+}
+
+impl NoiseEstimate {
+    pub fn fresh(stddev: f64) -> Self {
+        NoiseEstimate { variance: noise_model::encryption(stddev) }
+    }
+
+    /// After multiplication by an integer
+    pub fn scale(self, scalar: i64) -> Self {
+        NoiseEstimate { variance: noise_model::scalar_mul(self.variance, scalar) }
+    }
+
+    /// After multiplication by an integer polynomial, whose coefficients
+    /// scale the variance by their squared norm
+    pub fn scale_poly(self, coeffs: &[i32]) -> Self {
+        let norm: f64 = coeffs.iter().map(|&c| (c as f64).powi(2)).sum();
+        NoiseEstimate { variance: norm * self.variance }
+    }
+
+    /// Plus the variance an operation adds on its own
+    pub fn with_added(self, variance: f64) -> Self {
+        NoiseEstimate { variance: self.variance + variance }
+    }
+
+    /// Chance the error alone makes decryption fail
+    pub fn failure_probability(self) -> f64 {
+        noise_model::failure_probability(self.variance)
+    }
+
+    /// Bits of standard deviation left before the error may leave the
+    /// decryption margin; negative once past it
+    pub fn budget_bits(self) -> f64 {
+        0.5 * (noise_model::max_variance() / self.variance).log2()
+    }
+}
+
+/// Sum or difference of independent samples
+impl std::ops::Add for NoiseEstimate {
+    type Output = NoiseEstimate;
+
+    fn add(self, other: NoiseEstimate) -> NoiseEstimate {
+        NoiseEstimate { variance: noise_model::addition(self.variance, other.variance) }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_discrete_gaussian() {
+        let mut rng = crate::entropy::seeded_entropy([3; 32]);
+        let sampler = DiscreteGaussian::integer(3.0);
+        let samples: Vec<i64> = (0..20000).map(|_| sampler.sample_int(&mut rng)).collect();
+        let mean = samples.iter().sum::<i64>() as f64 / samples.len() as f64;
+        let variance = samples.iter().map(|&k| (k as f64 - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+        assert!(mean.abs() < 0.1, "mean {}", mean);
+        assert!((variance - 9.0).abs() < 0.5, "variance {}", variance);
+        assert!(samples.iter().all(|k| k.abs() <= 36));
+
+        // Tiny deviations keep the full 64 bits, wide ones give some up
+        let torus = DiscreteGaussian::new(1e-9);
+        assert_eq!(torus.precision, 64);
+        let value = torus.sample(&mut rng).value();
+        assert!(value.min(1.0 - value) < 2e-8);
+        assert_eq!(DiscreteGaussian::new(0.1).precision, 60);
+        assert_eq!(DiscreteGaussian::new(0.0).sample(&mut rng), Torus::default());
+    }
+
     #[test]
-    fn test_gaussian_noise_distribution() {
-        let samples: Vec<f64> = (0..10000)
-            .map(|_| gaussian_noise(1.0))
-            .collect();
-        
-        let mean: f64 = samples.iter().sum::<f64>() / samples.len() as f64;
-        assert!(mean.abs() < 0.1);
-        
-        let within_3std = samples.iter()
-            .filter(|&&x| x.abs() < 3.0)
-            .count();
-        assert!(within_3std as f64 / samples.len() as f64 > 0.99);
+    fn test_noise_level_propagation() {
+        // Budget of 100 fresh variances, up to rounding
+        let stddev = DECRYPTION_MARGIN / (MARGIN_SIGMAS * 10.0);
+        assert!((99..=100).contains(&NoiseLevel::budget(stddev)));
+
+        let sum = NoiseLevel::Fresh.add(NoiseLevel::Fresh, stddev);
+        assert_eq!(sum, NoiseLevel::PostLinear(2));
+        assert_eq!(sum.scale(-7, stddev), NoiseLevel::PostLinear(98));
+        assert_eq!(sum.scale(8, stddev), NoiseLevel::NeedsBootstrap);
+        assert!(NoiseLevel::NeedsBootstrap.add(NoiseLevel::Fresh, stddev).needs_bootstrap());
+        assert_eq!(NoiseLevel::PostLinear(0).scale(1 << 40, stddev), NoiseLevel::PostLinear(0));
     }
 }
\ No newline at end of file
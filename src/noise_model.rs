@@ -0,0 +1,136 @@
+//! Closed-form noise variances of every TFHE operation.
+//!
+//! Each function gives the variance, on the torus, of the error in the
+//! output of one operation from the parameters and the variances of its
+//! inputs, following Chillotti et al. The parameter validator, the key
+//! switching and gadget advisors and `NoiseLevel`'s budget all draw on
+//! these, and they can be compared directly with the spread of measured
+//! phases, e.g. through `TlweSample::decrypt_phase` against the known
+//! message.
+
+use crate::tfhe::TfheParams;
+use crate::tgsw::TgswParams;
+use crate::tlwe::KeySwitchParams;
+
+/// Distance from a boolean encoding to the nearest decision boundary
+pub const DECRYPTION_MARGIN: f64 = 0.125;
+/// Standard deviations of noise that must fit in the margin
+pub const MARGIN_SIGMAS: f64 = 6.0;
+
+/// Largest variance that keeps `MARGIN_SIGMAS` standard deviations inside
+/// the decryption margin
+pub fn max_variance() -> f64 {
+    (DECRYPTION_MARGIN / MARGIN_SIGMAS).powi(2)
+}
+
+/// Probability that Gaussian noise of `variance` leaves the decryption
+/// margin, in either direction
+pub fn failure_probability(variance: f64) -> f64 {
+    if variance <= 0.0 {
+        return 0.0;
+    }
+    erfc(DECRYPTION_MARGIN / (2.0 * variance).sqrt())
+}
+
+// Complementary error function, Chebyshev fit with relative error below
+// 1.2e-7 everywhere, so tiny tail probabilities keep their magnitude
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398 + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let tail = t * poly.exp();
+    if x >= 0.0 { tail } else { 2.0 - tail }
+}
+
+/// Fresh encryption with Gaussian noise of `stddev`
+pub fn encryption(stddev: f64) -> f64 {
+    stddev.powi(2)
+}
+
+/// Sum or difference of two independent samples
+pub fn addition(a: f64, b: f64) -> f64 {
+    a + b
+}
+
+/// Multiplication by an integer
+pub fn scalar_mul(variance: f64, scalar: i64) -> f64 {
+    (scalar as f64).powi(2) * variance
+}
+
+/// Variance an external product with a key bit adds over rank-`k`
+/// polynomials of degree `big_n`: `(k+1) l N (Bg/2)^2 sigma^2` from the key
+/// noise plus `(1 + kN) / (2 Bg^l)^2` from the gadget rounding
+pub fn external_product(params: &TgswParams, big_n: usize, k: usize) -> f64 {
+    let (k, l, big_n) = (k as f64, params.l as f64, big_n as f64);
+    let bg = (params.bg_bit as f64).exp2();
+    let epsilon = 0.5 / bg.powi(params.l as i32);
+
+    let key_noise = (k + 1.0) * l * big_n * (bg / 2.0).powi(2) * params.tlwe_params.stddev.powi(2);
+    let rounding = (1.0 + k * big_n) * epsilon.powi(2);
+    key_noise + rounding
+}
+
+/// CMUX between inputs of variance `c0` and `c1`: the selected input's
+/// noise, bounded by the larger, plus one external product
+pub fn cmux(params: &TgswParams, big_n: usize, k: usize, c0: f64, c1: f64) -> f64 {
+    c0.max(c1) + external_product(params, big_n, k)
+}
+
+/// Variance a key switch from dimension `n_in` adds under an output key
+/// with noise `output_stddev`: every nonzero digit adds one key sample, and
+/// rounding each mask coefficient to `t * base_bit` bits adds a uniform
+/// error per coefficient
+pub fn key_switch(ks: &KeySwitchParams, n_in: usize, output_stddev: f64) -> f64 {
+    let base = (ks.base_bit as f64).exp2();
+    let digit_square = (base - 1.0) * (2.0 * base - 1.0) / 6.0;
+    let key_noise = n_in as f64 * ks.t as f64 * digit_square * output_stddev.powi(2);
+    let precision = (ks.t as u32 * ks.base_bit) as f64;
+    let rounding = n_in as f64 * (-2.0 * precision).exp2() / 12.0;
+    key_noise + rounding
+}
+
+/// Blind rotation: `n` CMUXes from a noiseless test polynomial
+pub fn blind_rotation(params: &TfheParams) -> f64 {
+    params.n as f64 * external_product(&params.tgsw_params, params.N, params.k)
+}
+
+/// Gate bootstrap: blind rotation, then the key switch back to `n` chosen
+/// by `TfheParams::key_switch_params`. Independent of the input noise.
+pub fn bootstrap(params: &TfheParams) -> f64 {
+    let ks = params.key_switch_params();
+    blind_rotation(params) + key_switch(&ks, params.k * params.N, params.tlwe_params.stddev)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tlwe::TlweParams;
+
+    #[test]
+    fn test_composed_formulas() {
+        let fresh = encryption(1e-3);
+        assert_eq!(addition(fresh, fresh), 2e-6);
+        assert_eq!(scalar_mul(fresh, -3), 9.0 * fresh);
+
+        let tgsw = TgswParams { l: 2, bg_bit: 8, tlwe_params: TlweParams { n: 1024, stddev: 1e-9 } };
+        let product = external_product(&tgsw, 1024, 1);
+        assert_eq!(cmux(&tgsw, 1024, 1, fresh, 0.0), fresh + product);
+        assert_eq!(product, tgsw.external_product_variance(1024, 1));
+
+        let params = TfheParams::default_128();
+        let rotation = blind_rotation(&params);
+        assert!(bootstrap(&params) > rotation);
+        assert!(bootstrap(&params) < max_variance());
+
+        // Six standard deviations in the margin fail about twice in 10^9
+        let p = failure_probability(max_variance());
+        assert!((p / 1.973e-9 - 1.0).abs() < 1e-3);
+        assert_eq!(failure_probability(0.0), 0.0);
+    }
+}
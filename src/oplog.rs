@@ -0,0 +1,349 @@
+//! Replayable logs of the primitive operations an evaluation performed.
+//!
+//! A `Recording` evaluator appends every gate it runs to an `OpLog`, with
+//! the canonical digests of its operands and of its result. The log is the
+//! circuit that was actually evaluated, so it serializes to a file like one;
+//! `OpLog::replay` re-executes it step by step against the same inputs and
+//! stops at the first result whose digest differs from the recorded one.
+//! Gates are deterministic once the keys are fixed, so a production
+//! evaluation that misbehaved can be reproduced and bisected offline.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::canonical::Canonical;
+use crate::codec::{CodecError, Encoded, Json};
+use crate::circuit::{Node, Wire};
+use crate::evaluator::BooleanEvaluator;
+
+/// One step: the node, in terms of earlier steps, and the digests it saw
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpRecord {
+    pub node: Node,
+    /// Hex SHA-256 of the canonical bytes of each operand
+    pub operands: Vec<String>,
+    pub result: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpLog {
+    /// Indexed by `Wire`; inputs and constants are steps too
+    pub steps: Vec<OpRecord>,
+    pub outputs: Vec<Wire>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    InputCount { expected: usize, found: usize },
+    /// Input `index` is not the ciphertext that was recorded
+    InputMismatch { index: usize },
+    /// Step `step` produced a different result than when it was recorded
+    Diverged { step: usize, gate: &'static str, expected: String, found: String },
+    /// Step `step` reads `wire`, which is not an earlier step; the log is
+    /// malformed
+    BadWire { step: usize, wire: Wire },
+    /// Step `step` reads input `index`, past the inputs of the log
+    BadInput { step: usize, index: usize },
+    /// An output names `wire`, which is not a step of the log
+    BadOutput { wire: Wire },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::InputCount { expected, found } => {
+                write!(f, "log was recorded with {} inputs, got {}", expected, found)
+            }
+            ReplayError::InputMismatch { index } => write!(f, "input {} differs from the recorded one", index),
+            ReplayError::Diverged { step, gate, expected, found } => write!(
+                f,
+                "{} gate at step {} produced {} instead of the recorded {}",
+                gate, step, found, expected,
+            ),
+            ReplayError::BadWire { step, wire } => write!(f, "step {} reads wire {}, not an earlier step", step, wire.0),
+            ReplayError::BadInput { step, index } => write!(f, "step {} reads input {}, past the inputs", step, index),
+            ReplayError::BadOutput { wire } => write!(f, "output wire {} is not a step of the log", wire.0),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+fn digest<T: Canonical>(value: &T) -> String {
+    value.canonical_digest().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl OpLog {
+    pub fn num_inputs(&self) -> usize {
+        self.steps.iter().filter(|s| matches!(s.node, Node::Input(_))).count()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("op log serializes to JSON")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, CodecError> {
+        Self::from_bytes_with::<Json>(json.as_bytes())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Every wire a step or an output reads refers to an earlier step, and
+    /// every input index to one of the inputs. Logs are loaded from files,
+    /// so `replay` checks this before running anything.
+    pub fn validate(&self) -> Result<(), ReplayError> {
+        let inputs = self.num_inputs();
+        for (step, record) in self.steps.iter().enumerate() {
+            match record.node {
+                Node::Input(index) if index >= inputs => return Err(ReplayError::BadInput { step, index }),
+                _ => {}
+            }
+            if let Some(&wire) = record.node.operands().iter().find(|w| w.0 >= step) {
+                return Err(ReplayError::BadWire { step, wire });
+            }
+        }
+        match self.outputs.iter().find(|w| w.0 >= self.steps.len()) {
+            Some(&wire) => Err(ReplayError::BadOutput { wire }),
+            None => Ok(()),
+        }
+    }
+
+    /// Re-executes every step on `eval`, checking each result against its
+    /// recorded digest, and returns the outputs
+    pub fn replay<E>(&self, eval: &E, inputs: &[E::Bit]) -> Result<Vec<E::Bit>, ReplayError>
+    where
+        E: BooleanEvaluator,
+        E::Bit: Canonical,
+    {
+        if inputs.len() != self.num_inputs() {
+            return Err(ReplayError::InputCount { expected: self.num_inputs(), found: inputs.len() });
+        }
+        self.validate()?;
+
+        let mut values: Vec<E::Bit> = Vec::with_capacity(self.steps.len());
+        for (step, record) in self.steps.iter().enumerate() {
+            let get = |w: Wire| &values[w.0];
+            let value = match record.node {
+                Node::Input(k) => inputs[k].clone(),
+                Node::Constant(v) => eval.constant(v),
+                Node::Not(a) => eval.not(get(a)),
+                Node::And(a, b) => eval.and(get(a), get(b)),
+                Node::Or(a, b) => eval.or(get(a), get(b)),
+                Node::Xor(a, b) => eval.xor(get(a), get(b)),
+                Node::Nand(a, b) => eval.nand(get(a), get(b)),
+                Node::Mux(s, a, b) => eval.mux(get(s), get(a), get(b)),
+            };
+
+            let found = digest(&value);
+            if found != record.result {
+                return Err(match record.node {
+                    Node::Input(index) => ReplayError::InputMismatch { index },
+                    _ => ReplayError::Diverged { step, gate: record.node.name(), expected: record.result.clone(), found },
+                });
+            }
+            values.push(value);
+        }
+
+        Ok(self.outputs.iter().map(|w| values[w.0].clone()).collect())
+    }
+}
+
+/// A bit of the wrapped evaluator and the step that produced it
+#[derive(Debug, Clone)]
+pub struct Recorded<B> {
+    pub bit: B,
+    pub wire: Wire,
+}
+
+/// Evaluator appending every gate it runs to an `OpLog`
+pub struct Recording<E: BooleanEvaluator> {
+    eval: E,
+    log: Mutex<OpLog>,
+}
+
+impl<E: BooleanEvaluator> Recording<E>
+where
+    E::Bit: Canonical,
+{
+    pub fn new(eval: E) -> Self {
+        Recording { eval, log: Mutex::new(OpLog::default()) }
+    }
+
+    /// Registers the next input of the evaluation
+    pub fn input(&self, bit: E::Bit) -> Recorded<E::Bit> {
+        let mut log = self.log.lock().unwrap();
+        let node = Node::Input(log.num_inputs());
+        Self::append(&mut log, node, bit, &[])
+    }
+
+    pub fn inputs(&self, bits: Vec<E::Bit>) -> Vec<Recorded<E::Bit>> {
+        bits.into_iter().map(|bit| self.input(bit)).collect()
+    }
+
+    /// The log so far, with `outputs` as its results
+    pub fn finish(self, outputs: &[Recorded<E::Bit>]) -> OpLog {
+        let mut log = self.log.into_inner().unwrap();
+        log.outputs = outputs.iter().map(|r| r.wire).collect();
+        log
+    }
+
+    fn push(&self, node: Node, bit: E::Bit, operands: &[&Recorded<E::Bit>]) -> Recorded<E::Bit> {
+        Self::append(&mut self.log.lock().unwrap(), node, bit, operands)
+    }
+
+    fn append(log: &mut OpLog, node: Node, bit: E::Bit, operands: &[&Recorded<E::Bit>]) -> Recorded<E::Bit> {
+        log.steps.push(OpRecord {
+            node,
+            operands: operands.iter().map(|r| digest(&r.bit)).collect(),
+            result: digest(&bit),
+        });
+        Recorded { bit, wire: Wire(log.steps.len() - 1) }
+    }
+}
+
+impl<E: BooleanEvaluator> BooleanEvaluator for Recording<E>
+where
+    E::Bit: Canonical,
+{
+    type Bit = Recorded<E::Bit>;
+
+    fn constant(&self, value: bool) -> Self::Bit {
+        self.push(Node::Constant(value), self.eval.constant(value), &[])
+    }
+
+    fn not(&self, a: &Self::Bit) -> Self::Bit {
+        self.push(Node::Not(a.wire), self.eval.not(&a.bit), &[a])
+    }
+
+    fn and(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.push(Node::And(a.wire, b.wire), self.eval.and(&a.bit, &b.bit), &[a, b])
+    }
+
+    fn or(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.push(Node::Or(a.wire, b.wire), self.eval.or(&a.bit, &b.bit), &[a, b])
+    }
+
+    fn xor(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.push(Node::Xor(a.wire, b.wire), self.eval.xor(&a.bit, &b.bit), &[a, b])
+    }
+
+    fn nand(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.push(Node::Nand(a.wire, b.wire), self.eval.nand(&a.bit, &b.bit), &[a, b])
+    }
+
+    fn mux(&self, s: &Self::Bit, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.push(Node::Mux(s.wire, a.wire, b.wire), self.eval.mux(&s.bit, &a.bit, &b.bit), &[s, a, b])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+    use crate::integer::IntegerOps;
+
+    // Clear evaluation with a flaky XOR, as a stand-in for a production bug
+    struct FlakyXor;
+
+    impl BooleanEvaluator for FlakyXor {
+        type Bit = bool;
+
+        fn constant(&self, value: bool) -> bool {
+            value
+        }
+
+        fn not(&self, a: &bool) -> bool {
+            !a
+        }
+
+        fn and(&self, a: &bool, b: &bool) -> bool {
+            a & b
+        }
+
+        fn or(&self, a: &bool, b: &bool) -> bool {
+            a | b
+        }
+
+        fn xor(&self, a: &bool, b: &bool) -> bool {
+            a | b
+        }
+
+        fn nand(&self, a: &bool, b: &bool) -> bool {
+            !(a & b)
+        }
+
+        fn mux(&self, s: &bool, a: &bool, b: &bool) -> bool {
+            if *s { *a } else { *b }
+        }
+    }
+
+    fn record_sum(x: u64, y: u64) -> (OpLog, Vec<bool>) {
+        let eval = Recording::new(ClearEvaluator);
+        let a = eval.inputs(IntegerOps::constant(x, 4, &ClearEvaluator));
+        let b = eval.inputs(IntegerOps::constant(y, 4, &ClearEvaluator));
+        let inputs = a.iter().chain(b.iter()).map(|r| r.bit).collect();
+        let sum = IntegerOps::add_mod(&a, &b, &eval);
+        (eval.finish(&sum), inputs)
+    }
+
+    #[test]
+    fn test_record_save_and_replay() {
+        let (log, inputs) = record_sum(6, 7);
+        assert_eq!(log.num_inputs(), 8);
+        assert!(log.steps.iter().any(|s| matches!(s.node, Node::Xor(..)) && s.operands.len() == 2));
+
+        let path = std::env::temp_dir().join(format!("ghost-oplog-{}.json", std::process::id()));
+        log.save(&path).unwrap();
+        let loaded = OpLog::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, log);
+
+        let outputs = loaded.replay(&ClearEvaluator, &inputs).unwrap();
+        assert_eq!(outputs, IntegerOps::constant(13, 4, &ClearEvaluator));
+    }
+
+    #[test]
+    fn test_replay_reports_first_divergence() {
+        let (log, inputs) = record_sum(6, 7);
+        let first_xor = log.steps.iter().position(|s| matches!(s.node, Node::Xor(..))).unwrap();
+        match log.replay(&FlakyXor, &inputs) {
+            Err(ReplayError::Diverged { step, gate: "XOR", .. }) => assert!(step >= first_xor),
+            other => panic!("expected a divergence, got {:?}", other),
+        }
+
+        let mut other_inputs = inputs.clone();
+        other_inputs[2] = !other_inputs[2];
+        assert_eq!(log.replay(&ClearEvaluator, &other_inputs), Err(ReplayError::InputMismatch { index: 2 }));
+        assert_eq!(
+            log.replay(&ClearEvaluator, &inputs[1..]),
+            Err(ReplayError::InputCount { expected: 8, found: 7 }),
+        );
+    }
+
+    #[test]
+    fn test_replay_rejects_malformed_logs() {
+        let (log, inputs) = record_sum(6, 7);
+        let gate = log.steps.iter().position(|s| matches!(s.node, Node::Xor(..))).unwrap();
+
+        let mut forward = log.clone();
+        forward.steps[gate].node = Node::Xor(Wire(0), Wire(gate));
+        assert_eq!(forward.replay(&ClearEvaluator, &inputs), Err(ReplayError::BadWire { step: gate, wire: Wire(gate) }));
+
+        let mut input = log.clone();
+        input.steps[0].node = Node::Input(8);
+        assert_eq!(input.replay(&ClearEvaluator, &inputs), Err(ReplayError::BadInput { step: 0, index: 8 }));
+
+        let mut output = log.clone();
+        output.outputs.push(Wire(log.steps.len()));
+        assert_eq!(output.replay(&ClearEvaluator, &inputs), Err(ReplayError::BadOutput { wire: Wire(log.steps.len()) }));
+    }
+}
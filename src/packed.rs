@@ -0,0 +1,210 @@
+//! Bits packed into the coefficients of one TRLWE sample.
+//!
+//! Slot `i` of a packed sample holds a bit in the gate encoding (`0.125`
+//! for false, `0.375` for true) at coefficient `i`, so adding two packed
+//! samples combines up to `N` pairs of bits with one polynomial operation.
+//! The linear part of a packed gate therefore runs once per polynomial.
+//! Refreshing the noise still takes a blind rotation per slot: each of the
+//! first `len` slots is extracted, bootstrapped as an LWE sample, and the
+//! results are packed back into one TRLWE sample under the ring key by a
+//! `PackingKey`, a key switching key whose rows are TRLWE encryptions of
+//! the LWE key. Slots from `len` on come out empty.
+//!
+//! NOT is linear; AND, OR and XOR take the same combinations as their
+//! `TfheGates` counterparts and one blind rotation per slot in use. Every
+//! refresh runs the slots through `TfheGates::bootstrap_batch`.
+
+#[cfg(any(feature = "client", feature = "server"))]
+use crate::torus::Torus;
+#[cfg(any(feature = "client", feature = "server"))]
+use crate::trlwe::TorusPolynomial;
+use crate::trlwe::{TrlweParams, TrlweSample};
+#[cfg(feature = "client")]
+use crate::tfhe::TfheSecretKey;
+#[cfg(feature = "client")]
+use crate::tfhe::TfheEncoder;
+#[cfg(feature = "client")]
+use crate::tfhe::bool_phase;
+#[cfg(feature = "server")]
+use crate::tfhe::{gate_lut, TfheCloudKey, TfheGates};
+#[cfg(feature = "server")]
+use crate::tlwe::TlweSample;
+#[cfg(feature = "client")]
+use crate::tlwe::KeySwitchParams;
+#[cfg(feature = "client")]
+use crate::entropy::{default_entropy, EntropySource};
+
+/// Key switching key from the LWE key of gate outputs to the ring key:
+/// row `(i, j)` encrypts the constant polynomial `s_i / B^(j+1)`
+#[derive(Debug, Clone)]
+pub struct PackingKey {
+    pub samples: Vec<Vec<TrlweSample>>,
+    pub t: usize,
+    pub base_bit: u32,
+    pub ring_params: TrlweParams,
+}
+
+#[cfg(feature = "client")]
+impl PackingKey {
+    pub fn generate(sk: &TfheSecretKey, ks: KeySwitchParams) -> Self {
+        Self::generate_with_rng(sk, ks, &mut default_entropy())
+    }
+
+    pub fn generate_with_rng<R: EntropySource + ?Sized>(sk: &TfheSecretKey, ks: KeySwitchParams, rng: &mut R) -> Self {
+        let ring = &sk.trlwe_key.params;
+        let samples = sk.tlwe_key.coeffs.iter()
+            .map(|&s| {
+                (0..ks.t)
+                    .map(|j| {
+                        let weight = Torus::new((-(((j as u32 + 1) * ks.base_bit) as f64)).exp2());
+                        let mut message = TorusPolynomial::zero(ring.N);
+                        message.coeffs[0] = weight.mul_int(s as i64);
+                        TrlweSample::encrypt_with_rng(&message, &sk.trlwe_key, rng)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        PackingKey { samples, t: ks.t, base_bit: ks.base_bit, ring_params: ring.clone() }
+    }
+}
+
+#[cfg(feature = "server")]
+impl PackingKey {
+    /// Packs up to `N` LWE samples into the slots of one TRLWE sample, slot
+    /// `p` holding the message of `samples[p]`. The mask of every sample is
+    /// decomposed like `TlweKeySwitchKey::key_switch` does; digit `j` of
+    /// coefficient `i` across all samples forms one integer polynomial, so
+    /// each key row is multiplied once per pack.
+    pub fn pack(&self, samples: &[TlweSample]) -> TrlweSample {
+        let ring = &self.ring_params;
+        assert!(samples.len() <= ring.N, "{} samples do not fit {} slots", samples.len(), ring.N);
+        let precision = self.t as u32 * self.base_bit;
+        let base_mask = (1u64 << self.base_bit) - 1;
+
+        let mut body = TorusPolynomial::zero(ring.N);
+        for (p, sample) in samples.iter().enumerate() {
+            assert_eq!(sample.a.len(), self.samples.len(), "sample dimension does not match the packing key");
+            body.coeffs[p] = sample.b;
+        }
+        let mut result = TrlweSample::trivial(&body, ring.clone());
+
+        for (i, row) in self.samples.iter().enumerate() {
+            let rounded: Vec<u64> = samples.iter().map(|s| s.a[i].rounded_bits(precision)).collect();
+            for (j, key) in row.iter().enumerate() {
+                let shift = precision - (j as u32 + 1) * self.base_bit;
+                let mut digits = vec![0i32; ring.N];
+                for (digit, &r) in digits.iter_mut().zip(rounded.iter()) {
+                    *digit = ((r >> shift) & base_mask) as i32;
+                }
+                if digits.iter().all(|&d| d == 0) {
+                    continue;
+                }
+                let scaled = TrlweSample {
+                    a: key.a.iter().map(|p| p.mul_int(&digits)).collect(),
+                    b: key.b.mul_int(&digits),
+                    params: ring.clone(),
+                    noise: key.noise,
+                    estimate: key.estimate.scale_poly(&digits),
+                };
+                result = result.sub(&scaled);
+            }
+        }
+        result
+    }
+}
+
+// Constant `value` in every slot
+#[cfg(feature = "server")]
+fn constant(value: f64, ring: &TrlweParams) -> TrlweSample {
+    let poly = TorusPolynomial { coeffs: vec![Torus::new(value); ring.N] };
+    TrlweSample::trivial(&poly, ring.clone())
+}
+
+#[cfg(feature = "client")]
+impl TfheEncoder {
+    /// `bits` in the first slots of a sample under the ring key; the
+    /// remaining slots hold false
+    pub fn encode_packed(bits: &[bool], sk: &TfheSecretKey) -> TrlweSample {
+        let ring = &sk.trlwe_key.params;
+        assert!(bits.len() <= ring.N, "{} bits do not fit {} slots", bits.len(), ring.N);
+        let mut message = TorusPolynomial { coeffs: vec![bool_phase(false); ring.N] };
+        for (c, &bit) in message.coeffs.iter_mut().zip(bits) {
+            *c = bool_phase(bit);
+        }
+        TrlweSample::encrypt(&message, &sk.trlwe_key)
+    }
+
+    /// The first `len` slots
+    pub fn decode_packed(sample: &TrlweSample, len: usize, sk: &TfheSecretKey) -> Vec<bool> {
+        let phase = sample.decrypt_phase(&sk.trlwe_key);
+        phase.coeffs[..len].iter().map(|c| (c.value() - 0.5).abs() < 0.25).collect()
+    }
+}
+
+#[cfg(feature = "server")]
+impl TfheGates {
+    /// Slot-wise NOT; linear, so no bootstrap
+    pub fn not_packed(a: &TrlweSample) -> TrlweSample {
+        constant(0.5, &a.params).sub(a)
+    }
+
+    /// Slot-wise XOR of the first `len` slots: `2 (a + b) + 3/4`, as for
+    /// `TfheGates::xor`
+    pub fn xor_packed(a: &TrlweSample, b: &TrlweSample, len: usize, ck: &TfheCloudKey, pk: &PackingKey) -> TrlweSample {
+        let sum = a.add(b);
+        Self::gate_packed("xor", &sum.add(&sum).add(&constant(0.75, &a.params)), len, ck, pk)
+    }
+
+    /// Slot-wise AND of the first `len` slots: `a + b - 1/8`
+    pub fn and_packed(a: &TrlweSample, b: &TrlweSample, len: usize, ck: &TfheCloudKey, pk: &PackingKey) -> TrlweSample {
+        Self::gate_packed("and", &a.add(b).sub(&constant(0.125, &a.params)), len, ck, pk)
+    }
+
+    /// Slot-wise OR of the first `len` slots: `a + b + 1/8`
+    pub fn or_packed(a: &TrlweSample, b: &TrlweSample, len: usize, ck: &TfheCloudKey, pk: &PackingKey) -> TrlweSample {
+        Self::gate_packed("or", &a.add(b).add(&constant(0.125, &a.params)), len, ck, pk)
+    }
+
+    // The first `len` slots of a combined sample through the gate test vector
+    fn gate_packed(gate: &'static str, combined: &TrlweSample, len: usize, ck: &TfheCloudKey, pk: &PackingKey) -> TrlweSample {
+        pk.pack(&Self::bootstrap_batch(gate, &Self::slots(combined, len, ck, pk), &gate_lut(), ck))
+    }
+
+    // The first `len` slots as LWE samples under the gate key
+    fn slots(sample: &TrlweSample, len: usize, ck: &TfheCloudKey, pk: &PackingKey) -> Vec<TlweSample> {
+        assert_eq!(sample.params, pk.ring_params, "packed sample and packing key have different rings");
+        assert!(len <= sample.params.N, "{} slots do not fit {} coefficients", len, sample.params.N);
+        (0..len)
+            .map(|i| Self::key_switch(&TlweSample::extract_from_trlwe(sample, i), ck))
+            .collect()
+    }
+}
+
+#[cfg(all(test, feature = "client", feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::tfhe::fixtures::small_keys;
+
+    #[test]
+    fn test_packed_gates() {
+        let (sk, ck) = small_keys(16);
+        let pk = PackingKey::generate(&sk, sk.params.key_switch_params());
+
+        let xs = [false, false, true, true];
+        let ys = [false, true, false, true];
+        let a = TfheEncoder::encode_packed(&xs, &sk);
+        let b = TfheEncoder::encode_packed(&ys, &sk);
+        assert_eq!(TfheEncoder::decode_packed(&a, 4, &sk), xs);
+
+        let expect = |f: fn(bool, bool) -> bool| xs.iter().zip(&ys).map(|(&x, &y)| f(x, y)).collect::<Vec<_>>();
+        let xor = TfheGates::xor_packed(&a, &b, 4, &ck, &pk);
+        assert_eq!(TfheEncoder::decode_packed(&xor, 4, &sk), expect(|x, y| x ^ y));
+        let and = TfheGates::and_packed(&a, &b, 4, &ck, &pk);
+        assert_eq!(TfheEncoder::decode_packed(&and, 4, &sk), expect(|x, y| x & y));
+        let or = TfheGates::or_packed(&a, &b, 4, &ck, &pk);
+        assert_eq!(TfheEncoder::decode_packed(&or, 4, &sk), expect(|x, y| x | y));
+        let not = TfheGates::not_packed(&or);
+        assert_eq!(TfheEncoder::decode_packed(&not, 4, &sk), expect(|x, y| !(x | y)));
+    }
+}
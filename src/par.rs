@@ -0,0 +1,156 @@
+//! Rayon integration for encrypted rows and words.
+//!
+//! Encrypted bits are plain data, so rayon already spreads `par_iter` over
+//! them; what a closure is missing is an evaluator and the constants every
+//! circuit reaches for. `ParRows` hands each rayon job one `ThreadContext`,
+//! built once per job by `map_init` rather than once per row, that
+//! evaluates through the shared evaluator and serves `constant` from its
+//! own copies, so hot loops never rebuild trivial ciphertexts. Rows come
+//! from a slice of vectors or from one flat slice cut into words of a fixed
+//! width, and `with_chunk_size` keeps a job from splitting below a minimum
+//! number of rows when each row is cheap.
+
+use rayon::prelude::*;
+use crate::evaluator::BooleanEvaluator;
+
+/// Evaluator of one rayon job, with both constants cached
+pub struct ThreadContext<'e, E: BooleanEvaluator> {
+    pub eval: &'e E,
+    constants: [E::Bit; 2],
+}
+
+impl<'e, E: BooleanEvaluator> ThreadContext<'e, E> {
+    pub fn new(eval: &'e E) -> Self {
+        ThreadContext { eval, constants: [eval.constant(false), eval.constant(true)] }
+    }
+}
+
+impl<E: BooleanEvaluator> BooleanEvaluator for ThreadContext<'_, E> {
+    type Bit = E::Bit;
+
+    fn constant(&self, value: bool) -> Self::Bit {
+        self.constants[value as usize].clone()
+    }
+
+    fn not(&self, a: &Self::Bit) -> Self::Bit {
+        self.eval.not(a)
+    }
+
+    fn and(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.eval.and(a, b)
+    }
+
+    fn or(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.eval.or(a, b)
+    }
+
+    fn xor(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.eval.xor(a, b)
+    }
+
+    fn nand(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.eval.nand(a, b)
+    }
+
+    fn mux(&self, s: &Self::Bit, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.eval.mux(s, a, b)
+    }
+}
+
+/// Encrypted rows to evaluate in parallel
+#[derive(Debug, Clone)]
+pub struct ParRows<'a, B> {
+    rows: Vec<&'a [B]>,
+    min_len: usize,
+}
+
+impl<'a, B: Sync> ParRows<'a, B> {
+    pub fn new(rows: &'a [Vec<B>]) -> Self {
+        ParRows { rows: rows.iter().map(Vec::as_slice).collect(), min_len: 1 }
+    }
+
+    /// Consecutive `width`-bit words of `bits`, as stored by `FheUint` vectors
+    pub fn words(bits: &'a [B], width: usize) -> Self {
+        assert!(
+            width > 0 && bits.len().is_multiple_of(width),
+            "{} bits do not split into {}-bit words",
+            bits.len(),
+            width,
+        );
+        ParRows { rows: bits.chunks_exact(width).collect(), min_len: 1 }
+    }
+
+    /// Rayon jobs take at least `rows` rows each
+    pub fn with_chunk_size(mut self, rows: usize) -> Self {
+        self.min_len = rows.max(1);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// `f` of every row, in row order, with a context per rayon job
+    pub fn map<E, F, R>(self, eval: &E, f: F) -> Vec<R>
+    where
+        E: BooleanEvaluator + Sync,
+        F: Fn(&ThreadContext<'_, E>, &[B]) -> R + Sync + Send,
+        R: Send,
+    {
+        self.into_par_iter()
+            .map_init(|| ThreadContext::new(eval), |ctx, row| f(ctx, row))
+            .collect()
+    }
+}
+
+impl<'a, B: Sync + 'a> IntoParallelIterator for ParRows<'a, B> {
+    type Iter = rayon::iter::MinLen<rayon::vec::IntoIter<&'a [B]>>;
+    type Item = &'a [B];
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.rows.into_par_iter().with_min_len(self.min_len)
+    }
+}
+
+/// `par_rows` on slices of encrypted rows
+pub trait ParEncrypted<B> {
+    fn par_rows(&self) -> ParRows<'_, B>;
+}
+
+impl<B: Sync> ParEncrypted<B> for [Vec<B>] {
+    fn par_rows(&self) -> ParRows<'_, B> {
+        ParRows::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+    use crate::integer::{CmpOp, IntegerOps};
+
+    #[test]
+    fn test_parallel_rows_match_sequential() {
+        let eval = ClearEvaluator;
+        let rows: Vec<Vec<bool>> = (0..200u64).map(|v| IntegerOps::constant(v % 37, 6, &eval)).collect();
+        let predicate = |ctx: &ThreadContext<'_, ClearEvaluator>, row: &[bool]| {
+            let limit = IntegerOps::constant(20, 6, ctx);
+            IntegerOps::compare(row, &limit, CmpOp::Lt, ctx)
+        };
+
+        let parallel = rows.par_rows().with_chunk_size(16).map(&eval, predicate);
+        let expected: Vec<bool> = (0..200u64).map(|v| v % 37 < 20).collect();
+        assert_eq!(parallel, expected);
+
+        let flat: Vec<bool> = rows.concat();
+        let words = ParRows::words(&flat, 6);
+        assert_eq!(words.len(), 200);
+        assert_eq!(words.map(&eval, predicate), expected);
+        let widths: Vec<usize> = rows.par_rows().into_par_iter().map(<[bool]>::len).collect();
+        assert_eq!(widths, vec![6; 200]);
+    }
+}
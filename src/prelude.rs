@@ -0,0 +1,25 @@
+//! Everything an application needs to encrypt, evaluate and decrypt,
+//! without touching the torus/TLWE/TGSW internals.
+//!
+//! ```
+//! use ghost_tfhe::prelude::*;
+//!
+//! let config = ConfigBuilder::default().lwe_dimension(10).noise_stddev(1e-9).build();
+//! let (client_key, server_key) = generate_keys(config);
+//! set_server_key(server_key);
+//!
+//! let x = client_key.encrypt_uint(42, 8);
+//! assert_eq!(client_key.decrypt_uint(&x), 42);
+//! ```
+
+pub use crate::api::{Config, ConfigBuilder, FheBool, FheFloat, FheUint, ServerKey};
+#[cfg(feature = "client")]
+pub use crate::api::{generate_keys, ClientKey};
+#[cfg(feature = "bigint")]
+pub use crate::api::FheBigUint;
+#[cfg(feature = "server")]
+pub use crate::api::{set_server_key, unset_server_key};
+pub use crate::circuit::Circuit;
+pub use crate::evaluator::{BooleanEvaluator, ClearEvaluator};
+#[cfg(feature = "server")]
+pub use crate::evaluator::TfheEvaluator;
@@ -0,0 +1,316 @@
+//! Per-machine evaluation profile: measured bootstrap latency and thread
+//! scaling, and the integer strategies and chunk size they favour.
+//!
+//! Which adder or multiplier wins depends on how many bootstraps run at
+//! once: ripple-carry spends the fewest gates, the parallel-prefix and
+//! carry-save variants trade extra gates for logarithmic depth. Each
+//! strategy's bootstrap count and critical path are counted symbolically
+//! and weighed with the measured latency and speedup. Profiles serialize to
+//! JSON so a calibration can be reused across runs on the same machine.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::circuit::{Node, Wire};
+use crate::codec::{CodecError, Encoded, Json};
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::IntegerOps;
+
+/// Work a parallel task should carry so scheduling overhead stays negligible
+const TASK_TARGET: Duration = Duration::from_millis(1);
+const MAX_CHUNK_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdderStrategy {
+    /// `IntegerOps::add`: fewest gates, linear depth
+    #[default]
+    RippleCarry,
+    /// `IntegerOps::add_prefix`: logarithmic depth
+    ParallelPrefix,
+}
+
+impl AdderStrategy {
+    pub const ALL: [AdderStrategy; 2] = [AdderStrategy::RippleCarry, AdderStrategy::ParallelPrefix];
+
+    /// `a + b` wrapping within the width of `a`
+    pub fn add_mod<E: BooleanEvaluator>(self, a: &[E::Bit], b: &[E::Bit], eval: &E) -> Vec<E::Bit> {
+        let mut sum = match self {
+            AdderStrategy::RippleCarry => IntegerOps::add(a, b, eval),
+            AdderStrategy::ParallelPrefix => IntegerOps::add_prefix(a, b, eval),
+        };
+        sum.pop();
+        sum
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MultiplierStrategy {
+    /// `IntegerOps::mul`
+    #[default]
+    ShiftAdd,
+    /// `IntegerOps::mul_carry_save`
+    CarrySave,
+}
+
+impl MultiplierStrategy {
+    pub const ALL: [MultiplierStrategy; 2] = [MultiplierStrategy::ShiftAdd, MultiplierStrategy::CarrySave];
+
+    /// Product modulo `2^width`
+    pub fn mul<E: BooleanEvaluator>(self, a: &[E::Bit], b: &[E::Bit], width: usize, eval: &E) -> Vec<E::Bit> {
+        match self {
+            MultiplierStrategy::ShiftAdd => IntegerOps::mul(a, b, width, eval),
+            MultiplierStrategy::CarrySave => IntegerOps::mul_carry_save(a, b, width, eval),
+        }
+    }
+}
+
+/// Calibration result for one machine and parameter set
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalProfile {
+    /// Mean time of one bootstrapped gate
+    pub bootstrap_latency: Duration,
+    pub threads: usize,
+    /// Measured speedup of `threads` workers over one on independent gates
+    pub parallel_speedup: f64,
+    /// Gates per parallel task
+    pub chunk_size: usize,
+    pub adder: AdderStrategy,
+    pub multiplier: MultiplierStrategy,
+}
+
+impl Default for EvalProfile {
+    /// Sequential defaults, as used without calibration
+    fn default() -> Self {
+        EvalProfile {
+            bootstrap_latency: Duration::ZERO,
+            threads: 1,
+            parallel_speedup: 1.0,
+            chunk_size: 1,
+            adder: AdderStrategy::default(),
+            multiplier: MultiplierStrategy::default(),
+        }
+    }
+}
+
+impl EvalProfile {
+    /// Times `rounds` gates on `a` and `b`, which must be real ciphertexts
+    /// since gates on trivial operands skip the bootstrap, measures thread
+    /// scaling with the `parallel` feature and picks the strategies that
+    /// are fastest on `width`-bit operands
+    pub fn calibrate<E>(eval: &E, a: &E::Bit, b: &E::Bit, width: usize, rounds: usize) -> Self
+    where
+        E: BooleanEvaluator + Sync,
+        E::Bit: Send + Sync,
+    {
+        let rounds = rounds.max(1);
+        let start = Instant::now();
+        for _ in 0..rounds {
+            eval.nand(a, b);
+        }
+        let bootstrap_latency = start.elapsed() / rounds as u32;
+
+        let (threads, parallel_speedup) = Self::measure_scaling(eval, a, b, rounds);
+        let chunk_size = match bootstrap_latency.as_nanos() {
+            0 => MAX_CHUNK_SIZE,
+            nanos => (TASK_TARGET.as_nanos().div_ceil(nanos) as usize).clamp(1, MAX_CHUNK_SIZE),
+        };
+
+        let mut profile = EvalProfile {
+            bootstrap_latency,
+            threads,
+            parallel_speedup,
+            chunk_size,
+            ..EvalProfile::default()
+        };
+        profile.select_strategies(width);
+        profile
+    }
+
+    #[cfg(feature = "parallel")]
+    fn measure_scaling<E>(eval: &E, a: &E::Bit, b: &E::Bit, rounds: usize) -> (usize, f64)
+    where
+        E: BooleanEvaluator + Sync,
+        E::Bit: Send + Sync,
+    {
+        use rayon::prelude::*;
+
+        let threads = rayon::current_num_threads();
+        let gates = rounds * threads;
+
+        let start = Instant::now();
+        for _ in 0..gates {
+            eval.nand(a, b);
+        }
+        let sequential = start.elapsed();
+
+        let start = Instant::now();
+        (0..gates).into_par_iter().for_each(|_| {
+            eval.nand(a, b);
+        });
+        let parallel = start.elapsed();
+
+        let speedup = sequential.as_secs_f64() / parallel.as_secs_f64().max(f64::MIN_POSITIVE);
+        (threads, speedup.clamp(1.0, threads as f64))
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn measure_scaling<E>(_eval: &E, _a: &E::Bit, _b: &E::Bit, _rounds: usize) -> (usize, f64)
+    where
+        E: BooleanEvaluator + Sync,
+        E::Bit: Send + Sync,
+    {
+        (1, 1.0)
+    }
+
+    /// Predicted time of `bootstraps` gates with the given critical path,
+    /// with independent gates spread over the measured speedup
+    pub fn estimate(&self, cost: StrategyCost) -> Duration {
+        let parallel = cost.bootstraps as f64 / self.parallel_speedup.max(1.0);
+        self.bootstrap_latency.mul_f64(parallel.max(cost.depth as f64))
+    }
+
+    /// Picks the adder and multiplier with the lowest estimate on `width` bits;
+    /// ties keep the cheaper, earlier strategy
+    pub fn select_strategies(&mut self, width: usize) {
+        let by_estimate = |cost: StrategyCost| (self.estimate(cost), cost.bootstraps);
+
+        let adder = AdderStrategy::ALL.into_iter()
+            .min_by_key(|&adder| by_estimate(StrategyCost::of_adder(adder, width)))
+            .unwrap_or_default();
+        let multiplier = MultiplierStrategy::ALL.into_iter()
+            .min_by_key(|&multiplier| by_estimate(StrategyCost::of_multiplier(multiplier, width)))
+            .unwrap_or_default();
+        (self.adder, self.multiplier) = (adder, multiplier);
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("profile serializes to JSON")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, CodecError> {
+        Self::from_bytes_with::<Json>(json.as_bytes())
+    }
+}
+
+/// Bootstraps and bootstrap critical path of one strategy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrategyCost {
+    pub bootstraps: usize,
+    pub depth: usize,
+}
+
+impl StrategyCost {
+    pub fn of_adder(adder: AdderStrategy, width: usize) -> Self {
+        Self::count(width, |a, b, eval| adder.add_mod(a, b, eval))
+    }
+
+    pub fn of_multiplier(multiplier: MultiplierStrategy, width: usize) -> Self {
+        Self::count(width, |a, b, eval| multiplier.mul(a, b, width, eval))
+    }
+
+    fn count<F>(width: usize, op: F) -> Self
+    where
+        F: Fn(&[Option<usize>], &[Option<usize>], &CostEvaluator) -> Vec<Option<usize>>,
+    {
+        let eval = CostEvaluator { bootstraps: Cell::new(0) };
+        let inputs = vec![Some(0); width];
+        let outputs = op(&inputs, &inputs, &eval);
+        StrategyCost {
+            bootstraps: eval.bootstraps.get(),
+            depth: outputs.iter().flatten().copied().max().unwrap_or(0),
+        }
+    }
+}
+
+// Bits are bootstrap depths, `None` for public constants, which `TfheGates`
+// folds without bootstrapping; node costs follow `Node::bootstraps`
+struct CostEvaluator {
+    bootstraps: Cell<usize>,
+}
+
+impl CostEvaluator {
+    fn gate(&self, node: Node, operands: &[&Option<usize>]) -> Option<usize> {
+        let depth = operands.iter().filter_map(|d| **d).max()?;
+        self.bootstraps.set(self.bootstraps.get() + node.bootstraps());
+        Some(depth + node.bootstrap_depth())
+    }
+}
+
+impl BooleanEvaluator for CostEvaluator {
+    type Bit = Option<usize>;
+
+    fn constant(&self, _value: bool) -> Option<usize> {
+        None
+    }
+
+    fn not(&self, a: &Option<usize>) -> Option<usize> {
+        self.gate(Node::Not(Wire(0)), &[a])
+    }
+
+    fn and(&self, a: &Option<usize>, b: &Option<usize>) -> Option<usize> {
+        self.gate(Node::And(Wire(0), Wire(0)), &[a, b])
+    }
+
+    fn or(&self, a: &Option<usize>, b: &Option<usize>) -> Option<usize> {
+        self.gate(Node::Or(Wire(0), Wire(0)), &[a, b])
+    }
+
+    fn xor(&self, a: &Option<usize>, b: &Option<usize>) -> Option<usize> {
+        self.gate(Node::Xor(Wire(0), Wire(0)), &[a, b])
+    }
+
+    fn nand(&self, a: &Option<usize>, b: &Option<usize>) -> Option<usize> {
+        self.gate(Node::Nand(Wire(0), Wire(0)), &[a, b])
+    }
+
+    fn mux(&self, s: &Option<usize>, a: &Option<usize>, b: &Option<usize>) -> Option<usize> {
+        self.gate(Node::Mux(Wire(0), Wire(0), Wire(0)), &[s, a, b])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    #[test]
+    fn test_strategy_costs() {
+        let ripple = StrategyCost::of_adder(AdderStrategy::RippleCarry, 32);
+        let prefix = StrategyCost::of_adder(AdderStrategy::ParallelPrefix, 32);
+        assert!(prefix.depth < ripple.depth);
+        assert!(prefix.bootstraps > ripple.bootstraps);
+
+        let shift_add = StrategyCost::of_multiplier(MultiplierStrategy::ShiftAdd, 16);
+        let carry_save = StrategyCost::of_multiplier(MultiplierStrategy::CarrySave, 16);
+        assert!(carry_save.depth < shift_add.depth);
+    }
+
+    #[test]
+    fn test_selection_follows_parallelism() {
+        let mut profile = EvalProfile { bootstrap_latency: Duration::from_millis(10), ..EvalProfile::default() };
+        profile.select_strategies(32);
+        assert_eq!(profile.adder, AdderStrategy::RippleCarry);
+
+        // With ample parallelism only the critical path counts
+        profile.parallel_speedup = 1e6;
+        profile.select_strategies(32);
+        assert_eq!(profile.adder, AdderStrategy::ParallelPrefix);
+        assert_eq!(profile.multiplier, MultiplierStrategy::CarrySave);
+    }
+
+    #[test]
+    fn test_calibrate_and_persist() {
+        let eval = ClearEvaluator;
+        let profile = EvalProfile::calibrate(&eval, &true, &false, 16, 100);
+        assert!(profile.threads >= 1 && profile.parallel_speedup >= 1.0);
+        assert!((1..=MAX_CHUNK_SIZE).contains(&profile.chunk_size));
+        assert_eq!(EvalProfile::from_json(&profile.to_json()).unwrap(), profile);
+
+        let a = IntegerOps::constant(1234, 16, &eval);
+        let b = IntegerOps::constant(4321, 16, &eval);
+        assert_eq!(profile.adder.add_mod(&a, &b, &eval), IntegerOps::add_mod(&a, &b, &eval));
+        assert_eq!(profile.multiplier.mul(&a, &b, 16, &eval), IntegerOps::mul(&a, &b, 16, &eval));
+    }
+}
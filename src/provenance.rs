@@ -0,0 +1,237 @@
+//! Lineage of ciphertexts for debugging pipelines.
+//!
+//! A `Traced` evaluator pairs every bit with a `Provenance`: the labels of
+//! the inputs it was computed from and the gates that combined them. Nodes
+//! are shared, so carrying the lineage costs one allocation per gate, and
+//! each node stores a SHA-256 digest of its whole history. When a result
+//! decrypts wrong, `trace` lists the op chain that produced it, `inputs`
+//! names the ciphertexts it depends on, and equal digests across two runs
+//! show the same computation was performed on the same labelled inputs.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use sha2::{Digest, Sha256};
+use crate::evaluator::BooleanEvaluator;
+
+#[derive(Debug)]
+enum Origin {
+    Label(String),
+    Constant(bool),
+    Gate { name: &'static str, operands: Vec<Provenance> },
+}
+
+#[derive(Debug)]
+struct Node {
+    origin: Origin,
+    digest: [u8; 32],
+}
+
+/// Where a bit came from; cheap to clone
+#[derive(Debug, Clone)]
+pub struct Provenance(Arc<Node>);
+
+impl Provenance {
+    pub fn label(name: impl Into<String>) -> Self {
+        let name = name.into();
+        let digest = Sha256::new()
+            .chain_update(b"label")
+            .chain_update((name.len() as u64).to_le_bytes())
+            .chain_update(name.as_bytes())
+            .finalize()
+            .into();
+        Provenance(Arc::new(Node { origin: Origin::Label(name), digest }))
+    }
+
+    pub fn constant(value: bool) -> Self {
+        let digest = Sha256::new().chain_update(b"constant").chain_update([value as u8]).finalize().into();
+        Provenance(Arc::new(Node { origin: Origin::Constant(value), digest }))
+    }
+
+    pub fn gate(name: &'static str, operands: &[&Provenance]) -> Self {
+        let mut hasher = Sha256::new().chain_update(b"gate").chain_update(name.as_bytes());
+        for operand in operands {
+            hasher.update(operand.digest());
+        }
+        let operands = operands.iter().map(|&p| p.clone()).collect();
+        Provenance(Arc::new(Node { origin: Origin::Gate { name, operands }, digest: hasher.finalize().into() }))
+    }
+
+    /// Digest of the labels, gates and their order; equal for equal histories
+    pub fn digest(&self) -> [u8; 32] {
+        self.0.digest
+    }
+
+    /// Distinct input labels the bit depends on, in first-use order
+    pub fn inputs(&self) -> Vec<String> {
+        self.nodes()
+            .into_iter()
+            .filter_map(|node| match &node.0.origin {
+                Origin::Label(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Gates in the history, each shared node counted once
+    pub fn gates(&self) -> usize {
+        self.nodes().iter().filter(|node| matches!(node.0.origin, Origin::Gate { .. })).count()
+    }
+
+    /// The op chain, one gate per line in evaluation order, e.g.
+    /// `t0 = xor(a, b)` then `t1 = and(t0, c)`
+    pub fn trace(&self) -> String {
+        let mut names: HashMap<*const Node, String> = HashMap::new();
+        let mut lines = Vec::new();
+        for node in self.nodes() {
+            let name = match &node.0.origin {
+                Origin::Label(label) => label.clone(),
+                Origin::Constant(value) => value.to_string(),
+                Origin::Gate { name, operands } => {
+                    let temp = format!("t{}", lines.len());
+                    let args: Vec<&str> = operands.iter().map(|op| names[&Arc::as_ptr(&op.0)].as_str()).collect();
+                    lines.push(format!("{} = {}({})", temp, name, args.join(", ")));
+                    temp
+                }
+            };
+            names.insert(Arc::as_ptr(&node.0), name);
+        }
+        lines.join("\n")
+    }
+
+    // Every node once, operands before their users; iterative so long
+    // chains do not exhaust the stack
+    fn nodes(&self) -> Vec<Provenance> {
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![(self.clone(), false)];
+        while let Some((node, expanded)) = stack.pop() {
+            let ptr = Arc::as_ptr(&node.0);
+            if expanded {
+                order.push(node);
+                continue;
+            }
+            if !visited.insert(ptr) {
+                continue;
+            }
+            stack.push((node.clone(), true));
+            if let Origin::Gate { operands, .. } = &node.0.origin {
+                for operand in operands.iter().rev() {
+                    if !visited.contains(&Arc::as_ptr(&operand.0)) {
+                        stack.push((operand.clone(), false));
+                    }
+                }
+            }
+        }
+        order
+    }
+}
+
+/// A bit of the wrapped evaluator with its lineage
+#[derive(Debug, Clone)]
+pub struct Labeled<B> {
+    pub bit: B,
+    pub provenance: Provenance,
+}
+
+/// Evaluator recording the lineage of every bit it produces
+pub struct Traced<E: BooleanEvaluator> {
+    eval: E,
+}
+
+impl<E: BooleanEvaluator> Traced<E> {
+    pub fn new(eval: E) -> Self {
+        Traced { eval }
+    }
+
+    /// Tags an input ciphertext
+    pub fn label(&self, bit: E::Bit, name: impl Into<String>) -> Labeled<E::Bit> {
+        Labeled { bit, provenance: Provenance::label(name) }
+    }
+
+    /// Tags each bit of a word as `name[i]`, least significant first
+    pub fn label_bits(&self, bits: Vec<E::Bit>, name: &str) -> Vec<Labeled<E::Bit>> {
+        bits.into_iter()
+            .enumerate()
+            .map(|(i, bit)| self.label(bit, format!("{}[{}]", name, i)))
+            .collect()
+    }
+
+    fn record(&self, name: &'static str, bit: E::Bit, operands: &[&Labeled<E::Bit>]) -> Labeled<E::Bit> {
+        let operands: Vec<&Provenance> = operands.iter().map(|l| &l.provenance).collect();
+        Labeled { bit, provenance: Provenance::gate(name, &operands) }
+    }
+}
+
+impl<E: BooleanEvaluator> BooleanEvaluator for Traced<E> {
+    type Bit = Labeled<E::Bit>;
+
+    fn constant(&self, value: bool) -> Self::Bit {
+        Labeled { bit: self.eval.constant(value), provenance: Provenance::constant(value) }
+    }
+
+    fn not(&self, a: &Self::Bit) -> Self::Bit {
+        self.record("not", self.eval.not(&a.bit), &[a])
+    }
+
+    fn and(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.record("and", self.eval.and(&a.bit, &b.bit), &[a, b])
+    }
+
+    fn or(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.record("or", self.eval.or(&a.bit, &b.bit), &[a, b])
+    }
+
+    fn xor(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.record("xor", self.eval.xor(&a.bit, &b.bit), &[a, b])
+    }
+
+    fn nand(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.record("nand", self.eval.nand(&a.bit, &b.bit), &[a, b])
+    }
+
+    fn mux(&self, s: &Self::Bit, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.record("mux", self.eval.mux(&s.bit, &a.bit, &b.bit), &[s, a, b])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+    use crate::integer::IntegerOps;
+
+    #[test]
+    fn test_trace_and_inputs() {
+        let eval = Traced::new(ClearEvaluator);
+        let a = eval.label(true, "a");
+        let b = eval.label(false, "b");
+        let c = eval.label(true, "c");
+
+        let t = eval.xor(&a, &b);
+        let result = eval.and(&t, &eval.or(&t, &c));
+        assert!(result.bit);
+        assert_eq!(result.provenance.inputs(), vec!["a", "b", "c"]);
+        assert_eq!(result.provenance.gates(), 3);
+        assert_eq!(result.provenance.trace(), "t0 = xor(a, b)\nt1 = or(t0, c)\nt2 = and(t0, t1)");
+
+        // Same labels and ops give the same digest; swapped operands do not
+        let again = eval.and(&eval.xor(&a, &b), &eval.or(&eval.xor(&a, &b), &c));
+        assert_eq!(again.provenance.digest(), result.provenance.digest());
+        let swapped = eval.and(&eval.xor(&b, &a), &eval.or(&eval.xor(&b, &a), &c));
+        assert_ne!(swapped.provenance.digest(), result.provenance.digest());
+    }
+
+    #[test]
+    fn test_lineage_through_integer_ops() {
+        let eval = Traced::new(ClearEvaluator);
+        let x = eval.label_bits(IntegerOps::constant(5, 4, &ClearEvaluator), "x");
+        let y = eval.label_bits(IntegerOps::constant(3, 4, &ClearEvaluator), "y");
+        let sum = IntegerOps::add_mod(&x, &y, &eval);
+
+        let bits: Vec<bool> = sum.iter().map(|l| l.bit).collect();
+        assert_eq!(bits, IntegerOps::constant(8, 4, &ClearEvaluator));
+        // The low bit only sees the low input bits; the top bit sees the whole carry chain
+        assert_eq!(sum[0].provenance.inputs(), vec!["x[0]", "y[0]"]);
+        assert_eq!(sum[3].provenance.inputs().len(), 8);
+    }
+}
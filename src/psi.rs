@@ -0,0 +1,68 @@
+//! Private set intersection cardinality against a public server set.
+//!
+//! The flow: both sides map their elements to `width`-bit digests with
+//! `element_hash`; the client encrypts its digests (e.g. with
+//! `TfheEncoder::encrypt_uint_iter`) and sends them; the server tests each
+//! one for membership in its own, public digests and sums the membership
+//! bits with `popcount`. Only the encrypted count goes back, so the client
+//! learns how many of its elements the server holds but not which, and the
+//! server learns nothing about the client's set beyond its size.
+//!
+//! Membership costs about `width` gates per server element and client
+//! element, all independent, so the server set should stay in the
+//! thousands. Two distinct elements share a digest with probability
+//! `2^-width`, which bounds how far the count can be off; the client set is
+//! assumed free of duplicates, which would be counted once each.
+
+use sha2::{Digest, Sha256};
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::{CmpOp, IntegerOps};
+
+/// The low `width` bits of the SHA-256 digest of `element`
+pub fn element_hash(element: &[u8], width: usize) -> u64 {
+    assert!(width > 0 && width <= 64, "digests are between 1 and 64 bits");
+    let digest = Sha256::digest(element);
+    let value = u64::from_le_bytes(digest[..8].try_into().expect("digest prefix"));
+    if width == 64 { value } else { value & ((1u64 << width) - 1) }
+}
+
+/// Whether the encrypted digest `a` is one of the public `set`
+pub fn is_member<E: BooleanEvaluator>(a: &[E::Bit], set: &[u64], eval: &E) -> E::Bit {
+    let matches: Vec<E::Bit> = set.iter().map(|&s| IntegerOps::cmp_clear(a, s, CmpOp::Eq, eval)).collect();
+    IntegerOps::or_all(&matches, eval)
+}
+
+/// Encrypted number of `client` digests found in the `server` set, wide
+/// enough for `client.len()`
+pub fn intersection_cardinality<E: BooleanEvaluator>(client: &[Vec<E::Bit>], server: &[u64], eval: &E) -> Vec<E::Bit> {
+    let mut server = server.to_vec();
+    server.sort_unstable();
+    server.dedup();
+    let members: Vec<E::Bit> = client.iter().map(|a| is_member(a, &server, eval)).collect();
+    IntegerOps::popcount(&members, eval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    #[test]
+    fn test_intersection_cardinality() {
+        let eval = ClearEvaluator;
+        let width = 16;
+        let hashes = |names: &[&str]| -> Vec<u64> { names.iter().map(|n| element_hash(n.as_bytes(), width)).collect() };
+
+        let client: Vec<Vec<bool>> = hashes(&["alice", "bob", "carol", "dave"])
+            .into_iter()
+            .map(|h| IntegerOps::constant(h, width, &eval))
+            .collect();
+        let server = hashes(&["erin", "carol", "alice", "frank", "alice"]);
+
+        let count = intersection_cardinality(&client, &server, &eval);
+        assert_eq!(count.len(), 3);
+        assert_eq!(count.iter().enumerate().fold(0, |acc, (i, &b)| acc | ((b as u64) << i)), 2);
+        assert!(!is_member(&client[1], &server, &eval));
+        assert!(!is_member(&client[0], &[], &eval));
+    }
+}
@@ -0,0 +1,259 @@
+use std::fmt;
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::IntegerOps;
+
+pub use crate::integer::CmpOp;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnType {
+    Bool,
+    Uint(usize),
+}
+
+/// Ordered, named column layout shared by every row of a table
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schema {
+    pub columns: Vec<(String, ColumnType)>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Schema::default()
+    }
+
+    pub fn bool(mut self, name: &str) -> Self {
+        self.columns.push((name.to_string(), ColumnType::Bool));
+        self
+    }
+
+    pub fn uint(mut self, name: &str, width: usize) -> Self {
+        self.columns.push((name.to_string(), ColumnType::Uint(width)));
+        self
+    }
+
+    fn column(&self, name: &str) -> Result<(usize, ColumnType), QueryError> {
+        self.columns.iter()
+            .position(|(n, _)| n == name)
+            .map(|i| (i, self.columns[i].1))
+            .ok_or_else(|| QueryError::UnknownColumn(name.to_string()))
+    }
+}
+
+/// One encrypted value of a row
+#[derive(Debug, Clone)]
+pub enum Cell<B> {
+    Bool(B),
+    /// Little-endian bits
+    Uint(Vec<B>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Column(String),
+    Literal(u64),
+}
+
+/// Plaintext predicate AST, the `WHERE` clause of a private filter
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Compare(String, CmpOp, Operand),
+    /// A boolean column
+    IsTrue(String),
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+    Not(Box<Predicate>),
+}
+
+impl Predicate {
+    pub fn compare(column: &str, op: CmpOp, literal: u64) -> Self {
+        Predicate::Compare(column.to_string(), op, Operand::Literal(literal))
+    }
+
+    pub fn compare_columns(left: &str, op: CmpOp, right: &str) -> Self {
+        Predicate::Compare(left.to_string(), op, Operand::Column(right.to_string()))
+    }
+
+    pub fn is_true(column: &str) -> Self {
+        Predicate::IsTrue(column.to_string())
+    }
+
+    pub fn and(self, other: Predicate) -> Self {
+        Predicate::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Predicate) -> Self {
+        Predicate::Or(Box::new(self), Box::new(other))
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        Predicate::Not(Box::new(self))
+    }
+
+    /// Checks column names and types before any gate is evaluated
+    pub fn validate(&self, schema: &Schema) -> Result<(), QueryError> {
+        match self {
+            Predicate::Compare(column, _, operand) => {
+                let uint = |name: &str| match schema.column(name)? {
+                    (_, ColumnType::Uint(_)) => Ok(()),
+                    _ => Err(QueryError::TypeMismatch(name.to_string())),
+                };
+                uint(column)?;
+                if let Operand::Column(other) = operand {
+                    uint(other)?;
+                }
+                Ok(())
+            }
+            Predicate::IsTrue(column) => match schema.column(column)? {
+                (_, ColumnType::Bool) => Ok(()),
+                _ => Err(QueryError::TypeMismatch(column.clone())),
+            },
+            Predicate::And(a, b) | Predicate::Or(a, b) => {
+                a.validate(schema)?;
+                b.validate(schema)
+            }
+            Predicate::Not(a) => a.validate(schema),
+        }
+    }
+
+    fn eval_row<E: BooleanEvaluator>(&self, schema: &Schema, row: &[Cell<E::Bit>], eval: &E) -> E::Bit {
+        let uint = |name: &str| match &row[schema.column(name).expect("validated").0] {
+            Cell::Uint(bits) => bits.clone(),
+            Cell::Bool(_) => unreachable!("validated"),
+        };
+
+        match self {
+            Predicate::Compare(column, op, operand) => match operand {
+                Operand::Column(other) => IntegerOps::compare(&uint(column), &uint(other), *op, eval),
+                Operand::Literal(value) => IntegerOps::cmp_clear(&uint(column), *value, *op, eval),
+            },
+            Predicate::IsTrue(column) => match &row[schema.column(column).expect("validated").0] {
+                Cell::Bool(bit) => bit.clone(),
+                Cell::Uint(_) => unreachable!("validated"),
+            },
+            Predicate::And(a, b) => eval.and(&a.eval_row(schema, row, eval), &b.eval_row(schema, row, eval)),
+            Predicate::Or(a, b) => eval.or(&a.eval_row(schema, row, eval), &b.eval_row(schema, row, eval)),
+            Predicate::Not(a) => eval.not(&a.eval_row(schema, row, eval)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryError {
+    UnknownColumn(String),
+    TypeMismatch(String),
+    /// Row index whose cells don't follow the schema
+    MalformedRow(usize),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::UnknownColumn(name) => write!(f, "unknown column '{}'", name),
+            QueryError::TypeMismatch(name) => write!(f, "column '{}' has the wrong type for this predicate", name),
+            QueryError::MalformedRow(i) => write!(f, "row {} does not match the schema", i),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Encrypted match bit per row plus the encrypted number of matches
+#[derive(Debug, Clone)]
+pub struct QueryResult<B> {
+    pub matches: Vec<B>,
+    pub count: Vec<B>,
+}
+
+/// Evaluates `predicate` on every row without learning which rows match
+pub fn filter<E: BooleanEvaluator>(
+    schema: &Schema,
+    predicate: &Predicate,
+    rows: &[Vec<Cell<E::Bit>>],
+    eval: &E,
+) -> Result<QueryResult<E::Bit>, QueryError> {
+    predicate.validate(schema)?;
+
+    for (i, row) in rows.iter().enumerate() {
+        let well_formed = row.len() == schema.columns.len()
+            && row.iter().zip(schema.columns.iter()).all(|(cell, (_, ty))| match (cell, ty) {
+                (Cell::Bool(_), ColumnType::Bool) => true,
+                (Cell::Uint(bits), ColumnType::Uint(width)) => bits.len() == *width,
+                _ => false,
+            });
+        if !well_formed {
+            return Err(QueryError::MalformedRow(i));
+        }
+    }
+
+    let matches: Vec<E::Bit> = rows.iter()
+        .map(|row| predicate.eval_row(schema, row, eval))
+        .collect();
+    let count = IntegerOps::popcount(&matches, eval);
+
+    Ok(QueryResult { matches, count })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    fn row(age: u64, score: u64, active: bool) -> Vec<Cell<bool>> {
+        vec![
+            Cell::Uint(IntegerOps::constant(age, 7, &ClearEvaluator)),
+            Cell::Uint(IntegerOps::constant(score, 7, &ClearEvaluator)),
+            Cell::Bool(active),
+        ]
+    }
+
+    fn schema() -> Schema {
+        Schema::new().uint("age", 7).uint("score", 7).bool("active")
+    }
+
+    #[test]
+    fn test_filter_and_count() {
+        let rows = vec![row(17, 90, true), row(30, 40, true), row(45, 80, false), row(22, 99, true)];
+
+        // active AND age >= 18 AND (score > 50 OR age = 30)
+        let predicate = Predicate::is_true("active")
+            .and(Predicate::compare("age", CmpOp::Ge, 18))
+            .and(Predicate::compare("score", CmpOp::Gt, 50).or(Predicate::compare("age", CmpOp::Eq, 30)));
+
+        let result = filter(&schema(), &predicate, &rows, &ClearEvaluator).unwrap();
+        assert_eq!(result.matches, vec![false, true, false, true]);
+        assert_eq!(result.count, vec![false, true, false]);
+    }
+
+    #[test]
+    fn test_column_comparison() {
+        let rows = vec![row(10, 20, true), row(20, 10, true), row(15, 15, false)];
+        let predicate = Predicate::compare_columns("age", CmpOp::Le, "score").and(Predicate::is_true("active").not());
+
+        let result = filter(&schema(), &predicate, &rows, &ClearEvaluator).unwrap();
+        assert_eq!(result.matches, vec![false, false, true]);
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let rows = vec![row(1, 2, true)];
+
+        let unknown = Predicate::compare("height", CmpOp::Lt, 3);
+        assert_eq!(
+            filter(&schema(), &unknown, &rows, &ClearEvaluator).unwrap_err(),
+            QueryError::UnknownColumn("height".to_string()),
+        );
+
+        let mismatch = Predicate::is_true("age");
+        assert_eq!(
+            filter(&schema(), &mismatch, &rows, &ClearEvaluator).unwrap_err(),
+            QueryError::TypeMismatch("age".to_string()),
+        );
+
+        let short_row = vec![vec![Cell::Bool(true)]];
+        assert_eq!(
+            filter(&schema(), &Predicate::is_true("active"), &short_row, &ClearEvaluator).unwrap_err(),
+            QueryError::MalformedRow(0),
+        );
+    }
+}
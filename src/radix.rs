@@ -0,0 +1,184 @@
+//! Plaintext conversions between wide integers and digit vectors.
+//!
+//! Encrypted integers are vectors of small digits: bits for `FheUint`, or
+//! residues modulo pairwise coprime moduli for CRT representations. These
+//! helpers split `u128` (and, with the `bigint` feature, `BigUint`) values
+//! into digits before encryption and put decrypted digits back together, so
+//! the plaintext side is not limited to 64 bits. `to_decimal` prints digit
+//! vectors of any length through long division, without a bignum type.
+
+#[cfg(feature = "bigint")]
+use num_bigint::BigUint;
+
+/// The low `digits` digits of `value` in `base`, least significant first
+pub fn decompose_u128(mut value: u128, base: u64, digits: usize) -> Vec<u64> {
+    assert!(base >= 2, "base must be at least 2");
+    (0..digits)
+        .map(|_| {
+            let digit = (value % base as u128) as u64;
+            value /= base as u128;
+            digit
+        })
+        .collect()
+}
+
+/// Inverse of `decompose_u128`, or `None` when the value does not fit
+pub fn recompose_u128(digits: &[u64], base: u64) -> Option<u128> {
+    digits.iter().rev().try_fold(0u128, |acc, &d| {
+        acc.checked_mul(base as u128)?.checked_add(d as u128)
+    })
+}
+
+/// Residues of `value` modulo each of `moduli`
+pub fn crt_decompose_u128(value: u128, moduli: &[u64]) -> Vec<u64> {
+    moduli.iter().map(|&m| (value % m as u128) as u64).collect()
+}
+
+/// The value below the product of `moduli` with the given residues, or
+/// `None` when it does not fit a `u128` or the moduli share a factor
+pub fn crt_recompose_u128(residues: &[u64], moduli: &[u64]) -> Option<u128> {
+    let mixed = mixed_radix_digits(residues, moduli)?;
+    mixed.iter().zip(moduli.iter()).rev().try_fold(0u128, |acc, (&v, &m)| {
+        acc.checked_mul(m as u128)?.checked_add(v as u128)
+    })
+}
+
+// Garner's algorithm: x = v_0 + v_1 m_0 + v_2 m_0 m_1 + ..., with v_i < m_i,
+// or `None` unless the moduli are pairwise coprime
+fn mixed_radix_digits(residues: &[u64], moduli: &[u64]) -> Option<Vec<u64>> {
+    assert_eq!(residues.len(), moduli.len(), "one residue per modulus");
+
+    let mut digits: Vec<u64> = Vec::with_capacity(moduli.len());
+    for (i, (&r, &m)) in residues.iter().zip(moduli.iter()).enumerate() {
+        let m = m as u128;
+        let mut v = r as u128 % m;
+        for (&d, &mj) in digits.iter().zip(moduli[..i].iter()) {
+            let inverse = mod_inverse(mj as u128 % m, m)?;
+            v = (v + m - d as u128 % m) % m * inverse % m;
+        }
+        digits.push(v as u64);
+    }
+    Some(digits)
+}
+
+fn mod_inverse(a: u128, m: u128) -> Option<u128> {
+    if m == 1 {
+        return Some(0);
+    }
+    let (mut old_r, mut r) = (a as i128, m as i128);
+    let (mut old_s, mut s) = (1i128, 0i128);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_s, s) = (s, old_s - q * s);
+    }
+    (old_r == 1).then(|| old_s.rem_euclid(m as i128) as u128)
+}
+
+/// Decimal representation of `digits` in `base`, least significant first
+pub fn to_decimal(digits: &[u64], base: u64) -> String {
+    assert!(base >= 2, "base must be at least 2");
+    // Divide the whole number by 10^19 at a time, most significant digit first
+    const CHUNK: u128 = 10_000_000_000_000_000_000;
+
+    let mut number: Vec<u64> = digits.iter().rev().copied().collect();
+    let mut chunks = Vec::new();
+    while number.iter().any(|&d| d != 0) {
+        let mut remainder = 0u128;
+        for d in number.iter_mut() {
+            let current = remainder * base as u128 + *d as u128;
+            *d = (current / CHUNK) as u64;
+            remainder = current % CHUNK;
+        }
+        chunks.push(remainder as u64);
+    }
+
+    match chunks.split_last() {
+        None => "0".to_string(),
+        Some((most, rest)) => rest.iter().rev().fold(most.to_string(), |mut s, chunk| {
+            s.push_str(&format!("{:019}", chunk));
+            s
+        }),
+    }
+}
+
+/// The low `digits` digits of `value` in `base`, least significant first
+#[cfg(feature = "bigint")]
+pub fn decompose_biguint(value: &BigUint, base: u64, digits: usize) -> Vec<u64> {
+    assert!(base >= 2, "base must be at least 2");
+    let base = BigUint::from(base);
+    let mut value = value.clone();
+    (0..digits)
+        .map(|_| {
+            let digit = (&value % &base).iter_u64_digits().next().unwrap_or(0);
+            value /= &base;
+            digit
+        })
+        .collect()
+}
+
+#[cfg(feature = "bigint")]
+pub fn recompose_biguint(digits: &[u64], base: u64) -> BigUint {
+    digits.iter().rev().fold(BigUint::ZERO, |acc, &d| acc * base + d)
+}
+
+#[cfg(feature = "bigint")]
+pub fn crt_decompose_biguint(value: &BigUint, moduli: &[u64]) -> Vec<u64> {
+    moduli.iter()
+        .map(|&m| (value % m).iter_u64_digits().next().unwrap_or(0))
+        .collect()
+}
+
+/// The value below the product of `moduli` with the given residues, or
+/// `None` when the moduli share a factor
+#[cfg(feature = "bigint")]
+pub fn crt_recompose_biguint(residues: &[u64], moduli: &[u64]) -> Option<BigUint> {
+    let mixed = mixed_radix_digits(residues, moduli)?;
+    Some(mixed.iter().zip(moduli.iter()).rev().fold(BigUint::ZERO, |acc, (&v, &m)| acc * m + v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_u128_radix_and_crt() {
+        let value = u128::MAX - 12345;
+        let bits = decompose_u128(value, 2, 128);
+        assert_eq!(recompose_u128(&bits, 2), Some(value));
+        assert_eq!(recompose_u128(&decompose_u128(value, 10, 40), 10), Some(value));
+        assert_eq!(recompose_u128(&[1; 129], 2), None);
+
+        let moduli = [u64::MAX - 58, u64::MAX - 82, 7];
+        let value = 1u128 << 127 | 987_654_321;
+        let residues = crt_decompose_u128(value, &moduli);
+        assert_eq!(crt_recompose_u128(&residues, &moduli), Some(value));
+        assert_eq!(crt_recompose_u128(&[2, 3, 1], &[3, 5, 7]), Some(8));
+        assert_eq!(crt_recompose_u128(&[1, 2], &[6, 4]), None);
+    }
+
+    #[test]
+    fn test_decimal_by_long_division() {
+        assert_eq!(to_decimal(&[], 2), "0");
+        assert_eq!(to_decimal(&decompose_u128(u128::MAX, 2, 128), 2), u128::MAX.to_string());
+        assert_eq!(to_decimal(&decompose_u128(10u128.pow(19), 16, 20), 16), "10000000000000000000");
+
+        // 2^192, beyond any primitive type
+        let mut digits = vec![0; 192];
+        digits.push(1);
+        assert_eq!(to_decimal(&digits, 2), "6277101735386680763835789423207666416102355444464034512896");
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_biguint_conversions() {
+        let value = BigUint::from(3u8).pow(200);
+        let digits = decompose_biguint(&value, 1 << 16, 20);
+        assert_eq!(recompose_biguint(&digits, 1 << 16), value);
+        assert_eq!(to_decimal(&digits, 1 << 16), value.to_string());
+
+        let moduli = [u64::MAX - 58, u64::MAX - 82, u64::MAX - 94, u64::MAX - 178, u64::MAX - 188];
+        let residues = crt_decompose_biguint(&value, &moduli);
+        assert_eq!(crt_recompose_biguint(&residues, &moduli), Some(value));
+    }
+}
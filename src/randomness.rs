@@ -0,0 +1,80 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolError {
+    Exhausted { requested: usize, remaining: usize },
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::Exhausted { requested, remaining } => {
+                write!(f, "randomness pool exhausted: {} bits requested, {} left", requested, remaining)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PoolError {}
+
+/// Client-encrypted random bits for server-side algorithms that need
+/// randomness under encryption (stochastic rounding, randomized response,
+/// shuffling).
+///
+/// Every bit is handed out at most once: `take` moves bits out of the pool,
+/// and the pool is deliberately not `Clone`, since replaying the same
+/// encrypted bits would correlate results that are meant to be independent.
+#[derive(Debug)]
+pub struct RandomPool<B> {
+    /// Chosen by the client so both sides can refer to the pool in logs
+    pub id: u64,
+    bits: Vec<B>,
+    consumed: usize,
+}
+
+impl<B> RandomPool<B> {
+    pub fn new(id: u64, bits: Vec<B>) -> Self {
+        RandomPool { id, bits, consumed: 0 }
+    }
+
+    /// Removes the next `n` bits, or none when fewer than `n` remain
+    pub fn take(&mut self, n: usize) -> Result<Vec<B>, PoolError> {
+        if n > self.bits.len() {
+            return Err(PoolError::Exhausted { requested: n, remaining: self.bits.len() });
+        }
+        self.consumed += n;
+        Ok(self.bits.drain(..n).collect())
+    }
+
+    pub fn take_bit(&mut self) -> Result<B, PoolError> {
+        Ok(self.take(1)?.remove(0))
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// Bits handed out so far
+    pub fn consumed(&self) -> usize {
+        self.consumed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_accounting() {
+        let mut pool = RandomPool::new(7, vec![true, false, true, true, false]);
+
+        assert_eq!(pool.take(2), Ok(vec![true, false]));
+        assert_eq!(pool.take_bit(), Ok(true));
+        assert_eq!((pool.consumed(), pool.remaining()), (3, 2));
+
+        assert_eq!(pool.take(3), Err(PoolError::Exhausted { requested: 3, remaining: 2 }));
+        assert_eq!(pool.consumed(), 3);
+        assert_eq!(pool.take(2), Ok(vec![true, false]));
+        assert_eq!(pool.take_bit(), Err(PoolError::Exhausted { requested: 1, remaining: 0 }));
+    }
+}
@@ -0,0 +1,74 @@
+//! Key rotation for stored ciphertexts.
+//!
+//! A `ReEncryptionKey` is a key switching key from an old LWE key to a new
+//! one: the owner generates it holding both keys, and the server moves a
+//! long-lived dataset over with `re_encrypt` without ever decrypting it.
+//! Each rotation adds the noise of one key switch, so a dataset rotated many
+//! times should be bootstrapped under the new cloud key now and then, which
+//! resets its noise.
+
+#[cfg(feature = "client")]
+use crate::entropy::{default_entropy, EntropySource};
+#[cfg(feature = "client")]
+use crate::noise_model;
+#[cfg(feature = "client")]
+use crate::tfhe::TfheSecretKey;
+use crate::tlwe::{TlweKeySwitchKey, TlweParams, TlweSample};
+
+#[derive(Debug, Clone)]
+pub struct ReEncryptionKey {
+    pub key_switching_key: TlweKeySwitchKey,
+}
+
+impl ReEncryptionKey {
+    /// Key moving ciphertexts of `old` over to `new`, keeping the added
+    /// noise within half the decryption margin
+    #[cfg(feature = "client")]
+    pub fn generate(old: &TfheSecretKey, new: &TfheSecretKey) -> Self {
+        Self::generate_with_rng(old, new, &mut default_entropy())
+    }
+
+    #[cfg(feature = "client")]
+    pub fn generate_with_rng<R: EntropySource + ?Sized>(old: &TfheSecretKey, new: &TfheSecretKey, rng: &mut R) -> Self {
+        let output = &new.params.tlwe_params;
+        let max_stddev = noise_model::max_variance().sqrt() / 2.0;
+        let ks = TlweKeySwitchKey::recommend(old.params.tlwe_params.n, output, max_stddev).unwrap_or_default();
+        ReEncryptionKey {
+            key_switching_key: TlweKeySwitchKey::generate_with_rng(&old.tlwe_key, &new.tlwe_key, ks.t, ks.base_bit, rng),
+        }
+    }
+
+    /// Parameters of the re-encrypted samples
+    pub fn output_params(&self) -> &TlweParams {
+        self.key_switching_key.output_params()
+    }
+
+    /// `sample` under the new key, with the same message
+    pub fn re_encrypt(&self, sample: &TlweSample) -> TlweSample {
+        self.key_switching_key.key_switch(sample)
+    }
+
+    pub fn re_encrypt_all(&self, samples: &[TlweSample]) -> Vec<TlweSample> {
+        samples.iter().map(|s| self.re_encrypt(s)).collect()
+    }
+}
+
+#[cfg(all(test, feature = "client"))]
+mod tests {
+    use super::*;
+    use crate::tfhe::{TfheEncoder, TfheParams};
+
+    #[test]
+    fn test_rotation_keeps_messages() {
+        let params = TfheParams { tlwe_params: TlweParams { n: 64, stddev: 1e-9 }, ..TfheParams::default() };
+        let old = TfheSecretKey::generate(params.clone());
+        let new = TfheSecretKey::generate(params);
+        let key = ReEncryptionKey::generate(&old, &new);
+
+        let bits = [true, false, true, true, false];
+        let stored: Vec<TlweSample> = TfheEncoder::encrypt_iter(bits, &old).collect();
+        let rotated = key.re_encrypt_all(&stored);
+        assert_eq!(TfheEncoder::decrypt_iter(&rotated, &new).collect::<Vec<_>>(), bits);
+        assert_eq!(key.output_params(), &new.params.tlwe_params);
+    }
+}
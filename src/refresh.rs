@@ -0,0 +1,117 @@
+//! Automatic refreshing of operands whose noise got too large.
+//!
+//! Every gate first combines its operands linearly and then bootstraps the
+//! combination, which decrypts wrong if the combined error leaves the
+//! decryption margin. Gates only refuse operands past the `NoiseLevel`
+//! budget; operands that are merely noisier than fresh, e.g. from linear
+//! arithmetic of the caller, go through unnoticed. With a `RefreshPolicy`
+//! on the cloud key, the linear step checks the `NoiseEstimate` of its
+//! result against a target failure probability and, when the target is
+//! missed, bootstraps the operands to fresh ciphertexts first. Chains of
+//! gates such as the adders of `HomomorphicOps` then pay an extra bootstrap
+//! where they need one instead of silently computing on garbage.
+
+#[cfg(feature = "server")]
+use crate::tfhe::{TfheCloudKey, TfheGates};
+use crate::tlwe::TlweSample;
+
+/// Largest acceptable probability of a wrong decryption per linear step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RefreshPolicy {
+    pub max_failure_probability: f64,
+}
+
+impl RefreshPolicy {
+    pub fn new(max_failure_probability: f64) -> Self {
+        assert!(
+            max_failure_probability > 0.0 && max_failure_probability < 1.0,
+            "failure probability {} is not in (0, 1)",
+            max_failure_probability,
+        );
+        RefreshPolicy { max_failure_probability }
+    }
+
+    /// Whether `sample` misses the target, or is past its noise budget
+    pub fn exceeded(&self, sample: &TlweSample) -> bool {
+        sample.noise.needs_bootstrap() || sample.estimate.failure_probability() > self.max_failure_probability
+    }
+}
+
+/// A failure in `2^40` linear steps
+impl Default for RefreshPolicy {
+    fn default() -> Self {
+        RefreshPolicy::new((-40f64).exp2())
+    }
+}
+
+#[cfg(feature = "server")]
+impl TfheCloudKey {
+    /// Gates evaluated with this key refresh operands whenever a linear
+    /// step would miss `policy`
+    pub fn with_auto_refresh(mut self, policy: RefreshPolicy) -> Self {
+        self.refresh = Some(policy);
+        self
+    }
+}
+
+#[cfg(feature = "server")]
+impl TfheGates {
+    /// `combine` of `operands`; under a `RefreshPolicy` the operands are
+    /// refreshed first if the result would miss it
+    pub fn linear(
+        operands: &[&TlweSample],
+        combine: impl Fn(&[&TlweSample]) -> TlweSample,
+        ck: &TfheCloudKey,
+    ) -> TlweSample {
+        let result = combine(operands);
+        match ck.refresh {
+            Some(policy) if policy.exceeded(&result) => {
+                let owned: Vec<TlweSample> = operands.iter().map(|&s| s.clone()).collect();
+                let fresh = Self::refresh_batch(&owned, ck);
+                combine(&fresh.iter().collect::<Vec<_>>())
+            }
+            _ => result,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "client", feature = "server"))]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use crate::hooks::{BootstrapHook, BootstrapInfo};
+    use crate::tfhe::{TfheEncoder, TfheSecretKey};
+    use crate::tfhe::fixtures::small_params;
+
+    struct Gates(Mutex<Vec<&'static str>>);
+
+    impl BootstrapHook for Gates {
+        fn after_bootstrap(&self, info: &BootstrapInfo, _output: &mut TlweSample, _elapsed: Duration) {
+            self.0.lock().unwrap().push(info.gate);
+        }
+    }
+
+    #[test]
+    fn test_refresh_before_noisy_linear_steps() {
+        let params = small_params(64);
+        let sk = TfheSecretKey::generate(params);
+        let gates = Arc::new(Gates(Mutex::new(Vec::new())));
+        let mut ck = TfheCloudKey::generate(&sk).with_auto_refresh(RefreshPolicy::default());
+        ck.hooks.register(gates.clone());
+
+        // Standard deviation 1/100 on one operand: fine alone and through
+        // NAND, but XOR's `2 (a + b)` quadruples the variance
+        let mut a = TfheEncoder::encode_bool(true, &sk);
+        a.estimate.variance = 1e-4;
+        let b = TfheEncoder::encode_bool(false, &sk);
+        assert!(!RefreshPolicy::default().exceeded(&a));
+
+        TfheGates::nand(&a, &b, &ck);
+        assert_eq!(*gates.0.lock().unwrap(), ["nand"]);
+
+        gates.0.lock().unwrap().clear();
+        TfheGates::xor(&a, &b, &ck);
+        assert_eq!(*gates.0.lock().unwrap(), ["refresh", "refresh", "xor"]);
+    }
+}
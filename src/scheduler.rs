@@ -0,0 +1,358 @@
+//! Priority classes for evaluations sharing the worker threads of a process.
+//!
+//! A `Scheduler` hands out a fixed number of slots, one per worker thread,
+//! and every gate of a `Scheduled` evaluator holds a slot while it runs.
+//! Gates of concurrent evaluations therefore queue for slots instead of
+//! oversubscribing the machine, and the queue decides who runs next:
+//! classes are served by stride scheduling in proportion to their
+//! `Priority::weight`, so a latency-sensitive request overtakes a batch job
+//! without starving it, and sessions of one class take turns so two jobs of
+//! the same class progress at the same rate however many gates each queues.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use crate::evaluator::BooleanEvaluator;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Priority {
+    /// Small requests someone waits on
+    Interactive,
+    #[default]
+    Normal,
+    /// Large jobs that only need to finish eventually
+    Batch,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 3] = [Priority::Interactive, Priority::Normal, Priority::Batch];
+
+    /// Share of the slots a class gets while all classes have work queued
+    pub fn weight(self) -> u64 {
+        match self {
+            Priority::Interactive => 16,
+            Priority::Normal => 4,
+            Priority::Batch => 1,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    // Virtual time a grant costs the class: inversely proportional to its weight
+    fn stride(self) -> u64 {
+        Priority::Interactive.weight() / self.weight()
+    }
+}
+
+// Waiting tickets of one class, in turns across sessions
+#[derive(Debug, Default)]
+struct ClassQueue {
+    turns: VecDeque<u64>,
+    tickets: HashMap<u64, VecDeque<u64>>,
+    pass: u64,
+}
+
+impl ClassQueue {
+    fn len(&self) -> usize {
+        self.tickets.values().map(VecDeque::len).sum()
+    }
+
+    fn push(&mut self, session: u64, ticket: u64) {
+        let queue = self.tickets.entry(session).or_default();
+        if queue.is_empty() {
+            self.turns.push_back(session);
+        }
+        queue.push_back(ticket);
+    }
+
+    fn pop(&mut self) -> Option<u64> {
+        let session = self.turns.pop_front()?;
+        let queue = self.tickets.get_mut(&session)?;
+        let ticket = queue.pop_front();
+        if queue.is_empty() {
+            self.tickets.remove(&session);
+        } else {
+            self.turns.push_back(session);
+        }
+        ticket
+    }
+}
+
+// Per-class queues and the virtual clock of the stride scheduler
+#[derive(Debug, Default)]
+struct Queues {
+    classes: [ClassQueue; 3],
+    /// Pass of the latest grant; classes that were idle resume from here
+    clock: u64,
+}
+
+impl Queues {
+    fn push(&mut self, priority: Priority, session: u64, ticket: u64) {
+        let clock = self.clock;
+        let class = &mut self.classes[priority.index()];
+        if class.turns.is_empty() {
+            // No credit for time spent idle
+            class.pass = class.pass.max(clock);
+        }
+        class.push(session, ticket);
+    }
+
+    // Next ticket: the busy class with the lowest pass, ties to the higher priority
+    fn next(&mut self) -> Option<u64> {
+        let priority = Priority::ALL.into_iter()
+            .filter(|p| !self.classes[p.index()].turns.is_empty())
+            .min_by_key(|p| self.classes[p.index()].pass)?;
+        let class = &mut self.classes[priority.index()];
+        self.clock = class.pass;
+        class.pass += priority.stride();
+        class.pop()
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    free: usize,
+    queues: Queues,
+    granted: HashSet<u64>,
+    next_ticket: u64,
+}
+
+/// Slots shared by all evaluations of a process
+#[derive(Debug)]
+pub struct Scheduler {
+    slots: usize,
+    state: Mutex<State>,
+    changed: Condvar,
+    next_session: AtomicU64,
+}
+
+impl Scheduler {
+    pub fn new(slots: usize) -> Arc<Self> {
+        assert!(slots > 0, "a scheduler needs at least one slot");
+        Arc::new(Scheduler {
+            slots,
+            state: Mutex::new(State {
+                free: slots,
+                queues: Queues::default(),
+                granted: HashSet::new(),
+                next_ticket: 0,
+            }),
+            changed: Condvar::new(),
+            next_session: AtomicU64::new(0),
+        })
+    }
+
+    /// One slot per available hardware thread
+    pub fn with_available_parallelism() -> Arc<Self> {
+        Self::new(std::thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// Handle for one evaluation; clones share its turn within the class
+    pub fn session(self: &Arc<Self>, priority: Priority) -> Session {
+        Session {
+            scheduler: Arc::clone(self),
+            id: self.next_session.fetch_add(1, Ordering::Relaxed),
+            priority,
+        }
+    }
+
+    /// Gates of `priority` waiting for a slot
+    pub fn waiting(&self, priority: Priority) -> usize {
+        self.state.lock().unwrap().queues.classes[priority.index()].len()
+    }
+
+    // Grants free slots to the front of the queues
+    fn dispatch(state: &mut State) {
+        while state.free > 0 {
+            match state.queues.next() {
+                Some(ticket) => {
+                    state.free -= 1;
+                    state.granted.insert(ticket);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Session {
+    scheduler: Arc<Scheduler>,
+    id: u64,
+    priority: Priority,
+}
+
+impl Session {
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    /// Blocks until the scheduler grants this session a slot
+    pub fn acquire(&self) -> Permit<'_> {
+        let scheduler = &self.scheduler;
+        let mut state = scheduler.state.lock().unwrap();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.queues.push(self.priority, self.id, ticket);
+        Scheduler::dispatch(&mut state);
+        scheduler.changed.notify_all();
+
+        while !state.granted.remove(&ticket) {
+            state = scheduler.changed.wait(state).unwrap();
+        }
+        Permit { scheduler }
+    }
+}
+
+/// A slot, returned to the scheduler on drop
+#[derive(Debug)]
+pub struct Permit<'a> {
+    scheduler: &'a Scheduler,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.scheduler.state.lock().unwrap();
+        state.free += 1;
+        Scheduler::dispatch(&mut state);
+        self.scheduler.changed.notify_all();
+    }
+}
+
+/// Evaluator whose gates each run in a slot of its session
+pub struct Scheduled<E: BooleanEvaluator> {
+    eval: E,
+    session: Session,
+}
+
+impl<E: BooleanEvaluator> Scheduled<E> {
+    pub fn new(eval: E, session: Session) -> Self {
+        Scheduled { eval, session }
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+}
+
+impl<E: BooleanEvaluator> BooleanEvaluator for Scheduled<E> {
+    type Bit = E::Bit;
+
+    fn constant(&self, value: bool) -> E::Bit {
+        self.eval.constant(value)
+    }
+
+    fn not(&self, a: &E::Bit) -> E::Bit {
+        let _permit = self.session.acquire();
+        self.eval.not(a)
+    }
+
+    fn and(&self, a: &E::Bit, b: &E::Bit) -> E::Bit {
+        let _permit = self.session.acquire();
+        self.eval.and(a, b)
+    }
+
+    fn or(&self, a: &E::Bit, b: &E::Bit) -> E::Bit {
+        let _permit = self.session.acquire();
+        self.eval.or(a, b)
+    }
+
+    fn xor(&self, a: &E::Bit, b: &E::Bit) -> E::Bit {
+        let _permit = self.session.acquire();
+        self.eval.xor(a, b)
+    }
+
+    fn nand(&self, a: &E::Bit, b: &E::Bit) -> E::Bit {
+        let _permit = self.session.acquire();
+        self.eval.nand(a, b)
+    }
+
+    fn mux(&self, s: &E::Bit, a: &E::Bit, b: &E::Bit) -> E::Bit {
+        let _permit = self.session.acquire();
+        self.eval.mux(s, a, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+    use crate::evaluator::ClearEvaluator;
+    use crate::integer::IntegerOps;
+
+    #[test]
+    fn test_weighted_turns() {
+        let mut queues = Queues::default();
+        for ticket in 0..100 {
+            queues.push(Priority::Interactive, 0, ticket);
+        }
+        // Two batch jobs of very different sizes
+        for ticket in 100..150 {
+            queues.push(Priority::Batch, 1, ticket);
+        }
+        queues.push(Priority::Batch, 2, 150);
+
+        let order: Vec<u64> = (0..34).filter_map(|_| queues.next()).collect();
+        let batch: Vec<u64> = order.iter().copied().filter(|&t| t >= 100).collect();
+        // One batch gate per 16 interactive ones, alternating between the batch sessions
+        assert_eq!(batch, vec![100, 150]);
+        assert_eq!(order[0], 0);
+    }
+
+    #[test]
+    fn test_interactive_overtakes_queued_batch() {
+        let scheduler = Scheduler::new(1);
+        let batch = scheduler.session(Priority::Batch);
+        let interactive = scheduler.session(Priority::Interactive);
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        let held = batch.acquire();
+        let mut handles = Vec::new();
+        for _ in 0..8 {
+            let (session, order) = (batch.clone(), order.clone());
+            handles.push(thread::spawn(move || {
+                let _permit = session.acquire();
+                order.lock().unwrap().push(Priority::Batch);
+            }));
+        }
+        while scheduler.waiting(Priority::Batch) < 8 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let (session, log) = (interactive.clone(), order.clone());
+        // Logged under the permit, as the batch threads do, so a batch
+        // thread taking the slot next cannot log first
+        handles.push(thread::spawn(move || {
+            let _permit = session.acquire();
+            log.lock().unwrap().push(Priority::Interactive);
+        }));
+        while scheduler.waiting(Priority::Interactive) < 1 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        drop(held);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let order = order.lock().unwrap();
+        assert_eq!(order.len(), 9);
+        assert_eq!(order[0], Priority::Interactive);
+    }
+
+    #[test]
+    fn test_scheduled_evaluation() {
+        let scheduler = Scheduler::new(2);
+        let eval = Scheduled::new(ClearEvaluator, scheduler.session(Priority::Normal));
+        let a = IntegerOps::constant(9, 4, &eval);
+        let b = IntegerOps::constant(4, 4, &eval);
+        assert_eq!(IntegerOps::add_mod(&a, &b, &eval), IntegerOps::constant(13, 4, &ClearEvaluator));
+        assert_eq!(scheduler.waiting(Priority::Normal), 0);
+        assert_eq!(scheduler.state.lock().unwrap().free, 2);
+    }
+}
@@ -0,0 +1,330 @@
+//! Fresh ciphertexts and bootstrapping keys whose masks are expanded from
+//! a seed.
+//!
+//! The mask of a fresh TLWE sample is uniformly random and independent of
+//! the message, so the client can derive it from a short public seed and
+//! upload only the seed and the body: `SEED_BYTES` plus one torus element
+//! instead of `n + 1` of them. The server expands the seed again before
+//! computing. Expansion hashes the seed and a counter with SHA-256, so the
+//! mask is the same on every platform and release, independent of `rand`.
+//! Compression only applies to fresh encryptions; anything computed on
+//! the server has a mask no seed generates.
+//!
+//! The bootstrapping key is made of fresh encryptions too. A TRGSW row adds
+//! its gadget term `m g_j` to mask component `i`, which a seed cannot
+//! reproduce, so `SeededBootstrappingKey` subtracts `m g_j s_i` from the
+//! body instead. The phase of every row is unchanged, and the external
+//! product only depends on the phases and the linearity of its rows, so the
+//! expanded key bootstraps exactly like one from
+//! `BootstrappingKey::generate`. It stores one polynomial per row instead
+//! of `k + 1`.
+
+use sha2::{Digest, Sha256};
+use crate::canonical::{read_params, write_params, write_u64, Canonical, CanonicalError, CanonicalReader, Parameterized};
+use crate::noise::{NoiseEstimate, NoiseLevel};
+use crate::tfhe::TfheParams;
+#[cfg(feature = "client")]
+use crate::entropy::{default_entropy, EntropySource};
+#[cfg(feature = "client")]
+use crate::tfhe::TfheSecretKey;
+#[cfg(feature = "server")]
+use crate::tfhe::TfheCloudKey;
+use crate::tgsw::{BootstrappingKey, TrgswSample};
+use crate::tlwe::{TlweParams, TlweSample};
+#[cfg(feature = "client")]
+use crate::tlwe::TlweSecretKey;
+#[cfg(feature = "server")]
+use crate::tlwe::TlweKeySwitchKey;
+use crate::torus::Torus;
+use crate::trlwe::{TorusPolynomial, TrlweParams, TrlweSample};
+
+pub const SEED_BYTES: usize = 32;
+
+pub type Seed = [u8; SEED_BYTES];
+
+/// `len` uniform torus elements from `seed`: element `i` is the top 53
+/// bits of the first eight bytes of `SHA-256(seed || i)`, little-endian,
+/// which every torus representation holds exactly
+pub fn expand_mask(seed: &Seed, len: usize) -> Vec<Torus> {
+    (0..len as u64)
+        .map(|i| uniform(Sha256::new().chain_update(seed).chain_update(i.to_le_bytes())))
+        .collect()
+}
+
+/// Mask number `stream` of a key seeded by `seed`, element `i` from
+/// `SHA-256(seed || stream || i)`
+pub fn expand_stream(seed: &Seed, stream: u64, len: usize) -> Vec<Torus> {
+    let prefix = Sha256::new().chain_update(seed).chain_update(stream.to_le_bytes());
+    (0..len as u64)
+        .map(|i| uniform(prefix.clone().chain_update(i.to_le_bytes())))
+        .collect()
+}
+
+fn uniform(hasher: Sha256) -> Torus {
+    let digest = hasher.finalize();
+    let word = u64::from_le_bytes(digest[..8].try_into().expect("eight bytes"));
+    Torus::new((word >> 11) as f64 / 53f64.exp2())
+}
+
+/// Fresh TLWE sample stored as the seed of its mask and its body
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedTlweSample {
+    pub seed: Seed,
+    pub b: Torus,
+    pub params: TlweParams,
+}
+
+impl CompressedTlweSample {
+    #[cfg(feature = "client")]
+    pub fn encrypt(message: &Torus, sk: &TlweSecretKey) -> Self {
+        Self::encrypt_with_rng(message, sk, &mut default_entropy())
+    }
+
+    /// `encrypt` with the seed and the noise drawn from `rng`
+    #[cfg(feature = "client")]
+    pub fn encrypt_with_rng<R: EntropySource + ?Sized>(message: &Torus, sk: &TlweSecretKey, rng: &mut R) -> Self {
+        let mut seed: Seed = [0; SEED_BYTES];
+        rng.fill_bytes(&mut seed);
+        let a = expand_mask(&seed, sk.params.n);
+        let sample = TlweSample::encrypt_with_mask(a, message, sk, rng);
+        CompressedTlweSample { seed, b: sample.b, params: sample.params }
+    }
+
+    /// The full sample, mask expanded from the seed
+    pub fn decompress(&self) -> TlweSample {
+        TlweSample {
+            a: expand_mask(&self.seed, self.params.n),
+            b: self.b,
+            params: self.params.clone(),
+            noise: NoiseLevel::Fresh,
+            estimate: NoiseEstimate::fresh(self.params.stddev),
+            namespace: None,
+        }
+    }
+
+    /// Bytes of seed and body, against `TlweParams::ciphertext_bytes` uncompressed
+    pub fn size_bytes(&self) -> usize {
+        SEED_BYTES + std::mem::size_of::<Torus>()
+    }
+}
+
+impl Canonical for CompressedTlweSample {
+    const TAG: u8 = 11;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_params(out, &self.params);
+        out.extend_from_slice(&self.seed);
+        self.b.write(out);
+    }
+
+    fn read(input: &mut CanonicalReader<'_>) -> Result<Self, CanonicalError> {
+        let params: TlweParams = read_params(input)?;
+        let seed: Seed = input.bytes(SEED_BYTES)?.try_into().expect("seed length");
+        Ok(CompressedTlweSample { seed, b: Torus::read(input)?, params })
+    }
+}
+
+impl Parameterized for CompressedTlweSample {
+    type Params = TlweParams;
+
+    fn params(&self) -> &TlweParams {
+        &self.params
+    }
+}
+
+/// Bootstrapping key stored as one seed and the body of every TRGSW row.
+/// Rows are numbered by key coefficient, then component, then gadget level,
+/// and row `r` has the mask of stream `r`.
+#[derive(Debug, Clone)]
+pub struct SeededBootstrappingKey {
+    pub seed: Seed,
+    pub bodies: Vec<TorusPolynomial>,
+    pub params: TfheParams,
+}
+
+impl SeededBootstrappingKey {
+    #[cfg(feature = "client")]
+    pub fn generate(sk: &TfheSecretKey) -> Self {
+        Self::generate_with_rng(sk, &mut default_entropy())
+    }
+
+    /// `generate` with the seed and the noise drawn from `rng`
+    #[cfg(feature = "client")]
+    pub fn generate_with_rng<R: EntropySource + ?Sized>(sk: &TfheSecretKey, rng: &mut R) -> Self {
+        let ring = sk.params.trlwe_params();
+        let tgsw = &sk.params.tgsw_params;
+        let mut seed: Seed = [0; SEED_BYTES];
+        rng.fill_bytes(&mut seed);
+
+        let mut bodies = Vec::with_capacity(Self::rows(&sk.params));
+        for &s in &sk.tlwe_key.coeffs {
+            for i in 0..=ring.k {
+                for j in 0..tgsw.l {
+                    let weight = Torus::new((-(((j as u32 + 1) * tgsw.bg_bit) as f64)).exp2());
+                    // The gadget term of mask component `i`, moved into the body
+                    let mut message = TorusPolynomial::zero(ring.N);
+                    if i < ring.k {
+                        for (c, &key) in message.coeffs.iter_mut().zip(&sk.trlwe_key.polys[i]) {
+                            *c = weight.mul_int(-(s as i64) * key as i64);
+                        }
+                    } else {
+                        message.coeffs[0] = weight.mul_int(s as i64);
+                    }
+                    let mask = Self::mask(&seed, bodies.len() as u64, &ring);
+                    bodies.push(TrlweSample::encrypt_with_mask(mask, &message, &sk.trlwe_key, rng).b);
+                }
+            }
+        }
+
+        SeededBootstrappingKey { seed, bodies, params: sk.params.clone() }
+    }
+
+    /// The full key, every mask regenerated from the seed
+    pub fn expand(&self) -> BootstrappingKey {
+        let ring = self.params.trlwe_params();
+        let tgsw = &self.params.tgsw_params;
+        let mut bodies = self.bodies.iter().enumerate();
+
+        let bk = (0..self.params.n)
+            .map(|_| {
+                let rows = (0..=ring.k)
+                    .map(|_| {
+                        (0..tgsw.l)
+                            .map(|_| {
+                                let (r, b) = bodies.next().expect("one body per row");
+                                TrlweSample {
+                                    a: Self::mask(&self.seed, r as u64, &ring),
+                                    b: b.clone(),
+                                    params: ring.clone(),
+                                    noise: NoiseLevel::Fresh,
+                                    estimate: NoiseEstimate::fresh(ring.stddev),
+                                }
+                            })
+                            .collect()
+                    })
+                    .collect();
+                TrgswSample { rows, params: tgsw.clone() }
+            })
+            .collect();
+
+        BootstrappingKey::new(bk, self.params.n, tgsw.clone(), ring)
+    }
+
+    /// Bytes of seed and bodies, against `n` times
+    /// `TgswParams::sample_bytes` for the expanded key
+    pub fn size_bytes(&self) -> usize {
+        SEED_BYTES + self.bodies.len() * self.params.N * std::mem::size_of::<Torus>()
+    }
+
+    fn rows(params: &TfheParams) -> usize {
+        params.n * (params.k + 1) * params.tgsw_params.l
+    }
+
+    fn mask(seed: &Seed, row: u64, ring: &TrlweParams) -> Vec<TorusPolynomial> {
+        expand_stream(seed, row, ring.k * ring.N)
+            .chunks(ring.N)
+            .map(|coeffs| TorusPolynomial { coeffs: coeffs.to_vec() })
+            .collect()
+    }
+}
+
+impl Canonical for SeededBootstrappingKey {
+    const TAG: u8 = 12;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_params(out, &self.params);
+        out.extend_from_slice(&self.seed);
+        write_u64(out, self.bodies.len() as u64);
+        for body in &self.bodies {
+            for c in &body.coeffs {
+                c.write(out);
+            }
+        }
+    }
+
+    fn read(input: &mut CanonicalReader<'_>) -> Result<Self, CanonicalError> {
+        let params: TfheParams = read_params(input)?;
+        let seed: Seed = input.bytes(SEED_BYTES)?.try_into().expect("seed length");
+        if input.usize()? != Self::rows(&params) {
+            return Err(CanonicalError::Invalid("row count"));
+        }
+        let bodies = (0..Self::rows(&params))
+            .map(|_| {
+                let coeffs = (0..params.N).map(|_| Torus::read(input)).collect::<Result<_, _>>()?;
+                Ok(TorusPolynomial { coeffs })
+            })
+            .collect::<Result<_, CanonicalError>>()?;
+        Ok(SeededBootstrappingKey { seed, bodies, params })
+    }
+}
+
+impl Parameterized for SeededBootstrappingKey {
+    type Params = TfheParams;
+
+    fn params(&self) -> &TfheParams {
+        &self.params
+    }
+}
+
+#[cfg(feature = "server")]
+impl TfheCloudKey {
+    /// Cloud key from a transferred seeded bootstrapping key and the key
+    /// switching key
+    pub fn from_seeded(bsk: &SeededBootstrappingKey, key_switching_key: TlweKeySwitchKey) -> Self {
+        TfheCloudKey {
+            bootstrapping_key: bsk.expand(),
+            key_switching_key,
+            hooks: Default::default(),
+            refresh: None,
+            namespace: None,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "client", feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::tfhe::{TfheEncoder, TfheGates};
+    use crate::tfhe::fixtures::small_params;
+
+    #[test]
+    fn test_compressed_samples_decrypt() {
+        let params = TlweParams { n: 630, stddev: 1e-9 };
+        let sk = TlweSecretKey::generate_binary(params.clone());
+
+        for value in [0.125, 0.625] {
+            let compressed = CompressedTlweSample::encrypt(&Torus::new(value), &sk);
+            assert!(compressed.size_bytes() * 100 < params.ciphertext_bytes());
+
+            let bytes = compressed.to_canonical_bytes();
+            let received = CompressedTlweSample::from_canonical_bytes(&bytes).unwrap();
+            assert_eq!(received, compressed);
+            let sample = received.decompress();
+            assert_eq!(sample.decrypt_binary(&sk), value > 0.5);
+            assert_eq!(sample.a, expand_mask(&compressed.seed, 630));
+        }
+
+        // The expansion is fixed: the same seed gives the same mask
+        assert_eq!(expand_mask(&[7; SEED_BYTES], 3), expand_mask(&[7; SEED_BYTES], 3));
+        assert_ne!(expand_mask(&[7; SEED_BYTES], 3), expand_mask(&[8; SEED_BYTES], 3));
+    }
+
+    #[test]
+    fn test_seeded_bootstrapping_key() {
+        let params = small_params(64);
+        let sk = TfheSecretKey::generate(params.clone());
+        let seeded = SeededBootstrappingKey::generate(&sk);
+        assert_eq!(seeded.size_bytes() - SEED_BYTES, params.n * params.tgsw_params.sample_bytes(64, 1) / 2);
+
+        let received = SeededBootstrappingKey::from_canonical_bytes(&seeded.to_canonical_bytes()).unwrap();
+        let ks = params.key_switch_params();
+        let ksk = TlweKeySwitchKey::generate(&sk.extracted_key(), &sk.tlwe_key, ks.t, ks.base_bit);
+        let ck = TfheCloudKey::from_seeded(&received, ksk);
+
+        for m in 0..8 {
+            let input = TfheEncoder::encode_int(m, 8, &sk);
+            let output = TfheGates::apply_lut(&input, |x| 7 - x, 8, &ck);
+            assert_eq!(TfheEncoder::decode_int(&output, 8, &sk), 7 - m);
+        }
+    }
+}
@@ -0,0 +1,10 @@
+//! Server-side surface: cloud keys, gates and homomorphic operations.
+//!
+//! Available with the `server` feature. A build with only this feature contains
+//! no secret-key types and no encryption or decryption code paths.
+
+pub use crate::api::{set_server_key, unset_server_key, ServerKey};
+pub use crate::evaluator::TfheEvaluator;
+pub use crate::hooks::{BootstrapHook, BootstrapHooks, BootstrapInfo};
+pub use crate::operations::HomomorphicOps;
+pub use crate::tfhe::{TfheCloudKey, TfheGates};
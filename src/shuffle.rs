@@ -0,0 +1,172 @@
+//! Oblivious permutation of encrypted items through a Benes network.
+//!
+//! The client picks a permutation, computes the switch settings with
+//! `benes_controls` and sends them encrypted; the server routes the items
+//! through `shuffle` without learning where any of them ended up.
+
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::IntegerOps;
+
+/// Control bits of a Benes network over `n` items, `n` a power of two
+pub fn benes_switches(n: usize) -> usize {
+    assert!(n.is_power_of_two(), "Benes networks need a power-of-two size");
+    if n == 1 {
+        0
+    } else {
+        n / 2 * (2 * n.trailing_zeros() as usize - 1)
+    }
+}
+
+/// Switch settings sending input `i` to output `destinations[i]`, in the
+/// order `shuffle` consumes them, computed with the looping algorithm
+pub fn benes_controls(destinations: &[usize]) -> Vec<bool> {
+    let n = destinations.len();
+    assert!(n.is_power_of_two(), "Benes networks need a power-of-two size");
+
+    let mut seen = vec![false; n];
+    for &d in destinations {
+        assert!(d < n && !seen[d], "destinations must be a permutation");
+        seen[d] = true;
+    }
+
+    let mut controls = Vec::with_capacity(benes_switches(n));
+    route(destinations, &mut controls);
+    controls
+}
+
+fn route(dest: &[usize], controls: &mut Vec<bool>) {
+    let n = dest.len();
+    if n == 1 {
+        return;
+    }
+    if n == 2 {
+        controls.push(dest[0] == 1);
+        return;
+    }
+
+    let mut source = vec![0; n];
+    for (i, &d) in dest.iter().enumerate() {
+        source[d] = i;
+    }
+
+    // Inputs sharing an input switch, and inputs bound for the same output
+    // switch, must take different subnetworks; walk each constraint cycle
+    let mut lower: Vec<Option<bool>> = vec![None; n];
+    for start in (0..n).step_by(2) {
+        let mut x = start;
+        while lower[x].is_none() {
+            lower[x] = Some(false);
+            lower[x ^ 1] = Some(true);
+            x = source[dest[x ^ 1] ^ 1];
+        }
+    }
+    let lower: Vec<bool> = lower.into_iter().map(|s| s.expect("every input routed")).collect();
+
+    let half = n / 2;
+    let mut top = vec![0; half];
+    let mut bottom = vec![0; half];
+    for i in 0..half {
+        let (up, down) = if lower[2 * i] { (2 * i + 1, 2 * i) } else { (2 * i, 2 * i + 1) };
+        controls.push(lower[2 * i]);
+        top[i] = dest[up] / 2;
+        bottom[i] = dest[down] / 2;
+    }
+
+    route(&top, controls);
+    route(&bottom, controls);
+
+    // Output switch `j` receives top output `j` as its upper input
+    for j in 0..half {
+        controls.push(lower[source[2 * j]]);
+    }
+}
+
+/// Routes `items` through the network configured by `controls`
+pub fn shuffle<E: BooleanEvaluator>(items: &[Vec<E::Bit>], controls: &[E::Bit], eval: &E) -> Vec<Vec<E::Bit>> {
+    assert_eq!(controls.len(), benes_switches(items.len()), "wrong number of control bits");
+    let mut controls = controls.iter();
+    apply(items, &mut controls, eval)
+}
+
+fn switch<E: BooleanEvaluator>(c: &E::Bit, a: &[E::Bit], b: &[E::Bit], eval: &E) -> (Vec<E::Bit>, Vec<E::Bit>) {
+    (IntegerOps::mux(c, b, a, eval), IntegerOps::mux(c, a, b, eval))
+}
+
+fn apply<'a, E: BooleanEvaluator>(
+    items: &[Vec<E::Bit>],
+    controls: &mut impl Iterator<Item = &'a E::Bit>,
+    eval: &E,
+) -> Vec<Vec<E::Bit>>
+where
+    E::Bit: 'a,
+{
+    let n = items.len();
+    if n == 1 {
+        return items.to_vec();
+    }
+    if n == 2 {
+        let (a, b) = switch(controls.next().expect("counted"), &items[0], &items[1], eval);
+        return vec![a, b];
+    }
+
+    let (mut top, mut bottom) = (Vec::with_capacity(n / 2), Vec::with_capacity(n / 2));
+    for pair in items.chunks(2) {
+        let (up, down) = switch(controls.next().expect("counted"), &pair[0], &pair[1], eval);
+        top.push(up);
+        bottom.push(down);
+    }
+
+    let top = apply(&top, controls, eval);
+    let bottom = apply(&bottom, controls, eval);
+
+    top.iter()
+        .zip(bottom.iter())
+        .flat_map(|(up, down)| {
+            let (a, b) = switch(controls.next().expect("counted"), up, down, eval);
+            [a, b]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    fn value(bits: &[bool]) -> u64 {
+        bits.iter().enumerate().fold(0, |acc, (i, &b)| acc | ((b as u64) << i))
+    }
+
+    #[test]
+    fn test_shuffle_realizes_permutation() {
+        let eval = ClearEvaluator;
+        let destinations = [5, 0, 7, 2, 6, 1, 3, 4];
+        let items: Vec<Vec<bool>> = (0..8).map(|v| IntegerOps::constant(v * 10, 7, &eval)).collect();
+
+        let controls = benes_controls(&destinations);
+        assert_eq!(controls.len(), benes_switches(8));
+
+        let output = shuffle(&items, &controls, &eval);
+        for (i, &d) in destinations.iter().enumerate() {
+            assert_eq!(value(&output[d]), i as u64 * 10);
+        }
+    }
+
+    #[test]
+    fn test_every_permutation_of_four() {
+        let eval = ClearEvaluator;
+        let items: Vec<Vec<bool>> = (0..4).map(|v| IntegerOps::constant(v, 2, &eval)).collect();
+
+        for a in 0..4 {
+            for b in (0..4).filter(|&b| b != a) {
+                for c in (0..4).filter(|&c| c != a && c != b) {
+                    let destinations = [a, b, c, 6 - a - b - c];
+                    let output = shuffle(&items, &benes_controls(&destinations), &eval);
+                    for (i, &d) in destinations.iter().enumerate() {
+                        assert_eq!(value(&output[d]), i as u64);
+                    }
+                }
+            }
+        }
+    }
+}
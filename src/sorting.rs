@@ -0,0 +1,110 @@
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::IntegerOps;
+
+/// The `k` largest values in descending order, with optional one-hot positions
+#[derive(Debug, Clone)]
+pub struct TopK<B> {
+    pub values: Vec<Vec<B>>,
+    /// `indices[j][i]` is set when `values[j]` came from input `i`
+    pub indices: Vec<Vec<B>>,
+}
+
+pub struct SortingOps;
+
+impl SortingOps {
+    /// Orders a pair as `(max, min)`; ties keep `a` first
+    pub fn compare_exchange<E: BooleanEvaluator>(
+        a: &[E::Bit],
+        b: &[E::Bit],
+        eval: &E,
+    ) -> (Vec<E::Bit>, Vec<E::Bit>, E::Bit) {
+        let swap = IntegerOps::less_than(a, b, eval);
+        let max = IntegerOps::mux(&swap, b, a, eval);
+        let min = IntegerOps::mux(&swap, a, b, eval);
+        (max, min, swap)
+    }
+
+    /// Selects the `k` largest values with `k` bubble passes of a sorting network,
+    /// i.e. about `k * n` comparators instead of the `n log² n` of a full sort.
+    ///
+    /// When `with_indices` is set, a one-hot position vector travels with every
+    /// value through the same swaps.
+    pub fn top_k<E: BooleanEvaluator>(
+        values: &[Vec<E::Bit>],
+        k: usize,
+        with_indices: bool,
+        eval: &E,
+    ) -> TopK<E::Bit> {
+        let n = values.len();
+        let k = k.min(n);
+
+        let mut items: Vec<Vec<E::Bit>> = values.to_vec();
+        let mut tags: Vec<Vec<E::Bit>> = if with_indices {
+            (0..n).map(|i| (0..n).map(|j| eval.constant(i == j)).collect()).collect()
+        } else {
+            vec![vec![]; n]
+        };
+
+        for round in 0..k {
+            for i in (round + 1..n).rev() {
+                let (max, min, swap) = Self::compare_exchange(&items[i - 1], &items[i], eval);
+                items[i - 1] = max;
+                items[i] = min;
+
+                if with_indices {
+                    let upper = IntegerOps::mux(&swap, &tags[i], &tags[i - 1], eval);
+                    let lower = IntegerOps::mux(&swap, &tags[i - 1], &tags[i], eval);
+                    tags[i - 1] = upper;
+                    tags[i] = lower;
+                }
+            }
+        }
+
+        items.truncate(k);
+        tags.truncate(if with_indices { k } else { 0 });
+        TopK { values: items, indices: tags }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    fn value(bits: &[bool]) -> u64 {
+        bits.iter().enumerate().fold(0, |acc, (i, &b)| acc | ((b as u64) << i))
+    }
+
+    #[test]
+    fn test_top_k_with_indices() {
+        let eval = ClearEvaluator;
+        let inputs = [7u64, 3, 12, 9, 12, 1];
+        let encoded: Vec<Vec<bool>> = inputs.iter()
+            .map(|&v| IntegerOps::constant(v, 4, &eval))
+            .collect();
+
+        let top = SortingOps::top_k(&encoded, 3, true, &eval);
+
+        let values: Vec<u64> = top.values.iter().map(|v| value(v)).collect();
+        assert_eq!(values, vec![12, 12, 9]);
+
+        let positions: Vec<usize> = top.indices.iter()
+            .map(|onehot| onehot.iter().position(|&b| b).unwrap())
+            .collect();
+        assert_eq!(positions, vec![2, 4, 3]);
+        assert!(top.indices.iter().all(|onehot| onehot.iter().filter(|&&b| b).count() == 1));
+    }
+
+    #[test]
+    fn test_top_k_clamps_k() {
+        let eval = ClearEvaluator;
+        let encoded: Vec<Vec<bool>> = [2u64, 5].iter()
+            .map(|&v| IntegerOps::constant(v, 3, &eval))
+            .collect();
+
+        let top = SortingOps::top_k(&encoded, 4, false, &eval);
+        assert_eq!(top.values.len(), 2);
+        assert!(top.indices.is_empty());
+        assert_eq!(value(&top.values[0]), 5);
+    }
+}
@@ -0,0 +1,321 @@
+//! Competing implementations of the same gate, verified and timed side by side.
+//!
+//! Whether a direct OR beats NOT/NAND composition, or a direct MUX beats its
+//! AND/OR expansion, depends on the backend and the parameter set. A
+//! `StrategyRegistry` holds every candidate for each gate, `compare` checks
+//! each one against the full truth table through real encryption and times
+//! it, and `TunedEvaluator` dispatches every gate to the fastest correct
+//! candidate of the resulting `StrategyProfile`.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use crate::evaluator::BooleanEvaluator;
+
+/// Logical gate a strategy implements
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GateOp {
+    Not,
+    And,
+    Or,
+    Xor,
+    Nand,
+    Mux,
+}
+
+impl GateOp {
+    pub const ALL: [GateOp; 6] = [GateOp::Not, GateOp::And, GateOp::Or, GateOp::Xor, GateOp::Nand, GateOp::Mux];
+
+    pub fn arity(self) -> usize {
+        match self {
+            GateOp::Not => 1,
+            GateOp::Mux => 3,
+            _ => 2,
+        }
+    }
+
+    /// Reference truth table
+    pub fn apply(self, inputs: &[bool]) -> bool {
+        match self {
+            GateOp::Not => !inputs[0],
+            GateOp::And => inputs[0] && inputs[1],
+            GateOp::Or => inputs[0] || inputs[1],
+            GateOp::Xor => inputs[0] ^ inputs[1],
+            GateOp::Nand => !(inputs[0] && inputs[1]),
+            GateOp::Mux => if inputs[0] { inputs[1] } else { inputs[2] },
+        }
+    }
+}
+
+type GateFn<E> = Box<dyn Fn(&E, &[<E as BooleanEvaluator>::Bit]) -> <E as BooleanEvaluator>::Bit + Send + Sync>;
+
+struct Strategy<E: BooleanEvaluator> {
+    op: GateOp,
+    name: &'static str,
+    run: GateFn<E>,
+}
+
+/// Outcome of verifying and timing one strategy
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrategyReport {
+    pub op: GateOp,
+    pub name: &'static str,
+    /// Matched the truth table on every input combination
+    pub correct: bool,
+    pub mean_time: Duration,
+}
+
+/// Candidate implementations of each gate for one backend
+pub struct StrategyRegistry<E: BooleanEvaluator> {
+    strategies: Vec<Strategy<E>>,
+}
+
+impl<E: BooleanEvaluator> Default for StrategyRegistry<E> {
+    fn default() -> Self {
+        StrategyRegistry { strategies: Vec::new() }
+    }
+}
+
+impl<E: BooleanEvaluator> StrategyRegistry<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The evaluator's own gates, named "direct", and their textbook
+    /// compositions from the other gates
+    pub fn with_builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register(GateOp::Not, "direct", |e: &E, x: &[E::Bit]| e.not(&x[0]));
+        registry.register(GateOp::Not, "nand_self", |e: &E, x: &[E::Bit]| e.nand(&x[0], &x[0]));
+        registry.register(GateOp::And, "direct", |e: &E, x: &[E::Bit]| e.and(&x[0], &x[1]));
+        registry.register(GateOp::And, "not_nand", |e: &E, x: &[E::Bit]| e.not(&e.nand(&x[0], &x[1])));
+        registry.register(GateOp::Or, "direct", |e: &E, x: &[E::Bit]| e.or(&x[0], &x[1]));
+        registry.register(GateOp::Or, "nand_of_nots", |e: &E, x: &[E::Bit]| {
+            e.nand(&e.not(&x[0]), &e.not(&x[1]))
+        });
+        registry.register(GateOp::Xor, "direct", |e: &E, x: &[E::Bit]| e.xor(&x[0], &x[1]));
+        registry.register(GateOp::Xor, "or_and_nand", |e: &E, x: &[E::Bit]| {
+            e.and(&e.or(&x[0], &x[1]), &e.nand(&x[0], &x[1]))
+        });
+        registry.register(GateOp::Nand, "direct", |e: &E, x: &[E::Bit]| e.nand(&x[0], &x[1]));
+        registry.register(GateOp::Nand, "not_and", |e: &E, x: &[E::Bit]| e.not(&e.and(&x[0], &x[1])));
+        registry.register(GateOp::Mux, "direct", |e: &E, x: &[E::Bit]| e.mux(&x[0], &x[1], &x[2]));
+        registry.register(GateOp::Mux, "and_or", |e: &E, x: &[E::Bit]| {
+            e.or(&e.and(&x[0], &x[1]), &e.and(&e.not(&x[0]), &x[2]))
+        });
+        registry
+    }
+
+    /// Adds a candidate for `op`; it receives `op.arity()` operands
+    pub fn register<F>(&mut self, op: GateOp, name: &'static str, run: F)
+    where
+        F: Fn(&E, &[E::Bit]) -> E::Bit + Send + Sync + 'static,
+    {
+        self.strategies.push(Strategy { op, name, run: Box::new(run) });
+    }
+
+    pub fn len(&self) -> usize {
+        self.strategies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strategies.is_empty()
+    }
+
+    /// Verifies every strategy on the full truth table and times `rounds`
+    /// evaluations of each, with operands from `encrypt` and results read
+    /// back with `decrypt`
+    pub fn compare<Enc, Dec>(&self, eval: &E, encrypt: Enc, decrypt: Dec, rounds: usize) -> Vec<StrategyReport>
+    where
+        Enc: Fn(bool) -> E::Bit,
+        Dec: Fn(&E::Bit) -> bool,
+    {
+        let rounds = rounds.max(1);
+
+        self.strategies.iter()
+            .map(|strategy| {
+                let arity = strategy.op.arity();
+                let mut elapsed = Duration::ZERO;
+                let mut evaluations = 0u32;
+                let mut correct = true;
+
+                for _ in 0..rounds {
+                    for pattern in 0..1usize << arity {
+                        let plain: Vec<bool> = (0..arity).map(|i| (pattern >> i) & 1 == 1).collect();
+                        let operands: Vec<E::Bit> = plain.iter().map(|&b| encrypt(b)).collect();
+
+                        let start = Instant::now();
+                        let result = (strategy.run)(eval, &operands);
+                        elapsed += start.elapsed();
+                        evaluations += 1;
+
+                        correct &= decrypt(&result) == strategy.op.apply(&plain);
+                    }
+                }
+
+                StrategyReport {
+                    op: strategy.op,
+                    name: strategy.name,
+                    correct,
+                    mean_time: elapsed / evaluations,
+                }
+            })
+            .collect()
+    }
+
+    /// Fastest correct strategy per gate; gates without one use the evaluator directly
+    pub fn select(reports: &[StrategyReport]) -> StrategyProfile {
+        let mut choices: HashMap<GateOp, &StrategyReport> = HashMap::new();
+        for report in reports.iter().filter(|r| r.correct) {
+            let best = choices.entry(report.op).or_insert(report);
+            if report.mean_time < best.mean_time {
+                *best = report;
+            }
+        }
+        StrategyProfile {
+            choices: choices.into_iter().map(|(op, report)| (op, report.name)).collect(),
+        }
+    }
+
+    /// Evaluator running the strategies chosen by `profile`
+    pub fn tuned<'a>(&'a self, eval: &'a E, profile: &StrategyProfile) -> TunedEvaluator<'a, E> {
+        let chosen = GateOp::ALL
+            .iter()
+            .map(|&op| {
+                profile.choice(op).and_then(|name| {
+                    self.strategies.iter().find(|s| s.op == op && s.name == name)
+                })
+            })
+            .collect();
+        TunedEvaluator { eval, chosen }
+    }
+}
+
+/// Strategy chosen for each gate under one backend and parameter set
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StrategyProfile {
+    choices: HashMap<GateOp, &'static str>,
+}
+
+impl StrategyProfile {
+    pub fn choice(&self, op: GateOp) -> Option<&'static str> {
+        self.choices.get(&op).copied()
+    }
+}
+
+/// Evaluator dispatching each gate to the strategy of a `StrategyProfile`
+pub struct TunedEvaluator<'a, E: BooleanEvaluator> {
+    eval: &'a E,
+    /// Indexed like `GateOp::ALL`
+    chosen: Vec<Option<&'a Strategy<E>>>,
+}
+
+impl<'a, E: BooleanEvaluator> TunedEvaluator<'a, E> {
+    fn strategy(&self, op: GateOp) -> Option<&'a Strategy<E>> {
+        let index = GateOp::ALL.iter().position(|&o| o == op).expect("every gate is listed");
+        self.chosen[index]
+    }
+}
+
+impl<E: BooleanEvaluator> BooleanEvaluator for TunedEvaluator<'_, E> {
+    type Bit = E::Bit;
+
+    fn constant(&self, value: bool) -> E::Bit {
+        self.eval.constant(value)
+    }
+
+    fn not(&self, a: &E::Bit) -> E::Bit {
+        match self.strategy(GateOp::Not) {
+            Some(strategy) => (strategy.run)(self.eval, std::slice::from_ref(a)),
+            None => self.eval.not(a),
+        }
+    }
+
+    fn and(&self, a: &E::Bit, b: &E::Bit) -> E::Bit {
+        match self.strategy(GateOp::And) {
+            Some(strategy) => (strategy.run)(self.eval, &[a.clone(), b.clone()]),
+            None => self.eval.and(a, b),
+        }
+    }
+
+    fn or(&self, a: &E::Bit, b: &E::Bit) -> E::Bit {
+        match self.strategy(GateOp::Or) {
+            Some(strategy) => (strategy.run)(self.eval, &[a.clone(), b.clone()]),
+            None => self.eval.or(a, b),
+        }
+    }
+
+    fn xor(&self, a: &E::Bit, b: &E::Bit) -> E::Bit {
+        match self.strategy(GateOp::Xor) {
+            Some(strategy) => (strategy.run)(self.eval, &[a.clone(), b.clone()]),
+            None => self.eval.xor(a, b),
+        }
+    }
+
+    fn nand(&self, a: &E::Bit, b: &E::Bit) -> E::Bit {
+        match self.strategy(GateOp::Nand) {
+            Some(strategy) => (strategy.run)(self.eval, &[a.clone(), b.clone()]),
+            None => self.eval.nand(a, b),
+        }
+    }
+
+    fn mux(&self, s: &E::Bit, a: &E::Bit, b: &E::Bit) -> E::Bit {
+        match self.strategy(GateOp::Mux) {
+            Some(strategy) => (strategy.run)(self.eval, &[s.clone(), a.clone(), b.clone()]),
+            None => self.eval.mux(s, a, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+    use crate::integer::IntegerOps;
+
+    #[test]
+    fn test_compare_and_select() {
+        let eval = ClearEvaluator;
+        let mut registry = StrategyRegistry::with_builtin();
+        registry.register(GateOp::Or, "broken", |e: &ClearEvaluator, x: &[bool]| e.and(&x[0], &x[1]));
+        assert_eq!(registry.len(), 13);
+
+        let reports = registry.compare(&eval, |b| b, |b| *b, 3);
+        assert_eq!(reports.len(), 13);
+        for report in &reports {
+            assert_eq!(report.correct, report.name != "broken", "{:?}", report);
+        }
+
+        let profile = StrategyRegistry::<ClearEvaluator>::select(&reports);
+        for op in GateOp::ALL {
+            let choice = profile.choice(op).unwrap();
+            assert_ne!(choice, "broken");
+            let fastest = reports.iter()
+                .filter(|r| r.op == op && r.correct)
+                .map(|r| r.mean_time)
+                .min()
+                .unwrap();
+            let chosen = reports.iter().find(|r| r.op == op && r.name == choice).unwrap();
+            assert_eq!(chosen.mean_time, fastest);
+        }
+    }
+
+    #[test]
+    fn test_tuned_evaluator_dispatches_to_choice() {
+        let eval = ClearEvaluator;
+        let registry = StrategyRegistry::with_builtin();
+        let reports: Vec<StrategyReport> = registry.compare(&eval, |b| b, |b| *b, 1)
+            .into_iter()
+            .filter(|r| r.name != "direct")
+            .collect();
+        let profile = StrategyRegistry::<ClearEvaluator>::select(&reports);
+        assert_eq!(profile.choice(GateOp::Mux), Some("and_or"));
+
+        // Composed gates end to end through integer arithmetic
+        let tuned = registry.tuned(&eval, &profile);
+        let a = IntegerOps::constant(200, 8, &tuned);
+        let b = IntegerOps::constant(99, 8, &tuned);
+        let sum = IntegerOps::add_mod(&a, &b, &tuned);
+        let value = sum.iter().enumerate().fold(0, |acc, (i, &bit)| acc | ((bit as u64) << i));
+        assert_eq!(value, (200 + 99) % 256);
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::VecDeque;
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::{CmpOp, IntegerOps};
+
+/// Shift register over an encrypted bit stream that raises a flag whenever the
+/// last `k` bits were all set
+#[derive(Debug, Clone)]
+pub struct RunDetector<B> {
+    pub k: usize,
+    window: VecDeque<B>,
+}
+
+impl<B: Clone> RunDetector<B> {
+    pub fn new(k: usize) -> Self {
+        assert!(k > 0, "run length must be positive");
+        RunDetector { k, window: VecDeque::with_capacity(k) }
+    }
+
+    /// Shifts `bit` in and returns the encrypted flag for the current position.
+    ///
+    /// Until `k` bits have been seen the flag is a constant false, which leaks
+    /// nothing beyond the public stream length.
+    pub fn push<E: BooleanEvaluator<Bit = B>>(&mut self, bit: B, eval: &E) -> B {
+        if self.window.len() == self.k {
+            self.window.pop_front();
+        }
+        self.window.push_back(bit);
+
+        if self.window.len() < self.k {
+            return eval.constant(false);
+        }
+        let window: Vec<B> = self.window.iter().cloned().collect();
+        IntegerOps::and_all(&window, eval)
+    }
+}
+
+/// Per-position flags and whether any run of `k` ones occurred at all
+#[derive(Debug, Clone)]
+pub struct RunDetection<B> {
+    pub flags: Vec<B>,
+    pub detected: B,
+}
+
+/// Scans `bits` for `k` consecutive ones
+pub fn detect_run<E: BooleanEvaluator>(bits: &[E::Bit], k: usize, eval: &E) -> RunDetection<E::Bit> {
+    let mut detector = RunDetector::new(k);
+    let flags: Vec<E::Bit> = bits.iter()
+        .map(|bit| detector.push(bit.clone(), eval))
+        .collect();
+
+    // Positions before the window fills are constant false and need no gates
+    let detected = IntegerOps::or_all(&flags[flags.len().min(k - 1)..], eval);
+    RunDetection { flags, detected }
+}
+
+/// Running maximum of encrypted integers that arrive one at a time, and
+/// optionally the position of the first occurrence of it.
+///
+/// Each value costs one comparison against the current maximum and a
+/// `mux` per bit, so nothing is buffered; over `n` values the work equals
+/// a sequential reduction rather than the `max_all` tree, trading depth for
+/// constant memory.
+#[derive(Debug, Clone)]
+pub struct EncryptedMaxTracker<B> {
+    pub signed: bool,
+    /// Width of the encrypted index, `None` when positions are not tracked
+    pub index_width: Option<usize>,
+    max: Option<Vec<B>>,
+    index: Option<Vec<B>>,
+    seen: u64,
+}
+
+impl<B: Clone> EncryptedMaxTracker<B> {
+    pub fn new(signed: bool) -> Self {
+        EncryptedMaxTracker { signed, index_width: None, max: None, index: None, seen: 0 }
+    }
+
+    /// Also tracks the position of the maximum as an `index_width`-bit
+    /// integer; at most `2^index_width` values can be pushed
+    pub fn with_index(signed: bool, index_width: usize) -> Self {
+        EncryptedMaxTracker { index_width: Some(index_width), ..Self::new(signed) }
+    }
+
+    /// Folds `value` into the running maximum; ties keep the earlier one
+    pub fn push<E: BooleanEvaluator<Bit = B>>(&mut self, value: Vec<B>, eval: &E) {
+        // Checked first, so a rejected value leaves the tracker as it was
+        let position = self.seen;
+        if let Some(width) = self.index_width {
+            assert!(width >= 64 || position < 1 << width, "position {} does not fit {} index bits", position, width);
+        }
+        if let Some(max) = &self.max {
+            assert_eq!(max.len(), value.len(), "values must have the same width");
+        }
+        self.seen += 1;
+
+        let Some(max) = self.max.take() else {
+            self.index = self.index_width.map(|width| IntegerOps::constant(0, width, eval));
+            self.max = Some(value);
+            return;
+        };
+
+        let greater = if self.signed {
+            IntegerOps::less_than_signed(&max, &value, eval)
+        } else {
+            IntegerOps::less_than(&max, &value, eval)
+        };
+        self.max = Some(IntegerOps::mux(&greater, &value, &max, eval));
+        if let (Some(index), Some(width)) = (self.index.take(), self.index_width) {
+            let here = IntegerOps::constant(position, width, eval);
+            self.index = Some(IntegerOps::mux(&greater, &here, &index, eval));
+        }
+    }
+
+    /// Values pushed so far
+    pub fn count(&self) -> u64 {
+        self.seen
+    }
+
+    /// The maximum so far, `None` before the first value
+    pub fn max(&self) -> Option<&[B]> {
+        self.max.as_deref()
+    }
+
+    /// Position of the maximum, when tracked and a value has arrived
+    pub fn index(&self) -> Option<&[B]> {
+        self.index.as_deref()
+    }
+}
+
+/// Comparison of two unsigned integers streamed most significant bit
+/// first, one encrypted pair at a time.
+///
+/// The state is two encrypted bits whatever the width: whether the values
+/// differed yet, and whether `a < b` at the first difference. A pair costs
+/// four gates, so values too wide for `IntegerOps::compare` to hold at once,
+/// such as 256-bit hashes, can be compared as their bits arrive.
+#[derive(Debug, Clone)]
+pub struct StreamingComparator<B> {
+    /// Differed yet, and `a < b` at the first difference
+    state: Option<(B, B)>,
+    seen: u64,
+}
+
+impl<B: Clone> Default for StreamingComparator<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: Clone> StreamingComparator<B> {
+    pub fn new() -> Self {
+        StreamingComparator { state: None, seen: 0 }
+    }
+
+    /// Folds in the next bits of `a` and `b`; earlier pairs are more significant
+    pub fn push<E: BooleanEvaluator<Bit = B>>(&mut self, a: &B, b: &B, eval: &E) {
+        let differ = eval.xor(a, b);
+        let less_here = eval.and(&eval.not(a), b);
+        self.seen += 1;
+        self.state = Some(match self.state.take() {
+            Some((decided, less)) => (eval.or(&decided, &differ), eval.mux(&decided, &less, &less_here)),
+            None => (differ, less_here),
+        });
+    }
+
+    /// Bit pairs pushed so far
+    pub fn count(&self) -> u64 {
+        self.seen
+    }
+
+    /// `a op b` over the bits pushed so far; empty streams are equal
+    pub fn result<E: BooleanEvaluator<Bit = B>>(&self, op: CmpOp, eval: &E) -> B {
+        let (decided, less) = match &self.state {
+            Some(state) => state.clone(),
+            None => (eval.constant(false), eval.constant(false)),
+        };
+        let greater = || eval.and(&decided, &eval.not(&less));
+        match op {
+            CmpOp::Eq => eval.not(&decided),
+            CmpOp::Ne => decided,
+            CmpOp::Lt => less,
+            CmpOp::Ge => eval.not(&less),
+            CmpOp::Gt => greater(),
+            CmpOp::Le => eval.not(&greater()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    fn stream(pattern: &str) -> Vec<bool> {
+        pattern.chars().map(|c| c == '1').collect()
+    }
+
+    #[test]
+    fn test_detect_run() {
+        let result = detect_run(&stream("0110111010"), 3, &ClearEvaluator);
+        assert_eq!(result.flags, stream("0000001000"));
+        assert!(result.detected);
+
+        let result = detect_run(&stream("1101101"), 3, &ClearEvaluator);
+        assert!(!result.detected);
+
+        let result = detect_run(&stream("11"), 3, &ClearEvaluator);
+        assert!(!result.detected);
+    }
+
+    #[test]
+    fn test_detector_streaming() {
+        let mut detector = RunDetector::new(2);
+        let flags: Vec<bool> = stream("1110").into_iter()
+            .map(|bit| detector.push(bit, &ClearEvaluator))
+            .collect();
+        assert_eq!(flags, stream("0110"));
+    }
+
+    #[test]
+    fn test_max_tracker() {
+        let eval = ClearEvaluator;
+        let bits = |v: u64| IntegerOps::constant(v, 4, &eval);
+        let value = |b: &[bool]| b.iter().enumerate().fold(0, |acc, (i, &x)| acc | ((x as u64) << i));
+
+        let mut tracker = EncryptedMaxTracker::with_index(false, 3);
+        assert!(tracker.max().is_none());
+        for (i, v) in [3u64, 9, 2, 9, 12, 7].into_iter().enumerate() {
+            tracker.push(bits(v), &eval);
+            let expected = [3, 9, 9, 9, 12, 12][i];
+            let at = [0, 1, 1, 1, 4, 4][i];
+            assert_eq!(value(tracker.max().unwrap()), expected);
+            assert_eq!(value(tracker.index().unwrap()), at);
+        }
+        assert_eq!(tracker.count(), 6);
+
+        // 0b1111 is -1 in two's complement
+        let mut signed = EncryptedMaxTracker::new(true);
+        for v in [15u64, 2, 9] {
+            signed.push(bits(v), &eval);
+        }
+        assert_eq!(value(signed.max().unwrap()), 2);
+        assert!(signed.index().is_none());
+
+        // Rejected pushes change nothing
+        let push = |tracker: &mut EncryptedMaxTracker<bool>, v: Vec<bool>| {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| tracker.push(v, &eval))).is_err()
+        };
+        assert!(push(&mut signed, IntegerOps::constant(1, 8, &eval)));
+        assert_eq!((signed.count(), value(signed.max().unwrap())), (3, 2));
+        let mut full = EncryptedMaxTracker::with_index(false, 1);
+        full.push(bits(1), &eval);
+        full.push(bits(5), &eval);
+        assert!(push(&mut full, bits(9)));
+        assert_eq!((full.count(), value(full.max().unwrap()), value(full.index().unwrap())), (2, 5, 1));
+    }
+    #[test]
+    fn test_streaming_comparator() {
+        let eval = ClearEvaluator;
+        // 256-bit values as 32 bytes, most significant first
+        let wide = |seed: u8, last: u8| -> Vec<bool> {
+            let mut bytes: Vec<u8> = (0..32u8).map(|i| i.wrapping_mul(37) ^ seed).collect();
+            bytes[31] = last;
+            bytes.iter().flat_map(|&b| (0..8).rev().map(move |i| (b >> i) & 1 == 1)).collect()
+        };
+        let ops = [CmpOp::Eq, CmpOp::Ne, CmpOp::Lt, CmpOp::Le, CmpOp::Gt, CmpOp::Ge];
+        let expected = |a: &[bool], b: &[bool]| {
+            let ord = a.cmp(b);
+            [ord.is_eq(), ord.is_ne(), ord.is_lt(), ord.is_le(), ord.is_gt(), ord.is_ge()]
+        };
+        for (a, b) in [(wide(1, 5), wide(1, 5)), (wide(1, 5), wide(1, 6)), (wide(2, 0), wide(1, 9)), (wide(1, 9), wide(2, 0))] {
+            let mut comparator = StreamingComparator::new();
+            for (x, y) in a.iter().zip(&b) {
+                comparator.push(x, y, &eval);
+            }
+            assert_eq!(comparator.count(), 256);
+            let results: Vec<bool> = ops.iter().map(|&op| comparator.result(op, &eval)).collect();
+            assert_eq!(results, expected(&a, &b));
+        }
+        assert!(StreamingComparator::new().result(CmpOp::Eq, &eval));
+    }
+}
@@ -0,0 +1,137 @@
+//! Encrypted byte strings: bounded edit distance and UTF-8 validation.
+//!
+//! String lengths are public; only the bytes are encrypted. `levenshtein`
+//! runs the usual dynamic program with encrypted cells, saturated at a public
+//! bound: cells further than the bound from the diagonal are known to reach
+//! it and stay constants, so the cost is `O(bound * len)` cells of
+//! `log2(bound + 1)`-bit minima instead of the full table.
+
+use crate::dfa::Dfa;
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::{CmpOp, IntegerOps};
+#[cfg(feature = "client")]
+use crate::tfhe::{TfheEncoder, TfheSecretKey};
+#[cfg(feature = "client")]
+use crate::tlwe::TlweSample;
+
+/// A string of encrypted bytes, each least significant bit first
+#[derive(Debug, Clone)]
+pub struct FheString<B> {
+    pub bytes: Vec<Vec<B>>,
+}
+
+impl<B: Clone> FheString<B> {
+    pub fn new(bytes: Vec<Vec<B>>) -> Self {
+        assert!(bytes.iter().all(|b| b.len() == 8), "string bytes must be 8 bits wide");
+        FheString { bytes }
+    }
+
+    /// Trivial encryption of a public string
+    pub fn constant<E: BooleanEvaluator<Bit = B>>(text: &[u8], eval: &E) -> Self {
+        FheString { bytes: text.iter().map(|&c| IntegerOps::constant(c as u64, 8, eval)).collect() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Edit distance to `other` with unit-cost insertions, deletions and
+    /// substitutions, saturated at `bound`. The result has just enough bits
+    /// for `bound`.
+    pub fn levenshtein<E: BooleanEvaluator<Bit = B>>(&self, other: &FheString<B>, bound: usize, eval: &E) -> Vec<B> {
+        assert!(bound > 0, "the distance bound must be positive");
+        let width = (usize::BITS - bound.leading_zeros()) as usize;
+        let constant = |d: usize| IntegerOps::constant(d.min(bound) as u64, width, eval);
+        let saturating_add = |d: &[B], bit: &B| {
+            let room = IntegerOps::cmp_clear(d, bound as u64, CmpOp::Lt, eval);
+            IntegerOps::increment_if(d, &eval.and(bit, &room), eval)
+        };
+        let min = |a: &[B], b: &[B]| IntegerOps::mux(&IntegerOps::less_than(a, b, eval), a, b, eval);
+
+        let one = eval.constant(true);
+        let mut previous: Vec<Vec<B>> = (0..=other.len()).map(constant).collect();
+        for (i, a) in self.bytes.iter().enumerate() {
+            let i = i + 1;
+            let mut row = vec![constant(i)];
+            for (j, b) in other.bytes.iter().enumerate() {
+                let j = j + 1;
+                if i.abs_diff(j) > bound {
+                    row.push(constant(bound));
+                    continue;
+                }
+                let differs = eval.not(&IntegerOps::equal(a, b, eval));
+                let substitute = saturating_add(&previous[j - 1], &differs);
+                let edit = saturating_add(&min(&previous[j], &row[j - 1]), &one);
+                row.push(min(&substitute, &edit));
+            }
+            previous = row;
+        }
+        previous.pop().expect("the table has a last column")
+    }
+
+    /// Whether the bytes are well-formed UTF-8
+    pub fn is_valid_utf8<E: BooleanEvaluator<Bit = B>>(&self, eval: &E) -> B {
+        Dfa::utf8().accepts(&self.bytes, eval)
+    }
+
+    /// Whether the strings are at most `max_edits` edits apart
+    pub fn within_distance<E: BooleanEvaluator<Bit = B>>(&self, other: &FheString<B>, max_edits: usize, eval: &E) -> B {
+        let distance = self.levenshtein(other, max_edits + 1, eval);
+        IntegerOps::cmp_clear(&distance, max_edits as u64, CmpOp::Le, eval)
+    }
+}
+
+#[cfg(feature = "client")]
+impl FheString<TlweSample> {
+    pub fn encrypt(text: &[u8], sk: &TfheSecretKey) -> Self {
+        FheString { bytes: TfheEncoder::encrypt_uint_iter(text.iter().map(|&c| c as u64), 8, sk).collect() }
+    }
+
+    pub fn decrypt(&self, sk: &TfheSecretKey) -> Vec<u8> {
+        TfheEncoder::decrypt_uint_iter(&self.bytes, sk).map(|c| c as u8).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    fn value(bits: &[bool]) -> usize {
+        bits.iter().enumerate().fold(0, |acc, (i, &b)| acc | ((b as usize) << i))
+    }
+
+    fn reference(a: &[u8], b: &[u8]) -> usize {
+        let mut previous: Vec<usize> = (0..=b.len()).collect();
+        for (i, x) in a.iter().enumerate() {
+            let mut row = vec![i + 1];
+            for (j, y) in b.iter().enumerate() {
+                row.push((previous[j] + (x != y) as usize).min(previous[j + 1].min(row[j]) + 1));
+            }
+            previous = row;
+        }
+        previous[b.len()]
+    }
+
+    #[test]
+    fn test_bounded_levenshtein() {
+        let eval = ClearEvaluator;
+        let words: [&[u8]; 6] = [b"", b"smith", b"smyth", b"smithe", b"jones", b"mitsh"];
+        for a in words {
+            for b in words {
+                let (x, y) = (FheString::constant(a, &eval), FheString::constant(b, &eval));
+                for bound in [1, 2, 3, 6] {
+                    let distance = value(&x.levenshtein(&y, bound, &eval));
+                    assert_eq!(distance, reference(a, b).min(bound), "{:?} {:?} {}", a, b, bound);
+                }
+                assert_eq!(x.within_distance(&y, 1, &eval), reference(a, b) <= 1);
+            }
+        }
+        assert!(FheString::constant("sm\u{ef}th".as_bytes(), &eval).is_valid_utf8(&eval));
+        assert!(!FheString::constant(b"sm\xefth", &eval).is_valid_utf8(&eval));
+    }
+}
@@ -0,0 +1,179 @@
+//! Decoding of encrypted base64 and hex payloads.
+//!
+//! Symbols are encrypted ASCII bytes, as in `FheString`. Rather than a
+//! 6-bit (base64) or 4-bit (hex) lookup over all 256 bytes, which as a mux
+//! tree costs a few thousand gates per symbol, each symbol is tested
+//! against the ranges of its alphabet (`A-Z`, `a-z`, `0-9`, ...) with
+//! `IntegerOps::cmp_clear`. The flags select a public offset which one
+//! addition applies to the low bits of the symbol: about a hundred gates
+//! per symbol. Values are then regrouped into bytes by wiring alone. The
+//! decoded length depends only on the public number of symbols: padding
+//! is reported as an encrypted count instead of shortening the output, and
+//! `valid` is an encrypted flag the client checks after decryption.
+
+use crate::evaluator::BooleanEvaluator;
+use crate::integer::{CmpOp, IntegerOps};
+
+/// Decoded bytes, least significant bit first
+#[derive(Debug, Clone)]
+pub struct Decoded<B> {
+    pub bytes: Vec<Vec<B>>,
+    /// Every symbol was in the alphabet, padding only where allowed
+    pub valid: B,
+    /// Number of trailing bytes that are padding, as 2 bits; always 0 for hex
+    pub padding: Vec<B>,
+}
+
+// Symbols `first..=last` decode to `value`, `value + 1`, ...
+struct Class {
+    first: u8,
+    last: u8,
+    value: u8,
+}
+
+const fn class(first: u8, last: u8, value: u8) -> Class {
+    Class { first, last, value }
+}
+
+// Padding last, decoding to zero
+const BASE64: [Class; 6] = [
+    class(b'A', b'Z', 0),
+    class(b'a', b'z', 26),
+    class(b'0', b'9', 52),
+    class(b'+', b'+', 62),
+    class(b'/', b'/', 63),
+    class(b'=', b'=', 0),
+];
+
+const HEX: [Class; 3] = [class(b'0', b'9', 0), class(b'a', b'f', 10), class(b'A', b'F', 10)];
+
+/// Low `width` bits of the symbol's value, and one membership flag per
+/// class. The value of a symbol outside every class is unspecified.
+fn classify<E: BooleanEvaluator>(
+    symbol: &[E::Bit],
+    classes: &[Class],
+    width: usize,
+    eval: &E,
+) -> (Vec<E::Bit>, Vec<E::Bit>) {
+    let flags: Vec<E::Bit> = classes
+        .iter()
+        .map(|c| {
+            if c.first == c.last {
+                return IntegerOps::cmp_clear(symbol, c.first as u64, CmpOp::Eq, eval);
+            }
+            let above = IntegerOps::cmp_clear(symbol, c.first as u64, CmpOp::Ge, eval);
+            eval.and(&above, &IntegerOps::cmp_clear(symbol, c.last as u64, CmpOp::Le, eval))
+        })
+        .collect();
+
+    // At most one flag is set, so each offset bit is the OR of the flags of
+    // the classes whose offset has it
+    let offset: Vec<E::Bit> = (0..width)
+        .map(|i| {
+            let set: Vec<E::Bit> = classes
+                .iter()
+                .zip(&flags)
+                .filter(|(c, _)| (c.value.wrapping_sub(c.first) >> i) & 1 == 1)
+                .map(|(_, flag)| flag.clone())
+                .collect();
+            IntegerOps::or_all(&set, eval)
+        })
+        .collect();
+    (IntegerOps::add_mod(&symbol[..width], &offset, eval), flags)
+}
+
+/// Decodes base64 symbols, whose count must be a multiple of 4, into three
+/// bytes per group of four symbols
+pub fn decode_base64<E: BooleanEvaluator>(symbols: &[Vec<E::Bit>], eval: &E) -> Decoded<E::Bit> {
+    assert!(symbols.len().is_multiple_of(4), "base64 needs groups of 4 symbols, got {}", symbols.len());
+    let (values, flags): (Vec<_>, Vec<_>) = symbols.iter().map(|s| classify(s, &BASE64, 6, eval)).unzip();
+    let pads: Vec<&E::Bit> = flags.iter().map(|f| &f[BASE64.len() - 1]).collect();
+
+    // Padding may only fill the last two symbols, and the last one first
+    let n = values.len();
+    let mut checks: Vec<E::Bit> = Vec::with_capacity(n + 1);
+    for (i, flags) in flags.iter().enumerate() {
+        let allowed = if i + 2 < n { &flags[..BASE64.len() - 1] } else { &flags[..] };
+        checks.push(IntegerOps::or_all(allowed, eval));
+    }
+    let padding = if n == 0 {
+        IntegerOps::constant(0, 2, eval)
+    } else {
+        let (second, last) = (pads[n - 2], pads[n - 1]);
+        checks.push(eval.not(&eval.and(second, &eval.not(last))));
+        vec![eval.xor(second, last), eval.and(second, last)]
+    };
+
+    // Four sextets, first one most significant, make a 24-bit word of three bytes
+    let mut bytes = Vec::with_capacity(n / 4 * 3);
+    for group in values.chunks(4) {
+        let word: Vec<E::Bit> = group.iter().rev().flatten().cloned().collect();
+        bytes.extend(word.chunks(8).rev().map(|b| b.to_vec()));
+    }
+    Decoded { bytes, valid: IntegerOps::and_all(&checks, eval), padding }
+}
+
+/// Decodes hex symbols, two per byte with the high nibble first
+pub fn decode_hex<E: BooleanEvaluator>(symbols: &[Vec<E::Bit>], eval: &E) -> Decoded<E::Bit> {
+    assert!(symbols.len().is_multiple_of(2), "hex needs pairs of symbols, got {}", symbols.len());
+    let (values, flags): (Vec<_>, Vec<_>) = symbols.iter().map(|s| classify(s, &HEX, 4, eval)).unzip();
+
+    let checks: Vec<E::Bit> = flags.iter().map(|f| IntegerOps::or_all(f, eval)).collect();
+    let bytes = values
+        .chunks(2)
+        .map(|pair| pair[1].iter().chain(&pair[0]).cloned().collect())
+        .collect();
+    Decoded { bytes, valid: IntegerOps::and_all(&checks, eval), padding: IntegerOps::constant(0, 2, eval) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::ClearEvaluator;
+
+    fn run(text: &str, decode: fn(&[Vec<bool>], &ClearEvaluator) -> Decoded<bool>) -> (Vec<u8>, bool, usize) {
+        let eval = ClearEvaluator;
+        let symbols: Vec<Vec<bool>> = text.bytes().map(|c| IntegerOps::constant(c as u64, 8, &eval)).collect();
+        let decoded = decode(&symbols, &eval);
+        let value = |bits: &[bool]| bits.iter().enumerate().fold(0, |acc, (i, &b)| acc | ((b as usize) << i));
+        let bytes = decoded.bytes.iter().map(|b| value(b) as u8).collect();
+        (bytes, decoded.valid, value(&decoded.padding))
+    }
+
+    #[test]
+    fn test_decode_base64_and_hex() {
+        assert_eq!(run("TWFu", decode_base64), (b"Man".to_vec(), true, 0));
+        assert_eq!(run("aGk/Pz8+", decode_base64), (b"hi???>".to_vec(), true, 0));
+        assert_eq!(run("TWE=", decode_base64), (b"Ma\0".to_vec(), true, 1));
+        assert_eq!(run("TQ==", decode_base64), (b"M\0\0".to_vec(), true, 2));
+        assert!(!run("T=E=", decode_base64).1);
+        assert!(!run("TW=A", decode_base64).1);
+        assert!(!run("TW-u", decode_base64).1);
+        assert_eq!(run("", decode_base64), (vec![], true, 0));
+
+        assert_eq!(run("00ff7A", decode_hex), (vec![0x00, 0xff, 0x7a], true, 0));
+        assert!(!run("0g", decode_hex).1);
+    }
+
+    #[test]
+    fn test_classes_match_alphabets() {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let eval = ClearEvaluator;
+        for c in 0..=255u8 {
+            let symbol = IntegerOps::constant(c as u64, 8, &eval);
+            let value = |bits: &[bool]| bits.iter().rev().fold(0, |acc, &b| (acc << 1) | b as usize);
+
+            let (sextet, flags) = classify(&symbol, &BASE64, 6, &eval);
+            match ALPHABET.iter().position(|&a| a == c) {
+                Some(i) => assert_eq!((value(&sextet), flags.iter().filter(|&&f| f).count()), (i, 1), "{}", c),
+                None => assert_eq!(flags.iter().any(|&f| f), c == b'=', "{}", c),
+            }
+
+            let (nibble, flags) = classify(&symbol, &HEX, 4, &eval);
+            match (c as char).to_digit(16) {
+                Some(d) => assert_eq!(value(&nibble), d as usize, "{}", c),
+                None => assert!(!flags.iter().any(|&f| f), "{}", c),
+            }
+        }
+    }
+}
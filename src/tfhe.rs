@@ -1,8 +1,38 @@
+#[cfg(any(feature = "client", feature = "server"))]
 use crate::torus::Torus;
-use crate::tlwe::{TlweSample, TlweSecretKey, TlweParams, TlweKeySwitchKey};
-use crate::tgsw::{TgswParams, BootstrappingKey};
+#[cfg(any(feature = "client", feature = "server"))]
+use crate::tlwe::TlweSample;
+use crate::tlwe::{KeySwitchParams, TlweParams, TlweKeySwitchKey};
+#[cfg(feature = "client")]
+use crate::tlwe::TlweSecretKey;
+#[cfg(feature = "client")]
+use crate::entropy::{default_entropy, EntropySource};
+#[cfg(feature = "client")]
+use crate::lwe::LweCiphertext;
+use crate::tgsw::{TgswParams, TgswTradeoff, BootstrappingKey};
+use crate::trlwe::TrlweParams;
+#[cfg(feature = "client")]
+use crate::trlwe::TrlweSecretKey;
+#[cfg(feature = "server")]
+use crate::trlwe::{TorusPolynomial, TrlweSample};
+#[cfg(feature = "server")]
+use crate::lutdebug::TestVector;
+use crate::hooks::BootstrapHooks;
+use crate::refresh::RefreshPolicy;
+#[cfg(feature = "server")]
+use crate::hooks::BootstrapInfo;
+#[cfg(any(feature = "client", feature = "server"))]
+use crate::noise::NoiseLevel;
+#[cfg(feature = "client")]
+use crate::noise::NoiseEstimate;
+use crate::noise_model;
+use crate::canonical::Namespace;
+#[cfg(feature = "client")]
+use std::borrow::Borrow;
+#[cfg(feature = "server")]
+use std::time::Instant;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TfheParams {
     pub tlwe_params: TlweParams,
     pub tgsw_params: TgswParams,
@@ -23,149 +53,626 @@ impl Default for TfheParams {
     }
 }
 
+/// Parameter choices `TfheParams::validate` flags as unsafe or wasteful
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamsWarning {
+    /// The bootstrapping key does not cover the LWE dimension
+    DimensionMismatch { n: usize, tlwe_n: usize },
+    /// `l * bg_bit` reads more bits than the decomposition has
+    DecompositionPrecision { bits: u32 },
+    /// A blind rotation adds more noise than the decryption margin allows
+    BootstrapNoise { variance: f64, budget: f64 },
+    /// A smaller bootstrapping key stays within the noise budget
+    OversizedKey { suggested: TgswTradeoff, key_bytes: usize },
+    /// Negacyclic rotation needs a power-of-two ring degree
+    RingDegree { big_n: usize },
+    /// `part`, "lwe" or "ring", is estimated below the required security
+    Insecure { part: &'static str, bits: u32, required: u32 },
+}
+
+impl std::fmt::Display for ParamsWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamsWarning::DimensionMismatch { n, tlwe_n } => {
+                write!(f, "bootstrapping dimension {} differs from the TLWE dimension {}", n, tlwe_n)
+            }
+            ParamsWarning::DecompositionPrecision { bits } => write!(
+                f,
+                "l * bg_bit = {} exceeds the {} bits the decomposition reads",
+                bits,
+                TgswParams::DECOMPOSITION_BITS,
+            ),
+            ParamsWarning::BootstrapNoise { variance, budget } => {
+                write!(f, "bootstrap noise variance {:e} exceeds the budget {:e}", variance, budget)
+            }
+            ParamsWarning::OversizedKey { suggested, key_bytes } => write!(
+                f,
+                "l = {}, bg_bit = {} meets the noise budget with a {} byte key instead of {}",
+                suggested.l, suggested.bg_bit, suggested.key_bytes, key_bytes,
+            ),
+            ParamsWarning::RingDegree { big_n } => write!(f, "ring degree {} is not a power of two", big_n),
+            ParamsWarning::Insecure { part, bits, required } => {
+                write!(f, "{} parameters give about {} bits of security, {} required", part, bits, required)
+            }
+        }
+    }
+}
+
+/// Bits of security per unit of `n / log2(1 / stddev)`, calibrated so the
+/// weaker part of `TfheParams::default_128`, its ring, scores 128
+const SECURITY_PER_DIMENSION_BIT: f64 = 128.0 * 25.0 / 1024.0;
+
+/// Rough security of LWE in dimension `n` with noise `stddev` on the torus:
+/// linear in `n / log2(1 / stddev)` for a fixed torus precision. Good enough
+/// to tell test parameters from real ones, not a lattice estimator.
+pub fn estimated_security(n: usize, stddev: f64) -> u32 {
+    if stddev <= 0.0 || stddev >= 1.0 {
+        return if stddev >= 1.0 { u32::MAX } else { 0 };
+    }
+    (SECURITY_PER_DIMENSION_BIT * n as f64 / -stddev.log2()) as u32
+}
+
+impl TfheParams {
+    /// The 128-bit gate bootstrapping set of the TFHE library: `n = 630`
+    /// with noise `2^-15`, `N = 1024`, `k = 1`, bootstrapping key noise
+    /// `2^-25` and `l = 3`, `bg_bit = 7`
+    pub fn default_128() -> Self {
+        Self::preset(630, 2f64.powi(-15), 3, 7, 2f64.powi(-25))
+    }
+
+    /// The original 80-bit set of the TFHE library: `n = 500` with noise
+    /// `2.44e-5`, `N = 1024`, `k = 1`, bootstrapping key noise `7.18e-9`
+    /// and `l = 2`, `bg_bit = 10`
+    pub fn default_80() -> Self {
+        Self::preset(500, 2.44e-5, 2, 10, 7.18e-9)
+    }
+
+    fn preset(n: usize, stddev: f64, l: usize, bg_bit: u32, bk_stddev: f64) -> Self {
+        let (big_n, k) = (1024, 1);
+        TfheParams {
+            tlwe_params: TlweParams { n, stddev },
+            tgsw_params: TgswParams { l, bg_bit, tlwe_params: TlweParams { n: k * big_n, stddev: bk_stddev } },
+            n,
+            N: big_n,
+            k,
+        }
+    }
+
+    /// Estimated security: the weaker of the LWE samples gates take and the
+    /// ring samples of the bootstrapping key
+    pub fn estimated_security(&self) -> u32 {
+        let lwe = estimated_security(self.tlwe_params.n, self.tlwe_params.stddev);
+        let ring = estimated_security(self.k * self.N, self.tgsw_params.tlwe_params.stddev);
+        lwe.min(ring)
+    }
+
+    /// Rejects parameters that are inconsistent, too noisy to bootstrap
+    /// correctly, or estimated below `min_bits` of security. Advice about
+    /// key size is left to `validate`.
+    pub fn check_security(&self, min_bits: u32) -> Result<(), Vec<ParamsWarning>> {
+        let mut errors: Vec<ParamsWarning> = self.validate()
+            .into_iter()
+            .filter(|w| !matches!(w, ParamsWarning::OversizedKey { .. }))
+            .collect();
+
+        if !self.N.is_power_of_two() {
+            errors.push(ParamsWarning::RingDegree { big_n: self.N });
+        }
+        let lwe = estimated_security(self.tlwe_params.n, self.tlwe_params.stddev);
+        if lwe < min_bits {
+            errors.push(ParamsWarning::Insecure { part: "lwe", bits: lwe, required: min_bits });
+        }
+        let ring = estimated_security(self.k * self.N, self.tgsw_params.tlwe_params.stddev);
+        if ring < min_bits {
+            errors.push(ParamsWarning::Insecure { part: "ring", bits: ring, required: min_bits });
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// Ring the blind rotation accumulates in, with the bootstrapping key noise
+    pub fn trlwe_params(&self) -> TrlweParams {
+        TrlweParams {
+            N: self.N,
+            k: self.k,
+            stddev: self.tgsw_params.tlwe_params.stddev,
+        }
+    }
+
+    /// Smallest key switching key from the extracted dimension `k N` back to
+    /// `n` that adds at most a quarter of the decryption variance budget, or
+    /// the customary decomposition when none does
+    pub fn key_switch_params(&self) -> KeySwitchParams {
+        let max_stddev = self.variance_budget().sqrt() / 2.0;
+        TlweKeySwitchKey::recommend(self.k * self.N, &self.tlwe_params, max_stddev).unwrap_or_default()
+    }
+
+    // Largest output variance that still decrypts with the `NoiseLevel` margin
+    fn variance_budget(&self) -> f64 {
+        noise_model::max_variance()
+    }
+
+    /// Variance of every gate output: blind rotation, then the key switch of
+    /// `key_switch_params`; see `noise_model::bootstrap`
+    pub fn bootstrap_variance(&self) -> f64 {
+        noise_model::bootstrap(self)
+    }
+
+    /// External products a fresh ciphertext survives without a bootstrap:
+    /// how many `noise_model::external_product` fit in the budget
+    pub fn leveled_depth(&self) -> usize {
+        let per_product = noise_model::external_product(&self.tgsw_params, self.N, self.k);
+        if per_product <= 0.0 {
+            return usize::MAX;
+        }
+        let fresh = noise_model::encryption(self.tlwe_params.stddev);
+        ((self.variance_budget() - fresh).max(0.0) / per_product).min(usize::MAX as f64) as usize
+    }
+
+    /// Checks the gadget decomposition against the noise budget of a blind
+    /// rotation (`n` external products) and for a smaller key that would do
+    pub fn validate(&self) -> Vec<ParamsWarning> {
+        let mut warnings = Vec::new();
+        let tgsw = &self.tgsw_params;
+
+        if self.n != self.tlwe_params.n {
+            warnings.push(ParamsWarning::DimensionMismatch { n: self.n, tlwe_n: self.tlwe_params.n });
+        }
+        let bits = tgsw.l as u32 * tgsw.bg_bit;
+        if bits > TgswParams::DECOMPOSITION_BITS {
+            warnings.push(ParamsWarning::DecompositionPrecision { bits });
+        }
+
+        let budget = self.variance_budget();
+        let variance = noise_model::blind_rotation(self);
+        if variance > budget {
+            warnings.push(ParamsWarning::BootstrapNoise { variance, budget });
+        } else {
+            let key_bytes = self.n * tgsw.sample_bytes(self.N, self.k);
+            let stddev = tgsw.tlwe_params.stddev;
+            let smaller = TgswParams::recommend(self.N, self.k, self.n, stddev, budget)
+                .filter(|suggested| suggested.key_bytes < key_bytes);
+            if let Some(suggested) = smaller {
+                warnings.push(ParamsWarning::OversizedKey { suggested, key_bytes });
+            }
+        }
+
+        warnings
+    }
+}
+
+#[cfg(feature = "client")]
 #[derive(Debug, Clone)]
 pub struct TfheSecretKey {
     pub tlwe_key: TlweSecretKey,
+    /// Ring key bootstrapped samples come out under, before key switching
+    pub trlwe_key: TrlweSecretKey,
     pub params: TfheParams,
 }
 
+#[cfg(feature = "client")]
 impl TfheSecretKey {
     pub fn generate(params: TfheParams) -> Self {
-        let tlwe_key = TlweSecretKey::generate_binary(params.tlwe_params.clone());
+        Self::generate_with_rng(params, &mut default_entropy())
+    }
+
+    pub fn generate_with_rng<R: EntropySource + ?Sized>(params: TfheParams, rng: &mut R) -> Self {
+        let tlwe_key = TlweSecretKey::generate_binary_with_rng(params.tlwe_params.clone(), rng);
+        let trlwe_key = TrlweSecretKey::generate_binary_with_rng(params.trlwe_params(), rng);
 
         TfheSecretKey {
             tlwe_key,
+            trlwe_key,
             params,
         }
     }
+
+    /// The ring key read as an LWE key of dimension `k N`, which decrypts
+    /// samples extracted from a blind rotation
+    pub fn extracted_key(&self) -> TlweSecretKey {
+        let ring = &self.trlwe_key.params;
+        TlweSecretKey {
+            coeffs: self.trlwe_key.polys.concat(),
+            params: TlweParams { n: ring.k * ring.N, stddev: ring.stddev },
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct TfheCloudKey {
     pub bootstrapping_key: BootstrappingKey,
-    pub key_switching_key: Option<TlweKeySwitchKey>,
+    /// From the extracted key of the blind rotation back to the LWE key
+    pub key_switching_key: TlweKeySwitchKey,
+    pub hooks: BootstrapHooks,
+    /// Operands are refreshed before linear steps that would miss it
+    pub refresh: Option<RefreshPolicy>,
+    /// Tenant whose ciphertexts this key accepts and tags its outputs with
+    pub namespace: Option<Namespace>,
 }
 
+#[cfg(feature = "client")]
 impl TfheCloudKey {
     pub fn generate(sk: &TfheSecretKey) -> Self {
-        let bootstrapping_key = BootstrappingKey::generate(
+        Self::generate_with_rng(sk, &mut default_entropy())
+    }
+
+    pub fn generate_with_rng<R: EntropySource + ?Sized>(sk: &TfheSecretKey, rng: &mut R) -> Self {
+        let bootstrapping_key = BootstrappingKey::generate_with_rng(
             &sk.tlwe_key,
+            &sk.trlwe_key,
             sk.params.tgsw_params.clone(),
+            rng,
         );
 
-        let key_switching_key = None;
+        let ks = sk.params.key_switch_params();
+        let key_switching_key =
+            TlweKeySwitchKey::generate_with_rng(&sk.extracted_key(), &sk.tlwe_key, ks.t, ks.base_bit, rng);
 
         TfheCloudKey {
             bootstrapping_key,
             key_switching_key,
+            hooks: BootstrapHooks::default(),
+            refresh: None,
+            namespace: None,
         }
     }
 }
 
+impl TfheCloudKey {
+    /// Gates evaluated with this key reject operands of other namespaces
+    /// and tag their outputs with `namespace`
+    pub fn with_namespace(mut self, namespace: Namespace) -> Self {
+        self.namespace = Some(namespace);
+        self
+    }
+
+    // A gate input from another tenant is a bug at the call site
+    #[cfg(feature = "server")]
+    pub(crate) fn expect_namespace(&self, gate: &str, sample: &TlweSample) {
+        if let Err(e) = Namespace::join(self.namespace, sample.namespace) {
+            panic!("{} gate: {}", gate, e);
+        }
+    }
+}
+
+#[cfg(feature = "server")]
 pub struct TfheGates;
 
+#[cfg(feature = "server")]
 impl TfheGates {
+    /// Blind rotation of `lut` by the phase of `input`, read back as an LWE
+    /// sample of `lut[phase * lut.len()]` under `TfheSecretKey::extracted_key`.
+    ///
+    /// The accumulator starts from `TestVector::from_lut`, whose offset is
+    /// added to the extracted body. Tables where `lut(x) + lut(x + 1/2)` is
+    /// constant modulo 1, as for every gate, are therefore evaluated exactly;
+    /// `TestVector::probe` shows which slot a given phase reads.
     pub fn programmable_bootstrap(
         input: &TlweSample,
         lut: &[Torus],
         bk: &BootstrappingKey,
     ) -> TlweSample {
-        let n = bk.n;
-        let N = lut.len();
+        Self::programmable_bootstrap_with(input, &TestVector::from_lut(lut, bk.ring_params.N), bk)
+    }
 
-        let mut acc = TlweSample::trivial(&lut[0], input.params.clone());
+    /// `programmable_bootstrap` of a test vector built by the caller
+    pub fn programmable_bootstrap_with(input: &TlweSample, test: &TestVector, bk: &BootstrappingKey) -> TlweSample {
+        let acc = Self::blind_rotate(input, &test.poly, bk);
 
-        for i in 0..n {
-            acc = bk.bk[i].cmux(&acc, &acc);
-        }
+        let mut output = TlweSample::extract_from_trlwe(&acc, 0);
+        output.noise = NoiseLevel::Fresh;
+        output.b = output.b.add(&test.offset);
+        output
+    }
+
+    /// The accumulator `X^-p test`, `p` the phase of `input` switched to
+    /// `[0, 2N)`, before anything is extracted from it
+    pub fn blind_rotate(input: &TlweSample, test: &TorusPolynomial, bk: &BootstrappingKey) -> TrlweSample {
+        assert_eq!(input.a.len(), bk.n, "input dimension does not match the bootstrapping key");
+        let ring = &bk.ring_params;
+        let two_n = 2 * ring.N;
 
+        // Phases switched to the exponents of X, modulo X^2N = 1
+        let switched = input.modulus_switch(two_n as u64);
+
+        let mut acc = TrlweSample::trivial(test, ring.clone()).mul_by_xai(two_n - switched.b as usize);
+        for (i, &power) in switched.a.iter().enumerate() {
+            if power != 0 {
+                acc = bk.cmux(i, &acc, &acc.mul_by_xai(power as usize));
+            }
+        }
         acc
     }
 
-    pub fn nand(a: &TlweSample, b: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
+    /// Brings a sample under the extracted key back to the LWE key and
+    /// dimension gate inputs use; the last step of every bootstrapped gate
+    pub fn key_switch(sample: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
+        let mut output = ck.key_switching_key.key_switch(sample);
+        output.namespace = ck.namespace.or(output.namespace);
+        output
+    }
 
-        let mut result = a.scalar_mul(-1);
-        result = result.sub(b);
-        let offset = Torus::new(0.625);
-        result.b = result.b.add(&offset);
+    pub(crate) fn bootstrap(gate: &'static str, input: &TlweSample, lut: &[Torus], ck: &TfheCloudKey) -> TlweSample {
+        let test = TestVector::from_lut(lut, ck.bootstrapping_key.ring_params.N);
+        Self::bootstrap_with(gate, input, &test, lut.len(), ck)
+    }
+
+    // `bootstrap` of a prepared test vector standing for `lut_size` entries
+    pub(crate) fn bootstrap_with(
+        gate: &'static str,
+        input: &TlweSample,
+        test: &TestVector,
+        lut_size: usize,
+        ck: &TfheCloudKey,
+    ) -> TlweSample {
+        let info = BootstrapInfo {
+            gate,
+            input_dimension: input.params.n,
+            lut_size,
+        };
+
+        assert!(
+            !input.noise.needs_bootstrap(),
+            "{} gate input is past its noise budget; an operand needed a bootstrap first",
+            gate,
+        );
+        ck.expect_namespace(gate, input);
+
+        ck.hooks.before(&info, input);
+        let start = Instant::now();
+        let extracted = Self::programmable_bootstrap_with(input, test, &ck.bootstrapping_key);
+        let mut output = Self::key_switch(&extracted, ck);
+        output.noise = NoiseLevel::Fresh;
+        ck.hooks.after(&info, &mut output, start.elapsed());
 
-        let mut lut = vec![Torus::new(0.0); 1024];
-        for i in 0..512 {
-            lut[i] = Torus::new(0.625);
+        output
+    }
+
+    /// Noiseless encryption of a public bit under the parameters of `ck`,
+    /// decrypting to `value` under any key; gates fold it away
+    pub fn constant(value: bool, ck: &TfheCloudKey) -> TlweSample {
+        Self::trivial_bool(value, ck.key_switching_key.output_params())
+    }
+
+    // Gate encoding of a public bit
+    fn trivial_bool(value: bool, params: &TlweParams) -> TlweSample {
+        TlweSample::trivial(&bool_phase(value), params.clone())
+    }
+
+    // The bit a trivial operand carries; `None` for real ciphertexts
+    fn known(sample: &TlweSample) -> Option<bool> {
+        if sample.is_trivial() {
+            let phase = sample.b.value();
+            Some(phase > 0.25 && phase < 0.75)
+        } else {
+            None
         }
-        for i in 512..1024 {
-            lut[i] = Torus::new(0.125);
+    }
+
+    // A gate with a public operand is a constant, a copy or a NOT of the other
+    pub(crate) fn fold(f: fn(bool, bool) -> bool, a: &TlweSample, b: &TlweSample) -> Option<TlweSample> {
+        let rest = |g: &dyn Fn(bool) -> bool, other: &TlweSample| match (g(false), g(true)) {
+            (x, y) if x == y => Self::trivial_bool(x, &other.params),
+            (false, true) => other.clone(),
+            _ => Self::not(other),
+        };
+        match (Self::known(a), Self::known(b)) {
+            (Some(x), _) => Some(rest(&|y| f(x, y), b)),
+            (_, Some(y)) => Some(rest(&|x| f(x, y), a)),
+            _ => None,
         }
+    }
 
-        Self::programmable_bootstrap(&result, &lut, &ck.bootstrapping_key)
+    // `wa a + wb b + offset` of `spec`, refreshing the operands first if
+    // the cloud key's policy asks for it
+    pub(crate) fn gate_input(spec: &GateSpec, a: &TlweSample, b: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
+        let (wa, wb) = spec.weights;
+        let mut result = Self::linear(&[a, b], |s| s[0].scalar_mul(wa).add(&s[1].scalar_mul(wb)), ck);
+        result.b = result.b.add(&Torus::new(spec.offset));
+        result
     }
 
-    pub fn and(a: &TlweSample, b: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
-        let nand_result = Self::nand(a, b, ck);
+    fn binary_gate(spec: &GateSpec, a: &TlweSample, b: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
+        if let Some(folded) = Self::fold(spec.f, a, b) {
+            return folded;
+        }
+        Self::bootstrap(spec.name, &Self::gate_input(spec, a, b, ck), &gate_lut(), ck)
+    }
 
-        Self::not(&nand_result, ck)
+    pub fn nand(a: &TlweSample, b: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
+        Self::binary_gate(&GateSpec::NAND, a, b, ck)
+    }
+
+    pub fn and(a: &TlweSample, b: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
+        Self::binary_gate(&GateSpec::AND, a, b, ck)
     }
 
     pub fn or(a: &TlweSample, b: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
-        let not_a = Self::not(a, ck);
-        let not_b = Self::not(b, ck);
-        let and_result = Self::and(&not_a, &not_b, ck);
-        Self::not(&and_result, ck)
+        Self::binary_gate(&GateSpec::OR, a, b, ck)
+    }
+
+    pub fn nor(a: &TlweSample, b: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
+        Self::binary_gate(&GateSpec::NOR, a, b, ck)
     }
 
+    /// `2 (a + b)` is `0` for different bits and `1/2` for equal ones
     pub fn xor(a: &TlweSample, b: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
+        Self::binary_gate(&GateSpec::XOR, a, b, ck)
+    }
+
+    pub fn xnor(a: &TlweSample, b: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
+        Self::binary_gate(&GateSpec::XNOR, a, b, ck)
+    }
 
-        let mut result = a.sub(b);
-        result = result.scalar_mul(2);
+    /// `!a & b`
+    pub fn andny(a: &TlweSample, b: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
+        Self::binary_gate(&GateSpec::ANDNY, a, b, ck)
+    }
 
-        let mut lut = vec![Torus::new(0.0); 1024];
-        for i in 256..768 {
-            lut[i] = Torus::new(0.625);
+    /// `a & !b`
+    pub fn andyn(a: &TlweSample, b: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
+        Self::binary_gate(&GateSpec::ANDYN, a, b, ck)
+    }
+
+    /// `!a | b`
+    pub fn orny(a: &TlweSample, b: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
+        Self::binary_gate(&GateSpec::ORNY, a, b, ck)
+    }
+
+    /// `a | !b`
+    pub fn oryn(a: &TlweSample, b: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
+        Self::binary_gate(&GateSpec::ORYN, a, b, ck)
+    }
+
+    // `a + b + c` scaled by `weight`, plus `offset`, through the gate test
+    // vector. `k` true bits sum to `3/8 + k/4`, four cases a quarter apart,
+    // so the gate depends on the count alone
+    fn ternary_gate(
+        gate: &'static str,
+        a: &TlweSample,
+        b: &TlweSample,
+        c: &TlweSample,
+        weight: i32,
+        offset: f64,
+        ck: &TfheCloudKey,
+    ) -> TlweSample {
+        let mut result = Self::linear(&[a, b, c], |s| s[0].add(s[1]).add(s[2]).scalar_mul(weight), ck);
+        result.b = result.b.add(&Torus::new(offset));
+        Self::bootstrap(gate, &result, &gate_lut(), ck)
+    }
+
+    /// Whether at least two of `a`, `b`, `c` hold: the carry out of
+    /// `a + b + c`, from one bootstrap of `a + b + c - 1/4`
+    pub fn majority(a: &TlweSample, b: &TlweSample, c: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
+        let operands = [a, b, c];
+        if let Some(i) = operands.iter().position(|s| Self::known(s).is_some()) {
+            let (x, y) = match i {
+                0 => (b, c),
+                1 => (a, c),
+                _ => (a, b),
+            };
+            return if Self::known(operands[i]) == Some(true) { Self::or(x, y, ck) } else { Self::and(x, y, ck) };
         }
-        for i in 0..256 {
-            lut[i] = Torus::new(0.125);
+        Self::ternary_gate("majority", a, b, c, 1, -0.25, ck)
+    }
+
+    /// `a ^ b ^ c`, the sum bit of `a + b + c`: `2 (a + b + c)` is `3/4`
+    /// for an even count and `1/4` for an odd one
+    pub fn xor3(a: &TlweSample, b: &TlweSample, c: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
+        let operands = [a, b, c];
+        if let Some(i) = operands.iter().position(|s| Self::known(s).is_some()) {
+            let (x, y) = match i {
+                0 => (b, c),
+                1 => (a, c),
+                _ => (a, b),
+            };
+            return if Self::known(operands[i]) == Some(true) { Self::xnor(x, y, ck) } else { Self::xor(x, y, ck) };
         }
-        for i in 768..1024 {
-            lut[i] = Torus::new(0.125);
+        Self::ternary_gate("xor3", a, b, c, 2, 0.5, ck)
+    }
+
+    /// `1/2 - a`; linear, so no bootstrap
+    pub fn not(a: &TlweSample) -> TlweSample {
+        if let Some(value) = Self::known(a) {
+            return Self::trivial_bool(!value, &a.params);
         }
 
-        Self::programmable_bootstrap(&result, &lut, &ck.bootstrapping_key)
+        let mut result = a.scalar_mul(-1);
+        result.b = result.b.add(&Torus::new(0.5));
+        result
     }
 
-    pub fn not(a: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
-        let mut result = a.scalar_mul(-1);
-        let offset = Torus::new(0.5);
-        result.b = result.b.add(&offset);
+    /// Programmable bootstrap of `f` on a message in `[0, message_modulus)`
+    /// encoded by `TfheEncoder::encode_int`, the result reduced modulo
+    /// `message_modulus` in the same encoding. Messages use the first half
+    /// of the torus and the table is mirrored negated onto the second, so
+    /// any `f` is evaluated exactly; the modulus must leave every message
+    /// at least two slots of the test polynomial.
+    pub fn apply_lut(
+        input: &TlweSample,
+        f: impl Fn(u64) -> u64,
+        message_modulus: u64,
+        ck: &TfheCloudKey,
+    ) -> TlweSample {
+        let big_n = ck.bootstrapping_key.ring_params.N as u64;
+        assert!(
+            message_modulus >= 1 && 2 * message_modulus <= big_n,
+            "message modulus {} needs 1 <= p <= N/2 = {}",
+            message_modulus,
+            big_n / 2,
+        );
 
-        let mut lut = vec![Torus::new(0.0); 1024];
-        for i in 0..512 {
-            lut[i] = Torus::new(0.625);
-        }
-        for i in 512..1024 {
-            lut[i] = Torus::new(0.125);
-        }
+        // One entry per slot of the test polynomial and its negacyclic mirror
+        let two_n = 2 * big_n;
+        let lut: Vec<Torus> = (0..two_n)
+            .map(|j| {
+                let m = j * 2 * message_modulus / two_n;
+                let out = int_phase(f(m % message_modulus) % message_modulus, message_modulus);
+                if m < message_modulus { out } else { -out }
+            })
+            .collect();
 
-        Self::programmable_bootstrap(&result, &lut, &ck.bootstrapping_key)
+        Self::bootstrap("lut", input, &lut, ck)
     }
 
+    /// `a` where `s` is true, `b` elsewhere. `s & a` and `!s & b`, of
+    /// which at most one holds, are bootstrapped to `±1/8` under the
+    /// extracted key and summed, so one key switch of the sum plus `3/8`
+    /// closes the gate: two blind rotations instead of three gates. Hooks
+    /// see both inputs and the one output.
     pub fn mux(s: &TlweSample, a: &TlweSample, b: &TlweSample, ck: &TfheCloudKey) -> TlweSample {
-        let s_and_a = Self::and(s, a, ck);
-        let not_s = Self::not(s, ck);
-        let not_s_and_b = Self::and(&not_s, b, ck);
-        Self::or(&s_and_a, &not_s_and_b, ck)
+        match (Self::known(s), Self::known(a), Self::known(b)) {
+            (Some(true), _, _) => return a.clone(),
+            (Some(false), _, _) => return b.clone(),
+            (_, Some(x), Some(y)) if x == y => return a.clone(),
+            (_, Some(true), Some(false)) => return s.clone(),
+            (_, Some(false), Some(true)) => return Self::not(s),
+            (_, Some(true), _) => return Self::or(s, b, ck),
+            (_, Some(false), _) => return Self::andny(s, b, ck),
+            (_, _, Some(true)) => return Self::orny(s, a, ck),
+            (_, _, Some(false)) => return Self::and(s, a, ck),
+            _ => {}
+        }
+
+        let mut s_and_a = Self::linear(&[s, a], |x| x[0].add(x[1]), ck);
+        s_and_a.b = s_and_a.b.add(&Torus::new(-0.125));
+        let mut not_s_and_b = Self::linear(&[s, b], |x| x[1].sub(x[0]), ck);
+        not_s_and_b.b = not_s_and_b.b.add(&Torus::new(0.375));
+
+        let info = BootstrapInfo {
+            gate: "mux",
+            input_dimension: s.params.n,
+            lut_size: 2 * ck.bootstrapping_key.ring_params.N,
+        };
+        for input in [&s_and_a, &not_s_and_b] {
+            assert!(
+                !input.noise.needs_bootstrap(),
+                "mux gate input is past its noise budget; an operand needed a bootstrap first",
+            );
+            ck.expect_namespace("mux", input);
+            ck.hooks.before(&info, input);
+        }
+
+        let start = Instant::now();
+        let test = TestVector::negacyclic(|_| Torus::new(-0.125), ck.bootstrapping_key.ring_params.N);
+        let bk = &ck.bootstrapping_key;
+        let mut sum = Self::programmable_bootstrap_with(&s_and_a, &test, bk)
+            .add(&Self::programmable_bootstrap_with(&not_s_and_b, &test, bk));
+        sum.b = sum.b.add(&Torus::new(0.375));
+        let mut output = Self::key_switch(&sum, ck);
+        output.noise = NoiseLevel::Fresh;
+        ck.hooks.after(&info, &mut output, start.elapsed());
+        output
     }
 }
 
+#[cfg(feature = "client")]
 pub struct TfheEncoder;
 
+#[cfg(feature = "client")]
 impl TfheEncoder {
     pub fn encode_bool(value: bool, sk: &TfheSecretKey) -> TlweSample {
-        let message = if value {
-            Torus::new(0.625)
-        } else {
-            Torus::new(0.125)
-        };
-        TlweSample::encrypt(&message, &sk.tlwe_key)
+        TlweSample::encrypt(&bool_phase(value), &sk.tlwe_key)
     }
 
     pub fn decode_bool(sample: &TlweSample, sk: &TfheSecretKey) -> bool {
@@ -183,27 +690,434 @@ impl TfheEncoder {
             .map(|s| Self::decode_bool(s, sk))
             .collect()
     }
+
+    /// Encrypts bits as they are pulled, with one generator for the whole
+    /// stream, so a large input never has to sit in memory as ciphertexts
+    pub fn encrypt_iter<'a, I>(bits: I, sk: &'a TfheSecretKey) -> impl Iterator<Item = TlweSample> + 'a
+    where
+        I: IntoIterator<Item = bool>,
+        I::IntoIter: 'a,
+    {
+        Self::encrypt_iter_with_rng(bits, sk, default_entropy())
+    }
+
+    /// `encrypt_iter` drawing from `rng`, which the stream owns; pass
+    /// `&mut rng` to keep using it afterwards
+    pub fn encrypt_iter_with_rng<'a, I, R>(bits: I, sk: &'a TfheSecretKey, mut rng: R) -> impl Iterator<Item = TlweSample> + 'a
+    where
+        I: IntoIterator<Item = bool>,
+        I::IntoIter: 'a,
+        R: EntropySource + 'a,
+    {
+        bits.into_iter()
+            .map(move |b| TlweSample::encrypt_with_rng(&bool_phase(b), &sk.tlwe_key, &mut rng))
+    }
+
+    /// Lazy `decode_bool` of owned or borrowed samples
+    pub fn decrypt_iter<'a, I>(samples: I, sk: &'a TfheSecretKey) -> impl Iterator<Item = bool> + 'a
+    where
+        I: IntoIterator,
+        I::Item: Borrow<TlweSample>,
+        I::IntoIter: 'a,
+    {
+        samples.into_iter().map(move |s| Self::decode_bool(s.borrow(), sk))
+    }
+
+    /// `encrypt_iter` over `width`-bit integers, each yielded as its bits
+    /// least significant first, the layout `IntegerOps` works on
+    pub fn encrypt_uint_iter<'a, I>(
+        values: I,
+        width: usize,
+        sk: &'a TfheSecretKey,
+    ) -> impl Iterator<Item = Vec<TlweSample>> + 'a
+    where
+        I: IntoIterator<Item = u64>,
+        I::IntoIter: 'a,
+    {
+        Self::encrypt_uint_iter_with_rng(values, width, sk, default_entropy())
+    }
+
+    /// `encrypt_uint_iter` drawing from `rng`, like `encrypt_iter_with_rng`
+    pub fn encrypt_uint_iter_with_rng<'a, I, R>(
+        values: I,
+        width: usize,
+        sk: &'a TfheSecretKey,
+        mut rng: R,
+    ) -> impl Iterator<Item = Vec<TlweSample>> + 'a
+    where
+        I: IntoIterator<Item = u64>,
+        I::IntoIter: 'a,
+        R: EntropySource + 'a,
+    {
+        assert!(width <= 64, "integers of {} bits do not fit a u64", width);
+        values.into_iter().map(move |v| {
+            assert!(width == 64 || v >> width == 0, "{} does not fit in {} bits", v, width);
+            (0..width)
+                .map(|i| TlweSample::encrypt_with_rng(&bool_phase((v >> i) & 1 == 1), &sk.tlwe_key, &mut rng))
+                .collect()
+        })
+    }
+
+    /// Lazy decryption of integers laid out as by `encrypt_uint_iter`
+    pub fn decrypt_uint_iter<'a, I>(values: I, sk: &'a TfheSecretKey) -> impl Iterator<Item = u64> + 'a
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[TlweSample]>,
+        I::IntoIter: 'a,
+    {
+        values.into_iter().map(move |bits| {
+            Self::decrypt_iter(bits.as_ref(), sk)
+                .enumerate()
+                .fold(0, |acc, (i, b)| acc | ((b as u64) << i))
+        })
+    }
+
+    /// Constant time per sample; faster than `decode_bits` on large result vectors
+    pub fn decode_bits_batch(samples: &[TlweSample], sk: &TfheSecretKey) -> Vec<bool> {
+        sk.tlwe_key.batch_decryptor().decrypt_binary_batch(samples)
+    }
+
+    /// Message `m` of `[0, message_modulus)` at the centre of slot `m` of
+    /// the half torus, `(2m + 1) / 4p`, the encoding `TfheGates::apply_lut`
+    /// reads and writes
+    pub fn encode_int(m: u64, message_modulus: u64, sk: &TfheSecretKey) -> TlweSample {
+        assert!(m < message_modulus, "message {} is not below the modulus {}", m, message_modulus);
+        TlweSample::encrypt(&int_phase(m, message_modulus), &sk.tlwe_key)
+    }
+
+    pub fn decode_int(sample: &TlweSample, message_modulus: u64, sk: &TfheSecretKey) -> u64 {
+        let phase = sample.decrypt_phase(&sk.tlwe_key).value();
+        (phase * 2.0 * message_modulus as f64).floor() as u64 % message_modulus
+    }
+
+    /// Reinterprets an LWE ciphertext over `Z_q` as a torus sample under the
+    /// same key, in the encoding of `encode_int`. A message `m` encrypted as
+    /// `m delta` must keep a padding bit, `m < p / 2` for `p = q / delta`: it
+    /// lands at `m / p`, and half a slot more puts it at the centre of slot
+    /// `m` for `decode_int` and `apply_lut` with message modulus `p / 2`.
+    /// The noise is scaled by `1 / q`.
+    pub fn from_lwe(ct: &LweCiphertext, delta: u64) -> Result<TlweSample, ScalingError> {
+        let p = check_scaling(ct.params.q, delta)?;
+        let reinterpret = |x: u64| Torus::from_fixed64(rounded_div((x as u128) << 64, ct.params.q));
+        let stddev = ct.params.stddev / ct.params.q as f64;
+
+        Ok(TlweSample {
+            a: ct.a.iter().map(|&x| reinterpret(x)).collect(),
+            b: reinterpret(ct.b).add(&half_slot(p)),
+            params: TlweParams { n: ct.params.n, stddev },
+            noise: NoiseLevel::Fresh,
+            estimate: NoiseEstimate::fresh(stddev),
+            namespace: None,
+        })
+    }
+
+    /// Inverse of `from_lwe`: takes off the half slot and rounds every
+    /// coefficient to the nearest multiple of `1 / q`, so message `m` of
+    /// modulus `p / 2` becomes `m delta`
+    pub fn to_lwe(sample: &TlweSample, q: u64, delta: u64) -> Result<LweCiphertext, ScalingError> {
+        let p = check_scaling(q, delta)?;
+        let mut sample = sample.clone();
+        sample.b = sample.b.sub(&half_slot(p));
+        Ok(sample.modulus_switch(q))
+    }
 }
 
-#[cfg(test)]
+/// `TfheEncoder::from_lwe`/`to_lwe` need a scaling factor that cuts the
+/// modulus into an even number `p = q / delta` of at least 2 slots
+#[cfg(feature = "client")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScalingError {
+    pub q: u64,
+    pub delta: u64,
+}
+
+#[cfg(feature = "client")]
+impl std::fmt::Display for ScalingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "scaling factor {} does not divide the modulus {} into an even number of slots", self.delta, self.q)
+    }
+}
+
+#[cfg(feature = "client")]
+impl std::error::Error for ScalingError {}
+
+// Gate encoding of a boolean
+#[cfg(any(feature = "client", feature = "server"))]
+pub(crate) fn bool_phase(value: bool) -> Torus {
+    Torus::new(if value { 0.375 } else { 0.125 })
+}
+
+/// A two-input gate closed by one bootstrap: `wa a + wb b + offset`
+/// through the gate test vector. A bit is `1/4 ± 1/8`, so the weights and
+/// offset put the true cases in `[1/2, 1)` and the false ones in
+/// `[0, 1/2)`, each 1/8 from the edges.
+#[cfg(feature = "server")]
+pub(crate) struct GateSpec {
+    pub(crate) name: &'static str,
+    /// The gate on clear bits, for folding public operands
+    pub(crate) f: fn(bool, bool) -> bool,
+    pub(crate) weights: (i32, i32),
+    pub(crate) offset: f64,
+}
+
+#[cfg(feature = "server")]
+impl GateSpec {
+    pub(crate) const NAND: GateSpec = GateSpec { name: "nand", f: |x, y| !(x && y), weights: (-1, -1), offset: 0.125 };
+    pub(crate) const AND: GateSpec = GateSpec { name: "and", f: |x, y| x && y, weights: (1, 1), offset: -0.125 };
+    pub(crate) const OR: GateSpec = GateSpec { name: "or", f: |x, y| x || y, weights: (1, 1), offset: 0.125 };
+    pub(crate) const NOR: GateSpec = GateSpec { name: "nor", f: |x, y| !(x || y), weights: (-1, -1), offset: -0.125 };
+    pub(crate) const XOR: GateSpec = GateSpec { name: "xor", f: |x, y| x ^ y, weights: (2, 2), offset: 0.75 };
+    pub(crate) const XNOR: GateSpec = GateSpec { name: "xnor", f: |x, y| x == y, weights: (2, 2), offset: 0.25 };
+    pub(crate) const ANDNY: GateSpec = GateSpec { name: "andny", f: |x, y| !x && y, weights: (-1, 1), offset: 0.375 };
+    pub(crate) const ANDYN: GateSpec = GateSpec { name: "andyn", f: |x, y| x && !y, weights: (1, -1), offset: 0.375 };
+    pub(crate) const ORNY: GateSpec = GateSpec { name: "orny", f: |x, y| !x || y, weights: (-1, 1), offset: 0.625 };
+    pub(crate) const ORYN: GateSpec = GateSpec { name: "oryn", f: |x, y| x || !y, weights: (1, -1), offset: 0.625 };
+}
+
+// Gate test vector: false below 1/2, true above
+#[cfg(feature = "server")]
+pub(crate) fn gate_lut() -> Vec<Torus> {
+    let mut lut = vec![bool_phase(false); 1024];
+    lut[512..].fill(bool_phase(true));
+    lut
+}
+
+// Centre of slot `m` when `[0, 1/2)` is cut into `p` slots
+#[cfg(any(feature = "client", feature = "server"))]
+fn int_phase(m: u64, p: u64) -> Torus {
+    Torus::new((2 * m + 1) as f64 / (4 * p) as f64)
+}
+
+// The torus only holds messages of an integer plaintext modulus exactly,
+// and the half-torus encoding needs it even; returns the modulus `q / delta`
+#[cfg(feature = "client")]
+fn check_scaling(q: u64, delta: u64) -> Result<u64, ScalingError> {
+    match q.checked_div(delta) {
+        Some(p) if q.is_multiple_of(delta) && p >= 2 && p.is_multiple_of(2) => Ok(p),
+        _ => Err(ScalingError { q, delta }),
+    }
+}
+
+// `1 / 2p` on the 64-bit fixed point
+#[cfg(feature = "client")]
+fn half_slot(p: u64) -> Torus {
+    Torus::from_fixed64(rounded_div(1 << 63, p))
+}
+
+// `round(x / d)` modulo 2^64, exactly
+#[cfg(feature = "client")]
+fn rounded_div(x: u128, d: u64) -> u64 {
+    let d = d as u128;
+    let (quotient, remainder) = (x / d, x % d);
+    (quotient + (remainder >= d - remainder) as u128) as u64
+}
+
+/// Insecure toy parameters shared by the tests of the crate: a key of
+/// dimension 10 with negligible noise and a ring of degree `big_n`
+#[cfg(all(test, feature = "client", feature = "server"))]
+pub(crate) mod fixtures {
+    use super::*;
+
+    pub(crate) fn small_params(big_n: usize) -> TfheParams {
+        let small = TlweParams { n: 10, stddev: 1e-9 };
+        TfheParams {
+            tlwe_params: small.clone(),
+            tgsw_params: TgswParams { l: 2, bg_bit: 8, tlwe_params: small },
+            n: 10,
+            N: big_n,
+            k: 1,
+        }
+    }
+
+    pub(crate) fn small_keys(big_n: usize) -> (TfheSecretKey, TfheCloudKey) {
+        let sk = TfheSecretKey::generate(small_params(big_n));
+        let ck = TfheCloudKey::generate(&sk);
+        (sk, ck)
+    }
+}
+
+#[cfg(all(test, feature = "client", feature = "server"))]
 mod tests {
     use super::*;
+    use crate::lwe::{LweParams, LweSecretKey};
+    use fixtures::{small_keys, small_params};
 
     #[test]
     fn test_tfhe_gates() {
+        let (sk, ck) = small_keys(32);
+
+        type Gate = fn(&TlweSample, &TlweSample, &TfheCloudKey) -> TlweSample;
+        type Clear = fn(bool, bool) -> bool;
+        let gates: [(Gate, Clear); 10] = [
+            (TfheGates::nand, |x, y| !(x && y)),
+            (TfheGates::and, |x, y| x && y),
+            (TfheGates::or, |x, y| x || y),
+            (TfheGates::nor, |x, y| !(x || y)),
+            (TfheGates::xor, |x, y| x ^ y),
+            (TfheGates::xnor, |x, y| x == y),
+            (TfheGates::andny, |x, y| !x && y),
+            (TfheGates::andyn, |x, y| x && !y),
+            (TfheGates::orny, |x, y| !x || y),
+            (TfheGates::oryn, |x, y| x || !y),
+        ];
+        for x in [false, true] {
+            for y in [false, true] {
+                let (a, b) = (TfheEncoder::encode_bool(x, &sk), TfheEncoder::encode_bool(y, &sk));
+                for (i, (gate, expected)) in gates.iter().enumerate() {
+                    assert_eq!(TfheEncoder::decode_bool(&gate(&a, &b, &ck), &sk), expected(x, y), "gate {} on {} {}", i, x, y);
+                }
+                assert_eq!(TfheEncoder::decode_bool(&TfheGates::not(&a), &sk), !x);
+                let s = TfheEncoder::encode_bool(x ^ y, &sk);
+                assert_eq!(TfheEncoder::decode_bool(&TfheGates::mux(&s, &a, &b, &ck), &sk), if x ^ y { x } else { y });
+            }
+        }
+    }
+
+    #[test]
+    fn test_three_input_gates() {
+        let (sk, ck) = small_keys(32);
+
+        for bits in 0..8 {
+            let values = [bits & 1 == 1, bits & 2 == 2, bits & 4 == 4];
+            let count = values.iter().filter(|&&v| v).count();
+            // All encrypted, then each operand in turn public
+            for public in [None, Some(0), Some(1), Some(2)] {
+                let [a, b, c] = [0, 1, 2].map(|i| {
+                    if public == Some(i) {
+                        TfheGates::trivial_bool(values[i], &sk.params.tlwe_params)
+                    } else {
+                        TfheEncoder::encode_bool(values[i], &sk)
+                    }
+                });
+                let majority = TfheGates::majority(&a, &b, &c, &ck);
+                let parity = TfheGates::xor3(&a, &b, &c, &ck);
+                assert_eq!(TfheEncoder::decode_bool(&majority, &sk), count >= 2, "{:03b} {:?}", bits, public);
+                assert_eq!(TfheEncoder::decode_bool(&parity, &sk), count % 2 == 1, "{:03b} {:?}", bits, public);
+            }
+        }
+    }
+
+    #[test]
+    fn test_blind_rotation_evaluates_lut() {
+        let params = small_params(64);
+        let sk = TfheSecretKey::generate(params);
+        let bk = BootstrappingKey::generate(&sk.tlwe_key, &sk.trlwe_key, sk.params.tgsw_params.clone());
+        let extracted = sk.extracted_key();
+
+        let mut lut = vec![Torus::new(0.125); 1024];
+        lut[..512].fill(Torus::new(0.625));
+        for (phase, expected) in [(0.1, 0.625), (0.35, 0.625), (0.6, 0.125), (0.9, 0.125)] {
+            let input = TlweSample::encrypt(&Torus::new(phase), &sk.tlwe_key);
+            let output = TfheGates::programmable_bootstrap(&input, &lut, &bk);
+            assert_eq!(output.params.n, 64);
+            assert!((output.decrypt_phase(&extracted).value() - expected).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn test_gate_outputs_compose() {
+        let (sk, ck) = small_keys(64);
+        assert_eq!(ck.key_switching_key.decomposition(), sk.params.key_switch_params());
+        assert_eq!(ck.key_switching_key.output_params().n, 10);
+
+        for value in [false, true] {
+            let x = TfheEncoder::encode_bool(value, &sk);
+            let once = TfheGates::nand(&x, &x, &ck);
+            let twice = TfheGates::nand(&once, &once, &ck);
+            assert_eq!(twice.params.n, 10);
+            assert_eq!(TfheEncoder::decode_bool(&once, &sk), !value);
+            assert_eq!(TfheEncoder::decode_bool(&twice, &sk), value);
+        }
+    }
+
+    #[test]
+    fn test_apply_lut() {
+        let (sk, ck) = small_keys(64);
+
+        // A 3-bit sbox and a ReLU on two's complement messages
+        let sbox = [6, 4, 1, 5, 0, 7, 2, 3];
+        let relu = |x: u64| if x < 4 { x } else { 0 };
+        for m in 0..8 {
+            let input = TfheEncoder::encode_int(m, 8, &sk);
+            let out = TfheGates::apply_lut(&input, |x| sbox[x as usize], 8, &ck);
+            assert_eq!(TfheEncoder::decode_int(&out, 8, &sk), sbox[m as usize]);
+            let chained = TfheGates::apply_lut(&out, relu, 8, &ck);
+            assert_eq!(TfheEncoder::decode_int(&chained, 8, &sk), relu(sbox[m as usize]));
+        }
+    }
+
+    #[test]
+    fn test_lwe_torus_bridge() {
+        let (sk, ck) = small_keys(64);
+        // The same noise as the key, in units of `1 / q`
+        let lwe_params = LweParams { n: 10, q: 1 << 32, stddev: 1e-9 * (1u64 << 32) as f64 };
+        let lwe_key = LweSecretKey { coeffs: sk.tlwe_key.coeffs.clone(), params: lwe_params.clone() };
+
+        // 16 slots of `Z_q`, the top one a padding bit: messages modulo 8
+        let delta = 1 << 28;
+        for m in [0, 5, 7] {
+            let ct = LweCiphertext::encrypt(m * delta, &lwe_key);
+            let sample = TfheEncoder::from_lwe(&ct, delta).unwrap();
+            assert_eq!(TfheEncoder::decode_int(&sample, 8, &sk), m);
+
+            let squared = TfheGates::apply_lut(&sample, |x| x * x, 8, &ck);
+            assert_eq!(TfheEncoder::decode_int(&squared, 8, &sk), m * m % 8);
+
+            let back = TfheEncoder::to_lwe(&squared, lwe_params.q, delta).unwrap();
+            assert_eq!((back.decrypt(&lwe_key) + delta / 2) / delta % 16, m * m % 8);
+        }
+
+        let ct = LweCiphertext::encrypt(0, &lwe_key);
+        for delta in [0, 3, 1 << 30 | 1, 1 << 32, 1 << 33] {
+            assert_eq!(TfheEncoder::from_lwe(&ct, delta).unwrap_err(), ScalingError { q: 1 << 32, delta });
+        }
+        assert!(TfheEncoder::to_lwe(&TfheEncoder::encode_int(0, 2, &sk), 1 << 32, 3).is_err());
+
+        // (q - 1) / q is within 2^-64 of 1 for q near 2^64, yet short of it
+        let q = u64::MAX - 1;
+        let ct = LweCiphertext { a: vec![q - 1], b: 0, params: LweParams { n: 1, q, stddev: 0.0 } };
+        let sample = TfheEncoder::from_lwe(&ct, q / 2).unwrap();
+        assert_eq!(sample.a[0], Torus::from_fixed64(u64::MAX));
+        assert_eq!(sample.b, Torus::from_fixed64(1 << 62));
+    }
+
+    #[test]
+    fn test_encoder_decoder() {
+        let sk = TfheSecretKey::generate(small_params(32));
+
+        let enc_true = TfheEncoder::encode_bool(true, &sk);
+        let dec_true = TfheEncoder::decode_bool(&enc_true, &sk);
+        assert_eq!(dec_true, true);
+
+        let enc_false = TfheEncoder::encode_bool(false, &sk);
+        let dec_false = TfheEncoder::decode_bool(&enc_false, &sk);
+        assert_eq!(dec_false, false);
+
+        let bits = vec![true, false, true, true, false];
+        let encoded = TfheEncoder::encode_bits(&bits, &sk);
+        let decoded = TfheEncoder::decode_bits(&encoded, &sk);
+        assert_eq!(decoded, bits);
+
+        // Streams are encrypted and decrypted only as far as they are read
+        let stream = (0..1_000_000u32).map(|i| i % 3 == 0);
+        let first: Vec<TlweSample> = TfheEncoder::encrypt_iter(stream, &sk).take(6).collect();
+        let read: Vec<bool> = TfheEncoder::decrypt_iter(&first, &sk).collect();
+        assert_eq!(read, [true, false, false, true, false, false]);
+
+        let values = [0u64, 5, 255, 128];
+        let encrypted = TfheEncoder::encrypt_uint_iter(values, 8, &sk);
+        assert!(TfheEncoder::decrypt_uint_iter(encrypted, &sk).eq(values));
+    }
+
+    #[test]
+    #[should_panic(expected = "noise budget")]
+    fn test_gates_reject_degraded_operands() {
         let params = TfheParams {
             tlwe_params: TlweParams {
                 n: 10,
-                stddev: 1e-9,
-            },
-            tgsw_params: TgswParams {
-                l: 2,
-                bg_bit: 8,
-                tlwe_params: TlweParams {
-                    n: 10,
-                    stddev: 1e-9,
-                },
+                stddev: 1e-3,
             },
+            tgsw_params: TgswParams::default(),
             n: 10,
             N: 32,
             k: 1,
@@ -212,45 +1126,78 @@ mod tests {
         let sk = TfheSecretKey::generate(params);
         let ck = TfheCloudKey::generate(&sk);
 
-        let a_true = TfheEncoder::encode_bool(true, &sk);
-        let b_true = TfheEncoder::encode_bool(true, &sk);
-        let a_false = TfheEncoder::encode_bool(false, &sk);
+        let a = TfheEncoder::encode_bool(true, &sk);
+        let degraded = a.scalar_mul(25);
+        assert!(degraded.noise.needs_bootstrap());
+        TfheGates::and(&degraded, &a, &ck);
+    }
+
+    #[test]
+    #[should_panic(expected = "namespace mismatch")]
+    fn test_gates_reject_other_namespaces() {
+        let (sk, ck) = small_keys(64);
+        let alice = ck.clone().with_namespace(Namespace::new("alice"));
+        let bob = ck.with_namespace(Namespace::new("bob"));
 
-        let result_and = a_true.add(&b_true);
-        let phase = result_and.decrypt_phase(&sk.tlwe_key);
-        assert!(phase.value() > 0.5);
+        // Fresh encryptions belong to no tenant and are accepted by both keys
+        let x = TfheEncoder::encode_bool(true, &sk);
+        let from_alice = TfheGates::nand(&x, &x, &alice);
+        let from_bob = TfheGates::nand(&x, &x, &bob);
+        assert_eq!(from_alice.namespace, Some(Namespace::new("alice")));
+        assert!(TfheEncoder::decode_bool(&TfheGates::nand(&from_alice, &x, &alice), &sk));
 
-        let result_xor = a_true.sub(&a_false);
-        let phase_xor = result_xor.decrypt_phase(&sk.tlwe_key);
-        assert!(phase_xor.value() > 0.25 && phase_xor.value() < 0.75);
+        TfheGates::nand(&from_alice, &from_bob, &bob);
     }
 
     #[test]
-    fn test_encoder_decoder() {
-        let params = TfheParams {
-            tlwe_params: TlweParams {
-                n: 10,
-                stddev: 1e-9,
-            },
+    fn test_leveled_depth() {
+        let params = TfheParams::default();
+        let depth = params.leveled_depth();
+        assert!(depth > 0);
+
+        // Noisier bootstrapping keys leave room for fewer products
+        let mut noisy = params.clone();
+        noisy.tgsw_params.tlwe_params.stddev *= 100.0;
+        assert!(noisy.leveled_depth() < depth);
+    }
+
+    #[test]
+    fn test_security_presets() {
+        assert_eq!(TfheParams::default_128().check_security(128), Ok(()));
+        assert_eq!(TfheParams::default_80().check_security(80), Ok(()));
+        assert!(TfheParams::default_80().estimated_security() < 128);
+
+        // The toy parameters of the examples and tests
+        let tiny = TfheParams {
+            tlwe_params: TlweParams { n: 10, stddev: 1e-9 },
             tgsw_params: TgswParams::default(),
             n: 10,
             N: 32,
             k: 1,
         };
+        let errors = tiny.check_security(80).unwrap_err();
+        assert!(errors.contains(&ParamsWarning::Insecure { part: "lwe", bits: 1, required: 80 }));
+        assert!(errors.iter().any(|e| matches!(e, ParamsWarning::Insecure { part: "ring", .. })));
 
-        let sk = TfheSecretKey::generate(params);
-
-        let enc_true = TfheEncoder::encode_bool(true, &sk);
-        let dec_true = TfheEncoder::decode_bool(&enc_true, &sk);
-        assert_eq!(dec_true, true);
+        let odd_ring = TfheParams { N: 1000, ..TfheParams::default_128() };
+        assert!(odd_ring.check_security(128).unwrap_err().contains(&ParamsWarning::RingDegree { big_n: 1000 }));
+    }
 
-        let enc_false = TfheEncoder::encode_bool(false, &sk);
-        let dec_false = TfheEncoder::decode_bool(&enc_false, &sk);
-        assert_eq!(dec_false, false);
+    #[test]
+    fn test_validate() {
+        let params = TfheParams::default();
+        let warnings = params.validate();
+        assert!(warnings.iter().all(|w| matches!(w, ParamsWarning::OversizedKey { .. })));
 
-        let bits = vec![true, false, true, true, false];
-        let encoded = TfheEncoder::encode_bits(&bits, &sk);
-        let decoded = TfheEncoder::decode_bits(&encoded, &sk);
-        assert_eq!(decoded, bits);
+        let noisy = TfheParams {
+            tgsw_params: TgswParams { l: 1, bg_bit: 40, ..TgswParams::default() },
+            n: 500,
+            ..TfheParams::default()
+        };
+        let warnings = noisy.validate();
+        assert_eq!(warnings[0], ParamsWarning::DimensionMismatch { n: 500, tlwe_n: 630 });
+        assert_eq!(warnings[1], ParamsWarning::DecompositionPrecision { bits: 40 });
+        assert!(matches!(warnings[2], ParamsWarning::BootstrapNoise { .. }));
+        assert!(warnings[2].to_string().starts_with("bootstrap noise variance"));
     }
-}
\ No newline at end of file
+}
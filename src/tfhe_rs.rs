@@ -0,0 +1,81 @@
+use tfhe::boolean::prelude::{BinaryBooleanGates, Ciphertext, ClientKey, ServerKey};
+use crate::evaluator::BooleanEvaluator;
+
+/// Evaluates gates with tfhe-rs boolean keys, so circuits written against
+/// `BooleanEvaluator` run unchanged on a production-grade backend
+pub struct TfheRsEvaluator<'a> {
+    pub server_key: &'a ServerKey,
+}
+
+impl<'a> TfheRsEvaluator<'a> {
+    pub fn new(server_key: &'a ServerKey) -> Self {
+        TfheRsEvaluator { server_key }
+    }
+}
+
+/// Generates a tfhe-rs client/server key pair with its default parameters
+pub fn generate_keys() -> (ClientKey, ServerKey) {
+    tfhe::boolean::gen_keys()
+}
+
+impl BooleanEvaluator for TfheRsEvaluator<'_> {
+    type Bit = Ciphertext;
+
+    fn constant(&self, value: bool) -> Ciphertext {
+        self.server_key.trivial_encrypt(value)
+    }
+
+    fn not(&self, a: &Ciphertext) -> Ciphertext {
+        self.server_key.not(a)
+    }
+
+    fn and(&self, a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+        self.server_key.and(a, b)
+    }
+
+    fn or(&self, a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+        self.server_key.or(a, b)
+    }
+
+    fn xor(&self, a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+        self.server_key.xor(a, b)
+    }
+
+    fn nand(&self, a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+        self.server_key.nand(a, b)
+    }
+
+    fn mux(&self, s: &Ciphertext, a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+        self.server_key.mux(s, a, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+
+    #[test]
+    fn test_tfhe_rs_evaluator_runs_circuit() {
+        let (client_key, server_key) = generate_keys();
+        let eval = TfheRsEvaluator::new(&server_key);
+
+        let mut circuit = Circuit::new();
+        let s = circuit.input();
+        let a = circuit.input();
+        let b = circuit.input();
+        let m = circuit.mux(s, a, b);
+        let x = circuit.xor(m, b);
+        circuit.output(m);
+        circuit.output(x);
+
+        let inputs: Vec<Ciphertext> = [true, false, true].iter()
+            .map(|&bit| client_key.encrypt(bit))
+            .collect();
+        let outputs: Vec<bool> = circuit.evaluate(&eval, &inputs).iter()
+            .map(|ct| client_key.decrypt(ct))
+            .collect();
+
+        assert_eq!(outputs, vec![false, true]);
+    }
+}
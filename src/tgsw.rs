@@ -1,7 +1,18 @@
+#[cfg(feature = "client")]
+use crate::entropy::{default_entropy, seeded_entropy, EntropySource};
+use std::sync::Arc;
+use crate::fft::{Complex, NegacyclicFft};
+use crate::noise::NoiseEstimate;
+use crate::noise_model;
 use crate::torus::Torus;
-use crate::tlwe::{TlweSample, TlweSecretKey, TlweParams};
-
-#[derive(Debug, Clone)]
+use crate::tlwe::TlweParams;
+#[cfg(feature = "client")]
+use crate::tlwe::TlweSecretKey;
+use crate::trlwe::{TorusPolynomial, TrlweParams, TrlweSample, FFT_THRESHOLD};
+#[cfg(feature = "client")]
+use crate::trlwe::TrlweSecretKey;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct TgswParams {
     pub l: usize,
     pub bg_bit: u32,
@@ -18,165 +29,336 @@ impl Default for TgswParams {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct TgswSample {
-    pub samples: Vec<Vec<TlweSample>>,
-    pub k: usize,
+/// Noise and size of one `(l, bg_bit)` gadget decomposition
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TgswTradeoff {
     pub l: usize,
-    pub params: TgswParams,
+    pub bg_bit: u32,
+    /// Variance one external product adds
+    pub variance: f64,
+    /// Size of a bootstrapping key of `n` samples
+    pub key_bytes: usize,
 }
 
-impl TgswSample {
-    pub fn encrypt(message: i32, sk: &TlweSecretKey, params: TgswParams) -> Self {
-        let k = 1;
-        let l = params.l;
-        let bg = 1u64 << params.bg_bit;
+impl TgswParams {
+    /// Bits of each torus value the decomposition reads
+    pub const DECOMPOSITION_BITS: u32 = 32;
 
-        let mut samples = vec![vec![]; k + 1];
+    /// Variance one external product adds over rank-`k` polynomials of
+    /// degree `big_n`; see `noise_model::external_product`
+    pub fn external_product_variance(&self, big_n: usize, k: usize) -> f64 {
+        noise_model::external_product(self, big_n, k)
+    }
 
-        for i in 0..=k {
-            samples[i] = Vec::with_capacity(l);
-            for j in 0..l {
-                let h_value = 1.0 / (bg.pow(j as u32 + 1) as f64);
+    /// Size of one sample: `(k+1) l` rows of `k+1` polynomials of degree `big_n`
+    pub fn sample_bytes(&self, big_n: usize, k: usize) -> usize {
+        (k + 1) * self.l * (k + 1) * big_n * std::mem::size_of::<Torus>()
+    }
 
-                let msg_value = if i == 0 {
-                    -(sk.coeffs[0] as f64) * (message as f64) * h_value
-                } else {
-                    (message as f64) * h_value
-                };
+    /// Every decomposition within `DECOMPOSITION_BITS`, with the noise of
+    /// one external product and the size of an `n`-sample bootstrapping key
+    pub fn tradeoffs(big_n: usize, k: usize, n: usize, stddev: f64) -> Vec<TgswTradeoff> {
+        let tlwe_params = TlweParams { n: k * big_n, stddev };
+        (1..=Self::DECOMPOSITION_BITS as usize)
+            .flat_map(|l| (1..=Self::DECOMPOSITION_BITS / l as u32).map(move |bg_bit| (l, bg_bit)))
+            .map(|(l, bg_bit)| {
+                let params = TgswParams { l, bg_bit, tlwe_params: tlwe_params.clone() };
+                TgswTradeoff {
+                    l,
+                    bg_bit,
+                    variance: params.external_product_variance(big_n, k),
+                    key_bytes: n * params.sample_bytes(big_n, k),
+                }
+            })
+            .collect()
+    }
 
-                let msg_torus = Torus::new(msg_value);
+    /// Smallest bootstrapping key whose `n` chained external products stay
+    /// below `max_variance`, the least noisy among equal sizes
+    pub fn recommend(big_n: usize, k: usize, n: usize, stddev: f64, max_variance: f64) -> Option<TgswTradeoff> {
+        Self::tradeoffs(big_n, k, n, stddev)
+            .into_iter()
+            .filter(|t| t.variance * n as f64 <= max_variance)
+            .min_by(|a, b| a.key_bytes.cmp(&b.key_bytes).then(a.variance.total_cmp(&b.variance)))
+    }
+}
 
-                samples[i].push(TlweSample::encrypt(&msg_torus, sk));
-            }
-        }
+/// Ring GSW sample of an integer polynomial `m(X)`: `(k+1) l` TRLWE
+/// encryptions of zero, row `(i, j)` with `m(X) / Bg^(j+1)` added to mask
+/// `i`, or to the body for `i = k`. The external product with a TRLWE
+/// sample multiplies its message by `m(X)`, so a selector can rotate or
+/// choose between whole lookup polynomials; blind rotation uses constant
+/// ones, the key bits.
+#[derive(Debug, Clone)]
+pub struct TrgswSample {
+    pub rows: Vec<Vec<TrlweSample>>,
+    pub params: TgswParams,
+}
 
-        TgswSample {
-            samples,
-            k,
-            l,
-            params,
-        }
+impl TrgswSample {
+    /// Encryption of the constant polynomial `message`
+    #[cfg(feature = "client")]
+    pub fn encrypt(message: i32, sk: &TrlweSecretKey, params: TgswParams) -> Self {
+        Self::encrypt_with_rng(message, sk, params, &mut default_entropy())
     }
 
-    pub fn trivial(message: i32, params: TgswParams) -> Self {
-        let k = 1;
-        let l = params.l;
-        let bg = 1u64 << params.bg_bit;
+    #[cfg(feature = "client")]
+    pub fn encrypt_with_rng<R: EntropySource + ?Sized>(message: i32, sk: &TrlweSecretKey, params: TgswParams, rng: &mut R) -> Self {
+        let mut poly = vec![0; sk.params.N];
+        poly[0] = message;
+        Self::encrypt_poly_with_rng(&poly, sk, params, rng)
+    }
 
-        let mut samples = vec![vec![]; k + 1];
+    /// Encryption of `message`, coefficient `i` of `m(X)` at index `i`
+    #[cfg(feature = "client")]
+    pub fn encrypt_poly(message: &[i32], sk: &TrlweSecretKey, params: TgswParams) -> Self {
+        Self::encrypt_poly_with_rng(message, sk, params, &mut default_entropy())
+    }
 
-        for i in 0..=k {
-            samples[i] = Vec::with_capacity(l);
-            for j in 0..l {
-                let h_value = 1.0 / (bg.pow(j as u32 + 1) as f64);
-                let msg_value = if i == k {
-                    (message as f64) * h_value
-                } else {
-                    0.0
-                };
+    #[cfg(feature = "client")]
+    pub fn encrypt_poly_with_rng<R: EntropySource + ?Sized>(
+        message: &[i32],
+        sk: &TrlweSecretKey,
+        params: TgswParams,
+        rng: &mut R,
+    ) -> Self {
+        let zero = TorusPolynomial::zero(sk.params.N);
+        Self::gadget(message, &sk.params, params, || TrlweSample::encrypt_with_rng(&zero, sk, rng))
+    }
+
+    /// Noiseless sample of a public `message`
+    pub fn trivial(message: &[i32], ring: &TrlweParams, params: TgswParams) -> Self {
+        let zero = TorusPolynomial::zero(ring.N);
+        Self::gadget(message, ring, params, || TrlweSample::trivial(&zero, ring.clone()))
+    }
+
+    // Rows of zero from `zero`, with the gadget multiples of `message` added
+    fn gadget(
+        message: &[i32],
+        ring: &TrlweParams,
+        params: TgswParams,
+        mut zero: impl FnMut() -> TrlweSample,
+    ) -> Self {
+        assert_eq!(message.len(), ring.N, "message degree does not match the ring");
+        let rows = (0..=ring.k)
+            .map(|i| {
+                (0..params.l)
+                    .map(|j| {
+                        let mut row = zero();
+                        let weight = Torus::new((-(((j as u32 + 1) * params.bg_bit) as f64)).exp2());
+                        let component = if i < ring.k { &mut row.a[i] } else { &mut row.b };
+                        for (c, &m) in component.coeffs.iter_mut().zip(message) {
+                            *c = c.add(&weight.mul_int(m as i64));
+                        }
+                        row
+                    })
+                    .collect()
+            })
+            .collect();
+
+        TrgswSample { rows, params }
+    }
+
+    /// Signed gadget digits of every coefficient: `l` integer polynomials
+    /// `d_j` with coefficients in `[-Bg/2, Bg/2)` and `p ~ sum d_j / Bg^(j+1)`
+    pub fn decompose(p: &TorusPolynomial, params: &TgswParams) -> Vec<Vec<i32>> {
+        let bits = params.l as u32 * params.bg_bit;
+        assert!(bits < 64, "decomposition precision must be below 64 bits");
+
+        let mut digits = vec![vec![0; p.degree()]; params.l];
+        for (idx, c) in p.coeffs.iter().enumerate() {
+            for (level, digit) in digits.iter_mut().zip(c.decompose(params.l, params.bg_bit)) {
+                level[idx] = digit;
+            }
+        }
+        digits
+    }
+
+    /// Encryption of `m(X)` times the message of `sample`; from
+    /// `FFT_THRESHOLD` coefficients on, through a `TrgswSpectrum` of the rows
+    pub fn external_product(&self, sample: &TrlweSample) -> TrlweSample {
+        let ring = &sample.params;
+        if ring.N >= FFT_THRESHOLD {
+            return TrgswSpectrum::new(self).external_product(sample);
+        }
 
-                let msg_torus = Torus::new(msg_value);
-                samples[i].push(TlweSample::trivial(&msg_torus, params.tlwe_params.clone()));
+        let mut a = vec![TorusPolynomial::zero(ring.N); ring.k];
+        let mut b = TorusPolynomial::zero(ring.N);
+        let components = sample.a.iter().chain(std::iter::once(&sample.b));
+        for (rows, component) in self.rows.iter().zip(components) {
+            for (row, digit) in rows.iter().zip(Self::decompose(component, &self.params)) {
+                for (acc, row_a) in a.iter_mut().zip(row.a.iter()) {
+                    *acc = acc.add(&row_a.mul_int_schoolbook(&digit));
+                }
+                b = b.add(&row.b.mul_int_schoolbook(&digit));
             }
         }
 
-        TgswSample {
-            samples,
-            k,
-            l,
-            params,
+        TrlweSample {
+            a,
+            b,
+            params: ring.clone(),
+            noise: sample.noise,
+            estimate: sample.estimate.with_added(noise_model::external_product(&self.params, ring.N, ring.k)),
         }
     }
 
-    pub fn decompose(value: &Torus, params: &TgswParams) -> Vec<i32> {
-        let bg = 1u64 << params.bg_bit;
-        let half_bg = (bg / 2) as i32;
+    /// `c1` if the sample encrypts 1, `c0` if it encrypts 0; in general
+    /// `c0 + m(X) (c1 - c0)`
+    pub fn cmux(&self, c0: &TrlweSample, c1: &TrlweSample) -> TrlweSample {
+        select(&self.params, self.external_product(&c1.sub(c0)), c0, c1)
+    }
+}
 
-        let mut result = Vec::with_capacity(params.l);
-        let mut v = (value.value() * (1u64 << 32) as f64) as i64;
+// `c0` plus the external product of `c1 - c0`; only the selected input's
+// noise carries over, not the difference's
+fn select(params: &TgswParams, product: TrlweSample, c0: &TrlweSample, c1: &TrlweSample) -> TrlweSample {
+    let mut output = product.add(c0);
+    let ring = &c0.params;
+    let variance = noise_model::cmux(params, ring.N, ring.k, c0.estimate.variance, c1.estimate.variance);
+    output.estimate = NoiseEstimate { variance };
+    output
+}
 
-        for _ in 0..params.l {
-            let digit = ((v % bg as i64) as i32) - half_bg;
-            result.push(digit);
-            v /= bg as i64;
-        }
+/// A `TrgswSample` with every row polynomial in the FFT domain. An
+/// external product then transforms only the digits of the other operand,
+/// sums the pointwise products and takes one inverse transform per output
+/// polynomial.
+#[derive(Debug, Clone)]
+pub struct TrgswSpectrum {
+    /// Row `(i, j)` as the spectra of its masks, then of its body
+    rows: Vec<Vec<Vec<Vec<Complex>>>>,
+    params: TgswParams,
+    fft: Arc<NegacyclicFft>,
+}
 
-        result
+impl TrgswSpectrum {
+    pub fn new(sample: &TrgswSample) -> Self {
+        let fft = NegacyclicFft::cached(sample.rows[0][0].params.N);
+        let rows = sample.rows.iter()
+            .map(|rows| {
+                rows.iter()
+                    .map(|row| row.a.iter().chain(std::iter::once(&row.b)).map(|p| fft.forward_torus(p)).collect())
+                    .collect()
+            })
+            .collect();
+        TrgswSpectrum { rows, params: sample.params.clone(), fft }
     }
 
-    pub fn external_product(&self, tlwe: &TlweSample) -> TlweSample {
-        let mut decomposed_a = Vec::with_capacity(self.params.tlwe_params.n);
-        for i in 0..self.params.tlwe_params.n {
-            decomposed_a.push(Self::decompose(&tlwe.a[i], &self.params));
-        }
-        let decomposed_b = Self::decompose(&tlwe.b, &self.params);
-
-        let mut result_a = vec![Torus::new(0.0); self.params.tlwe_params.n];
-        let mut result_b = Torus::new(0.0);
-
-        for i in 0..=self.k {
-            for j in 0..self.l {
-                if i < self.k {
-                    for idx in 0..self.params.tlwe_params.n {
-                        let scalar = decomposed_a[i][j];
-                        result_a[idx] = result_a[idx].add(
-                            &self.samples[i][j].a[idx].mul_scalar(scalar as f64)
-                        );
-                    }
-                    result_b = result_b.add(
-                        &self.samples[i][j].b.mul_scalar(decomposed_a[i][j] as f64)
-                    );
-                } else {
-                    for idx in 0..self.params.tlwe_params.n {
-                        result_a[idx] = result_a[idx].add(
-                            &self.samples[i][j].a[idx].mul_scalar(decomposed_b[j] as f64)
-                        );
+    /// `TrgswSample::external_product` of the sample this was built from
+    pub fn external_product(&self, sample: &TrlweSample) -> TrlweSample {
+        let ring = &sample.params;
+        assert_eq!(ring.N, self.fft.degree(), "sample and TRGSW rows have different degrees");
+
+        let mut sums = vec![vec![Complex::new(0.0, 0.0); ring.N]; ring.k + 1];
+        let components = sample.a.iter().chain(std::iter::once(&sample.b));
+        for (rows, component) in self.rows.iter().zip(components) {
+            for (row, digit) in rows.iter().zip(TrgswSample::decompose(component, &self.params)) {
+                let digit = self.fft.forward_int(&digit);
+                for (sum, spectrum) in sums.iter_mut().zip(row) {
+                    for ((s, &x), &d) in sum.iter_mut().zip(spectrum).zip(&digit) {
+                        *s = *s + x * d;
                     }
-                    result_b = result_b.add(
-                        &self.samples[i][j].b.mul_scalar(decomposed_b[j] as f64)
-                    );
                 }
             }
         }
 
-        TlweSample {
-            a: result_a,
-            b: result_b,
-            params: self.params.tlwe_params.clone(),
+        let mut a: Vec<TorusPolynomial> = sums.iter().map(|sum| self.fft.inverse_torus(sum)).collect();
+        let b = a.pop().expect("the body follows the masks");
+        TrlweSample {
+            a,
+            b,
+            params: ring.clone(),
+            noise: sample.noise,
+            estimate: sample.estimate.with_added(noise_model::external_product(&self.params, ring.N, ring.k)),
         }
     }
 
-    pub fn cmux(&self, c0: &TlweSample, c1: &TlweSample) -> TlweSample {
-        let diff = c1.sub(c0);
-
-        let product = self.external_product(&diff);
-
-        product.add(c0)
+    /// `TrgswSample::cmux` of the sample this was built from
+    pub fn cmux(&self, c0: &TrlweSample, c1: &TrlweSample) -> TrlweSample {
+        select(&self.params, self.external_product(&c1.sub(c0)), c0, c1)
     }
 }
 
+/// One `TrgswSample` per coefficient of the LWE key, encrypted under the
+/// ring key the blind rotation accumulates in
 #[derive(Debug, Clone)]
 pub struct BootstrappingKey {
-    pub bk: Vec<TgswSample>,
+    pub bk: Vec<TrgswSample>,
     pub n: usize,
     pub params: TgswParams,
+    pub ring_params: TrlweParams,
+    /// `bk` in the FFT domain, from `FFT_THRESHOLD` coefficients on
+    spectra: Vec<TrgswSpectrum>,
 }
 
 impl BootstrappingKey {
-    pub fn generate(sk: &TlweSecretKey, params: TgswParams) -> Self {
-        let n = sk.params.n;
-        let mut bk = Vec::with_capacity(n);
+    /// Key of the given entries, moved to the FFT domain once here so blind
+    /// rotations never transform a key row
+    pub fn new(bk: Vec<TrgswSample>, n: usize, params: TgswParams, ring_params: TrlweParams) -> Self {
+        let spectra = if ring_params.N >= FFT_THRESHOLD {
+            #[cfg(feature = "parallel")]
+            {
+                use rayon::prelude::*;
+                bk.par_iter().map(TrgswSpectrum::new).collect()
+            }
+            #[cfg(not(feature = "parallel"))]
+            bk.iter().map(TrgswSpectrum::new).collect()
+        } else {
+            Vec::new()
+        };
+        BootstrappingKey { bk, n, params, ring_params, spectra }
+    }
 
-        for i in 0..n {
-            bk.push(TgswSample::encrypt(sk.coeffs[i], sk, params.clone()));
+    /// `cmux` by entry `i`, on its spectrum when the key has one
+    pub fn cmux(&self, i: usize, c0: &TrlweSample, c1: &TrlweSample) -> TrlweSample {
+        match self.spectra.get(i) {
+            Some(spectrum) => spectrum.cmux(c0, c1),
+            None => self.bk[i].cmux(c0, c1),
         }
+    }
+}
+
+#[cfg(feature = "client")]
+impl BootstrappingKey {
+    pub fn generate(sk: &TlweSecretKey, ring_key: &TrlweSecretKey, params: TgswParams) -> Self {
+        Self::generate_with_rng(sk, ring_key, params, &mut default_entropy())
+    }
+
+    /// Every entry draws from its own ChaCha20 stream seeded from `rng`, so
+    /// the entries can be encrypted on several threads (`parallel` feature)
+    /// and a seeded `rng` replays the same key either way
+    pub fn generate_with_rng<R: EntropySource + ?Sized>(
+        sk: &TlweSecretKey,
+        ring_key: &TrlweSecretKey,
+        params: TgswParams,
+        rng: &mut R,
+    ) -> Self {
+        let n = sk.params.n;
+        let seeds: Vec<[u8; 32]> = sk.coeffs.iter()
+            .map(|_| {
+                let mut seed = [0u8; 32];
+                rng.fill_bytes(&mut seed);
+                seed
+            })
+            .collect();
+        let encrypt = |(&s, seed): (&i32, &[u8; 32])| {
+            TrgswSample::encrypt_with_rng(s, ring_key, params.clone(), &mut seeded_entropy(*seed))
+        };
+
+        #[cfg(feature = "parallel")]
+        let bk = {
+            use rayon::prelude::*;
+            sk.coeffs.par_iter().zip(seeds.par_iter()).map(encrypt).collect()
+        };
+        #[cfg(not(feature = "parallel"))]
+        let bk = sk.coeffs.iter().zip(seeds.iter()).map(encrypt).collect();
 
-        BootstrappingKey { bk, n, params }
+        BootstrappingKey::new(bk, n, params, ring_key.params.clone())
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "client"))]
 mod tests {
     use super::*;
 
@@ -184,7 +366,7 @@ mod tests {
     fn test_tgsw_decompose() {
         let params = TgswParams::default();
         let value = Torus::new(0.123456);
-        let decomposed = TgswSample::decompose(&value, &params);
+        let decomposed = value.decompose(params.l, params.bg_bit);
 
         assert_eq!(decomposed.len(), params.l);
 
@@ -195,36 +377,91 @@ mod tests {
     }
 
     #[test]
-    fn test_tgsw_cmux() {
-        let tlwe_params = TlweParams {
-            n: 10,
-            stddev: 1e-9,
-        };
+    fn test_trgsw_cmux() {
+        let sk = TrlweSecretKey::generate_binary(TrlweParams { N: 64, k: 1, stddev: 1e-9 });
+        let params = TgswParams::default();
+        let m0 = TorusPolynomial { coeffs: (0..64).map(|i| Torus::new(i as f64 / 64.0)).collect() };
+        let m1 = m0.mul_by_xai(7);
+        let c0 = TrlweSample::encrypt(&m0, &sk);
+        let c1 = TrlweSample::encrypt(&m1, &sk);
+
+        for (bit, expected) in [(0, &m0), (1, &m1)] {
+            let selector = TrgswSample::encrypt(bit, &sk, params.clone());
+            let phase = selector.cmux(&c0, &c1).decrypt_phase(&sk);
+            for (x, y) in phase.coeffs.iter().zip(expected.coeffs.iter()) {
+                let error = (x.value() - y.value() + 0.5).rem_euclid(1.0) - 0.5;
+                assert!(error.abs() < 1e-4);
+            }
+        }
+    }
 
-        let tgsw_params = TgswParams {
-            l: 2,
-            bg_bit: 8,
-            tlwe_params: tlwe_params.clone(),
+    #[test]
+    fn test_trgsw_polynomial_messages() {
+        let ring = TrlweParams { N: 64, k: 1, stddev: 1e-9 };
+        let sk = TrlweSecretKey::generate_binary(ring.clone());
+        let params = TgswParams::default();
+        let lut = TorusPolynomial { coeffs: (0..64).map(|i| Torus::new(i as f64 / 128.0)).collect() };
+        let c = TrlweSample::encrypt(&lut, &sk);
+        let close = |p: &TorusPolynomial, q: &TorusPolynomial| {
+            p.coeffs.iter().zip(q.coeffs.iter()).all(|(x, y)| {
+                let error = (x.value() - y.value() + 0.5).rem_euclid(1.0) - 0.5;
+                error.abs() < 1e-4
+            })
         };
 
-        let sk = TlweSecretKey::generate_binary(tlwe_params.clone());
-
-        let m0 = Torus::new(0.1);
-        let m1 = Torus::new(0.7);
-        let c0 = TlweSample::encrypt(&m0, &sk);
-        let c1 = TlweSample::encrypt(&m1, &sk);
-
-        let selector = TgswSample::encrypt(1, &sk, tgsw_params.clone());
-
-        let result = selector.cmux(&c0, &c1);
-        let decrypted = result.decrypt_phase(&sk);
+        // X^5 - X^2 times the table, against the same product in the clear
+        let mut m = vec![0; 64];
+        m[5] = 1;
+        m[2] = -1;
+        let expected = lut.mul_int_schoolbook(&m);
+        let product = TrgswSample::encrypt_poly(&m, &sk, params.clone()).external_product(&c);
+        assert!(close(&product.decrypt_phase(&sk), &expected));
+        let public = TrgswSample::trivial(&m, &ring, params.clone()).external_product(&c);
+        assert!(close(&public.decrypt_phase(&sk), &expected));
+
+        // A monomial selector rotates the whole table
+        let mut x3 = vec![0; 64];
+        x3[3] = 1;
+        let rotated = TrgswSample::encrypt_poly(&x3, &sk, params).external_product(&c);
+        assert!(close(&rotated.decrypt_phase(&sk), &lut.mul_by_xai(3)));
+    }
 
-        assert!((decrypted.value() - 0.7).abs() < 0.01);
+    #[test]
+    fn test_tradeoff_advisor() {
+        let params = TgswParams::default();
+        let base = params.external_product_variance(1024, 1);
+        let more_levels = TgswParams { l: 4, ..params.clone() };
+        // An extra level shrinks the rounding term but grows the key noise and size
+        assert!(more_levels.external_product_variance(1024, 1) > base);
+        assert_eq!(more_levels.sample_bytes(1024, 1), params.sample_bytes(1024, 1) / 3 * 4);
+
+        let tradeoffs = TgswParams::tradeoffs(1024, 1, 630, 2e-9);
+        assert!(tradeoffs.iter().all(|t| t.l * t.bg_bit as usize <= 32));
+        let default = tradeoffs.iter().find(|t| t.l == 3 && t.bg_bit == 10).unwrap();
+        assert_eq!(default.variance, base);
+
+        let best = TgswParams::recommend(1024, 1, 630, 2e-9, 1e-5).unwrap();
+        assert!(best.variance * 630.0 <= 1e-5);
+        assert!(tradeoffs.iter()
+            .filter(|t| t.key_bytes < best.key_bytes)
+            .all(|t| t.variance * 630.0 > 1e-5));
+        assert_eq!(TgswParams::recommend(1024, 1, 630, 1e-2, 1e-5), None);
+    }
 
-        let selector0 = TgswSample::encrypt(0, &sk, tgsw_params);
-        let result0 = selector0.cmux(&c0, &c1);
-        let decrypted0 = result0.decrypt_phase(&sk);
+    #[test]
+    fn test_bootstrapping_key_replays_from_seed() {
+        let sk = TlweSecretKey::generate_binary(TlweParams { n: 6, stddev: 1e-9 });
+        let ring_key = TrlweSecretKey::generate_binary(TrlweParams { N: 16, k: 1, stddev: 1e-9 });
+        let bodies = |seed| {
+            let bk = BootstrappingKey::generate_with_rng(&sk, &ring_key, TgswParams::default(), &mut seeded_entropy(seed));
+            assert_eq!(bk.bk.len(), 6);
+            bk.bk.iter()
+                .flat_map(|entry| entry.rows.iter().flatten().flat_map(|row| row.b.coeffs.clone()))
+                .collect::<Vec<Torus>>()
+        };
 
-        assert!((decrypted0.value() - 0.1).abs() < 0.01);
+        // Whether or not the entries were encrypted in parallel
+        assert_eq!(bodies([3; 32]), bodies([3; 32]));
+        assert_ne!(bodies([3; 32]), bodies([4; 32]));
     }
-}
\ No newline at end of file
+}
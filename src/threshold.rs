@@ -0,0 +1,477 @@
+//! Threshold key generation and distributed decryption.
+//!
+//! `t` of `n` parties share the LWE key by replicated secret sharing: the
+//! joint key is the concatenation of one binary key per subset of
+//! `n - t + 1` parties, held by every member of that subset. Any `t` parties
+//! meet every subset, so a quorum holds the whole key between them, while a
+//! smaller coalition misses at least one subset key entirely. Ciphertexts are
+//! ordinary TLWE samples under `joint_params`, whose dimension is that of one
+//! subset key times the number of subsets, so this suits a handful of parties.
+//!
+//! Key generation: the smallest member of each subset deals its key to the
+//! other members (`deal`, over private channels), the dealers publish their
+//! part of the encryptions of zero over common seeded masks
+//! (`public_key_share`), and `assemble_public_key` sums them into a
+//! `TlwePublicKey` anyone can encrypt under. The bootstrapping key must
+//! encrypt the joint key, so `cloud_key` assembles it from a quorum's shares
+//! with a fresh ring key that is dropped afterwards; it is a one-off ceremony
+//! to run in a trusted environment, after which no party decrypts alone.
+//!
+//! Decryption: each member of a quorum returns the mask's inner product with
+//! the subset keys assigned to it, smudged with fresh noise, and `combine`
+//! subtracts the parts from the body. A part also carries the party's share
+//! of the sample's error, which the smudging noise must drown: over repeated
+//! queries an exposed error leaks the key share. `partial_decrypt` refuses
+//! samples whose noise is not `SMUDGING_BITS` below the smudging deviation.
+//! The default deviation spends half the decryption budget on smudging,
+//! which covers fresh encryptions under the secret key, but neither
+//! bootstrapped outputs nor, at the default noise, public key encryptions,
+//! which sum thousands of zeros: threshold parameters need a smaller fresh
+//! noise than single-key ones.
+
+use std::fmt;
+use crate::lwe::{PublicKeyError, TlwePublicKey};
+use crate::entropy::{default_entropy, EntropySource};
+use crate::noise::{DiscreteGaussian, NoiseEstimate, NoiseLevel};
+use crate::noise_model;
+use crate::seeded::{expand_stream, Seed};
+use crate::tfhe::{TfheCloudKey, TfheParams, TfheSecretKey};
+use crate::tlwe::{binary_from_phase, TlweParams, TlweSample, TlweSecretKey};
+use crate::torus::Torus;
+use crate::trlwe::TrlweSecretKey;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThresholdError {
+    /// Fewer distinct parties took part than the threshold requires
+    NoQuorum { parties: usize, threshold: usize },
+    /// The share of `party` is needed but was not provided
+    MissingShare { party: usize },
+    /// The keys given to `party` are not those of the subsets it belongs to
+    WrongShare { party: usize },
+    /// Party indices run from 0 to `parties - 1`
+    UnknownParty { party: usize },
+    /// `party` sent more than one partial decryption
+    DuplicateShare { party: usize },
+    /// The partial decryptions were computed for different quorums, or come
+    /// from a party outside the quorum they were computed for
+    QuorumMismatch,
+    /// The smudging deviation exceeds the noise of the sample, or the fresh
+    /// noise of the parameters, by only `2^bits`, short of `SMUDGING_BITS`
+    WeakSmudging { bits: i32 },
+    /// Smudging noise of every party together would not decrypt
+    SmudgingTooWide,
+    /// The shares hold too few encryptions of zero for a public key
+    PublicKey(PublicKeyError),
+}
+
+impl fmt::Display for ThresholdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThresholdError::NoQuorum { parties, threshold } => {
+                write!(f, "{} parties took part, {} are required", parties, threshold)
+            }
+            ThresholdError::MissingShare { party } => write!(f, "no share from party {}", party),
+            ThresholdError::WrongShare { party } => {
+                write!(f, "party {} does not hold the keys of its subsets", party)
+            }
+            ThresholdError::UnknownParty { party } => write!(f, "there is no party {}", party),
+            ThresholdError::DuplicateShare { party } => write!(f, "party {} sent more than one share", party),
+            ThresholdError::QuorumMismatch => write!(f, "partial decryptions computed for different quorums"),
+            ThresholdError::WeakSmudging { bits } => {
+                write!(f, "smudging exceeds the ciphertext noise by 2^{}, 2^{} are required", bits, SMUDGING_BITS)
+            }
+            ThresholdError::SmudgingTooWide => write!(f, "smudging noise of all parties would not decrypt"),
+            ThresholdError::PublicKey(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ThresholdError {}
+
+/// Smudging noise must exceed the noise of a decrypted sample by this many
+/// bits: a partial decryption is then within about `2^-SMUDGING_BITS` of
+/// one computed without the party's error
+pub const SMUDGING_BITS: u32 = 20;
+
+#[derive(Debug, Clone)]
+pub struct ThresholdParams {
+    pub parties: usize,
+    pub threshold: usize,
+    /// Parameters of one subset key
+    pub params: TfheParams,
+    /// Noise of partial decryptions, hiding the parties' errors; set it with
+    /// `with_smudging_stddev`, which checks it
+    pub smudging_stddev: f64,
+}
+
+/// The key of one subset of parties, by its index in `ThresholdParams::subsets`
+#[derive(Debug, Clone)]
+pub struct SubsetKey {
+    pub subset: usize,
+    pub key: TlweSecretKey,
+}
+
+/// The subset keys one party holds
+#[derive(Debug, Clone)]
+pub struct KeyShare {
+    pub party: usize,
+    pub keys: Vec<SubsetKey>,
+}
+
+/// Bodies a dealer contributes to the joint public key
+#[derive(Debug, Clone)]
+pub struct PublicKeyShare {
+    pub party: usize,
+    pub bodies: Vec<Torus>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PartialDecryption {
+    pub party: usize,
+    /// The quorum it was computed for, sorted
+    pub quorum: Vec<usize>,
+    pub value: Torus,
+}
+
+impl ThresholdParams {
+    /// Smudging noise defaults to half the decryption budget, shared by
+    /// the partial decryptions of all parties
+    pub fn new(parties: usize, threshold: usize, params: TfheParams) -> Self {
+        assert!(threshold > 0 && threshold <= parties, "the threshold must be between 1 and the number of parties");
+        let smudging_stddev = (noise_model::max_variance() / (2 * parties) as f64).sqrt();
+        let fresh = params.tlwe_params.stddev;
+        ThresholdParams { parties, threshold, params, smudging_stddev: 0.0 }
+            .with_smudging_stddev(smudging_stddev)
+            .unwrap_or_else(|e| panic!("a fresh noise of {} leaves no room for smudging: {}", fresh, e))
+    }
+
+    /// Replaces the smudging deviation, which must exceed the fresh noise
+    /// of the parameters by `SMUDGING_BITS` and, over all parties, still
+    /// decrypt
+    pub fn with_smudging_stddev(mut self, stddev: f64) -> Result<Self, ThresholdError> {
+        Self::check_smudging(stddev, self.params.tlwe_params.stddev)?;
+        if self.parties as f64 * stddev.powi(2) > noise_model::max_variance() {
+            return Err(ThresholdError::SmudgingTooWide);
+        }
+        self.smudging_stddev = stddev;
+        Ok(self)
+    }
+
+    fn check_smudging(smudging: f64, noise: f64) -> Result<(), ThresholdError> {
+        let bits = (smudging / noise).log2();
+        if bits < SMUDGING_BITS as f64 {
+            return Err(ThresholdError::WeakSmudging { bits: bits.floor().max(i32::MIN as f64) as i32 });
+        }
+        Ok(())
+    }
+
+    /// Every subset of `parties - threshold + 1` parties, in lexicographic order
+    pub fn subsets(&self) -> Vec<Vec<usize>> {
+        let size = self.parties - self.threshold + 1;
+        let mut subsets = Vec::new();
+        let mut current: Vec<usize> = (0..size).collect();
+        loop {
+            subsets.push(current.clone());
+            let Some(i) = (0..size).rev().find(|&i| current[i] < self.parties - size + i) else {
+                return subsets;
+            };
+            current[i] += 1;
+            for j in i + 1..size {
+                current[j] = current[j - 1] + 1;
+            }
+        }
+    }
+
+    /// Parameters of the joint key and of the ciphertexts under it
+    pub fn joint_params(&self) -> TfheParams {
+        let n = self.params.tlwe_params.n * self.subsets().len();
+        TfheParams {
+            n,
+            tlwe_params: TlweParams { n, ..self.params.tlwe_params.clone() },
+            ..self.params.clone()
+        }
+    }
+
+    /// Fresh keys of the subsets `party` is the smallest member of, for it to
+    /// keep and send to the other members
+    pub fn deal(&self, party: usize) -> Vec<SubsetKey> {
+        self.deal_with_rng(party, &mut default_entropy())
+    }
+
+    pub fn deal_with_rng<R: EntropySource + ?Sized>(&self, party: usize, rng: &mut R) -> Vec<SubsetKey> {
+        self.subsets()
+            .iter()
+            .enumerate()
+            .filter(|(_, members)| members[0] == party)
+            .map(|(subset, _)| SubsetKey {
+                subset,
+                key: TlweSecretKey::generate_binary_with_rng(self.params.tlwe_params.clone(), rng),
+            })
+            .collect()
+    }
+
+    /// Sums the dealers' shares into `len` encryptions of zero under the
+    /// joint key, `len` being what the shares were computed for and at
+    /// least `TlwePublicKey::min_len` of the joint parameters
+    pub fn assemble_public_key(&self, seed: &Seed, shares: &[PublicKeyShare]) -> Result<TlwePublicKey, ThresholdError> {
+        let params = self.joint_params().tlwe_params;
+        let len = shares.first().map_or(0, |s| s.bodies.len());
+        let mut bodies = vec![Torus::default(); len];
+        for dealer in self.dealers() {
+            let share = shares.iter().find(|s| s.party == dealer).ok_or(ThresholdError::MissingShare { party: dealer })?;
+            for (sum, body) in bodies.iter_mut().zip(&share.bodies) {
+                *sum = sum.add(body);
+            }
+        }
+
+        let subsets = self.subsets().len() as f64;
+        let zeros = bodies
+            .into_iter()
+            .enumerate()
+            .map(|(i, b)| TlweSample {
+                a: expand_stream(seed, i as u64, params.n),
+                b,
+                params: params.clone(),
+                noise: NoiseLevel::Fresh,
+                estimate: NoiseEstimate::fresh(params.stddev * subsets.sqrt()),
+                namespace: None,
+            })
+            .collect();
+        TlwePublicKey::from_zeros(params, zeros).map_err(ThresholdError::PublicKey)
+    }
+
+    /// Cloud key over the joint key, assembled from the shares of a quorum
+    pub fn cloud_key(&self, shares: &[&KeyShare]) -> Result<TfheCloudKey, ThresholdError> {
+        self.cloud_key_with_rng(shares, &mut default_entropy())
+    }
+
+    pub fn cloud_key_with_rng<R: EntropySource + ?Sized>(
+        &self,
+        shares: &[&KeyShare],
+        rng: &mut R,
+    ) -> Result<TfheCloudKey, ThresholdError> {
+        self.quorum(shares.iter().map(|s| s.party))?;
+        let mut coeffs = Vec::new();
+        for subset in 0..self.subsets().len() {
+            let key = shares
+                .iter()
+                .find_map(|s| s.keys.iter().find(|k| k.subset == subset))
+                .expect("a quorum holds every subset key");
+            coeffs.extend_from_slice(&key.key.coeffs);
+        }
+
+        let params = self.joint_params();
+        let joint = TfheSecretKey {
+            tlwe_key: TlweSecretKey { coeffs, params: params.tlwe_params.clone() },
+            trlwe_key: TrlweSecretKey::generate_binary_with_rng(params.trlwe_params(), rng),
+            params,
+        };
+        Ok(TfheCloudKey::generate_with_rng(&joint, rng))
+    }
+
+    /// Decrypts `sample` from the partial decryptions of a quorum: one from
+    /// each member, all computed for exactly that quorum
+    pub fn combine(&self, sample: &TlweSample, partials: &[PartialDecryption]) -> Result<bool, ThresholdError> {
+        for (i, p) in partials.iter().enumerate() {
+            if partials[..i].iter().any(|q| q.party == p.party) {
+                return Err(ThresholdError::DuplicateShare { party: p.party });
+            }
+        }
+        let quorum = self.quorum(partials.iter().map(|p| p.party))?;
+        if partials.iter().any(|p| p.quorum != quorum) {
+            return Err(ThresholdError::QuorumMismatch);
+        }
+        let phase = partials.iter().fold(sample.b, |phase, p| phase.sub(&p.value));
+        Ok(binary_from_phase(phase.value()))
+    }
+
+    // Parties dealing at least one subset key
+    fn dealers(&self) -> Vec<usize> {
+        let mut dealers: Vec<usize> = self.subsets().iter().map(|members| members[0]).collect();
+        dealers.dedup();
+        dealers
+    }
+
+    fn quorum(&self, parties: impl Iterator<Item = usize>) -> Result<Vec<usize>, ThresholdError> {
+        let mut quorum: Vec<usize> = parties.collect();
+        if let Some(&party) = quorum.iter().find(|&&p| p >= self.parties) {
+            return Err(ThresholdError::UnknownParty { party });
+        }
+        quorum.sort_unstable();
+        quorum.dedup();
+        if quorum.len() < self.threshold {
+            return Err(ThresholdError::NoQuorum { parties: quorum.len(), threshold: self.threshold });
+        }
+        Ok(quorum)
+    }
+}
+
+impl KeyShare {
+    /// Collects the subset keys dealt to `party`, which must be exactly
+    /// those of the subsets it belongs to
+    pub fn new(params: &ThresholdParams, party: usize, mut keys: Vec<SubsetKey>) -> Result<Self, ThresholdError> {
+        keys.sort_by_key(|k| k.subset);
+        let expected: Vec<usize> = params.subsets()
+            .iter()
+            .enumerate()
+            .filter(|(_, members)| members.contains(&party))
+            .map(|(subset, _)| subset)
+            .collect();
+        let held: Vec<usize> = keys.iter().map(|k| k.subset).collect();
+        if held != expected || keys.iter().any(|k| k.key.params != params.params.tlwe_params) {
+            return Err(ThresholdError::WrongShare { party });
+        }
+        Ok(KeyShare { party, keys })
+    }
+
+    /// This dealer's part of `len` encryptions of zero over the masks
+    /// expanded from the common `seed`: the inner products with the keys it
+    /// dealt, plus fresh noise for each
+    pub fn public_key_share(&self, params: &ThresholdParams, seed: &Seed, len: usize) -> PublicKeyShare {
+        self.public_key_share_with_rng(params, seed, len, &mut default_entropy())
+    }
+
+    pub fn public_key_share_with_rng<R: EntropySource + ?Sized>(
+        &self,
+        params: &ThresholdParams,
+        seed: &Seed,
+        len: usize,
+        rng: &mut R,
+    ) -> PublicKeyShare {
+        let subsets = params.subsets();
+        let n = params.params.tlwe_params.n;
+        let joint_n = n * subsets.len();
+        let dealt: Vec<&SubsetKey> = self.keys.iter().filter(|k| subsets[k.subset][0] == self.party).collect();
+
+        let bodies = (0..len as u64)
+            .map(|i| {
+                let mask = expand_stream(seed, i, joint_n);
+                dealt.iter().fold(Torus::default(), |body, k| {
+                    let noise = DiscreteGaussian::new(k.key.params.stddev).sample(rng);
+                    body.add(&inner_product(&mask[k.subset * n..(k.subset + 1) * n], &k.key)).add(&noise)
+                })
+            })
+            .collect();
+        PublicKeyShare { party: self.party, bodies }
+    }
+
+    /// This party's part of decrypting `sample` together with `quorum`: each
+    /// subset key is used by its smallest member in the quorum
+    pub fn partial_decrypt(
+        &self,
+        params: &ThresholdParams,
+        sample: &TlweSample,
+        quorum: &[usize],
+    ) -> Result<PartialDecryption, ThresholdError> {
+        self.partial_decrypt_with_rng(params, sample, quorum, &mut default_entropy())
+    }
+
+    /// `partial_decrypt` with the smudging noise drawn from `rng`
+    pub fn partial_decrypt_with_rng<R: EntropySource + ?Sized>(
+        &self,
+        params: &ThresholdParams,
+        sample: &TlweSample,
+        quorum: &[usize],
+        rng: &mut R,
+    ) -> Result<PartialDecryption, ThresholdError> {
+        let quorum = params.quorum(quorum.iter().copied())?;
+        if !quorum.contains(&self.party) {
+            return Err(ThresholdError::MissingShare { party: self.party });
+        }
+        ThresholdParams::check_smudging(params.smudging_stddev, sample.estimate.variance.sqrt())?;
+        let subsets = params.subsets();
+        let n = params.params.tlwe_params.n;
+        let noise = DiscreteGaussian::new(params.smudging_stddev).sample(rng);
+        let value = self.keys
+            .iter()
+            .filter(|k| subsets[k.subset].iter().find(|p| quorum.contains(p)) == Some(&self.party))
+            .fold(noise, |value, k| value.add(&inner_product(&sample.a[k.subset * n..(k.subset + 1) * n], &k.key)));
+        Ok(PartialDecryption { party: self.party, quorum, value })
+    }
+}
+
+fn inner_product(mask: &[Torus], key: &TlweSecretKey) -> Torus {
+    mask.iter()
+        .zip(&key.coeffs)
+        .fold(Torus::default(), |acc, (a, &s)| acc.add(&a.mul_int(s as i64)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_of_three_decryption() {
+        // Public key encryptions sum about 1600 zeros of the joint key
+        let params = ThresholdParams::new(3, 2, TfheParams {
+            tlwe_params: TlweParams { n: 16, stddev: 1e-11 },
+            ..TfheParams::default()
+        });
+        assert_eq!(params.subsets(), vec![vec![0, 1], vec![0, 2], vec![1, 2]]);
+        assert_eq!(params.joint_params().tlwe_params.n, 48);
+
+        let dealt: Vec<SubsetKey> = (0..3).flat_map(|p| params.deal(p)).collect();
+        let members = params.subsets();
+        let shares: Vec<KeyShare> = (0..3)
+            .map(|p| {
+                let keys = dealt.iter().filter(|k| members[k.subset].contains(&p)).cloned().collect();
+                KeyShare::new(&params, p, keys).unwrap()
+            })
+            .collect();
+        assert_eq!(
+            KeyShare::new(&params, 2, dealt.clone()).unwrap_err(),
+            ThresholdError::WrongShare { party: 2 },
+        );
+
+        let seed = [5; 32];
+        let len = TlwePublicKey::min_len(&params.joint_params().tlwe_params);
+        let short: Vec<PublicKeyShare> = shares.iter().map(|s| s.public_key_share(&params, &seed, 16)).collect();
+        assert_eq!(
+            params.assemble_public_key(&seed, &short).unwrap_err(),
+            ThresholdError::PublicKey(PublicKeyError { len: 16, min_len: len }),
+        );
+        let pk_shares: Vec<PublicKeyShare> = shares.iter().map(|s| s.public_key_share(&params, &seed, len)).collect();
+        assert_eq!(
+            params.assemble_public_key(&seed, &pk_shares[2..]).unwrap_err(),
+            ThresholdError::MissingShare { party: 0 },
+        );
+        let pk = params.assemble_public_key(&seed, &pk_shares).unwrap();
+
+        for (value, expected) in [(0.125, false), (0.625, true)] {
+            let sample = TlweSample::encrypt_with_public_key(&Torus::new(value), &pk);
+            for quorum in [[0, 1], [0, 2], [1, 2]] {
+                let partials: Vec<PartialDecryption> = quorum
+                    .iter()
+                    .map(|&p| shares[p].partial_decrypt(&params, &sample, &quorum).unwrap())
+                    .collect();
+                assert_eq!(params.combine(&sample, &partials), Ok(expected));
+            }
+
+            let alone = shares[1].partial_decrypt(&params, &sample, &[1]).unwrap_err();
+            assert_eq!(alone, ThresholdError::NoQuorum { parties: 1, threshold: 2 });
+
+            let part = |p: usize, quorum: &[usize]| shares[p].partial_decrypt(&params, &sample, quorum).unwrap();
+            let twice = [part(0, &[0, 1]), part(0, &[0, 1])];
+            assert_eq!(params.combine(&sample, &twice), Err(ThresholdError::DuplicateShare { party: 0 }));
+            let mixed = [part(0, &[0, 1]), part(2, &[0, 2])];
+            assert_eq!(params.combine(&sample, &mixed), Err(ThresholdError::QuorumMismatch));
+            let partial = [part(0, &[0, 1, 2]), part(1, &[0, 1, 2])];
+            assert_eq!(params.combine(&sample, &partial), Err(ThresholdError::QuorumMismatch));
+        }
+
+        // Smudging must drown the noise of what is decrypted
+        assert!(params.smudging_stddev > params.params.tlwe_params.stddev * 2f64.powi(SMUDGING_BITS as i32));
+        assert!(matches!(
+            params.clone().with_smudging_stddev(params.params.tlwe_params.stddev),
+            Err(ThresholdError::WeakSmudging { bits: 0 }),
+        ));
+        assert_eq!(params.clone().with_smudging_stddev(0.1).unwrap_err(), ThresholdError::SmudgingTooWide);
+        let mut noisy = TlweSample::encrypt_with_public_key(&Torus::new(0.125), &pk);
+        noisy.estimate = NoiseEstimate::fresh(params.smudging_stddev);
+        assert!(matches!(
+            shares[0].partial_decrypt(&params, &noisy, &[0, 1]),
+            Err(ThresholdError::WeakSmudging { .. }),
+        ));
+        assert!(matches!(params.cloud_key(&[&shares[0]]), Err(ThresholdError::NoQuorum { .. })));
+    }
+}
@@ -1,8 +1,16 @@
+#[cfg(feature = "client")]
 use rand::Rng;
+use crate::canonical::{Namespace, Parameterized};
+#[cfg(feature = "client")]
+use crate::entropy::{default_entropy, EntropySource};
 use crate::torus::Torus;
-use crate::noise::gaussian_noise;
+#[cfg(feature = "client")]
+use crate::noise::DiscreteGaussian;
+use crate::noise::{NoiseEstimate, NoiseLevel};
+use crate::noise_model;
+use crate::trlwe::TrlweSample;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TlweParams {
     pub n: usize,
     pub stddev: f64,
@@ -17,15 +25,35 @@ impl Default for TlweParams {
     }
 }
 
+impl TlweParams {
+    /// In-memory size of the mask and body of one sample
+    pub fn ciphertext_bytes(&self) -> usize {
+        (self.n + 1) * std::mem::size_of::<Torus>()
+    }
+}
+
+#[cfg(feature = "client")]
 #[derive(Debug, Clone)]
 pub struct TlweSecretKey {
     pub coeffs: Vec<i32>,
     pub params: TlweParams,
 }
 
+#[cfg(feature = "client")]
 impl TlweSecretKey {
+    /// Prepares the key for decrypting many samples at once
+    pub fn batch_decryptor(&self) -> TlweBatchDecryptor {
+        TlweBatchDecryptor {
+            params: self.params.clone(),
+            coeffs: self.coeffs.iter().map(|&c| c as f64).collect(),
+        }
+    }
+
     pub fn generate_binary(params: TlweParams) -> Self {
-        let mut rng = rand::rng();
+        Self::generate_binary_with_rng(params, &mut default_entropy())
+    }
+
+    pub fn generate_binary_with_rng<R: EntropySource + ?Sized>(params: TlweParams, rng: &mut R) -> Self {
         let coeffs: Vec<i32> = (0..params.n)
             .map(|_| if rng.random_bool(0.5) { 1 } else { 0 })
             .collect();
@@ -34,7 +62,10 @@ impl TlweSecretKey {
     }
 
     pub fn generate_ternary(params: TlweParams) -> Self {
-        let mut rng = rand::rng();
+        Self::generate_ternary_with_rng(params, &mut default_entropy())
+    }
+
+    pub fn generate_ternary_with_rng<R: EntropySource + ?Sized>(params: TlweParams, rng: &mut R) -> Self {
         let coeffs: Vec<i32> = (0..params.n)
             .map(|_| {
                 let r: f64 = rng.random();
@@ -48,52 +79,149 @@ impl TlweSecretKey {
     }
 }
 
+// Whether the phase lies in (0.25, 0.75), without branching on it
+#[cfg(feature = "client")]
+pub(crate) fn binary_from_phase(phase: f64) -> bool {
+    let margin = 0.25 - (phase - 0.5).abs();
+    margin.to_bits() >> 63 == 0 && margin != 0.0
+}
+
+/// Secret key with coefficients pre-converted for decrypting result vectors.
+///
+/// Each sample costs the same work whatever it decrypts to: a fixed-length
+/// inner product and a branch-free threshold. With the `parallel` feature the
+/// batch is split over the rayon thread pool.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+pub struct TlweBatchDecryptor {
+    params: TlweParams,
+    coeffs: Vec<f64>,
+}
+
+#[cfg(feature = "client")]
+impl TlweBatchDecryptor {
+    pub fn phase(&self, sample: &TlweSample) -> f64 {
+        sample.expect_params(&self.params);
+
+        // Independent lanes let the compiler vectorize the inner product
+        let mut lanes = [0.0f64; 4];
+        let chunks = sample.a.chunks_exact(4).zip(self.coeffs.chunks_exact(4));
+        for (a, s) in chunks {
+            for ((lane, a), s) in lanes.iter_mut().zip(a).zip(s) {
+                *lane += a.value() * s;
+            }
+        }
+        let tail = sample.a.len() / 4 * 4;
+        let rest: f64 = sample.a[tail..].iter()
+            .zip(self.coeffs[tail..].iter())
+            .map(|(a, s)| a.value() * s)
+            .sum();
+
+        let phase = sample.b.value() - (lanes.iter().sum::<f64>() + rest);
+        phase - phase.floor()
+    }
+
+    pub fn decrypt_binary(&self, sample: &TlweSample) -> bool {
+        binary_from_phase(self.phase(sample))
+    }
+
+    pub fn decrypt_binary_batch(&self, samples: &[TlweSample]) -> Vec<bool> {
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            samples.par_iter().map(|s| self.decrypt_binary(s)).collect()
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            samples.iter().map(|s| self.decrypt_binary(s)).collect()
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TlweSample {
     pub a: Vec<Torus>,
     pub b: Torus,
     pub params: TlweParams,
+    pub noise: NoiseLevel,
+    /// Variance bound of the error; see `noise_budget`
+    pub estimate: NoiseEstimate,
+    /// Tenant of the cloud key that produced the sample, if any
+    pub namespace: Option<Namespace>,
 }
 
 impl TlweSample {
+    #[cfg(feature = "client")]
     pub fn encrypt(message: &Torus, sk: &TlweSecretKey) -> Self {
-        let mut rng = rand::rng();
+        Self::encrypt_with_rng(message, sk, &mut default_entropy())
+    }
 
+    /// `encrypt` drawing the mask and noise from `rng`
+    #[cfg(feature = "client")]
+    pub fn encrypt_with_rng<R: EntropySource + ?Sized>(message: &Torus, sk: &TlweSecretKey, rng: &mut R) -> Self {
         let a: Vec<Torus> = (0..sk.params.n)
-            .map(|_| Torus::new(rng.random::<f64>()))
+            .map(|_| Torus::random(rng))
             .collect();
+        Self::encrypt_with_mask(a, message, sk, rng)
+    }
 
-        let mut inner_product = 0.0;
+    /// `encrypt` under a mask chosen by the caller, which must be uniform
+    #[cfg(feature = "client")]
+    pub(crate) fn encrypt_with_mask<R: EntropySource + ?Sized>(
+        a: Vec<Torus>,
+        message: &Torus,
+        sk: &TlweSecretKey,
+        rng: &mut R,
+    ) -> Self {
+        assert_eq!(a.len(), sk.params.n, "mask length does not match the key");
+        let mut inner_product = Torus::default();
         for i in 0..sk.params.n {
-            inner_product += a[i].value() * (sk.coeffs[i] as f64);
+            inner_product = inner_product.add(&a[i].mul_int(sk.coeffs[i] as i64));
         }
 
-        let error = gaussian_noise(sk.params.stddev);
-        let b = Torus::new(inner_product + message.value() + error);
+        let error = DiscreteGaussian::new(sk.params.stddev).sample(rng);
+        let b = inner_product.add(message).add(&error);
 
         TlweSample {
             a,
             b,
             params: sk.params.clone(),
+            noise: NoiseLevel::Fresh,
+            estimate: NoiseEstimate::fresh(sk.params.stddev),
+            namespace: None,
         }
     }
 
+    #[cfg(feature = "client")]
     pub fn decrypt_phase(&self, sk: &TlweSecretKey) -> Torus {
-        let mut inner_product = 0.0;
+        self.expect_params(&sk.params);
+        let mut inner_product = Torus::default();
         for i in 0..sk.params.n {
-            inner_product += self.a[i].value() * (sk.coeffs[i] as f64);
+            inner_product = inner_product.add(&self.a[i].mul_int(sk.coeffs[i] as i64));
         }
 
-        Torus::new(self.b.value() - inner_product)
+        self.b.sub(&inner_product)
     }
 
+    #[cfg(feature = "client")]
     pub fn decrypt_binary(&self, sk: &TlweSecretKey) -> bool {
-        let phase = self.decrypt_phase(sk);
-        phase.value() > 0.25 && phase.value() < 0.75
+        binary_from_phase(self.decrypt_phase(sk).value())
+    }
+
+    // Combining samples from different parameter sets is a bug at the call site
+    fn expect_params(&self, params: &TlweParams) {
+        if let Err(e) = self.check_params(params) {
+            panic!("{}", e);
+        }
+    }
+
+    // So is combining samples of two tenants
+    fn joint_namespace(&self, other: &TlweSample) -> Option<Namespace> {
+        Namespace::join(self.namespace, other.namespace).unwrap_or_else(|e| panic!("{}", e))
     }
 
     pub fn add(&self, other: &TlweSample) -> TlweSample {
-        assert_eq!(self.params.n, other.params.n);
+        other.expect_params(&self.params);
 
         let a: Vec<Torus> = self.a.iter()
             .zip(other.a.iter())
@@ -106,11 +234,14 @@ impl TlweSample {
             a,
             b,
             params: self.params.clone(),
+            noise: self.noise.add(other.noise, self.params.stddev),
+            estimate: self.estimate + other.estimate,
+            namespace: self.joint_namespace(other),
         }
     }
 
     pub fn sub(&self, other: &TlweSample) -> TlweSample {
-        assert_eq!(self.params.n, other.params.n);
+        other.expect_params(&self.params);
 
         let a: Vec<Torus> = self.a.iter()
             .zip(other.a.iter())
@@ -123,38 +254,105 @@ impl TlweSample {
             a,
             b,
             params: self.params.clone(),
+            noise: self.noise.add(other.noise, self.params.stddev),
+            estimate: self.estimate + other.estimate,
+            namespace: self.joint_namespace(other),
         }
     }
 
     pub fn scalar_mul(&self, scalar: i32) -> TlweSample {
         let a: Vec<Torus> = self.a.iter()
-            .map(|x| x.mul_scalar(scalar as f64))
+            .map(|x| x.mul_int(scalar as i64))
             .collect();
 
-        let b = self.b.mul_scalar(scalar as f64);
+        let b = self.b.mul_int(scalar as i64);
 
         TlweSample {
             a,
             b,
             params: self.params.clone(),
+            noise: self.noise.scale(scalar as i64, self.params.stddev),
+            estimate: self.estimate.scale(scalar as i64),
+            namespace: self.namespace,
         }
     }
 
+    /// Bits of standard deviation the error can still grow by before
+    /// decryption may fail, from the tracked `estimate`; negative once past
+    /// the margin and infinite for a noiseless sample
+    pub fn noise_budget(&self) -> f64 {
+        self.estimate.budget_bits()
+    }
+
+    /// Noiseless sample with a zero mask, as built by `trivial`
+    pub fn is_trivial(&self) -> bool {
+        self.a.iter().all(|x| x.value() == 0.0)
+    }
+
     pub fn trivial(message: &Torus, params: TlweParams) -> Self {
         let a = vec![Torus::new(0.0); params.n];
         let b = message.clone();
 
-        TlweSample { a, b, params }
+        // No noise at all
+        TlweSample { a, b, params, noise: NoiseLevel::PostLinear(0), estimate: NoiseEstimate::default(), namespace: None }
     }
 
-    pub fn extract_from_trlwe(trlwe_a: &[Vec<Torus>], trlwe_b: &Torus, _index: usize) -> Self {
-        let n = trlwe_a[0].len();
-        let params = TlweParams { n, stddev: 1e-9 };
+    /// LWE sample of coefficient `index` of a TRLWE sample, under the ring
+    /// key read as one LWE key of dimension `k N`. Coefficient `p` of `a s`
+    /// is `sum_{j<=p} a_{p-j} s_j - sum_{j>p} a_{N+p-j} s_j`, the sign flip
+    /// coming from `X^N = -1`.
+    pub fn extract_from_trlwe(trlwe: &TrlweSample, index: usize) -> Self {
+        let ring = &trlwe.params;
+        assert!(index < ring.N, "coefficient {} is outside a ring of degree {}", index, ring.N);
+
+        let a = trlwe.a.iter()
+            .flat_map(|poly| {
+                (0..ring.N).map(move |j| {
+                    if j <= index {
+                        poly.coeffs[index - j]
+                    } else {
+                        -poly.coeffs[ring.N + index - j]
+                    }
+                })
+            })
+            .collect();
+        let params = TlweParams { n: ring.k * ring.N, stddev: ring.stddev };
+
+        TlweSample {
+            a,
+            b: trlwe.b.coeffs[index],
+            params,
+            noise: trlwe.noise,
+            estimate: trlwe.estimate,
+            namespace: None,
+        }
+    }
+}
 
-        let a = trlwe_a[0].clone();
-        let b = *trlwe_b;
+/// Decomposition of a key switching key: `t` digits of `base_bit` bits each
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeySwitchParams {
+    pub t: usize,
+    pub base_bit: u32,
+}
 
-        TlweSample { a, b, params }
+/// The customary TFHE decomposition
+impl Default for KeySwitchParams {
+    fn default() -> Self {
+        KeySwitchParams { t: 8, base_bit: 2 }
+    }
+}
+
+impl KeySwitchParams {
+    /// Variance a key switch from dimension `n_in` adds under an output key
+    /// with noise `output_stddev`; see `noise_model::key_switch`
+    pub fn added_variance(&self, n_in: usize, output_stddev: f64) -> f64 {
+        noise_model::key_switch(self, n_in, output_stddev)
+    }
+
+    /// In-memory size of the key for the given dimensions
+    pub fn key_bytes(&self, n_in: usize, output: &TlweParams) -> usize {
+        n_in * self.t * output.ciphertext_bytes()
     }
 }
 
@@ -166,12 +364,23 @@ pub struct TlweKeySwitchKey {
     pub base_bit: u32,
 }
 
+#[cfg(feature = "client")]
 impl TlweKeySwitchKey {
     pub fn generate(
         key_in: &TlweSecretKey,
         key_out: &TlweSecretKey,
         t: usize,
         base_bit: u32,
+    ) -> Self {
+        Self::generate_with_rng(key_in, key_out, t, base_bit, &mut default_entropy())
+    }
+
+    pub fn generate_with_rng<R: EntropySource + ?Sized>(
+        key_in: &TlweSecretKey,
+        key_out: &TlweSecretKey,
+        t: usize,
+        base_bit: u32,
+        rng: &mut R,
     ) -> Self {
         let n = key_in.params.n;
         let mut samples = vec![vec![]; n];
@@ -179,9 +388,10 @@ impl TlweKeySwitchKey {
         for i in 0..n {
             samples[i] = Vec::with_capacity(t);
             for j in 0..t {
-                let message_value = (key_in.coeffs[i] as f64) / (1u64 << (j as u32 * base_bit)) as f64;
-                let message = Torus::new(message_value);
-                samples[i].push(TlweSample::encrypt(&message, key_out));
+                // s_i / base^(j+1): digit j of the decomposition carries that weight
+                let weight = Torus::new((-(((j as u32 + 1) * base_bit) as f64)).exp2());
+                let message = weight.mul_int(key_in.coeffs[i] as i64);
+                samples[i].push(TlweSample::encrypt_with_rng(&message, key_out, rng));
             }
         }
 
@@ -194,9 +404,67 @@ impl TlweKeySwitchKey {
     }
 }
 
-#[cfg(test)]
+impl TlweKeySwitchKey {
+    /// Smallest key switching key from dimension `n_in` to `output` whose
+    /// added noise stays below `max_stddev`, or `None` if no decomposition
+    /// within 64 bits of precision gets there. Among keys of equal size the
+    /// one adding the least noise wins.
+    pub fn recommend(n_in: usize, output: &TlweParams, max_stddev: f64) -> Option<KeySwitchParams> {
+        let max_variance = max_stddev.powi(2);
+        (1..=16u32)
+            .flat_map(|base_bit| {
+                (1..=(63 / base_bit as usize)).map(move |t| KeySwitchParams { t, base_bit })
+            })
+            .map(|ks| (ks, ks.added_variance(n_in, output.stddev)))
+            .filter(|&(_, variance)| variance <= max_variance)
+            .min_by(|(a, va), (b, vb)| a.t.cmp(&b.t).then(va.total_cmp(vb)))
+            .map(|(ks, _)| ks)
+    }
+
+    pub fn decomposition(&self) -> KeySwitchParams {
+        KeySwitchParams { t: self.t, base_bit: self.base_bit }
+    }
+
+    pub fn output_params(&self) -> &TlweParams {
+        &self.samples[0][0].params
+    }
+
+    /// Re-encrypts `sample` under the output key, rounding each mask
+    /// coefficient to `t * base_bit` bits of precision first
+    pub fn key_switch(&self, sample: &TlweSample) -> TlweSample {
+        assert_eq!(sample.a.len(), self.n, "sample dimension does not match the key switching key");
+        let precision = self.t as u32 * self.base_bit;
+        assert!(precision < 64, "decomposition precision must be below 64 bits");
+        let base_mask = (1u64 << self.base_bit) - 1;
+
+        let mut result = TlweSample::trivial(&sample.b, self.output_params().clone());
+        for (a_i, row) in sample.a.iter().zip(self.samples.iter()) {
+            let rounded = a_i.rounded_bits(precision);
+
+            for (j, ks) in row.iter().enumerate() {
+                let digit = (rounded >> (precision - (j as u32 + 1) * self.base_bit)) & base_mask;
+                if digit == 0 {
+                    continue;
+                }
+                let scale = digit as i64;
+                for (r, k) in result.a.iter_mut().zip(ks.a.iter()) {
+                    *r = r.sub(&k.mul_int(scale));
+                }
+                result.b = result.b.sub(&ks.b.mul_int(scale));
+            }
+        }
+        // Levels count multiples of the output key's fresh noise from here on
+        result.noise = NoiseLevel::Fresh;
+        result.estimate = sample.estimate.with_added(self.decomposition().added_variance(self.n, self.output_params().stddev));
+        result.namespace = sample.namespace;
+        result
+    }
+}
+
+#[cfg(all(test, feature = "client"))]
 mod tests {
     use super::*;
+    use crate::trlwe::{TorusPolynomial, TrlweParams, TrlweSecretKey};
 
     #[test]
     fn test_tlwe_encrypt_decrypt_binary() {
@@ -216,6 +484,114 @@ mod tests {
         assert!(ct1.decrypt_binary(&sk));
     }
 
+    #[test]
+    fn test_batch_decryptor_matches_scalar_path() {
+        let params = TlweParams {
+            n: 10,
+            stddev: 1e-9,
+        };
+
+        let sk = TlweSecretKey::generate_binary(params.clone());
+        let messages = [0.0, 0.5, 0.125, 0.625, 0.3, 0.9];
+        let samples: Vec<TlweSample> = messages.iter()
+            .map(|&m| TlweSample::encrypt(&Torus::new(m), &sk))
+            .collect();
+
+        // Distance on the torus, so a phase just below 1 is close to 0
+        let distance = |x: f64, y: f64| {
+            let d = (x - y).rem_euclid(1.0);
+            d.min(1.0 - d)
+        };
+
+        let decryptor = sk.batch_decryptor();
+        for (sample, &m) in samples.iter().zip(messages.iter()) {
+            assert!(distance(decryptor.phase(sample), sample.decrypt_phase(&sk).value()) < 1e-9);
+            assert!(distance(decryptor.phase(sample), m) < 1e-6);
+        }
+
+        let scalar: Vec<bool> = samples.iter().map(|s| s.decrypt_binary(&sk)).collect();
+        assert_eq!(decryptor.decrypt_binary_batch(&samples), scalar);
+        assert_eq!(scalar, vec![false, true, false, true, true, false]);
+    }
+
+    #[test]
+    fn test_key_switch_to_smaller_key() {
+        let key_in = TlweSecretKey::generate_binary(TlweParams { n: 64, stddev: 1e-9 });
+        let key_out = TlweSecretKey::generate_binary(TlweParams { n: 16, stddev: 1e-7 });
+        let ksk = TlweKeySwitchKey::generate(&key_in, &key_out, 8, 2);
+        assert_eq!(ksk.output_params().n, 16);
+
+        for &message in &[0.125, 0.625, 0.3, 0.9] {
+            let sample = TlweSample::encrypt(&Torus::new(message), &key_in);
+            let switched = ksk.key_switch(&sample);
+            assert_eq!(switched.a.len(), 16);
+
+            let phase = switched.decrypt_phase(&key_out).value();
+            let error = (phase - message + 0.5).rem_euclid(1.0) - 0.5;
+            assert!(error.abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_key_switch_recommendation() {
+        let output = TlweParams { n: 16, stddev: 1e-7 };
+        let ks = TlweKeySwitchKey::recommend(64, &output, 1e-4).unwrap();
+        assert!(ks.added_variance(64, output.stddev) <= 1e-8);
+        assert!(ks.t * ks.base_bit as usize >= 14);
+
+        // No key with fewer digits meets the target
+        for base_bit in 1..=16 {
+            let smaller = KeySwitchParams { t: ks.t - 1, base_bit };
+            assert!(smaller.added_variance(64, output.stddev) > 1e-8);
+        }
+        assert_eq!(ks.key_bytes(64, &output), 64 * ks.t * output.ciphertext_bytes());
+
+        // The recommendation actually switches within the target
+        let key_in = TlweSecretKey::generate_binary(TlweParams { n: 64, stddev: 1e-9 });
+        let key_out = TlweSecretKey::generate_binary(output.clone());
+        let ksk = TlweKeySwitchKey::generate(&key_in, &key_out, ks.t, ks.base_bit);
+        assert_eq!(ksk.decomposition(), ks);
+        let switched = ksk.key_switch(&TlweSample::encrypt(&Torus::new(0.25), &key_in));
+        let error = (switched.decrypt_phase(&key_out).value() - 0.25 + 0.5).rem_euclid(1.0) - 0.5;
+        assert!(error.abs() < 1e-3);
+
+        // Output noise above the target rules out every decomposition
+        assert_eq!(TlweKeySwitchKey::recommend(64, &output, 1e-7), None);
+    }
+
+    #[test]
+    fn test_noise_level_tracking() {
+        let sk = TlweSecretKey::generate_binary(TlweParams { n: 10, stddev: 1e-3 });
+        let fresh = TlweSample::encrypt(&Torus::new(0.125), &sk);
+        assert_eq!(fresh.noise, NoiseLevel::Fresh);
+
+        let sum = fresh.add(&fresh).sub(&TlweSample::trivial(&Torus::new(0.5), sk.params.clone()));
+        assert_eq!(sum.noise, NoiseLevel::PostLinear(2));
+        assert_eq!(sum.scalar_mul(-3).noise, NoiseLevel::PostLinear(18));
+        // Budget at this noise is (1/8 / 6e-3)^2 = 434 fresh variances
+        assert!(sum.scalar_mul(15).noise.needs_bootstrap());
+    }
+
+    #[test]
+    fn test_noise_budget() {
+        let sk = TlweSecretKey::generate_binary(TlweParams { n: 10, stddev: 1e-3 });
+        let fresh = TlweSample::encrypt(&Torus::new(0.125), &sk);
+        assert_eq!(fresh.estimate.variance, 1e-6);
+        assert!(TlweSample::trivial(&Torus::new(0.5), sk.params.clone()).noise_budget().is_infinite());
+
+        // Doubling the variance costs half a bit, a factor 3 log2(3) bits
+        let sum = fresh.add(&fresh);
+        assert!((fresh.noise_budget() - sum.noise_budget() - 0.5).abs() < 1e-9);
+        assert!((sum.noise_budget() - sum.scalar_mul(-3).noise_budget() - 3f64.log2()).abs() < 1e-9);
+        assert!(sum.scalar_mul(15).noise_budget() < 0.0);
+
+        let key_out = TlweSecretKey::generate_binary(TlweParams { n: 8, stddev: 1e-6 });
+        let ksk = TlweKeySwitchKey::generate(&sk, &key_out, 8, 2);
+        let switched = ksk.key_switch(&fresh);
+        let added = ksk.decomposition().added_variance(10, 1e-6);
+        assert_eq!(switched.estimate.variance, fresh.estimate.variance + added);
+    }
+
     #[test]
     fn test_tlwe_homomorphic_ops() {
         let params = TlweParams {
@@ -239,4 +615,25 @@ mod tests {
         let phase_scaled = ct_scaled.decrypt_phase(&sk);
         assert!((phase_scaled.value() - 0.3).abs() < 1e-6);
     }
+
+    #[test]
+    fn test_extract_every_coefficient() {
+        let ring = TrlweParams { N: 16, k: 2, stddev: 1e-9 };
+        let ring_key = TrlweSecretKey::generate_binary(ring.clone());
+        let extracted = TlweSecretKey {
+            coeffs: ring_key.polys.concat(),
+            params: TlweParams { n: 32, stddev: ring.stddev },
+        };
+        let message = TorusPolynomial { coeffs: (0..16).map(|i| Torus::new(i as f64 / 16.0)).collect() };
+        // A rotation makes some coefficients negated copies
+        let sample = TrlweSample::encrypt(&message, &ring_key).mul_by_xai(5);
+        let expected = message.mul_by_xai(5);
+
+        for index in 0..16 {
+            let lwe = TlweSample::extract_from_trlwe(&sample, index);
+            assert_eq!(lwe.params.n, 32);
+            let error = (lwe.decrypt_phase(&extracted).value() - expected.coeffs[index].value() + 0.5).rem_euclid(1.0) - 0.5;
+            assert!(error.abs() < 1e-6, "coefficient {}", index);
+        }
+    }
 }
\ No newline at end of file
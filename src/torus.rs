@@ -1,32 +1,226 @@
-#[derive(Debug, Clone, Copy)]
-pub struct Torus(f64);
+//! Elements of the real torus `T = R/Z`.
+//!
+//! `Torus32` and `Torus64` hold `x` as the integer `x 2^32` or `x 2^64` with
+//! wrapping arithmetic: sums and integer multiples are exact, and the gadget
+//! decompositions of TGSW and key switching read exact bits instead of
+//! rounding a float. `TorusF64` is the original `f64` representation, which
+//! loses the low bits of every operation. `Torus`, the type the schemes are
+//! written against, is `Torus64` unless the `float-torus` feature selects
+//! `TorusF64` for comparison. All three share one interface.
 
-impl Torus {
+use std::ops::Neg;
+use rand::Rng;
+
+#[cfg(not(feature = "float-torus"))]
+pub type Torus = Torus64;
+#[cfg(feature = "float-torus")]
+pub type Torus = TorusF64;
+
+// Top `bits` of an element given as a `width`-bit fixed-point integer,
+// rounded to nearest and reduced modulo `2^bits`
+fn round_bits(raw: u64, width: u32, bits: u32) -> u64 {
+    assert!(bits > 0 && bits < 64, "precision must be between 1 and 63 bits");
+    if bits >= width {
+        return raw << (bits - width);
+    }
+    let shift = width - bits;
+    ((raw >> shift) + ((raw >> (shift - 1)) & 1)) & ((1u64 << bits) - 1)
+}
+
+// Signed digits of a `levels * base_bit`-bit value, most significant first,
+// each in `[-2^base_bit / 2, 2^base_bit / 2)`
+fn signed_digits(mut v: u64, levels: usize, base_bit: u32) -> Vec<i32> {
+    let base = 1i64 << base_bit;
+    let mut digits = vec![0; levels];
+    for level in digits.iter_mut().rev() {
+        let mut digit = (v & (base as u64 - 1)) as i64;
+        v >>= base_bit;
+        if digit >= base / 2 {
+            digit -= base;
+            v += 1;
+        }
+        *level = digit as i32;
+    }
+    digits
+}
+
+macro_rules! integer_torus {
+    ($(#[$doc:meta])* $name:ident, $raw:ty, $bits:expr) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+        pub struct $name($raw);
+
+        impl $name {
+            pub const BITS: u32 = $bits;
+
+            /// Nearest element to `value` modulo 1
+            pub fn new(value: f64) -> Self {
+                let wrapped = value - value.floor();
+                // Rounding can reach 2^BITS, which wraps to zero
+                $name(((wrapped * (Self::BITS as f64).exp2()).round() as u128) as $raw)
+            }
+
+            pub fn from_raw(raw: $raw) -> Self {
+                $name(raw)
+            }
+
+            pub fn raw(&self) -> $raw {
+                self.0
+            }
+
+            /// Nearest `f64` in `[0, 1)`
+            pub fn value(&self) -> f64 {
+                let value = self.0 as f64 / (Self::BITS as f64).exp2();
+                if value < 1.0 { value } else { 0.0 }
+            }
+
+            /// The element as a 64-bit fixed-point integer, exactly
+            pub fn fixed64(&self) -> u64 {
+                (self.0 as u64) << (64 - Self::BITS)
+            }
+
+            /// Nearest element to the 64-bit fixed-point integer `raw`
+            pub fn from_fixed64(raw: u64) -> Self {
+                if Self::BITS == 64 {
+                    return $name(raw as $raw);
+                }
+                $name(round_bits(raw, 64, Self::BITS) as $raw)
+            }
+
+            pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+                $name(rng.random())
+            }
+
+            pub fn add(&self, other: &$name) -> Self {
+                $name(self.0.wrapping_add(other.0))
+            }
+
+            pub fn sub(&self, other: &$name) -> Self {
+                $name(self.0.wrapping_sub(other.0))
+            }
+
+            /// Exact integer multiple
+            pub fn mul_int(&self, scalar: i64) -> Self {
+                $name(self.0.wrapping_mul(scalar as $raw))
+            }
+
+            /// Real multiple, through `f64`
+            pub fn mul_scalar(&self, scalar: f64) -> Self {
+                if scalar.fract() == 0.0 && scalar.abs() < i64::MAX as f64 {
+                    self.mul_int(scalar as i64)
+                } else {
+                    $name::new(self.value() * scalar)
+                }
+            }
+
+            pub fn round(&self, precision: u32) -> Self {
+                if precision == 0 {
+                    return $name(0);
+                }
+                if precision >= Self::BITS {
+                    return *self;
+                }
+                $name((round_bits(self.0 as u64, Self::BITS, precision) << (Self::BITS - precision)) as $raw)
+            }
+
+            /// The element rounded to `bits` bits, as an integer below `2^bits`
+            pub fn rounded_bits(&self, bits: u32) -> u64 {
+                round_bits(self.0 as u64, Self::BITS, bits)
+            }
+
+            /// Signed gadget digits `d_j` in `[-2^base_bit / 2, 2^base_bit / 2)`
+            /// with `x ~ sum_j d_j / 2^((j+1) base_bit)`, most significant first
+            pub fn decompose(&self, levels: usize, base_bit: u32) -> Vec<i32> {
+                signed_digits(self.rounded_bits(levels as u32 * base_bit), levels, base_bit)
+            }
+        }
+
+        impl Neg for $name {
+            type Output = $name;
+
+            fn neg(self) -> $name {
+                $name(self.0.wrapping_neg())
+            }
+        }
+    };
+}
+
+integer_torus!(
+    /// Torus element with 32 bits of precision
+    Torus32, u32, 32
+);
+
+integer_torus!(
+    /// Torus element with 64 bits of precision
+    Torus64, u64, 64
+);
+
+/// Torus element as an `f64` in `[0, 1)`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TorusF64(f64);
+
+impl TorusF64 {
     pub fn new(value: f64) -> Self {
         let wrapped = value - value.floor();
-        Torus(wrapped)
+        TorusF64(wrapped)
     }
 
     pub fn value(&self) -> f64 {
-        self.0        
+        self.0
+    }
+
+    /// `round(x 2^64)` modulo `2^64`; scaling by a power of two and
+    /// rounding are exact in IEEE-754, so the result is platform-independent
+    pub fn fixed64(&self) -> u64 {
+        // Rounding can reach 2^64, which wraps to zero
+        ((self.0 * 64f64.exp2()).round() as u128) as u64
+    }
+
+    pub fn from_fixed64(raw: u64) -> Self {
+        TorusF64::new(raw as f64 / 64f64.exp2())
+    }
+
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        TorusF64::new(rng.random::<f64>())
+    }
+
+    pub fn add(&self, other: &TorusF64) -> Self {
+        TorusF64::new(self.0 + other.0)
     }
 
-    pub fn add(&self, other: &Torus) -> Self {
-        Torus::new(self.0 + other.0)
+    pub fn sub(&self, other: &TorusF64) -> Self {
+        TorusF64::new(self.0 - other.0)
     }
 
-    pub fn sub(&self, other: &Torus) -> Self {
-        Torus::new(self.0 - other.0)
+    pub fn mul_int(&self, scalar: i64) -> Self {
+        TorusF64::new(self.0 * scalar as f64)
     }
 
     pub fn mul_scalar(&self, scalar: f64) -> Self {
-        Torus::new(self.0 * scalar)
+        TorusF64::new(self.0 * scalar)
     }
 
-    pub fn round(&self, precision: u32) -> Torus {
+    pub fn round(&self, precision: u32) -> TorusF64 {
         let divisor = 2.0_f64.powi(precision as i32);
         let rounded = (self.0 * divisor).round() / divisor;
-        Torus::new(rounded)
+        TorusF64::new(rounded)
+    }
+
+    pub fn rounded_bits(&self, bits: u32) -> u64 {
+        assert!(bits > 0 && bits < 64, "precision must be between 1 and 63 bits");
+        (self.0 * (bits as f64).exp2()).round() as u64 % (1u64 << bits)
+    }
+
+    pub fn decompose(&self, levels: usize, base_bit: u32) -> Vec<i32> {
+        signed_digits(self.rounded_bits(levels as u32 * base_bit), levels, base_bit)
+    }
+}
+
+impl Neg for TorusF64 {
+    type Output = TorusF64;
+
+    fn neg(self) -> TorusF64 {
+        TorusF64::new(-self.0)
     }
 }
 
@@ -60,4 +254,37 @@ mod tests {
 
         assert!((result.value() - 0.8).abs() < 1e-10);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_integer_torus_is_exact() {
+        let x = Torus32::from_raw(0xdead_beef);
+        let y = Torus32::new(0.75);
+        assert_eq!(x.add(&y).sub(&y), x);
+        assert_eq!((-x).add(&x), Torus32::default());
+        assert_eq!(x.mul_int(3).sub(&x.mul_int(2)), x);
+        assert_eq!(Torus32::new(1.0 - 1e-12), Torus32::default());
+
+        // A sum of many f64 terms drifts; the integer one does not
+        let third = Torus64::new(1.0 / 3.0);
+        let sum = (0..3_000_000).fold(Torus64::default(), |acc, _| acc.add(&third));
+        assert_eq!(sum, third.mul_int(3_000_000));
+    }
+
+    #[test]
+    fn test_decompose_recomposes() {
+        for raw in [0u64, 1, 0x8000_0000_0000_0000, 0x1234_5678_9abc_def0, u64::MAX] {
+            let x = Torus64::from_raw(raw);
+            let (levels, base_bit) = (4, 8);
+            let digits = x.decompose(levels, base_bit);
+            assert!(digits.iter().all(|&d| (-128..128).contains(&d)));
+
+            let recomposed = digits.iter().enumerate().fold(Torus64::default(), |acc, (j, &d)| {
+                acc.add(&Torus64::from_raw(1 << (64 - (j as u32 + 1) * base_bit)).mul_int(d as i64))
+            });
+            // Off by at most the rounding of the bits below the decomposition
+            let error = recomposed.sub(&x).raw().min(x.sub(&recomposed).raw());
+            assert!(error <= 1 << 31, "raw {:#x}: error {}", raw, error);
+        }
+        assert_eq!(Torus32::new(0.123456).decompose(3, 10), TorusF64::new(0.123456).decompose(3, 10));
+    }
+}
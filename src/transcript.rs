@@ -0,0 +1,255 @@
+//! Differential evaluation against the cleartext backend.
+//!
+//! A `Differential` evaluator runs every gate twice: on the wrapped backend
+//! and on `ClearEvaluator`, the clear side starting from the decrypted
+//! inputs and never looking at the backend's results again. After each
+//! gate the backend's result is decrypted with a debug secret key and
+//! compared to the clear one, and both land in a `GateTranscript`, one
+//! entry per wire. The first entry that disagrees is the gate that broke,
+//! whatever the circuit did with its result afterwards, which makes the
+//! transcript the quickest check while gate implementations change.
+
+use std::fmt;
+use std::sync::Mutex;
+use crate::circuit::{Node, Wire};
+use crate::evaluator::{BooleanEvaluator, ClearEvaluator};
+#[cfg(all(feature = "client", feature = "server"))]
+use crate::evaluator::TfheEvaluator;
+#[cfg(all(feature = "client", feature = "server"))]
+use crate::tfhe::{TfheCloudKey, TfheEncoder, TfheSecretKey};
+#[cfg(all(feature = "client", feature = "server"))]
+use crate::tlwe::TlweSample;
+
+/// One wire: the node that produced it and its value on both backends
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscriptEntry {
+    pub wire: Wire,
+    pub node: Node,
+    /// Value on the cleartext backend
+    pub expected: bool,
+    /// Decrypted value on the evaluated backend
+    pub actual: bool,
+}
+
+impl TranscriptEntry {
+    pub fn diverged(&self) -> bool {
+        self.expected != self.actual
+    }
+}
+
+impl fmt::Display for TranscriptEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "wire {} = {:?}: expected {}, got {}", self.wire.0, self.node, self.expected, self.actual)
+    }
+}
+
+/// Every wire of a differential evaluation, in evaluation order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GateTranscript {
+    /// Indexed by `Wire`; inputs and constants are entries too
+    pub entries: Vec<TranscriptEntry>,
+}
+
+impl GateTranscript {
+    /// The earliest wire whose backends disagree
+    pub fn first_divergence(&self) -> Option<&TranscriptEntry> {
+        self.entries.iter().find(|e| e.diverged())
+    }
+
+    /// All disagreeing wires; later ones may only inherit the first
+    pub fn divergences(&self) -> impl Iterator<Item = &TranscriptEntry> {
+        self.entries.iter().filter(|e| e.diverged())
+    }
+
+    /// Gates evaluated, not counting inputs and constants
+    pub fn gates(&self) -> usize {
+        self.entries.iter().filter(|e| !matches!(e.node, Node::Input(_) | Node::Constant(_))).count()
+    }
+}
+
+/// A bit of the wrapped backend, its cleartext shadow and its wire
+#[derive(Debug, Clone)]
+pub struct Shadowed<B> {
+    pub bit: B,
+    pub clear: bool,
+    pub wire: Wire,
+}
+
+/// Evaluator running each gate on `eval` and on the cleartext backend,
+/// with `decrypt` reading the results of `eval`
+pub struct Differential<E: BooleanEvaluator, D> {
+    eval: E,
+    decrypt: D,
+    transcript: Mutex<GateTranscript>,
+}
+
+#[cfg(all(feature = "client", feature = "server"))]
+impl<'a> Differential<TfheEvaluator<'a>, Box<dyn Fn(&TlweSample) -> bool + Send + Sync + 'a>> {
+    /// Gates of `ck`, checked with the secret key it was generated from
+    pub fn tfhe(ck: &'a TfheCloudKey, sk: &'a TfheSecretKey) -> Self {
+        Differential::new(TfheEvaluator::new(ck), Box::new(move |bit| TfheEncoder::decode_bool(bit, sk)))
+    }
+}
+
+impl<E, D> Differential<E, D>
+where
+    E: BooleanEvaluator,
+    D: Fn(&E::Bit) -> bool,
+{
+    pub fn new(eval: E, decrypt: D) -> Self {
+        Differential { eval, decrypt, transcript: Mutex::new(GateTranscript::default()) }
+    }
+
+    /// Registers the next input; its decryption seeds the clear backend
+    pub fn input(&self, bit: E::Bit) -> Shadowed<E::Bit> {
+        let clear = (self.decrypt)(&bit);
+        let mut transcript = self.transcript.lock().unwrap();
+        let index = transcript.entries.iter().filter(|e| matches!(e.node, Node::Input(_))).count();
+        Self::append(&mut transcript, Node::Input(index), bit, clear, clear)
+    }
+
+    pub fn inputs(&self, bits: Vec<E::Bit>) -> Vec<Shadowed<E::Bit>> {
+        bits.into_iter().map(|bit| self.input(bit)).collect()
+    }
+
+    /// The earliest divergence so far
+    pub fn first_divergence(&self) -> Option<TranscriptEntry> {
+        self.transcript.lock().unwrap().first_divergence().cloned()
+    }
+
+    pub fn finish(self) -> GateTranscript {
+        self.transcript.into_inner().unwrap()
+    }
+
+    fn push(&self, node: Node, bit: E::Bit, expected: bool) -> Shadowed<E::Bit> {
+        let actual = (self.decrypt)(&bit);
+        Self::append(&mut self.transcript.lock().unwrap(), node, bit, expected, actual)
+    }
+
+    fn append(transcript: &mut GateTranscript, node: Node, bit: E::Bit, expected: bool, actual: bool) -> Shadowed<E::Bit> {
+        let wire = Wire(transcript.entries.len());
+        transcript.entries.push(TranscriptEntry { wire, node, expected, actual });
+        Shadowed { bit, clear: expected, wire }
+    }
+}
+
+impl<E, D> BooleanEvaluator for Differential<E, D>
+where
+    E: BooleanEvaluator,
+    D: Fn(&E::Bit) -> bool,
+{
+    type Bit = Shadowed<E::Bit>;
+
+    fn constant(&self, value: bool) -> Self::Bit {
+        self.push(Node::Constant(value), self.eval.constant(value), value)
+    }
+
+    fn not(&self, a: &Self::Bit) -> Self::Bit {
+        self.push(Node::Not(a.wire), self.eval.not(&a.bit), ClearEvaluator.not(&a.clear))
+    }
+
+    fn and(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.push(Node::And(a.wire, b.wire), self.eval.and(&a.bit, &b.bit), ClearEvaluator.and(&a.clear, &b.clear))
+    }
+
+    fn or(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.push(Node::Or(a.wire, b.wire), self.eval.or(&a.bit, &b.bit), ClearEvaluator.or(&a.clear, &b.clear))
+    }
+
+    fn xor(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.push(Node::Xor(a.wire, b.wire), self.eval.xor(&a.bit, &b.bit), ClearEvaluator.xor(&a.clear, &b.clear))
+    }
+
+    fn nand(&self, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.push(Node::Nand(a.wire, b.wire), self.eval.nand(&a.bit, &b.bit), ClearEvaluator.nand(&a.clear, &b.clear))
+    }
+
+    fn mux(&self, s: &Self::Bit, a: &Self::Bit, b: &Self::Bit) -> Self::Bit {
+        self.push(
+            Node::Mux(s.wire, a.wire, b.wire),
+            self.eval.mux(&s.bit, &a.bit, &b.bit),
+            ClearEvaluator.mux(&s.clear, &a.clear, &b.clear),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer::IntegerOps;
+
+    // Clear bits with an XOR that misfires on two true operands
+    struct BrokenXor;
+
+    impl BooleanEvaluator for BrokenXor {
+        type Bit = bool;
+
+        fn constant(&self, value: bool) -> bool {
+            value
+        }
+
+        fn not(&self, a: &bool) -> bool {
+            !a
+        }
+
+        fn and(&self, a: &bool, b: &bool) -> bool {
+            a & b
+        }
+
+        fn or(&self, a: &bool, b: &bool) -> bool {
+            a | b
+        }
+
+        fn xor(&self, a: &bool, b: &bool) -> bool {
+            a | b
+        }
+
+        fn nand(&self, a: &bool, b: &bool) -> bool {
+            !(a & b)
+        }
+
+        fn mux(&self, s: &bool, a: &bool, b: &bool) -> bool {
+            if *s { *a } else { *b }
+        }
+    }
+
+    #[test]
+    fn test_first_divergent_gate() {
+        let eval = Differential::new(BrokenXor, |bit: &bool| *bit);
+        let a = eval.inputs(IntegerOps::constant(5, 4, &ClearEvaluator));
+        let b = eval.inputs(IntegerOps::constant(1, 4, &ClearEvaluator));
+        let sum = IntegerOps::add_mod(&a, &b, &eval);
+        assert_eq!(sum.iter().map(|s| s.clear).collect::<Vec<_>>(), IntegerOps::constant(6, 4, &ClearEvaluator));
+
+        let transcript = eval.finish();
+        assert_eq!(transcript.entries.iter().filter(|e| matches!(e.node, Node::Input(_))).count(), 8);
+        let first = transcript.first_divergence().unwrap();
+        assert!(matches!(first.node, Node::Xor(..)));
+        assert!(!first.expected && first.actual);
+        assert!(transcript.entries[..first.wire.0].iter().all(|e| !e.diverged()));
+
+        // The same sum on a correct backend agrees everywhere
+        let eval = Differential::new(ClearEvaluator, |bit: &bool| *bit);
+        let a = eval.inputs(IntegerOps::constant(5, 4, &ClearEvaluator));
+        let b = eval.inputs(IntegerOps::constant(1, 4, &ClearEvaluator));
+        IntegerOps::add_mod(&a, &b, &eval);
+        assert_eq!(eval.first_divergence(), None);
+    }
+
+    #[cfg(all(feature = "client", feature = "server"))]
+    #[test]
+    fn test_tfhe_gates_match_clear() {
+        use crate::tfhe::fixtures::small_keys;
+
+        let (sk, ck) = small_keys(32);
+
+        let eval = Differential::tfhe(&ck, &sk);
+        let a = eval.inputs(TfheEncoder::encode_bits(&[true, false, true], &sk));
+        let b = eval.inputs(TfheEncoder::encode_bits(&[true, true, false], &sk));
+        IntegerOps::less_than(&a, &b, &eval);
+        IntegerOps::add(&a, &b, &eval);
+        let transcript = eval.finish();
+        assert!(transcript.gates() > 0);
+        assert_eq!(transcript.first_divergence(), None);
+    }
+}
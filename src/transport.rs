@@ -0,0 +1,137 @@
+//! Modulus-switched result ciphertexts for the trip back to the client.
+//!
+//! A result the client only decrypts once doesn't need 64-bit torus
+//! coefficients: rounding every coefficient to `log_modulus` bits and packing
+//! them shrinks it by `64 / log_modulus`. The rounding adds noise to the
+//! phase with standard deviation `2^-log_modulus * sqrt((1 + n/2) / 12)` for
+//! a binary key of dimension `n` (see `extra_noise_stddev`). Boolean results
+//! have a decryption margin of 1/8, so at `n = 630` anything from about 8
+//! bits up decrypts reliably.
+
+use crate::canonical::{read_params, write_params, write_u64, Canonical, CanonicalError, CanonicalReader, Parameterized};
+use crate::noise::{NoiseEstimate, NoiseLevel};
+use crate::tlwe::{TlweParams, TlweSample};
+use crate::torus::Torus;
+
+#[derive(Debug, Clone)]
+pub struct TransportTlweSample {
+    pub log_modulus: u32,
+    pub params: TlweParams,
+    /// Mask then body, `log_modulus` bits each, little-endian bit order
+    packed: Vec<u8>,
+}
+
+impl TlweSample {
+    /// Rounds the sample to `log_modulus` bits per coefficient, right before serialization
+    pub fn compress_for_transport(&self, log_modulus: u32) -> TransportTlweSample {
+        assert!((1..=32).contains(&log_modulus), "log_modulus must be between 1 and 32");
+        let scale = (log_modulus as f64).exp2();
+
+        let mut packed = vec![0u8; ((self.a.len() + 1) * log_modulus as usize).div_ceil(8)];
+        for (i, t) in self.a.iter().chain(std::iter::once(&self.b)).enumerate() {
+            let value = (t.value() * scale).round() as u64 % (1 << log_modulus);
+            for bit in 0..log_modulus as usize {
+                if (value >> bit) & 1 == 1 {
+                    let pos = i * log_modulus as usize + bit;
+                    packed[pos / 8] |= 1 << (pos % 8);
+                }
+            }
+        }
+
+        TransportTlweSample { log_modulus, params: self.params.clone(), packed }
+    }
+}
+
+impl TransportTlweSample {
+    pub fn decompress(&self) -> TlweSample {
+        let scale = (self.log_modulus as f64).exp2();
+        let k = self.log_modulus as usize;
+
+        let mut coeffs: Vec<Torus> = (0..=self.params.n)
+            .map(|i| {
+                let value = (0..k).fold(0u64, |acc, bit| {
+                    let pos = i * k + bit;
+                    acc | ((((self.packed[pos / 8] >> (pos % 8)) & 1) as u64) << bit)
+                });
+                Torus::new(value as f64 / scale)
+            })
+            .collect();
+
+        let b = coeffs.pop().expect("body coefficient");
+        let estimate = NoiseEstimate::fresh(self.params.stddev);
+        // Compressed results are on their way to the client and leave the namespace behind
+        TlweSample { a: coeffs, b, params: self.params.clone(), noise: NoiseLevel::Fresh, estimate, namespace: None }
+    }
+
+    /// Standard deviation of the rounding noise added to the phase
+    pub fn extra_noise_stddev(&self) -> f64 {
+        let step = (-(self.log_modulus as f64)).exp2();
+        step * ((1.0 + self.params.n as f64 / 2.0) / 12.0).sqrt()
+    }
+
+    /// Bytes of packed coefficients
+    pub fn size_bytes(&self) -> usize {
+        self.packed.len()
+    }
+}
+
+impl Canonical for TransportTlweSample {
+    const TAG: u8 = 6;
+
+    fn write(&self, out: &mut Vec<u8>) {
+        write_u64(out, self.log_modulus as u64);
+        write_params(out, &self.params);
+        out.extend_from_slice(&self.packed);
+    }
+
+    fn read(input: &mut CanonicalReader<'_>) -> Result<Self, CanonicalError> {
+        let log_modulus = input.u64()?;
+        if !(1..=32).contains(&log_modulus) {
+            return Err(CanonicalError::Invalid("log_modulus"));
+        }
+        let params: TlweParams = read_params(input)?;
+        let len = params.n.checked_add(1)
+            .and_then(|n| n.checked_mul(log_modulus as usize))
+            .ok_or(CanonicalError::Invalid("dimension"))?
+            .div_ceil(8);
+        let packed = input.bytes(len)?.to_vec();
+
+        Ok(TransportTlweSample { log_modulus: log_modulus as u32, params, packed })
+    }
+}
+
+impl Parameterized for TransportTlweSample {
+    type Params = TlweParams;
+
+    fn params(&self) -> &TlweParams {
+        &self.params
+    }
+}
+
+#[cfg(all(test, feature = "client"))]
+mod tests {
+    use super::*;
+    use crate::tlwe::TlweSecretKey;
+
+    #[test]
+    fn test_compressed_results_decrypt() {
+        let params = TlweParams { n: 630, stddev: 1e-9 };
+        let sk = TlweSecretKey::generate_binary(params);
+
+        for &value in &[true, false, true, true, false] {
+            let message = if value { Torus::new(0.625) } else { Torus::new(0.125) };
+            let sample = TlweSample::encrypt(&message, &sk);
+
+            let compressed = sample.compress_for_transport(10);
+            assert_eq!(compressed.size_bytes(), 631 * 10 / 8 + 1);
+            assert!(compressed.extra_noise_stddev() < 0.01);
+
+            let bytes = compressed.to_canonical_bytes();
+            let restored = TransportTlweSample::from_canonical_bytes(&bytes).unwrap().decompress();
+            assert_eq!(restored.decrypt_binary(&sk), value);
+
+            let error = restored.decrypt_phase(&sk).value() - message.value();
+            assert!(error.abs() < 8.0 * compressed.extra_noise_stddev());
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! Ring LWE over torus polynomials modulo `X^N + 1`.
+//!
+//! A TRLWE sample encrypts a whole polynomial of `N` torus coefficients:
+//! `b = sum a_i s_i + m + e` with `k` random mask polynomials and a binary
+//! key polynomial per mask. Multiplying by a monomial `X^a` rotates every
+//! coefficient negacyclically, which is the step blind rotation repeats.
+
+use rand::Rng;
+use crate::entropy::default_entropy;
+#[cfg(feature = "client")]
+use crate::entropy::EntropySource;
+#[cfg(feature = "client")]
+use crate::noise::DiscreteGaussian;
+use crate::fft::NegacyclicFft;
+use crate::noise::{NoiseEstimate, NoiseLevel};
+use crate::torus::Torus;
+
+/// Degree from which polynomial products go through the FFT
+pub const FFT_THRESHOLD: usize = 64;
+
+/// Polynomial with torus coefficients modulo `X^N + 1`
+#[derive(Debug, Clone)]
+pub struct TorusPolynomial {
+    pub coeffs: Vec<Torus>,
+}
+
+impl TorusPolynomial {
+    pub fn zero(degree: usize) -> Self {
+        TorusPolynomial { coeffs: vec![Torus::new(0.0); degree] }
+    }
+
+    pub fn random(degree: usize) -> Self {
+        Self::random_with_rng(degree, &mut default_entropy())
+    }
+
+    pub fn random_with_rng<R: Rng + ?Sized>(degree: usize, rng: &mut R) -> Self {
+        TorusPolynomial { coeffs: (0..degree).map(|_| Torus::random(rng)).collect() }
+    }
+
+    pub fn degree(&self) -> usize {
+        self.coeffs.len()
+    }
+
+    pub fn add(&self, other: &TorusPolynomial) -> TorusPolynomial {
+        assert_eq!(self.degree(), other.degree(), "polynomials of different degrees");
+        let coeffs = self.coeffs.iter().zip(other.coeffs.iter()).map(|(x, y)| x.add(y)).collect();
+        TorusPolynomial { coeffs }
+    }
+
+    pub fn sub(&self, other: &TorusPolynomial) -> TorusPolynomial {
+        assert_eq!(self.degree(), other.degree(), "polynomials of different degrees");
+        let coeffs = self.coeffs.iter().zip(other.coeffs.iter()).map(|(x, y)| x.sub(y)).collect();
+        TorusPolynomial { coeffs }
+    }
+
+    /// Product with `X^power`; powers are taken modulo `2N` since `X^N = -1`
+    pub fn mul_by_xai(&self, power: usize) -> TorusPolynomial {
+        let n = self.degree();
+        let power = power % (2 * n);
+        let mut coeffs = vec![Torus::new(0.0); n];
+        for (i, c) in self.coeffs.iter().enumerate() {
+            let target = i + power;
+            coeffs[target % n] = if (target / n) % 2 == 1 { -*c } else { *c };
+        }
+        TorusPolynomial { coeffs }
+    }
+
+    /// Negacyclic product with an integer polynomial, such as a key; through
+    /// the FFT from `FFT_THRESHOLD` coefficients on
+    pub fn mul_int(&self, other: &[i32]) -> TorusPolynomial {
+        assert_eq!(other.len(), self.degree(), "polynomials of different degrees");
+        if self.degree() >= FFT_THRESHOLD {
+            NegacyclicFft::cached(self.degree()).poly_mul(self, other)
+        } else {
+            self.mul_int_schoolbook(other)
+        }
+    }
+
+    /// `mul_int` by coefficient products, quadratic in the degree
+    pub fn mul_int_schoolbook(&self, other: &[i32]) -> TorusPolynomial {
+        let n = self.degree();
+        assert_eq!(other.len(), n, "polynomials of different degrees");
+        let mut coeffs = vec![Torus::default(); n];
+        for (j, &s) in other.iter().enumerate().filter(|(_, s)| **s != 0) {
+            for (i, c) in self.coeffs.iter().enumerate() {
+                let term = c.mul_int(s as i64);
+                if i + j < n {
+                    coeffs[i + j] = coeffs[i + j].add(&term);
+                } else {
+                    coeffs[i + j - n] = coeffs[i + j - n].sub(&term);
+                }
+            }
+        }
+        TorusPolynomial { coeffs }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[allow(non_snake_case)]
+pub struct TrlweParams {
+    /// Ring degree, a power of two
+    pub N: usize,
+    /// Mask polynomials per sample
+    pub k: usize,
+    pub stddev: f64,
+}
+
+impl Default for TrlweParams {
+    fn default() -> Self {
+        TrlweParams {
+            N: 1024,
+            k: 1,
+            stddev: 2.0e-9,
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+pub struct TrlweSecretKey {
+    /// One binary polynomial per mask polynomial
+    pub polys: Vec<Vec<i32>>,
+    pub params: TrlweParams,
+}
+
+#[cfg(feature = "client")]
+impl TrlweSecretKey {
+    pub fn generate_binary(params: TrlweParams) -> Self {
+        Self::generate_binary_with_rng(params, &mut default_entropy())
+    }
+
+    pub fn generate_binary_with_rng<R: EntropySource + ?Sized>(params: TrlweParams, rng: &mut R) -> Self {
+        assert!(params.N.is_power_of_two(), "ring degree must be a power of two");
+        let polys = (0..params.k)
+            .map(|_| (0..params.N).map(|_| rng.random_bool(0.5) as i32).collect())
+            .collect();
+
+        TrlweSecretKey { polys, params }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrlweSample {
+    pub a: Vec<TorusPolynomial>,
+    pub b: TorusPolynomial,
+    pub params: TrlweParams,
+    pub noise: NoiseLevel,
+    pub estimate: NoiseEstimate,
+}
+
+impl TrlweSample {
+    #[cfg(feature = "client")]
+    pub fn encrypt(message: &TorusPolynomial, sk: &TrlweSecretKey) -> Self {
+        Self::encrypt_with_rng(message, sk, &mut default_entropy())
+    }
+
+    /// `encrypt` drawing the masks and noise from `rng`
+    #[cfg(feature = "client")]
+    pub fn encrypt_with_rng<R: EntropySource + ?Sized>(message: &TorusPolynomial, sk: &TrlweSecretKey, rng: &mut R) -> Self {
+        let params = &sk.params;
+        assert_eq!(message.degree(), params.N, "message degree does not match the ring");
+
+        let a: Vec<TorusPolynomial> = (0..params.k).map(|_| TorusPolynomial::random_with_rng(params.N, rng)).collect();
+        Self::encrypt_with_mask(a, message, sk, rng)
+    }
+
+    /// `encrypt` under a mask chosen by the caller, which must be uniform
+    #[cfg(feature = "client")]
+    pub(crate) fn encrypt_with_mask<R: EntropySource + ?Sized>(
+        a: Vec<TorusPolynomial>,
+        message: &TorusPolynomial,
+        sk: &TrlweSecretKey,
+        rng: &mut R,
+    ) -> Self {
+        let params = &sk.params;
+        assert_eq!(a.len(), params.k, "mask rank does not match the key");
+        let mut b = message.clone();
+        for (a_i, s_i) in a.iter().zip(sk.polys.iter()) {
+            b = b.add(&a_i.mul_int(s_i));
+        }
+        let noise = DiscreteGaussian::new(params.stddev);
+        for c in b.coeffs.iter_mut() {
+            *c = c.add(&noise.sample(rng));
+        }
+
+        TrlweSample {
+            a,
+            b,
+            params: params.clone(),
+            noise: NoiseLevel::Fresh,
+            estimate: NoiseEstimate::fresh(params.stddev),
+        }
+    }
+
+    /// Message plus noise, coefficient by coefficient
+    #[cfg(feature = "client")]
+    pub fn decrypt_phase(&self, sk: &TrlweSecretKey) -> TorusPolynomial {
+        assert_eq!(self.params, sk.params, "sample and key have different parameters");
+        let mut phase = self.b.clone();
+        for (a_i, s_i) in self.a.iter().zip(sk.polys.iter()) {
+            phase = phase.sub(&a_i.mul_int(s_i));
+        }
+        phase
+    }
+
+    pub fn trivial(message: &TorusPolynomial, params: TrlweParams) -> Self {
+        assert_eq!(message.degree(), params.N, "message degree does not match the ring");
+        let a = vec![TorusPolynomial::zero(params.N); params.k];
+
+        // No noise at all
+        TrlweSample { a, b: message.clone(), params, noise: NoiseLevel::PostLinear(0), estimate: NoiseEstimate::default() }
+    }
+
+    pub fn add(&self, other: &TrlweSample) -> TrlweSample {
+        assert_eq!(self.params, other.params, "samples have different parameters");
+        TrlweSample {
+            a: self.a.iter().zip(other.a.iter()).map(|(x, y)| x.add(y)).collect(),
+            b: self.b.add(&other.b),
+            params: self.params.clone(),
+            noise: self.noise.add(other.noise, self.params.stddev),
+            estimate: self.estimate + other.estimate,
+        }
+    }
+
+    pub fn sub(&self, other: &TrlweSample) -> TrlweSample {
+        assert_eq!(self.params, other.params, "samples have different parameters");
+        TrlweSample {
+            a: self.a.iter().zip(other.a.iter()).map(|(x, y)| x.sub(y)).collect(),
+            b: self.b.sub(&other.b),
+            params: self.params.clone(),
+            noise: self.noise.add(other.noise, self.params.stddev),
+            estimate: self.estimate + other.estimate,
+        }
+    }
+
+    /// Encryption of `X^power * m`; rotation leaves the noise level unchanged
+    pub fn mul_by_xai(&self, power: usize) -> TrlweSample {
+        TrlweSample {
+            a: self.a.iter().map(|p| p.mul_by_xai(power)).collect(),
+            b: self.b.mul_by_xai(power),
+            params: self.params.clone(),
+            noise: self.noise,
+            estimate: self.estimate,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "client"))]
+mod tests {
+    use super::*;
+
+    fn params() -> TrlweParams {
+        TrlweParams { N: 16, k: 1, stddev: 1e-9 }
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_with_fft_products() {
+        let params = TrlweParams { N: 256, k: 2, stddev: 1e-9 };
+        let sk = TrlweSecretKey::generate_binary(params);
+        let m = message(256);
+        assert_close(&TrlweSample::encrypt(&m, &sk).decrypt_phase(&sk), &m);
+    }
+
+    fn message(degree: usize) -> TorusPolynomial {
+        TorusPolynomial { coeffs: (0..degree).map(|i| Torus::new(i as f64 / 32.0)).collect() }
+    }
+
+    fn assert_close(a: &TorusPolynomial, b: &TorusPolynomial) {
+        for (x, y) in a.coeffs.iter().zip(b.coeffs.iter()) {
+            let error = (x.value() - y.value() + 0.5).rem_euclid(1.0) - 0.5;
+            assert!(error.abs() < 1e-6, "{} != {}", x.value(), y.value());
+        }
+    }
+
+    #[test]
+    fn test_negacyclic_rotation() {
+        let m = message(16);
+        let rotated = m.mul_by_xai(3);
+        assert!((rotated.coeffs[3].value() - m.coeffs[0].value()).abs() < 1e-12);
+        // Coefficient 15 wraps to X^18 = -X^2
+        assert!((rotated.coeffs[2].value() - Torus::new(-m.coeffs[15].value()).value()).abs() < 1e-12);
+        // X^2N is the identity
+        assert_close(&m.mul_by_xai(32), &m);
+        assert_close(&m.mul_by_xai(16).mul_by_xai(16), &m);
+
+        let mut monomial = vec![0; 16];
+        monomial[3] = 1;
+        assert_close(&m.mul_int(&monomial), &rotated);
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_and_ops() {
+        let sk = TrlweSecretKey::generate_binary(params());
+        let m = message(16);
+        let ct = TrlweSample::encrypt(&m, &sk);
+        assert_close(&ct.decrypt_phase(&sk), &m);
+
+        let sum = ct.add(&ct);
+        assert_eq!(sum.noise, NoiseLevel::PostLinear(2));
+        assert_close(&sum.decrypt_phase(&sk), &m.add(&m));
+        assert_close(&sum.sub(&ct).decrypt_phase(&sk), &m);
+
+        assert_close(&ct.mul_by_xai(5).decrypt_phase(&sk), &m.mul_by_xai(5));
+
+        let trivial = TrlweSample::trivial(&m, params());
+        assert_close(&trivial.decrypt_phase(&sk), &m);
+    }
+}